@@ -0,0 +1,75 @@
+//! Fast-path latency gate for `StateMachine::trigger`.
+//!
+//! Run with `cargo bench --bench trigger`. Exits with an error if the mean
+//! cost per trigger exceeds `PFSM_TRIGGER_MAX_NS` (default 1000ns).
+//!
+//! Lookup is a single hash probe whatever the size of the table, so the
+//! large machine (with aliases in use) must stay within the same bound,
+//! both as built and after `freeze`.
+
+use std::{hint::black_box, process::ExitCode, time::Instant};
+
+use pfsm::{builder::StateMachineBuilder, fixtures, fsm::FSM};
+
+
+const ITERATIONS: u32 = 1_000_000;
+
+
+fn main() -> ExitCode
+{
+    let max_ns: f64 = std::env::var("PFSM_TRIGGER_MAX_NS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(1000.0);
+
+    let mut fsm = StateMachineBuilder::new(0u8)
+        .transition(0, 'a', 1)
+        .transition(1, 'a', 2)
+        .transition(2, 'a', 0)
+        .build()
+        .unwrap();
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        let _ = black_box(fsm.trigger(black_box('a')));
+    }
+    let accepted = start.elapsed().as_nanos() as f64 / ITERATIONS as f64;
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        let _ = black_box(fsm.try_trigger_quiet(black_box('z')));
+    }
+    let rejected = start.elapsed().as_nanos() as f64 / ITERATIONS as f64;
+
+    // Alternates between states 1 and 2, half the time through the alias.
+    let mut large = fixtures::complete_graph(400).alias(1, 1000).build().unwrap();
+
+    let start = Instant::now();
+    for i in 0..ITERATIONS {
+        let event = if i % 2 == 0 { 1000 } else { 2 };
+        let _ = black_box(large.trigger(black_box(event)));
+    }
+    let large_accepted = start.elapsed().as_nanos() as f64 / ITERATIONS as f64;
+
+    large.freeze();
+    let start = Instant::now();
+    for i in 0..ITERATIONS {
+        let event = if i % 2 == 0 { 1000 } else { 2 };
+        let _ = black_box(large.trigger(black_box(event)));
+    }
+    let frozen_accepted = start.elapsed().as_nanos() as f64 / ITERATIONS as f64;
+
+    println!("trigger (accepted): {accepted:.1} ns/iter");
+    println!("try_trigger_quiet (rejected): {rejected:.1} ns/iter");
+    println!("trigger on 160k transitions (accepted): {large_accepted:.1} ns/iter");
+    println!("trigger on 160k transitions, frozen (accepted): {frozen_accepted:.1} ns/iter");
+
+    let worst = [accepted, rejected, large_accepted, frozen_accepted]
+        .into_iter()
+        .fold(0.0, f64::max);
+    if worst > max_ns {
+        eprintln!("fast path regressed beyond {max_ns} ns/iter");
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,89 @@
+//! A coin-operated vending machine driven through its whole life cycle.
+
+use std::{cell::RefCell, rc::Rc};
+
+use pfsm::{builder::StateMachineBuilder, fsm::FSM};
+
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+enum State {
+    Idle,
+    HasCredit,
+    Vending,
+    OutOfOrder
+}
+
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+enum Event {
+    Coin,
+    Select,
+    Done,
+    Refund,
+    Fault
+}
+
+
+#[derive(Debug, Default)]
+struct Vending {
+    credit: u32,
+    stock: u32
+}
+
+
+const COIN: u32 = 25;
+const PRICE: u32 = 100;
+
+
+fn main()
+{
+    let ctx = Rc::new(RefCell::new(Vending{ credit: 0, stock: 1 }));
+    let (c1, c2, guard, buy, refund) =
+        (ctx.clone(), ctx.clone(), ctx.clone(), ctx.clone(), ctx.clone());
+
+    let mut fsm = StateMachineBuilder::new(State::Idle)
+        .transition(State::Idle, Event::Coin, State::HasCredit)
+        .action(move || c1.borrow_mut().credit += COIN)
+        .transition(State::HasCredit, Event::Coin, State::HasCredit)
+        .action(move || c2.borrow_mut().credit += COIN)
+        .transition(State::HasCredit, Event::Select, State::Vending)
+        .guard(move || {
+            let ctx = guard.borrow();
+            ctx.credit >= PRICE && ctx.stock > 0
+        })
+        .action(move || {
+            let mut ctx = buy.borrow_mut();
+            ctx.credit -= PRICE;
+            ctx.stock -= 1;
+            println!("dispensing, {} left", ctx.stock);
+        })
+        .transition(State::Vending, Event::Done, State::Idle)
+        .transition(State::HasCredit, Event::Refund, State::Idle)
+        .transition(State::Idle, Event::Fault, State::OutOfOrder)
+        .transition(State::HasCredit, Event::Fault, State::OutOfOrder)
+        .transition(State::Vending, Event::Fault, State::OutOfOrder)
+        .on_enter(State::Idle, move || {
+            let change = std::mem::take(&mut refund.borrow_mut().credit);
+            if change > 0 {
+                println!("returning {change} in change");
+            }
+        })
+        .tag(State::HasCredit, "refundable")
+        .build()
+        .expect("vending machine schema is valid");
+
+    println!("{}", fsm.to_dot().unwrap());
+
+    fsm.trigger_batch([Event::Coin; 3]).unwrap();
+    println!("{}", fsm.explain(Event::Select));
+
+    fsm.trigger_batch([Event::Coin, Event::Coin, Event::Select, Event::Done]).unwrap();
+    println!("state: {:?}, credit: {}", fsm.state(), ctx.borrow().credit);
+
+    fsm.trigger(Event::Fault).unwrap();
+    if let Err(err) = fsm.trigger(Event::Coin) {
+        println!("{err}");
+    }
+    fsm.reset();
+    println!("after reset: {:?}", fsm.state());
+}
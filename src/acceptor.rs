@@ -0,0 +1,365 @@
+//! Machines as acceptors of event sequences, and their composition.
+//!
+//! An [`Acceptor`] reads a machine's transition table with a set of
+//! accepting states: an input is accepted when the events, triggered in
+//! order from the initial state, all succeed and end in an accepting
+//! state. Guards, flags and enablement are not part of the language, only
+//! the declared transitions.
+//!
+//! [`Acceptor::union`] and [`Acceptor::concat`] link their operands with
+//! epsilon moves into a nondeterministic automaton and determinize it by
+//! subset construction, so the result is again an ordinary acceptor. Its
+//! states are numbered from 0, the initial state, in the order the
+//! construction reaches them over the events sorted by `Debug` rendering,
+//! so equal operands give equal results.
+
+use std::{
+    collections::{BTreeSet, HashMap, HashSet, VecDeque},
+    fmt::Debug,
+    hash::Hash
+};
+
+use crate::{builder::StateMachineBuilder, fsm::StateMachine};
+
+
+/// A deterministic acceptor over events `E`; see the module documentation.
+#[derive(Clone, Debug)]
+pub struct Acceptor<E> {
+    initial: usize,
+    transitions: HashMap<(usize, E), usize>,
+    accepting: HashSet<usize>
+}
+
+
+impl<E> Acceptor<E>
+where E: Copy + Hash + Eq + Debug
+{
+    /// Reads `fsm`'s transitions, accepting in the states of `accepting`.
+    pub fn new<S>(fsm: &StateMachine<S, E>, accepting: &[S]) -> Self
+    where S: Copy + Hash + Eq + Debug
+    {
+        let mut index: HashMap<S, usize> = HashMap::new();
+        let mut number = |state: S| {
+            let next = index.len();
+            *index.entry(state).or_insert(next)
+        };
+
+        let initial = number(fsm.initial);
+        let transitions = fsm
+            .describe()
+            .transitions
+            .iter()
+            .map(|t| ((number(t.from), t.event), number(t.to)))
+            .collect();
+        let accepting = accepting.iter().map(|state| number(*state)).collect();
+
+        Self{ initial, transitions, accepting }
+    }
+
+
+    pub fn accepts(&self, input: &[E]) -> bool
+    {
+        input
+            .iter()
+            .try_fold(self.initial, |state, event| self.transitions.get(&(state, *event)).copied())
+            .is_some_and(|state| self.accepting.contains(&state))
+    }
+
+
+    /// Returns the sorted accepting states.
+    pub fn accepting_states(&self) -> Vec<usize>
+    {
+        let mut states: Vec<usize> = self.accepting.iter().copied().collect();
+
+        states.sort();
+        states
+    }
+
+
+    /// Accepts the inputs accepted by `a` or by `b`.
+    pub fn union(a: &Self, b: &Self) -> Self
+    {
+        let mut nfa = Nfa::default();
+        let start = nfa.add_state();
+        let a_start = nfa.embed(a, true);
+        let b_start = nfa.embed(b, true);
+
+        nfa.epsilon[start].extend([a_start, b_start]);
+        nfa.determinize(start)
+    }
+
+
+    /// Accepts the inputs that split into a prefix accepted by `a` and a
+    /// suffix accepted by `b`.
+    pub fn concat(a: &Self, b: &Self) -> Self
+    {
+        let mut nfa = Nfa::default();
+        let offset = nfa.epsilon.len();
+        let a_start = nfa.embed(a, false);
+        let b_start = nfa.embed(b, true);
+
+        for state in &a.accepting {
+            nfa.epsilon[offset + state].push(b_start);
+        }
+        nfa.determinize(a_start)
+    }
+
+
+    /// Builds a machine taking the acceptor's transitions; it accepts an
+    /// input when triggering it ends in one of `accepting_states`.
+    pub fn to_machine(&self) -> StateMachine<usize, E>
+    {
+        let mut transitions: Vec<_> = self.transitions.iter().collect();
+
+        transitions.sort_by_cached_key(|((from, event), _)| (*from, format!("{event:?}")));
+        transitions
+            .into_iter()
+            .fold(StateMachineBuilder::new(self.initial), |builder, (&(from, event), &to)| {
+                builder.transition(from, event, to)
+            })
+            .build()
+            .unwrap()
+    }
+}
+
+
+/// Nondeterministic automaton with epsilon moves, built by embedding
+/// acceptors side by side.
+struct Nfa<E> {
+    moves: Vec<Vec<(E, usize)>>,
+    epsilon: Vec<Vec<usize>>,
+    accepting: HashSet<usize>
+}
+
+
+impl<E> Default for Nfa<E> {
+    fn default() -> Self
+    {
+        Self{ moves: Vec::new(), epsilon: Vec::new(), accepting: HashSet::new() }
+    }
+}
+
+
+impl<E> Nfa<E>
+where E: Copy + Hash + Eq + Debug
+{
+    fn add_state(&mut self) -> usize
+    {
+        self.moves.push(Vec::new());
+        self.epsilon.push(Vec::new());
+        self.moves.len() - 1
+    }
+
+
+    /// Copies `acceptor`'s states after the existing ones, keeping its
+    /// accepting states if `accepting`; returns where its initial state
+    /// went.
+    fn embed(&mut self, acceptor: &Acceptor<E>, accepting: bool) -> usize
+    {
+        let offset = self.moves.len();
+        let states = acceptor.transitions
+            .iter()
+            .flat_map(|(&(from, _), &to)| [from, to])
+            .chain(acceptor.accepting.iter().copied())
+            .chain([acceptor.initial])
+            .max()
+            .unwrap_or(0) + 1;
+
+        for _ in 0..states {
+            self.add_state();
+        }
+        for (&(from, event), &to) in &acceptor.transitions {
+            self.moves[offset + from].push((event, offset + to));
+        }
+        if accepting {
+            self.accepting.extend(acceptor.accepting.iter().map(|state| offset + state));
+        }
+        offset + acceptor.initial
+    }
+
+
+    fn closure(&self, states: impl IntoIterator<Item = usize>) -> BTreeSet<usize>
+    {
+        let mut closed = BTreeSet::new();
+        let mut pending: Vec<usize> = states.into_iter().collect();
+
+        while let Some(state) = pending.pop() {
+            if closed.insert(state) {
+                pending.extend(&self.epsilon[state]);
+            }
+        }
+        closed
+    }
+
+
+    fn determinize(&self, start: usize) -> Acceptor<E>
+    {
+        let mut alphabet: Vec<E> = self.moves.iter().flatten().map(|(event, _)| *event).collect();
+        alphabet.sort_by_cached_key(|event| format!("{event:?}"));
+        alphabet.dedup();
+
+        let first = self.closure([start]);
+        let mut numbers = HashMap::from([(first.clone(), 0)]);
+        let mut pending = VecDeque::from([first]);
+        let mut transitions = HashMap::new();
+        let mut accepting = HashSet::new();
+
+        while let Some(set) = pending.pop_front() {
+            let from = numbers[&set];
+
+            if set.iter().any(|state| self.accepting.contains(state)) {
+                accepting.insert(from);
+            }
+            for &event in &alphabet {
+                let targets = set
+                    .iter()
+                    .flat_map(|&state| &self.moves[state])
+                    .filter(|(on, _)| *on == event)
+                    .map(|(_, to)| *to);
+                let next = self.closure(targets);
+
+                if next.is_empty() {
+                    continue;
+                }
+                let count = numbers.len();
+                let to = *numbers.entry(next.clone()).or_insert_with(|| {
+                    pending.push_back(next);
+                    count
+                });
+                transitions.insert((from, event), to);
+            }
+        }
+        Acceptor{ initial: 0, transitions, accepting }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fsm::FSM;
+    use Letter::*;
+
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    enum Letter {
+        A,
+        B,
+        C
+    }
+
+
+    fn ends_with_ab() -> Acceptor<Letter>
+    {
+        let fsm = StateMachineBuilder::new("none")
+            .transition("none", A, "a")
+            .transition("none", B, "none")
+            .transition("none", C, "none")
+            .transition("a", A, "a")
+            .transition("a", B, "ab")
+            .transition("a", C, "none")
+            .transition("ab", A, "a")
+            .transition("ab", B, "none")
+            .transition("ab", C, "none")
+            .build()
+            .unwrap();
+
+        Acceptor::new(&fsm, &["ab"])
+    }
+
+
+    fn starts_with_c() -> Acceptor<Letter>
+    {
+        let fsm = StateMachineBuilder::new("start")
+            .transition("start", C, "rest")
+            .transition("rest", A, "rest")
+            .transition("rest", B, "rest")
+            .transition("rest", C, "rest")
+            .build()
+            .unwrap();
+
+        Acceptor::new(&fsm, &["rest"])
+    }
+
+
+    /// Every input of at most `max` letters.
+    fn inputs(max: usize) -> Vec<Vec<Letter>>
+    {
+        let mut all = vec![Vec::new()];
+        let mut last = vec![Vec::new()];
+
+        for _ in 0..max {
+            last = last
+                .iter()
+                .flat_map(|input: &Vec<Letter>| [A, B, C].map(|letter| {
+                    let mut longer = input.clone();
+                    longer.push(letter);
+                    longer
+                }))
+                .collect();
+            all.extend(last.iter().cloned());
+        }
+        all
+    }
+
+
+    fn in_first(input: &[Letter]) -> bool
+    {
+        input.ends_with(&[A, B])
+    }
+
+
+    fn in_second(input: &[Letter]) -> bool
+    {
+        input.first() == Some(&C)
+    }
+
+
+    #[test]
+    fn test_union_of_languages()
+    {
+        let (first, second) = (ends_with_ab(), starts_with_c());
+        let union = Acceptor::union(&first, &second);
+        let inputs = inputs(6);
+
+        assert_eq!(inputs.len(), 1093);
+        for input in &inputs {
+            assert_eq!(first.accepts(input), in_first(input));
+            assert_eq!(second.accepts(input), in_second(input));
+            assert_eq!(union.accepts(input), in_first(input) || in_second(input), "{input:?}");
+        }
+        assert_eq!(union.to_machine().initial, 0);
+    }
+
+
+    #[test]
+    fn test_concatenation_of_languages()
+    {
+        let concat = Acceptor::concat(&ends_with_ab(), &starts_with_c());
+        let reversed = Acceptor::concat(&starts_with_c(), &ends_with_ab());
+
+        for input in inputs(6) {
+            let split = |i: usize| in_first(&input[..i]) && in_second(&input[i..]);
+            let reversed_split = |i: usize| in_second(&input[..i]) && in_first(&input[i..]);
+
+            assert_eq!(concat.accepts(&input), (0..=input.len()).any(split), "{input:?}");
+            assert_eq!(reversed.accepts(&input), (0..=input.len()).any(reversed_split));
+        }
+    }
+
+
+    #[test]
+    fn test_machine_of_composed_acceptor()
+    {
+        let concat = Acceptor::concat(&ends_with_ab(), &starts_with_c());
+        let accepting = concat.accepting_states();
+        let mut fsm = concat.to_machine();
+
+        for event in [C, A, B, C, C] {
+            fsm.trigger(event).unwrap();
+        }
+        assert!(accepting.contains(&fsm.state()));
+        assert!(!Acceptor::new(&fsm, &[]).accepts(&[]));
+        assert!(Acceptor::concat(&concat, &concat).accepts(&[A, B, C, A, B, C]));
+    }
+}
@@ -0,0 +1,198 @@
+//! Alerting on bursts of events the machine will not act on.
+//!
+//! A run of rejected or ignored events usually means the machine and
+//! whatever feeds it have drifted apart. The rejection alarm counts them
+//! and calls back once `threshold` pile up without a single transition
+//! in between, then starts counting afresh.
+
+use std::{
+    collections::VecDeque,
+    fmt::Debug,
+    hash::Hash,
+    time::{Duration, Instant}
+};
+
+use crate::fsm::StateMachine;
+
+
+pub type AlarmCallback<S, E> = Box<dyn Fn(&RejectionBurst<S, E>)>;
+
+
+/// Summary passed to the rejection alarm.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RejectionBurst<S, E> {
+    /// The state the machine is stuck in.
+    pub state: S,
+    /// The rejected or ignored events, oldest first.
+    pub events: Vec<E>,
+    /// Time from the first to the last of them.
+    pub duration: Duration
+}
+
+
+pub(crate) struct RejectionAlarm<S, E> {
+    threshold: usize,
+    window: Option<Duration>,
+    callback: AlarmCallback<S, E>,
+    rejected: VecDeque<(E, Instant)>
+}
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    /// Calls `callback` once `threshold` triggers in a row end without a
+    /// transition, or, with a `window`, once that many do so within the
+    /// window on the machine's clock. Replaces any previous alarm.
+    pub fn set_rejection_alarm(
+        &mut self,
+        threshold: usize,
+        window: Option<Duration>,
+        callback: AlarmCallback<S, E>
+    )
+    {
+        self.rejection_alarm = Some(RejectionAlarm{
+            threshold: threshold.max(1),
+            window,
+            callback,
+            rejected: VecDeque::new()
+        });
+    }
+
+
+    pub fn clear_rejection_alarm(&mut self)
+    {
+        self.rejection_alarm = None;
+    }
+
+
+    pub(crate) fn note_outcome(&mut self, event: E, transitioned: bool)
+    {
+        let Some(alarm) = &mut self.rejection_alarm else {
+            return;
+        };
+        if transitioned {
+            alarm.rejected.clear();
+            return;
+        }
+
+        let now = self.clock.now();
+        alarm.rejected.push_back((event, now));
+        if let Some(window) = alarm.window {
+            while alarm.rejected.front().is_some_and(|(_, at)| now.duration_since(*at) > window) {
+                alarm.rejected.pop_front();
+            }
+        }
+        if alarm.rejected.len() >= alarm.threshold {
+            let first = alarm.rejected.front().map_or(now, |(_, at)| *at);
+            let burst = RejectionBurst{
+                state: self.state,
+                events: alarm.rejected.drain(..).map(|(event, _)| event).collect(),
+                duration: now.duration_since(first)
+            };
+            (alarm.callback)(&burst);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{builder::StateMachineBuilder, clock::MockClock, fsm::FSM};
+    use std::{cell::RefCell, rc::Rc, sync::Arc};
+
+
+    type Bursts = Rc<RefCell<Vec<RejectionBurst<&'static str, char>>>>;
+
+
+    type Kiosk = StateMachine<&'static str, char>;
+
+
+    fn kiosk(window: Option<Duration>) -> (Kiosk, Arc<MockClock>, Bursts)
+    {
+        let clock = Arc::new(MockClock::new());
+        let bursts = Bursts::default();
+        let mut fsm = StateMachineBuilder::new("menu")
+            .transition("menu", 'p', "paying")
+            .transition("paying", 'c', "menu")
+            .build()
+            .unwrap();
+        let b = bursts.clone();
+
+        fsm.set_clock(clock.clone());
+        fsm.set_rejection_alarm(3, window, Box::new(move |burst| {
+            b.borrow_mut().push(burst.clone())
+        }));
+        (fsm, clock, bursts)
+    }
+
+
+    #[test]
+    fn test_consecutive_rejections()
+    {
+        let (mut fsm, clock, bursts) = kiosk(None);
+
+        for event in ['x', 'y', 'z'] {
+            fsm.trigger(event).unwrap_err();
+            clock.advance(Duration::from_secs(10));
+        }
+        assert_eq!(
+            *bursts.borrow(),
+            [RejectionBurst{
+                state: "menu",
+                events: vec!['x', 'y', 'z'],
+                duration: Duration::from_secs(20)
+            }]
+        );
+
+        fsm.trigger('x').unwrap_err();
+        fsm.trigger('y').unwrap_err();
+        assert_eq!(bursts.borrow().len(), 1);
+        fsm.trigger('z').unwrap_err();
+        assert_eq!(bursts.borrow().len(), 2);
+    }
+
+
+    #[test]
+    fn test_success_resets_the_count()
+    {
+        let (mut fsm, _, bursts) = kiosk(None);
+
+        fsm.trigger('x').unwrap_err();
+        fsm.trigger('x').unwrap_err();
+        fsm.trigger('p').unwrap();
+        fsm.trigger('x').unwrap_err();
+        fsm.trigger('x').unwrap_err();
+        assert!(bursts.borrow().is_empty());
+
+        fsm.trigger('x').unwrap_err();
+        assert_eq!(bursts.borrow()[0].state, "paying");
+    }
+
+
+    #[test]
+    fn test_windowed_rejections()
+    {
+        let (mut fsm, clock, bursts) = kiosk(Some(Duration::from_secs(5)));
+
+        fsm.trigger('x').unwrap_err();
+        clock.advance(Duration::from_secs(4));
+        fsm.trigger('y').unwrap_err();
+        clock.advance(Duration::from_secs(4));
+        fsm.trigger('z').unwrap_err();
+        assert!(bursts.borrow().is_empty());
+
+        clock.advance(Duration::from_secs(1));
+        fsm.trigger('w').unwrap_err();
+        assert_eq!(
+            *bursts.borrow(),
+            [RejectionBurst{
+                state: "menu",
+                events: vec!['y', 'z', 'w'],
+                duration: Duration::from_secs(5)
+            }]
+        );
+    }
+}
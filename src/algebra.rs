@@ -0,0 +1,411 @@
+//! Union and intersection of transition tables.
+//!
+//! Both operate on [`Schema`]s and on [`StateMachineBuilder`]s; a built
+//! machine's closures cannot be copied, so machines are combined before
+//! they are built. Two transitions conflict when they share a source and
+//! an event but not their targets. The result is a new definition, with
+//! its own fingerprint once built.
+//!
+//! Closures built with origin-, cancellation-, memoized or dependent
+//! variants keep reading the handles of the builder they were added to:
+//! start an overlay with `overlay()` on the base builder so that the two
+//! share them.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Debug, Display},
+    hash::Hash
+};
+
+use crate::{builder::StateMachineBuilder, schema::Schema};
+
+
+/// Which side wins when both define a transition with different targets.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    PreferSelf,
+    PreferOther,
+    Error
+}
+
+
+/// Where the combined definition's initial state comes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Initial<S> {
+    FromSelf,
+    FromOther,
+    Given(S)
+}
+
+
+impl<S> Initial<S> {
+    fn pick(self, ours: S, theirs: S) -> S
+    {
+        match self {
+            Initial::FromSelf => ours,
+            Initial::FromOther => theirs,
+            Initial::Given(state) => state
+        }
+    }
+}
+
+
+/// A transition both sides define with different targets.
+///
+/// Fields may be added, so it cannot be built outside this crate:
+///
+/// ```compile_fail
+/// use pfsm::algebra::ConflictError;
+///
+/// let error = ConflictError{ from: 0, event: 'a', ours: vec![1], theirs: vec![2] };
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ConflictError<S, E> {
+    pub from: S,
+    pub event: E,
+    pub ours: Vec<S>,
+    pub theirs: Vec<S>
+}
+
+
+impl<S: Debug, E: Debug> Display for ConflictError<S, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(
+            f,
+            "Event '{:?}' from state '{:?}' leads to {:?} on one side and {:?} on the other",
+            self.event, self.from, self.ours, self.theirs
+        )
+    }
+}
+
+
+impl<S: Debug, E: Debug> std::error::Error for ConflictError<S, E> {}
+
+
+impl<S, E> ConflictError<S, E> {
+    /// Returns the numeric code listed in [`ERROR_CODES`](crate::error::ERROR_CODES).
+    pub fn code(&self) -> u16
+    {
+        505
+    }
+}
+
+
+impl<S, E> StateMachineBuilder<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    /// Returns an empty builder sharing this builder's handles, for
+    /// definitions meant to be combined with it.
+    pub fn overlay(&self) -> Self
+    {
+        let mut overlay = Self::new(self.initial);
+
+        overlay.guard_memo = self.guard_memo.clone();
+        overlay.guard_deps = self.guard_deps.clone();
+        overlay.origin = self.origin.clone();
+        overlay.cancel = self.cancel.clone();
+        overlay
+    }
+
+
+    /// Adds every transition of `other`, resolving conflicts by `conflict`.
+    ///
+    /// Tags, terminal and deprecation marks and aliases are merged; for
+    /// entry and exit actions, timeouts and transitions with equal
+    /// targets, the preferred side wins, `self` unless `conflict` is
+    /// `PreferOther`.
+    pub fn union(
+        mut self,
+        other: Self,
+        conflict: ConflictPolicy,
+        initial: Initial<S>
+    ) -> Result<Self, Vec<ConflictError<S, E>>>
+    {
+        let prefer_other = conflict == ConflictPolicy::PreferOther;
+        let index: HashMap<(S, E), usize> = self.transitions
+            .iter()
+            .enumerate()
+            .map(|(i, (from, event, _))| ((*from, *event), i))
+            .collect();
+        let mut errors = Vec::new();
+
+        for (from, event, theirs) in other.transitions {
+            let Some(&i) = index.get(&(from, event)) else {
+                self.transitions.push((from, event, theirs));
+                continue;
+            };
+            let ours = &mut self.transitions[i].2;
+
+            if conflict == ConflictPolicy::Error && !ours.targets().eq(theirs.targets()) {
+                errors.push(ConflictError{
+                    from,
+                    event,
+                    ours: ours.targets().collect(),
+                    theirs: theirs.targets().collect()
+                });
+            } else if prefer_other {
+                *ours = theirs;
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        for (state, tags) in other.tags {
+            let ours = self.tags.entry(state).or_default();
+            for tag in tags {
+                if !ours.contains(&tag) {
+                    ours.push(tag);
+                }
+            }
+        }
+        merge_by_state(&mut self.entry_actions, other.entry_actions, prefer_other);
+        merge_by_state(&mut self.exit_actions, other.exit_actions, prefer_other);
+        merge_by_state(&mut self.timeouts, other.timeouts, prefer_other);
+        self.terminals.extend(other.terminals);
+        self.deprecated.extend(other.deprecated);
+        for alias in other.aliases {
+            if !self.aliases.contains(&alias) {
+                self.aliases.push(alias);
+            }
+        }
+        self.initial_choice = match initial {
+            Initial::FromSelf => self.initial_choice,
+            Initial::FromOther => other.initial_choice,
+            Initial::Given(_) => None
+        };
+        self.initial = initial.pick(self.initial, other.initial);
+        Ok(self)
+    }
+
+
+    /// Keeps only the transitions `other` defines with the same targets.
+    /// Everything else, closures included, comes from `self`; an initial
+    /// choice is kept only with `Initial::FromSelf`.
+    pub fn intersection(mut self, other: &Self, initial: Initial<S>) -> Self
+    {
+        let theirs: HashMap<(S, E), Vec<S>> = other.transitions
+            .iter()
+            .map(|(from, event, t)| ((*from, *event), t.targets().collect()))
+            .collect();
+
+        self.transitions.retain(|(from, event, t)| {
+            theirs
+                .get(&(*from, *event))
+                .is_some_and(|targets| t.targets().eq(targets.iter().copied()))
+        });
+        if initial != Initial::FromSelf {
+            self.initial_choice = None;
+        }
+        self.initial = initial.pick(self.initial, other.initial);
+        self
+    }
+}
+
+
+fn merge_by_state<S: Hash + Eq, V>(
+    ours: &mut HashMap<S, V>,
+    theirs: HashMap<S, V>,
+    prefer_other: bool
+)
+{
+    for (state, value) in theirs {
+        if prefer_other || !ours.contains_key(&state) {
+            ours.insert(state, value);
+        }
+    }
+}
+
+
+impl Schema {
+    /// Like `StateMachineBuilder::union`. State and scenario records are
+    /// merged by name, the preferred side winning.
+    pub fn union(
+        &self,
+        other: &Schema,
+        conflict: ConflictPolicy,
+        initial: Initial<&str>
+    ) -> Result<Schema, Vec<ConflictError<String, String>>>
+    {
+        let prefer_other = conflict == ConflictPolicy::PreferOther;
+        let mut result = self.clone();
+        let mut errors = Vec::new();
+
+        for theirs in &other.transitions {
+            let ours = result.transitions
+                .iter_mut()
+                .find(|t| t.from == theirs.from && t.event == theirs.event);
+
+            match ours {
+                None => result.transitions.push(theirs.clone()),
+                Some(ours) if conflict == ConflictPolicy::Error && ours.to != theirs.to => {
+                    errors.push(ConflictError{
+                        from: ours.from.clone(),
+                        event: ours.event.clone(),
+                        ours: vec![ours.to.clone()],
+                        theirs: vec![theirs.to.clone()]
+                    });
+                }
+                Some(ours) if prefer_other => *ours = theirs.clone(),
+                Some(_) => {}
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        for theirs in &other.states {
+            match result.states.iter_mut().find(|s| s.name == theirs.name) {
+                None => result.states.push(theirs.clone()),
+                Some(ours) if prefer_other => *ours = theirs.clone(),
+                Some(_) => {}
+            }
+        }
+        for theirs in &other.scenarios {
+            match result.scenarios.iter_mut().find(|s| s.name == theirs.name) {
+                None => result.scenarios.push(theirs.clone()),
+                Some(ours) if prefer_other => *ours = theirs.clone(),
+                Some(_) => {}
+            }
+        }
+        result.initial = initial.pick(&self.initial, &other.initial).to_string();
+        Ok(result)
+    }
+
+
+    /// Like `StateMachineBuilder::intersection`. Only the state and
+    /// scenario records both sides have are kept, taken from `self`.
+    pub fn intersection(&self, other: &Schema, initial: Initial<&str>) -> Schema
+    {
+        let theirs: HashSet<(&str, &str, &str)> = other.transitions
+            .iter()
+            .map(|t| (t.from.as_str(), t.event.as_str(), t.to.as_str()))
+            .collect();
+        let mut result = self.clone();
+
+        result.transitions.retain(|t| theirs.contains(&(&t.from, &t.event, &t.to)));
+        result.states.retain(|s| other.states.iter().any(|o| o.name == s.name));
+        result.scenarios.retain(|s| other.scenarios.iter().any(|o| o.name == s.name));
+        result.initial = initial.pick(&self.initial, &other.initial).to_string();
+        result
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{fsm::FSM, schema::TransitionSpec};
+    use std::{cell::Cell, rc::Rc};
+
+
+    type Builder = StateMachineBuilder<u8, char>;
+
+
+    fn base(ran: &Rc<Cell<&'static str>>) -> Builder
+    {
+        let ran = ran.clone();
+
+        StateMachineBuilder::new(0)
+            .transition(0, 'a', 1)
+            .transition(1, 'b', 2)
+            .action(move || ran.set("base"))
+    }
+
+
+    fn experiment(base: &Builder, ran: &Rc<Cell<&'static str>>) -> Builder
+    {
+        let ran = ran.clone();
+
+        base.overlay()
+            .transition(1, 'b', 3)
+            .action(move || ran.set("experiment"))
+            .transition(3, 'c', 0)
+            .tag(3, "experimental")
+    }
+
+
+    #[test]
+    fn test_union_policies()
+    {
+        let ran = Rc::new(Cell::new(""));
+        let fingerprint = base(&ran).build().unwrap().fingerprint();
+
+        let b = base(&ran);
+        let other = experiment(&b, &ran);
+        let mut ours = b
+            .union(other, ConflictPolicy::PreferSelf, Initial::FromSelf)
+            .unwrap()
+            .build()
+            .unwrap();
+        ours.trigger('a').unwrap();
+        ours.trigger('b').unwrap();
+        assert_eq!((ours.state(), ran.get()), (2, "base"));
+        assert_ne!(ours.fingerprint(), fingerprint);
+
+        let b = base(&ran);
+        let other = experiment(&b, &ran);
+        let mut theirs = b
+            .union(other, ConflictPolicy::PreferOther, Initial::Given(1))
+            .unwrap()
+            .build()
+            .unwrap();
+        theirs.trigger('b').unwrap();
+        assert_eq!((theirs.state(), ran.get()), (3, "experiment"));
+        assert!(theirs.has_tag("experimental"));
+
+        let b = base(&ran);
+        let other = experiment(&b, &ran);
+        assert_eq!(
+            b.union(other, ConflictPolicy::Error, Initial::FromSelf).err(),
+            Some(vec![ConflictError{ from: 1, event: 'b', ours: vec![2], theirs: vec![3] }])
+        );
+    }
+
+
+    #[test]
+    fn test_intersection_drops_retargeted_edge()
+    {
+        let ran = Rc::new(Cell::new(""));
+        let b = base(&ran);
+        let other = experiment(&b, &ran).transition(0, 'a', 1);
+        let common = b.intersection(&other, Initial::FromSelf).build().unwrap();
+
+        assert_eq!(common.describe().transitions.len(), 1);
+        assert!(common.is_reachable(&1));
+        assert!(!common.is_reachable(&2));
+    }
+
+
+    #[test]
+    fn test_schema_union_and_intersection()
+    {
+        let spec = |from: &str, event: &str, to: &str| TransitionSpec{
+            from: from.into(),
+            event: event.into(),
+            to: to.into(),
+            ..TransitionSpec::default()
+        };
+        let ours = Schema{
+            initial: "Red".into(),
+            transitions: vec![spec("Red", "Next", "Yellow"), spec("Yellow", "Next", "Red")],
+            ..Schema::default()
+        };
+        let theirs = Schema{
+            initial: "Green".into(),
+            transitions: vec![spec("Red", "Next", "Yellow"), spec("Yellow", "Next", "Green")],
+            ..Schema::default()
+        };
+
+        let union = ours.union(&theirs, ConflictPolicy::PreferOther, Initial::FromOther).unwrap();
+        assert_eq!(union.initial, "Green");
+        assert_eq!(union.transitions[1].to, "Green");
+        let conflicts = ours.union(&theirs, ConflictPolicy::Error, Initial::FromSelf).unwrap_err();
+        assert_eq!(conflicts.len(), 1);
+
+        let common = ours.intersection(&theirs, Initial::Given("Red"));
+        assert_eq!(common.transitions, [spec("Red", "Next", "Yellow")]);
+    }
+}
@@ -0,0 +1,161 @@
+//! Alternative names for events.
+//!
+//! Triggering an alias behaves exactly like triggering its canonical
+//! event: the same transition, guards, cooldown and observers, all keyed
+//! by the canonical event. Only the trace records the alias that was
+//! actually received. Aliases cannot point at other aliases, nor carry
+//! transitions of their own.
+
+use std::{fmt::Debug, hash::Hash};
+
+use crate::{error::TransitionError, fsm::StateMachine, origin::Origin};
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    /// Returns the canonical event `event` stands for; `event` itself if
+    /// it is not an alias.
+    pub fn canonical(&self, event: E) -> E
+    {
+        self.aliases.get(&event).copied().unwrap_or(event)
+    }
+
+
+    /// Returns the aliases of `canonical`, ordered by `Debug` rendering.
+    pub fn aliases_of(&self, canonical: E) -> Vec<E>
+    {
+        let mut aliases: Vec<E> = self.aliases
+            .iter()
+            .filter(|(_, c)| **c == canonical)
+            .map(|(alias, _)| *alias)
+            .collect();
+
+        aliases.sort_by_cached_key(|alias| format!("{alias:?}"));
+        aliases
+    }
+
+
+    /// Makes `trigger_str(name)` trigger `canonical`, for names that have
+    /// no event value of their own. The trace records `canonical`.
+    pub fn alias_str(&mut self, canonical: E, name: &str)
+    {
+        self.name_aliases.insert(name.to_string(), canonical);
+    }
+
+
+    /// Resolves a name registered with `alias_str`.
+    pub(crate) fn trigger_name_alias(
+        &mut self,
+        name: &str
+    ) -> Option<Result<(), TransitionError<S, E>>>
+    {
+        let event = *self.name_aliases.get(name)?;
+        Some(self.fire(event, Origin::unspecified(), true))
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        builder::{BuildError, StateMachineBuilder},
+        error::TransitionError,
+        fsm::FSM
+    };
+    use std::{sync::Arc, time::Duration};
+
+
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    enum Event {
+        Cancel,
+        Abort,
+        CancelOrder,
+        Reopen
+    }
+
+
+    #[test]
+    fn test_aliases_share_one_transition()
+    {
+        let mut fsm = StateMachineBuilder::new(0)
+            .transition(0, Event::Cancel, 1)
+            .cooldown(Duration::from_secs(60))
+            .transition(1, Event::Reopen, 0)
+            .alias(Event::Cancel, Event::Abort)
+            .alias(Event::Cancel, Event::CancelOrder)
+            .build()
+            .unwrap();
+
+        fsm.set_clock(Arc::new(crate::clock::MockClock::new()));
+        fsm.enable_trace(8);
+        assert_eq!(fsm.aliases_of(Event::Cancel), [Event::Abort, Event::CancelOrder]);
+
+        fsm.trigger(Event::Abort).unwrap();
+        fsm.trigger(Event::Reopen).unwrap();
+        // The cooldown started by `Abort` is kept under `Cancel`.
+        assert!(matches!(
+            fsm.trigger(Event::CancelOrder),
+            Err(TransitionError::CoolingDown{ event: Event::Cancel, .. })
+        ));
+        assert_eq!(
+            fsm.explain(Event::CancelOrder).to_string(),
+            "CancelOrder in 0: transition to 1 is cooling down for 60s"
+        );
+
+        let received: Vec<_> = fsm.trace().map(|entry| entry.event).collect();
+        assert_eq!(received, [Event::Abort, Event::Reopen, Event::CancelOrder]);
+    }
+
+
+    #[test]
+    fn test_alias_of_alias_rejected()
+    {
+        let chain = StateMachineBuilder::<u8, Event>::new(0)
+            .alias(Event::Cancel, Event::Abort)
+            .alias(Event::Abort, Event::CancelOrder)
+            .build();
+        let cycle = StateMachineBuilder::<u8, Event>::new(0)
+            .alias(Event::Cancel, Event::Abort)
+            .alias(Event::Abort, Event::Cancel)
+            .build();
+
+        assert_eq!(
+            chain.err(),
+            Some(BuildError::InvalidAlias{ alias: Event::CancelOrder, canonical: Event::Abort })
+        );
+        assert_eq!(
+            cycle.err(),
+            Some(BuildError::InvalidAlias{ alias: Event::Abort, canonical: Event::Cancel })
+        );
+    }
+
+
+    #[test]
+    fn test_transition_on_alias_rejected()
+    {
+        let err = StateMachineBuilder::new(0)
+            .transition(0, Event::Abort, 1)
+            .alias(Event::Cancel, Event::Abort)
+            .build()
+            .unwrap_err();
+
+        assert_eq!(err, BuildError::TransitionOnAlias{ from: 0, alias: Event::Abort });
+        assert_eq!(err.code(), 105);
+        assert_eq!(err.to_string(), "Transition from state '0' is declared on alias 'Abort'");
+    }
+
+
+    #[test]
+    fn test_string_alias()
+    {
+        let mut fsm = StateMachineBuilder::new(0)
+            .transition(0, Event::Cancel, 1)
+            .build()
+            .unwrap();
+
+        fsm.alias_str(Event::Cancel, "CANCEL_ORDER");
+        fsm.trigger_str("CANCEL_ORDER").unwrap();
+        assert_eq!(fsm.state(), 1);
+    }
+}
@@ -0,0 +1,178 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    hash::Hash
+};
+
+use crate::{
+    error::TransitionError,
+    fsm::StateMachine,
+    naming::{NameCollision, Names},
+    origin::Origin
+};
+
+
+/// Declared set of events a machine accepts, with their `Debug` names.
+pub(crate) struct Alphabet<E> {
+    pub(crate) events: HashSet<E>,
+    pub(crate) names: HashMap<String, E>
+}
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    /// Restricts the machine to `events`. Triggering anything else fails
+    /// with `UnknownEvent` instead of `NoTransition`.
+    ///
+    /// `trigger_str` looks events up by their `Debug` rendering, so two
+    /// events rendering alike are rejected; see `set_alphabet_with`.
+    pub fn set_alphabet(
+        &mut self,
+        events: impl IntoIterator<Item = E>
+    ) -> Result<(), NameCollision<S, E>>
+    {
+        self.set_alphabet_with(events, &Names::default())
+    }
+
+
+    /// Like `set_alphabet`, with `trigger_str` names given by `names`.
+    pub fn set_alphabet_with(
+        &mut self,
+        events: impl IntoIterator<Item = E>,
+        names: &Names<S, E>
+    ) -> Result<(), NameCollision<S, E>>
+    {
+        let events: HashSet<E> = events.into_iter().collect();
+        let listed: Vec<E> = events.iter().copied().collect();
+        let names = names.event_map(&listed)?;
+
+        self.alphabet = Some(Alphabet{ events, names });
+        Ok(())
+    }
+
+
+    /// Returns the declared alphabet ordered by `Debug` rendering, or
+    /// `None` if any event is accepted.
+    pub fn alphabet(&self) -> Option<Vec<E>>
+    {
+        self.alphabet.as_ref().map(|alphabet| {
+            let mut events: Vec<E> = alphabet.events.iter().copied().collect();
+            events.sort_by_cached_key(|event| format!("{event:?}"));
+            events
+        })
+    }
+
+
+    /// Triggers the alphabet event whose `Debug` rendering is `name`.
+    ///
+    /// Names registered with `alias_str` are accepted too. Without an
+    /// alphabet every other name is unknown.
+    pub fn trigger_str(&mut self, name: &str) -> Result<(), TransitionError<S, E>>
+    {
+        match self.alphabet.as_ref().and_then(|alphabet| alphabet.names.get(name)) {
+            Some(&event) => self.fire(event, Origin::unspecified(), true),
+            None => match self.trigger_name_alias(name) {
+                Some(result) => result,
+                None => {
+                    let error = self.unknown_event(name.to_string());
+                    Err(self.with_context(error))
+                }
+            }
+        }
+    }
+
+
+    /// Starts counting unknown events by name instead of only rejecting
+    /// them.
+    pub fn enable_quarantine(&mut self)
+    {
+        self.quarantine_enabled = true;
+    }
+
+
+    /// Returns how many times each unknown event name arrived while the
+    /// quarantine was enabled.
+    pub fn unknown_events(&self) -> &HashMap<String, u64>
+    {
+        &self.quarantine
+    }
+
+
+    pub(crate) fn unknown_event(&mut self, name: String) -> TransitionError<S, E>
+    {
+        if self.quarantine_enabled {
+            *self.quarantine.entry(name.clone()).or_default() += 1;
+        }
+        TransitionError::UnknownEvent{ state: self.state, name }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        fixtures::traffic_light::{self, Event, State},
+        fsm::FSM
+    };
+
+
+    /// A traffic light whose alphabet leaves out `GreenTimeout`.
+    fn light() -> StateMachine<State, Event>
+    {
+        let mut fsm = traffic_light::machine();
+
+        fsm.set_alphabet([Event::RedTimeout, Event::Yellow2GreenTimeout, Event::Yellow2RedTimeout])
+            .unwrap();
+        fsm.enable_quarantine();
+        fsm
+    }
+
+
+    #[test]
+    fn test_typo_is_unknown_and_quarantined()
+    {
+        let mut fsm = light();
+
+        for _ in 0..2 {
+            assert_eq!(
+                fsm.trigger_str("RedTimeuot"),
+                Err(TransitionError::UnknownEvent{
+                    state: State::Red,
+                    name: "RedTimeuot".to_string()
+                })
+            );
+        }
+        assert!(matches!(
+            fsm.trigger(Event::GreenTimeout),
+            Err(TransitionError::UnknownEvent { .. })
+        ));
+        assert_eq!(fsm.unknown_events().get("RedTimeuot"), Some(&2));
+        assert_eq!(fsm.unknown_events().get("GreenTimeout"), Some(&1));
+        assert_eq!(
+            fsm.explain(Event::GreenTimeout).to_string(),
+            "GreenTimeout in Red: event is not in the alphabet"
+        );
+
+        fsm.trigger_str("RedTimeout").unwrap();
+        assert_eq!(fsm.state(), State::Yellow);
+    }
+
+
+    #[test]
+    fn test_wrong_state_event_is_not_quarantined()
+    {
+        let mut fsm = light();
+
+        assert_eq!(
+            fsm.trigger_str("Yellow2RedTimeout"),
+            Err(TransitionError::NoTransition{ state: State::Red, event: Event::Yellow2RedTimeout })
+        );
+        assert!(fsm.unknown_events().is_empty());
+        assert_eq!(
+            fsm.alphabet(),
+            Some(vec![Event::RedTimeout, Event::Yellow2GreenTimeout, Event::Yellow2RedTimeout])
+        );
+    }
+}
@@ -0,0 +1,381 @@
+//! Graph queries over the transition table.
+//!
+//! Results are computed lazily and cached until the machine's structure
+//! generation changes, so repeated calls between mutations are cheap.
+//! Only enabled transitions count as edges.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    hash::Hash,
+    rc::Rc
+};
+
+use crate::fsm::StateMachine;
+
+
+struct Cached<S> {
+    generation: u64,
+    index: Rc<GraphIndex<S>>,
+    reachable: Option<Rc<HashSet<S>>>,
+    sccs: Option<Rc<Vec<Vec<S>>>>
+}
+
+
+/// States numbered in `Debug` order with forward and reverse adjacency.
+struct GraphIndex<S> {
+    states: Vec<S>,
+    ids: HashMap<S, usize>,
+    forward: Vec<Vec<usize>>,
+    reverse: Vec<Vec<usize>>
+}
+
+
+pub(crate) struct AnalysisCache<S> {
+    cached: RefCell<Option<Cached<S>>>,
+    pub(crate) computations: Cell<usize>
+}
+
+
+impl<S> Default for AnalysisCache<S> {
+    fn default() -> Self
+    {
+        Self{ cached: RefCell::new(None), computations: Cell::new(0) }
+    }
+}
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    /// Returns the states reachable from the initial state, including it.
+    pub fn reachable_states(&self) -> Vec<S>
+    {
+        let reachable = self.reachable_set();
+
+        self.graph().states.iter().copied().filter(|s| reachable.contains(s)).collect()
+    }
+
+
+    pub fn is_reachable(&self, state: &S) -> bool
+    {
+        self.reachable_set().contains(state)
+    }
+
+
+    /// Returns the known states that cannot be reached from the initial state.
+    pub fn unreachable_states(&self) -> Vec<S>
+    {
+        let reachable = self.reachable_set();
+
+        self.graph().states.iter().copied().filter(|s| !reachable.contains(s)).collect()
+    }
+
+
+    /// Returns the states without any enabled outgoing transition.
+    pub fn sink_states(&self) -> Vec<S>
+    {
+        let graph = self.graph();
+
+        graph.states
+            .iter()
+            .zip(&graph.forward)
+            .filter(|(_, out)| out.is_empty())
+            .map(|(state, _)| *state)
+            .collect()
+    }
+
+
+    /// Returns the sink states that are not marked terminal: the ones
+    /// where the machine would get stuck by accident.
+    pub fn unexpected_sinks(&self) -> Vec<S>
+    {
+        self.sink_states()
+            .into_iter()
+            .filter(|state| !self.terminals.contains(state))
+            .collect()
+    }
+
+
+    /// Returns the states with an enabled transition into `state`.
+    pub fn predecessors(&self, state: &S) -> Vec<S>
+    {
+        let graph = self.graph();
+
+        match graph.ids.get(state) {
+            Some(&id) => graph.reverse[id].iter().map(|&p| graph.states[p]).collect(),
+            None => Vec::new()
+        }
+    }
+
+
+    /// Returns the strongly connected components of the transition graph.
+    ///
+    /// Members and components are ordered by `Debug` rendering of the states.
+    pub fn strongly_connected_components(&self) -> Vec<Vec<S>>
+    {
+        self.with_cache(|cached| {
+            let index = Rc::clone(&cached.index);
+            Rc::clone(cached.sccs.get_or_insert_with(|| Rc::new(kosaraju(&index))))
+        }).as_ref().clone()
+    }
+
+
+    fn graph(&self) -> Rc<GraphIndex<S>>
+    {
+        self.with_cache(|cached| Rc::clone(&cached.index))
+    }
+
+
+    fn reachable_set(&self) -> Rc<HashSet<S>>
+    {
+        let initial = self.initial;
+
+        self.with_cache(|cached| {
+            let index = Rc::clone(&cached.index);
+            Rc::clone(cached.reachable.get_or_insert_with(|| {
+                Rc::new(reachable_from(&index, initial, |_| true))
+            }))
+        })
+    }
+
+
+    /// Returns the states reachable from the initial state without
+    /// entering a deprecated one; not cached.
+    pub(crate) fn reachable_avoiding_deprecated(&self) -> HashSet<S>
+    {
+        reachable_from(&self.graph(), self.initial, |state| !self.deprecated.contains(state))
+    }
+
+
+    /// Whether results were computed for an older structure generation.
+    pub(crate) fn analysis_stale(&self) -> bool
+    {
+        self.analysis.cached.borrow().as_ref().is_some_and(|cached| {
+            cached.generation != self.generation
+        })
+    }
+
+
+    /// Recomputes the graph index and the reachable states for the
+    /// current structure.
+    pub(crate) fn refresh_analysis(&self)
+    {
+        self.reachable_set();
+    }
+
+
+    fn with_cache<T>(&self, f: impl FnOnce(&mut Cached<S>) -> T) -> T
+    {
+        let mut slot = self.analysis.cached.borrow_mut();
+
+        if slot.as_ref().is_none_or(|cached| cached.generation != self.generation) {
+            let computations = &self.analysis.computations;
+            computations.set(computations.get() + 1);
+            *slot = Some(Cached{
+                generation: self.generation,
+                index: Rc::new(self.build_index()),
+                reachable: None,
+                sccs: None
+            });
+        }
+        f(slot.as_mut().unwrap())
+    }
+
+
+    fn build_index(&self) -> GraphIndex<S>
+    {
+        let states = self.states();
+        let ids: HashMap<S, usize> =
+            states.iter().enumerate().map(|(i, s)| (*s, i)).collect();
+        let mut forward = vec![Vec::new(); states.len()];
+        let mut reverse = vec![Vec::new(); states.len()];
+
+        for ((from, _), transition) in &self.transitions {
+            if transition.enabled {
+                for to in transition.targets() {
+                    let (from, to) = (ids[from], ids[&to]);
+                    forward[from].push(to);
+                    reverse[to].push(from);
+                }
+            }
+        }
+        for edges in forward.iter_mut().chain(reverse.iter_mut()) {
+            edges.sort_unstable();
+            edges.dedup();
+        }
+
+        GraphIndex{ states, ids, forward, reverse }
+    }
+}
+
+
+/// Returns the states reachable from `initial` through states for which
+/// `enterable` holds.
+fn reachable_from<S: Copy + Hash + Eq>(
+    graph: &GraphIndex<S>,
+    initial: S,
+    enterable: impl Fn(&S) -> bool
+) -> HashSet<S>
+{
+    let mut seen = vec![false; graph.states.len()];
+    let mut stack = vec![graph.ids[&initial]];
+
+    while let Some(id) = stack.pop() {
+        if !std::mem::replace(&mut seen[id], true) {
+            stack.extend(
+                graph.forward[id]
+                    .iter()
+                    .filter(|&&next| !seen[next] && enterable(&graph.states[next]))
+            );
+        }
+    }
+
+    graph.states
+        .iter()
+        .zip(seen)
+        .filter(|(_, seen)| *seen)
+        .map(|(state, _)| *state)
+        .collect()
+}
+
+
+fn kosaraju<S: Copy>(graph: &GraphIndex<S>) -> Vec<Vec<S>>
+{
+    let n = graph.states.len();
+    let mut order = Vec::with_capacity(n);
+    let mut visited = vec![false; n];
+
+    // Iterative post-order over the forward graph.
+    for root in 0..n {
+        if visited[root] {
+            continue;
+        }
+        visited[root] = true;
+        let mut stack = vec![(root, 0)];
+
+        while let Some((node, next)) = stack.last_mut() {
+            if let Some(&succ) = graph.forward[*node].get(*next) {
+                *next += 1;
+                if !visited[succ] {
+                    visited[succ] = true;
+                    stack.push((succ, 0));
+                }
+            } else {
+                order.push(*node);
+                stack.pop();
+            }
+        }
+    }
+
+    let mut component = vec![usize::MAX; n];
+    let mut components: Vec<Vec<usize>> = Vec::new();
+
+    for &root in order.iter().rev() {
+        if component[root] != usize::MAX {
+            continue;
+        }
+        let id = components.len();
+        let mut members = Vec::new();
+        let mut stack = vec![root];
+        component[root] = id;
+
+        while let Some(node) = stack.pop() {
+            members.push(node);
+            for &pred in &graph.reverse[node] {
+                if component[pred] == usize::MAX {
+                    component[pred] = id;
+                    stack.push(pred);
+                }
+            }
+        }
+        members.sort_unstable();
+        components.push(members);
+    }
+
+    components.sort_unstable_by_key(|members| members[0]);
+    components
+        .into_iter()
+        .map(|members| members.into_iter().map(|id| graph.states[id]).collect())
+        .collect()
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::{builder::StateMachineBuilder, fsm::{StateMachine, Transition}};
+
+
+    fn machine() -> StateMachine<u32, char>
+    {
+        // 0 -> 1 <-> 2 -> 3, and 4 -> 0 is unreachable
+        StateMachineBuilder::new(0)
+            .transition(0, 'a', 1)
+            .transition(1, 'b', 2)
+            .transition(2, 'c', 1)
+            .transition(2, 'd', 3)
+            .transition(4, 'e', 0)
+            .build()
+            .unwrap()
+    }
+
+
+    #[test]
+    fn test_queries()
+    {
+        let fsm = machine();
+
+        assert_eq!(fsm.reachable_states(), [0, 1, 2, 3]);
+        assert_eq!(fsm.unreachable_states(), [4]);
+        assert_eq!(fsm.sink_states(), [3]);
+        assert_eq!(fsm.predecessors(&1), [0, 2]);
+        assert_eq!(fsm.predecessors(&4), Vec::<u32>::new());
+        assert_eq!(
+            fsm.strongly_connected_components(),
+            [vec![0], vec![1, 2], vec![3], vec![4]]
+        );
+    }
+
+
+    #[test]
+    fn test_cache_reused_between_mutations()
+    {
+        let fsm = machine();
+
+        fsm.reachable_states();
+        fsm.sink_states();
+        fsm.strongly_connected_components();
+        fsm.is_reachable(&3);
+        assert_eq!(fsm.analysis.computations.get(), 1);
+    }
+
+
+    #[test]
+    fn test_cache_invalidated_by_mutation()
+    {
+        let mut fsm = machine();
+
+        assert!(fsm.is_reachable(&3));
+        assert_eq!(fsm.strongly_connected_components().len(), 4);
+
+        fsm.remove_transition(1, 'b');
+        assert!(!fsm.is_reachable(&3));
+        assert_eq!(fsm.reachable_states(), [0, 1]);
+        assert_eq!(fsm.sink_states(), [1, 3]);
+
+        fsm.set_enabled(0, 'a', false);
+        assert_eq!(fsm.reachable_states(), [0]);
+        assert_eq!(fsm.sink_states(), [0, 1, 3]);
+
+        fsm.set_enabled(0, 'a', true);
+        fsm.add_transition(1, 'b', Transition::create(2, None));
+        fsm.add_transition(3, 'f', Transition::create(0, None));
+        assert!(fsm.is_reachable(&3));
+        assert_eq!(
+            fsm.strongly_connected_components(),
+            [vec![0, 1, 2, 3], vec![4]]
+        );
+        assert_eq!(fsm.analysis.computations.get(), 4);
+    }
+}
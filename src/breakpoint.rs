@@ -0,0 +1,267 @@
+//! Debugger hooks pausing a dispatch before it commits a transition.
+//!
+//! Breakpoints run once the transition has been found and its guards
+//! have passed, but before the exit, transition and entry actions, so
+//! aborting from one leaves no side effects behind.
+
+use std::{fmt::Debug, hash::Hash};
+
+use crate::{
+    describe::{describe_transition, TransitionDescription},
+    error::TransitionError,
+    fsm::StateMachine,
+    origin::Origin
+};
+
+
+/// The transition a dispatch is about to take; `event` is canonical,
+/// and `to` is the drawn target when the transition is a choice.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct PendingTransition<S, E> {
+    pub from: S,
+    pub event: E,
+    pub to: S,
+    pub origin: Origin,
+    /// What `describe` reports about the transition being taken.
+    pub transition: TransitionDescription<S, E>
+}
+
+
+/// What a breakpoint tells the dispatch to do.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BreakpointDecision {
+    Continue,
+    /// Fail with `TransitionError::BreakpointAborted`; nothing runs.
+    Abort,
+    /// Continue, and break again on the very next transition whatever
+    /// the breakpoint's filter says.
+    Single
+}
+
+
+/// Identifies a breakpoint for `remove_breakpoint`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BreakpointId(u64);
+
+
+type Callback<S, E> = Box<dyn Fn(&PendingTransition<S, E>) -> BreakpointDecision>;
+
+
+pub(crate) struct Breakpoint<S, E> {
+    id: BreakpointId,
+    pub(crate) state: Option<S>,
+    pub(crate) event: Option<E>,
+    stepping: bool,
+    callback: Callback<S, E>
+}
+
+
+impl<S: PartialEq, E: PartialEq> Breakpoint<S, E> {
+    fn applies(&self, from: S, event: E) -> bool
+    {
+        self.stepping
+            || self.state.as_ref().is_none_or(|s| *s == from)
+                && self.event.as_ref().is_none_or(|e| *e == event)
+    }
+}
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    /// Breaks before every transition.
+    pub fn set_breakpoint(
+        &mut self,
+        callback: impl Fn(&PendingTransition<S, E>) -> BreakpointDecision + 'static
+    ) -> BreakpointId
+    {
+        self.set_breakpoint_on(None, None, callback)
+    }
+
+
+    /// Breaks before transitions out of `state` and on `event`; `None`
+    /// matches anything.
+    pub fn set_breakpoint_on(
+        &mut self,
+        state: Option<S>,
+        event: Option<E>,
+        callback: impl Fn(&PendingTransition<S, E>) -> BreakpointDecision + 'static
+    ) -> BreakpointId
+    {
+        self.next_breakpoint_id += 1;
+        let id = BreakpointId(self.next_breakpoint_id);
+
+        self.breakpoints.push(Breakpoint{
+            id,
+            state,
+            event,
+            stepping: false,
+            callback: Box::new(callback)
+        });
+        id
+    }
+
+
+    /// Returns `false` if `id` is not set.
+    pub fn remove_breakpoint(&mut self, id: BreakpointId) -> bool
+    {
+        let before = self.breakpoints.len();
+
+        self.breakpoints.retain(|b| b.id != id);
+        self.breakpoints.len() != before
+    }
+
+
+    /// Whether some breakpoint would be consulted before `event` fires
+    /// from `from`.
+    pub(crate) fn breakpoint_applies(&self, from: S, event: E) -> bool
+    {
+        self.breakpoints.iter().any(|breakpoint| breakpoint.applies(from, event))
+    }
+
+
+    /// Runs the matching breakpoints in registration order; the first
+    /// `Abort` stops the dispatch.
+    pub(crate) fn check_breakpoints(
+        &mut self,
+        from: S,
+        event: E,
+        to: S
+    ) -> Result<(), TransitionError<S, E>>
+    {
+        if self.breakpoints.is_empty() {
+            return Ok(());
+        }
+
+        let pending = PendingTransition{
+            from,
+            event,
+            to,
+            origin: self.origin.borrow().clone(),
+            transition: describe_transition(&(from, event), &self.transitions[&(from, event)])
+        };
+
+        for breakpoint in &mut self.breakpoints {
+            if !breakpoint.applies(from, event) {
+                continue;
+            }
+            let decision = (breakpoint.callback)(&pending);
+
+            breakpoint.stepping = decision == BreakpointDecision::Single;
+            if decision == BreakpointDecision::Abort {
+                return Err(TransitionError::BreakpointAborted{ state: from, event });
+            }
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{builder::StateMachineBuilder, fsm::FSM};
+    use std::{cell::{Cell, RefCell}, rc::Rc};
+
+
+    fn machine(actions: &Rc<Cell<u32>>) -> StateMachine<u8, char>
+    {
+        let a = actions.clone();
+
+        StateMachineBuilder::new(0)
+            .transition(0, 'a', 1)
+            .action(move || a.set(a.get() + 1))
+            .transition(1, 'b', 2)
+            .transition(2, 'c', 0)
+            .on_exit(0, || panic!("exit action must not run"))
+            .build()
+            .unwrap()
+    }
+
+
+    #[test]
+    fn test_abort_has_no_side_effects()
+    {
+        let actions = Rc::new(Cell::new(0));
+        let mut fsm = machine(&actions);
+
+        let id = fsm.set_breakpoint(|pending| {
+            assert_eq!((pending.from, pending.event, pending.to), (0, 'a', 1));
+            BreakpointDecision::Abort
+        });
+
+        assert_eq!(
+            fsm.trigger('a'),
+            Err(TransitionError::BreakpointAborted{ state: 0, event: 'a' })
+        );
+        assert_eq!((fsm.state(), actions.get()), (0, 0));
+        assert!(fsm.remove_breakpoint(id));
+        assert!(!fsm.remove_breakpoint(id));
+    }
+
+
+    #[test]
+    fn test_removed_ids_are_not_reused()
+    {
+        let mut fsm = machine(&Rc::new(Cell::new(0)));
+
+        let first = fsm.set_breakpoint(|_| BreakpointDecision::Continue);
+        let newest = fsm.set_breakpoint(|_| BreakpointDecision::Abort);
+
+        assert!(fsm.remove_breakpoint(newest));
+        let replacement = fsm.set_breakpoint(|_| BreakpointDecision::Continue);
+
+        assert_ne!(replacement, newest);
+        assert!(!fsm.remove_breakpoint(newest));
+        assert!(fsm.remove_breakpoint(first) && fsm.remove_breakpoint(replacement));
+    }
+
+
+    #[test]
+    fn test_pending_describes_the_transition()
+    {
+        let mut fsm: StateMachine<u8, char> = StateMachineBuilder::new(0)
+            .transition(0, 'a', 1)
+            .guard(|| true)
+            .group("setup")
+            .build()
+            .unwrap();
+
+        fsm.set_breakpoint(|pending| {
+            let transition = &pending.transition;
+
+            assert!(transition.guarded && !transition.has_action);
+            assert_eq!(transition.group.as_deref(), Some("setup"));
+            assert_eq!((transition.from, transition.event, transition.to), (0, 'a', 1));
+            BreakpointDecision::Continue
+        });
+        fsm.trigger('a').unwrap();
+    }
+
+
+    #[test]
+    fn test_filter_continue_and_single_step()
+    {
+        let actions = Rc::new(Cell::new(0));
+        let mut fsm = machine(&actions);
+        let hits = Rc::new(RefCell::new(Vec::new()));
+        let h = hits.clone();
+
+        fsm.set_exit_action(0, Box::new(|| {}));
+        fsm.set_breakpoint_on(Some(2), None, move |pending| {
+            h.borrow_mut().push(pending.event);
+            match pending.event {
+                'c' => BreakpointDecision::Single,
+                _ => BreakpointDecision::Continue
+            }
+        });
+
+        for event in ['a', 'b', 'c', 'a', 'b', 'c'] {
+            fsm.trigger(event).unwrap();
+        }
+        // 'a' from 0 only breaks while single-stepping after 'c'.
+        assert_eq!(*hits.borrow(), ['c', 'a', 'c']);
+        assert_eq!(actions.get(), 2);
+    }
+}
@@ -0,0 +1,684 @@
+use std::{
+    any::Any,
+    borrow::Cow,
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fmt::{self, Debug, Display},
+    hash::Hash,
+    rc::Rc,
+    sync::Arc,
+    time::Duration
+};
+
+use crate::{
+    alphabet::Alphabet,
+    cancel::{Cancelled, CancellationToken},
+    choice::{Candidate, SelectionMode},
+    clock::Clock,
+    flag::FlagProvider,
+    fsm::{Action, StateMachine, Transition, FSM},
+    deps::{depend, DepCache},
+    initial::Selector,
+    memo::{memoize, GuardMemo},
+    origin::Origin,
+    policy::Policies
+};
+
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BuildError<S, E> {
+    DuplicateTransition { from: S, event: E },
+    /// `alias` is registered twice, or `canonical` is itself an alias.
+    InvalidAlias { alias: E, canonical: E },
+    /// A transition leads into a state marked with `deprecate_state`.
+    EntersDeprecated { from: S, event: E, to: S },
+    /// `build_resuming` was given a state the machine does not know.
+    UnknownResumeState { state: S },
+    /// A transition is declared on `alias` instead of its canonical event.
+    TransitionOnAlias { from: S, alias: E }
+}
+
+
+impl<S: Debug, E: Debug> Display for BuildError<S, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        match self {
+            BuildError::DuplicateTransition { from, event } => write!(
+                f,
+                "Duplicate transition for event '{event:?}' from state '{from:?}'"
+            ),
+            BuildError::InvalidAlias { alias, canonical } => write!(
+                f,
+                "Event '{alias:?}' cannot be an alias of '{canonical:?}'"
+            ),
+            BuildError::EntersDeprecated { from, event, to } => write!(
+                f,
+                "Event '{event:?}' from state '{from:?}' leads into deprecated state '{to:?}'"
+            ),
+            BuildError::UnknownResumeState { state } => write!(
+                f,
+                "Cannot resume in state '{state:?}': it is not part of the machine"
+            ),
+            BuildError::TransitionOnAlias { from, alias } => write!(
+                f,
+                "Transition from state '{from:?}' is declared on alias '{alias:?}'"
+            )
+        }
+    }
+}
+
+
+impl<S: Debug, E: Debug> std::error::Error for BuildError<S, E> {}
+
+
+impl<S, E> BuildError<S, E> {
+    /// Returns the numeric code listed in [`ERROR_CODES`](crate::error::ERROR_CODES).
+    pub fn code(&self) -> u16
+    {
+        match self {
+            BuildError::DuplicateTransition { .. } => 101,
+            BuildError::InvalidAlias { .. } => 102,
+            BuildError::EntersDeprecated { .. } => 103,
+            BuildError::UnknownResumeState { .. } => 104,
+            BuildError::TransitionOnAlias { .. } => 105
+        }
+    }
+}
+
+
+/// Incremental construction of a [`StateMachine`].
+///
+/// `action`, `guard` and `cooldown` apply to the most recently added
+/// transition:
+///
+/// ```
+/// use pfsm::{builder::StateMachineBuilder, fsm::FSM};
+///
+/// let mut fsm = StateMachineBuilder::new("locked")
+///     .transition("locked", "coin", "unlocked")
+///     .action(|| println!("unlocked"))
+///     .transition("unlocked", "push", "locked")
+///     .tag("unlocked", "passable")
+///     .build()
+///     .unwrap();
+///
+/// fsm.trigger("coin").unwrap();
+/// assert!(fsm.has_tag("passable"));
+/// ```
+pub struct StateMachineBuilder<S: Copy, E: Copy> {
+    pub(crate) initial: S,
+    pub(crate) transitions: Vec<(S, E, Transition<S>)>,
+    pub(crate) tags: HashMap<S, Vec<String>>,
+    pub(crate) entry_actions: HashMap<S, Action>,
+    pub(crate) exit_actions: HashMap<S, Action>,
+    pub(crate) timeouts: HashMap<S, (Duration, E)>,
+    pub(crate) terminals: HashSet<S>,
+    pub(crate) deprecated: HashSet<S>,
+    pub(crate) aliases: Vec<(E, E)>,
+    pub(crate) initial_choice: Option<Selector<S>>,
+    pub(crate) guard_memo: Rc<GuardMemo>,
+    pub(crate) guard_deps: Rc<DepCache>,
+    pub(crate) origin: Rc<RefCell<Origin>>,
+    pub(crate) cancel: CancellationToken,
+    pub(crate) carried: Option<Carried<S, E>>
+}
+
+
+/// Settings of the machine a builder was taken from with `into_builder`,
+/// applied again by `build`.
+pub(crate) struct Carried<S, E> {
+    name: Option<Cow<'static, str>>,
+    policies: Policies,
+    flag_provider: Option<FlagProvider>,
+    state_data: HashMap<S, Box<dyn Any>>,
+    alphabet: Option<Alphabet<E>>,
+    name_aliases: HashMap<String, E>,
+    clock: Arc<dyn Clock>
+}
+
+
+impl<S, E> StateMachineBuilder<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    pub fn new(initial: S) -> Self
+    {
+        Self{
+            initial,
+            transitions: Vec::new(),
+            tags: HashMap::new(),
+            entry_actions: HashMap::new(),
+            exit_actions: HashMap::new(),
+            timeouts: HashMap::new(),
+            terminals: HashSet::new(),
+            deprecated: HashSet::new(),
+            aliases: Vec::new(),
+            initial_choice: None,
+            guard_memo: Rc::default(),
+            guard_deps: Rc::default(),
+            origin: Rc::default(),
+            cancel: CancellationToken::default(),
+            carried: None
+        }
+    }
+
+
+    pub fn transition(mut self, from: S, event: E, to: S) -> Self
+    {
+        self.transitions.push((from, event, Transition::create(to, None)));
+        self
+    }
+
+
+    pub fn action(mut self, action: impl Fn() + 'static) -> Self
+    {
+        self.last_transition().action = Some(Box::new(action));
+        self
+    }
+
+
+    pub fn guard(mut self, guard: impl Fn() -> bool + 'static) -> Self
+    {
+        self.last_transition().guard = Some(Box::new(guard));
+        self
+    }
+
+
+    /// Like `guard`, for guards that depend on the origin of the event.
+    pub fn guard_with_origin(mut self, guard: impl Fn(&Origin) -> bool + 'static) -> Self
+    {
+        let origin = self.origin.clone();
+
+        self.last_transition().guard = Some(Box::new(move || guard(&origin.borrow())));
+        self
+    }
+
+
+    /// Like `action`, for actions that depend on the origin of the event.
+    pub fn action_with_origin(mut self, action: impl Fn(&Origin) + 'static) -> Self
+    {
+        let origin = self.origin.clone();
+
+        self.last_transition().action = Some(Box::new(move || action(&origin.borrow())));
+        self
+    }
+
+
+    /// Like `action`, for actions that may stop early when the machine's
+    /// cancellation token is set; see the `cancel` module.
+    pub fn action_cancellable(
+        mut self,
+        action: impl Fn(&CancellationToken) -> Result<(), Cancelled> + 'static
+    ) -> Self
+    {
+        let token = self.cancel.clone();
+
+        self.last_transition().action = Some(Box::new(move || {
+            if action(&token).is_err() {
+                token.observe();
+            }
+        }));
+        self
+    }
+
+
+    /// Like `guard`, but the result is cached under `key` for the rest
+    /// of each dispatch. `guard` must be pure; see the `memo` module.
+    pub fn guard_memoized(mut self, key: &str, guard: impl Fn() -> bool + 'static) -> Self
+    {
+        self.last_transition().guard = Some(memoize(&self.guard_memo, key, guard));
+        self
+    }
+
+
+    /// Like `guard`, declaring the context `guard` reads so that
+    /// `available` can cache it; see the `deps` module.
+    pub fn guard_dep(mut self, deps: &[&str], guard: impl Fn() -> bool + 'static) -> Self
+    {
+        self.last_transition().guard = Some(depend(&self.guard_deps, deps, guard));
+        self
+    }
+
+
+    pub fn cooldown(mut self, cooldown: Duration) -> Self
+    {
+        self.last_transition().cooldown = Some(cooldown);
+        self
+    }
+
+
+    /// Puts the last transition in `group`, to be enabled and disabled
+    /// with the rest of it.
+    pub fn group(mut self, group: &str) -> Self
+    {
+        self.last_transition().group = Some(group.to_string());
+        self
+    }
+
+
+    /// Gates the last transition on `flag`; see the `flag` module.
+    pub fn feature_flag(mut self, flag: &str) -> Self
+    {
+        self.last_transition().feature_flag = Some(flag.to_string());
+        self
+    }
+
+
+    /// Adds `to` as another candidate target of the last transition,
+    /// turning it into a choice.
+    pub fn alternative(mut self, to: S) -> Self
+    {
+        self.last_candidates().push(Candidate::new(to));
+        self
+    }
+
+
+    /// Sets the weight of the last candidate of the last transition.
+    pub fn weight(mut self, weight: f64) -> Self
+    {
+        self.last_candidates().last_mut().unwrap().weight = weight;
+        self
+    }
+
+
+    /// Guards the last candidate of the last transition.
+    pub fn candidate_guard(mut self, guard: impl Fn() -> bool + 'static) -> Self
+    {
+        self.last_candidates().last_mut().unwrap().guard = Some(Box::new(guard));
+        self
+    }
+
+
+    /// Gates the last candidate of the last transition on `flag`.
+    pub fn candidate_flag(mut self, flag: &str) -> Self
+    {
+        self.last_candidates().last_mut().unwrap().feature_flag = Some(flag.to_string());
+        self
+    }
+
+
+    /// Like `candidate_guard`, memoized under `key`.
+    pub fn candidate_guard_memoized(
+        mut self,
+        key: &str,
+        guard: impl Fn() -> bool + 'static
+    ) -> Self
+    {
+        let guard = memoize(&self.guard_memo, key, guard);
+        self.last_candidates().last_mut().unwrap().guard = Some(guard);
+        self
+    }
+
+
+    /// Sets how the last transition picks among its candidates.
+    pub fn select(mut self, mode: SelectionMode) -> Self
+    {
+        self.last_transition().selection = mode;
+        self
+    }
+
+
+    /// Sets the action run whenever a transition enters `state`.
+    pub fn on_enter(mut self, state: S, action: impl Fn() + 'static) -> Self
+    {
+        self.entry_actions.insert(state, Box::new(action));
+        self
+    }
+
+
+    /// Sets the action run whenever a transition leaves `state`.
+    pub fn on_exit(mut self, state: S, action: impl Fn() + 'static) -> Self
+    {
+        self.exit_actions.insert(state, Box::new(action));
+        self
+    }
+
+
+    /// Triggers `event` once the machine has spent `after` in `state`; see
+    /// `StateMachine::set_timeout`.
+    pub fn timeout(mut self, state: S, after: Duration, event: E) -> Self
+    {
+        self.timeouts.insert(state, (after, event));
+        self
+    }
+
+
+    /// Points the existing transition on `event` from `from` at `to`,
+    /// dropping any other candidate targets. Its guard, action and other
+    /// settings are kept.
+    pub fn retarget(mut self, from: S, event: E, to: S) -> Self
+    {
+        let (_, _, transition) = self.transitions
+            .iter_mut()
+            .find(|(f, e, _)| *f == from && *e == event)
+            .expect("`retarget` needs an existing transition");
+
+        transition.next_state = to;
+        transition.candidates.clear();
+        self
+    }
+
+
+    /// Attaches a user-defined flag to `state`; a state may carry any number.
+    pub fn tag(mut self, state: S, tag: &str) -> Self
+    {
+        let tags = self.tags.entry(state).or_default();
+
+        if !tags.iter().any(|t| t == tag) {
+            tags.push(tag.to_string());
+        }
+        self
+    }
+
+
+    /// Marks `state` as terminal; any number of states may be terminal.
+    pub fn terminal(mut self, state: S) -> Self
+    {
+        self.terminals.insert(state);
+        self
+    }
+
+
+    /// Forbids transitions into `state`; see the `deprecate` module.
+    pub fn deprecate_state(mut self, state: S) -> Self
+    {
+        self.deprecated.insert(state);
+        self
+    }
+
+
+    /// Makes triggering `alias` behave like triggering `canonical`; see
+    /// the `alias` module.
+    pub fn alias(mut self, canonical: E, alias: E) -> Self
+    {
+        self.aliases.push((alias, canonical));
+        self
+    }
+
+
+    pub fn build(self) -> Result<StateMachine<S, E>, BuildError<S, E>>
+    {
+        let mut transitions = HashMap::with_capacity(self.transitions.len());
+        let mut aliases = HashMap::with_capacity(self.aliases.len());
+
+        for &(alias, canonical) in &self.aliases {
+            if alias == canonical
+                || self.aliases.iter().any(|(a, _)| *a == canonical)
+                || aliases.insert(alias, canonical).is_some()
+            {
+                return Err(BuildError::InvalidAlias{ alias, canonical });
+            }
+        }
+        for (from, event, transition) in self.transitions {
+            if aliases.contains_key(&event) {
+                return Err(BuildError::TransitionOnAlias{ from, alias: event });
+            }
+            if let Some(to) = transition.targets().find(|to| self.deprecated.contains(to)) {
+                return Err(BuildError::EntersDeprecated{ from, event, to });
+            }
+            if transitions.insert((from, event), transition).is_some() {
+                return Err(BuildError::DuplicateTransition{ from, event });
+            }
+        }
+
+        let selection = self.initial_choice.as_ref().map(|choose| choose());
+        let initial = selection.as_ref().map_or(self.initial, |selection| selection.state);
+
+        let mut fsm = StateMachine::initialize(initial, transitions);
+        fsm.initial_choice = self.initial_choice;
+        fsm.initial_selection = selection;
+        fsm.aliases = aliases;
+        fsm.tags = self.tags;
+        fsm.entry_actions = self.entry_actions;
+        fsm.exit_actions = self.exit_actions;
+        fsm.timeouts = self.timeouts;
+        fsm.terminals = self.terminals;
+        fsm.deprecated = self.deprecated;
+        fsm.guard_memo = self.guard_memo;
+        fsm.guard_deps = self.guard_deps;
+        fsm.origin = self.origin;
+        fsm.cancel = self.cancel;
+        if let Some(carried) = self.carried {
+            fsm.name = carried.name;
+            fsm.policies = carried.policies;
+            fsm.flag_provider = carried.flag_provider;
+            fsm.state_data = carried.state_data;
+            fsm.alphabet = carried.alphabet;
+            fsm.name_aliases = carried.name_aliases;
+            fsm.set_clock(carried.clock);
+        }
+        Ok(fsm)
+    }
+
+
+    /// Builds the machine and puts it in `current`, typically the state of
+    /// the machine this builder was taken from with `into_builder`.
+    pub fn build_resuming(self, current: S) -> Result<StateMachine<S, E>, BuildError<S, E>>
+    {
+        let mut fsm = self.build()?;
+
+        if !fsm.states().contains(&current) {
+            return Err(BuildError::UnknownResumeState{ state: current });
+        }
+        fsm.state = current;
+        Ok(fsm)
+    }
+
+
+    fn last_transition(&mut self) -> &mut Transition<S>
+    {
+        &mut self.transitions
+            .last_mut()
+            .expect("transition modifiers must follow a call to `transition`")
+            .2
+    }
+
+
+    fn last_candidates(&mut self) -> &mut Vec<Candidate<S>>
+    {
+        let transition = self.last_transition();
+
+        if transition.candidates.is_empty() {
+            transition.candidates.push(Candidate::new(transition.next_state));
+        }
+        &mut transition.candidates
+    }
+}
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    /// Turns the machine back into a builder for editing, moving its
+    /// transitions, actions, guards, tags, timeouts, marks and aliases
+    /// along, with the enabled flags set by `set_enabled` and group
+    /// toggles. The built machine gets the name, policies, flag provider,
+    /// state data, alphabet, event names and clock back.
+    ///
+    /// What belongs to the run rather than the structure is dropped: the
+    /// trace, cooldown timestamps, quarantine counts and counters, a
+    /// pending deadline, prepared transition or posted events, and the
+    /// fair queue, whose handles keep pointing at this machine. So are the
+    /// hooks registered on it — middleware, subscriptions, observers,
+    /// breakpoints, the rejection alarm, the finish callback, the command
+    /// mapper and the context refresher — which are written against its
+    /// structure and must be registered again. The current state is
+    /// forgotten unless the edit is rebuilt with `build_resuming`.
+    pub fn into_builder(self) -> StateMachineBuilder<S, E>
+    {
+        let mut transitions: Vec<(S, E, Transition<S>)> = self.transitions
+            .into_iter()
+            .map(|((from, event), transition)| (from, event, transition))
+            .collect();
+        let mut aliases: Vec<(E, E)> = self.aliases.into_iter().collect();
+
+        transitions.sort_by_cached_key(|(from, event, _)| format!("{from:?}\0{event:?}"));
+        aliases.sort_by_cached_key(|alias| format!("{alias:?}"));
+
+        StateMachineBuilder{
+            initial: self.initial,
+            transitions,
+            tags: self.tags,
+            entry_actions: self.entry_actions,
+            exit_actions: self.exit_actions,
+            timeouts: self.timeouts,
+            terminals: self.terminals,
+            deprecated: self.deprecated,
+            aliases,
+            initial_choice: self.initial_choice,
+            guard_memo: self.guard_memo,
+            guard_deps: self.guard_deps,
+            origin: self.origin,
+            cancel: self.cancel,
+            carried: Some(Carried{
+                name: self.name,
+                policies: self.policies,
+                flag_provider: self.flag_provider,
+                state_data: self.state_data,
+                alphabet: self.alphabet,
+                name_aliases: self.name_aliases,
+                clock: self.clock
+            })
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::error::TransitionError;
+
+
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    enum State {
+        Idle,
+        Uploading,
+        Verifying,
+        Done
+    }
+
+
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    enum Event {
+        Start,
+        Uploaded,
+        Verified
+    }
+
+
+    #[test]
+    fn test_tags_follow_current_state()
+    {
+        let mut fsm = StateMachineBuilder::new(State::Idle)
+            .transition(State::Idle, Event::Start, State::Uploading)
+            .transition(State::Uploading, Event::Uploaded, State::Verifying)
+            .transition(State::Verifying, Event::Verified, State::Done)
+            .tag(State::Uploading, "cancellable")
+            .tag(State::Uploading, "busy")
+            .tag(State::Verifying, "cancellable")
+            .build()
+            .unwrap();
+
+        assert!(!fsm.has_tag("cancellable"));
+        assert_eq!(fsm.states_with_tag("cancellable"), [State::Uploading, State::Verifying]);
+        assert_eq!(fsm.states_with_tag("busy"), [State::Uploading]);
+        assert!(fsm.states_with_tag("unknown").is_empty());
+
+        fsm.trigger(Event::Start).unwrap();
+        assert!(fsm.has_tag("cancellable"));
+        assert!(fsm.has_tag("busy"));
+
+        fsm.trigger(Event::Uploaded).unwrap();
+        assert!(fsm.has_tag("cancellable"));
+        assert!(!fsm.has_tag("busy"));
+
+        fsm.trigger(Event::Verified).unwrap();
+        assert!(!fsm.has_tag("cancellable"));
+    }
+
+
+    #[test]
+    fn test_round_trip_edit_resuming()
+    {
+        let uploads = Rc::new(std::cell::Cell::new(0));
+        let u = uploads.clone();
+        let mut fsm = StateMachineBuilder::new(State::Idle)
+            .transition(State::Idle, Event::Start, State::Uploading)
+            .action(move || u.set(u.get() + 1))
+            .transition(State::Uploading, Event::Uploaded, State::Verifying)
+            .transition(State::Verifying, Event::Verified, State::Done)
+            .tag(State::Uploading, "busy")
+            .build()
+            .unwrap();
+
+        fsm.trigger(Event::Start).unwrap();
+        let current = fsm.state();
+        let mut fsm = fsm
+            .into_builder()
+            .retarget(State::Uploading, Event::Uploaded, State::Done)
+            .transition(State::Done, Event::Start, State::Uploading)
+            .build_resuming(current)
+            .unwrap();
+
+        assert_eq!(fsm.state(), State::Uploading);
+        assert!(fsm.has_tag("busy"));
+        fsm.trigger(Event::Uploaded).unwrap();
+        assert_eq!(fsm.state(), State::Done);
+        assert!(!fsm.is_reachable(&State::Verifying));
+        fsm.trigger(Event::Start).unwrap();
+        assert_eq!(uploads.get(), 1);
+
+        fsm.reset();
+        fsm.trigger(Event::Start).unwrap();
+        assert_eq!(uploads.get(), 2);
+        assert_eq!(
+            fsm.into_builder().build_resuming(State::Done).map(|fsm| fsm.state()),
+            Ok(State::Done)
+        );
+    }
+
+
+    #[test]
+    fn test_round_trip_keeps_settings()
+    {
+        let mut fsm = StateMachineBuilder::new(State::Idle)
+            .transition(State::Idle, Event::Start, State::Uploading)
+            .feature_flag("uploads")
+            .transition(State::Uploading, Event::Uploaded, State::Done)
+            .group("finishing")
+            .build()
+            .unwrap();
+
+        fsm.set_name("uploader");
+        fsm.set_policies(Policies::default().with_listed_events_limit(3));
+        fsm.set_flag_provider(Box::new(|flag| flag == "uploads"));
+        fsm.set_state_data(State::Done, 42u32);
+        fsm.set_group_enabled("finishing", false);
+
+        let mut fsm = fsm.into_builder().build().unwrap();
+        assert_eq!(fsm.name(), Some("uploader"));
+        assert_eq!(fsm.policies().listed_events_limit, 3);
+        assert_eq!(fsm.state_data::<u32>(&State::Done), Some(&42));
+        fsm.trigger(Event::Start).unwrap();
+        assert_eq!(
+            fsm.trigger(Event::Uploaded),
+            Err(TransitionError::Disabled{ state: State::Uploading, event: Event::Uploaded })
+        );
+    }
+
+
+    #[test]
+    fn test_duplicate_transition()
+    {
+        let result = StateMachineBuilder::new(State::Idle)
+            .transition(State::Idle, Event::Start, State::Uploading)
+            .transition(State::Idle, Event::Start, State::Done)
+            .build();
+
+        assert_eq!(
+            result.err(),
+            Some(BuildError::DuplicateTransition{ from: State::Idle, event: Event::Start })
+        );
+    }
+}
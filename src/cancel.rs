@@ -0,0 +1,166 @@
+//! Cooperative cancellation of long-running actions.
+//!
+//! A machine owns one [`CancellationToken`]. Actions registered with
+//! `action_cancellable` receive it and may give up by returning
+//! `Err(Cancelled)`; the `cancellation` policy decides whether the
+//! transition then fails with `TransitionError::Cancelled`, leaving the
+//! state unchanged, or completes anyway. The token is cleared at the
+//! start of every dispatch unless the `sticky_cancel` policy is set.
+
+use std::{
+    fmt::Debug,
+    hash::Hash,
+    sync::{atomic::{AtomicBool, Ordering}, Arc}
+};
+
+use crate::fsm::StateMachine;
+
+
+/// Returned by a cancellable action that stopped early.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cancelled;
+
+
+/// Shared cancellation flag; clones observe the same flag and may be
+/// sent to other threads.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    requested: Arc<AtomicBool>,
+    observed: Arc<AtomicBool>
+}
+
+
+impl CancellationToken {
+    pub fn cancel(&self)
+    {
+        self.requested.store(true, Ordering::SeqCst);
+    }
+
+
+    pub fn is_cancelled(&self) -> bool
+    {
+        self.requested.load(Ordering::SeqCst)
+    }
+
+
+    /// Records that an action gave up because of the request.
+    pub(crate) fn observe(&self)
+    {
+        self.observed.store(true, Ordering::SeqCst);
+    }
+
+
+    pub(crate) fn take_observed(&self) -> bool
+    {
+        self.observed.swap(false, Ordering::SeqCst)
+    }
+
+
+    pub(crate) fn reset(&self, sticky: bool)
+    {
+        if !sticky {
+            self.requested.store(false, Ordering::SeqCst);
+        }
+        self.observed.store(false, Ordering::SeqCst);
+    }
+}
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    pub fn cancel_token(&self) -> CancellationToken
+    {
+        self.cancel.clone()
+    }
+
+
+    /// Asks the running or next cancellable action to stop.
+    pub fn request_cancel(&self)
+    {
+        self.cancel.cancel();
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        builder::StateMachineBuilder,
+        error::TransitionError,
+        fsm::FSM,
+        policy::{CancellationPolicy, Policies}
+    };
+    use std::{sync::mpsc, thread, time::Duration};
+
+
+    fn copier(policy: CancellationPolicy) -> (StateMachine<u8, char>, thread::JoinHandle<()>)
+    {
+        let (started, wait) = mpsc::channel();
+        let mut fsm = StateMachineBuilder::new(0)
+            .transition(0, 'c', 1)
+            .action_cancellable(move |token| {
+                started.send(()).unwrap();
+                for _ in 0..10_000 {
+                    if token.is_cancelled() {
+                        return Err(Cancelled);
+                    }
+                    thread::sleep(Duration::from_millis(1));
+                }
+                panic!("cancellation was never noticed");
+            })
+            .build()
+            .unwrap();
+        let token = fsm.cancel_token();
+
+        fsm.set_policies(Policies{ cancellation: policy, ..Policies::default() });
+        let canceller = thread::spawn(move || {
+            wait.recv().unwrap();
+            token.cancel();
+        });
+        (fsm, canceller)
+    }
+
+
+    #[test]
+    fn test_cancelled_action_fails_transition()
+    {
+        let (mut fsm, canceller) = copier(CancellationPolicy::Fail);
+
+        assert_eq!(fsm.trigger('c'), Err(TransitionError::Cancelled{ state: 0, event: 'c' }));
+        assert_eq!(fsm.state(), 0);
+        canceller.join().unwrap();
+    }
+
+
+    #[test]
+    fn test_cancelled_action_completes_transition()
+    {
+        let (mut fsm, canceller) = copier(CancellationPolicy::Complete);
+
+        assert_eq!(fsm.trigger('c'), Ok(()));
+        assert_eq!(fsm.state(), 1);
+        canceller.join().unwrap();
+    }
+
+
+    #[test]
+    fn test_request_reset_unless_sticky()
+    {
+        let mut fsm = StateMachineBuilder::new(0)
+            .transition(0, 'a', 0)
+            .action_cancellable(|token| if token.is_cancelled() { Err(Cancelled) } else { Ok(()) })
+            .build()
+            .unwrap();
+
+        fsm.request_cancel();
+        assert_eq!(fsm.trigger('a'), Ok(()));
+
+        fsm.set_policies(Policies{ sticky_cancel: true, ..Policies::default() });
+        fsm.request_cancel();
+        assert!(fsm.trigger('a').is_err());
+        assert!(fsm.trigger('a').is_err());
+        assert!(fsm.cancel_token().is_cancelled());
+    }
+}
@@ -0,0 +1,196 @@
+//! Run-to-completion processing of events posted from within a dispatch.
+//!
+//! Actions can post events through an [`EventPoster`]. Posted events are
+//! queued and processed, in order, after the transition that posted them
+//! and before `trigger` returns. A rejected internal event is traced and
+//! skipped; the dispatch still reports the triggered event's result.
+//!
+//! Entry/exit actions and internal events are budgeted per dispatch by
+//! the `cascade_limit` and `internal_event_limit` policies. Exhausting
+//! either fails the dispatch with `CascadeOverflow` and drops whatever is
+//! still queued.
+
+use std::{cell::RefCell, fmt::Debug, hash::Hash, rc::Rc};
+
+use crate::{coalesce::PostQueue, error::TransitionError, fsm::StateMachine, origin::Origin};
+
+
+/// What the last dispatch consumed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DispatchStats {
+    pub exits: usize,
+    pub entries: usize,
+    /// Posted events processed after the triggered one.
+    pub internal_events: usize
+}
+
+
+/// Handle for posting events to a machine from its own actions.
+pub struct EventPoster<E> {
+    queue: Rc<RefCell<PostQueue<E>>>,
+    origin: Rc<RefCell<Origin>>
+}
+
+
+impl<E> Clone for EventPoster<E> {
+    fn clone(&self) -> Self
+    {
+        Self{ queue: self.queue.clone(), origin: self.origin.clone() }
+    }
+}
+
+
+impl<E: Copy + Hash + Eq> EventPoster<E> {
+    /// Queues `event` for processing before the current dispatch ends.
+    /// It inherits the origin of the current dispatch, and may be
+    /// coalesced with an equal queued event; see the `coalesce` module.
+    pub fn post(&self, event: E)
+    {
+        let origin = self.origin.borrow().clone();
+        self.post_from(event, origin);
+    }
+
+
+    /// Like `post`, with `origin` instead of the inherited one.
+    pub fn post_from(&self, event: E, origin: Origin)
+    {
+        self.queue.borrow_mut().push(event, origin);
+    }
+
+
+    /// Returns how many posts `CoalescePolicy::Count` merged into the
+    /// event being processed; 1 for any other event.
+    pub fn coalesced(&self) -> usize
+    {
+        self.queue.borrow().current.1
+    }
+}
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    pub fn poster(&self) -> EventPoster<E>
+    {
+        EventPoster{ queue: self.posted.clone(), origin: self.origin.clone() }
+    }
+
+
+    pub fn last_dispatch_stats(&self) -> DispatchStats
+    {
+        self.dispatch_stats
+    }
+
+
+    /// Processes posted events until the queue is empty or a budget runs
+    /// out.
+    pub(crate) fn drain_posted(&mut self) -> Result<(), TransitionError<S, E>>
+    {
+        loop {
+            let Some((event, origin)) = self.posted.borrow_mut().pop_front() else {
+                return Ok(());
+            };
+            *self.origin.borrow_mut() = origin;
+
+            self.dispatch_stats.internal_events += 1;
+            let limit = self.policies.internal_event_limit;
+            let overflow = if self.dispatch_stats.internal_events > limit {
+                Err(TransitionError::CascadeOverflow{ state: self.state })
+            } else {
+                match self.fire_one(event, true) {
+                    Err(err @ TransitionError::CascadeOverflow { .. }) => Err(err),
+                    _ => Ok(())
+                }
+            };
+            if overflow.is_err() {
+                self.posted.borrow_mut().clear();
+                return overflow;
+            }
+        }
+    }
+}
+
+
+/// Counts one entry or exit action run in `state` against the budget.
+pub(crate) fn spend<S: Copy, E>(
+    stats: &mut DispatchStats,
+    limit: usize,
+    state: S,
+    entry: bool
+) -> Result<(), TransitionError<S, E>>
+{
+    if stats.entries + stats.exits >= limit {
+        return Err(TransitionError::CascadeOverflow{ state });
+    }
+    if entry {
+        stats.entries += 1;
+    } else {
+        stats.exits += 1;
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{builder::StateMachineBuilder, fsm::FSM, policy::Policies};
+
+
+    #[test]
+    fn test_stats_of_normal_cascade()
+    {
+        let mut fsm = StateMachineBuilder::new('a')
+            .transition('a', 1, 'b')
+            .transition('b', 2, 'c')
+            .transition('c', 3, 'a')
+            .on_exit('a', || {})
+            .build()
+            .unwrap();
+        let poster = fsm.poster();
+
+        fsm.set_entry_action('b', Box::new(move || poster.post(2)));
+        fsm.set_entry_action('c', Box::new(|| {}));
+
+        fsm.trigger(1).unwrap();
+        assert_eq!(fsm.state(), 'c');
+        assert_eq!(
+            fsm.last_dispatch_stats(),
+            DispatchStats{ exits: 1, entries: 2, internal_events: 1 }
+        );
+
+        fsm.trigger(3).unwrap();
+        assert_eq!(fsm.last_dispatch_stats(), DispatchStats::default());
+    }
+
+
+    #[test]
+    fn test_endless_cascade_trips_budget()
+    {
+        let mut fsm = StateMachineBuilder::new(0)
+            .transition(0, 'n', 1)
+            .transition(1, 'n', 2)
+            .transition(2, 'n', 0)
+            .build()
+            .unwrap();
+
+        for state in 0..3 {
+            let poster = fsm.poster();
+            fsm.set_entry_action(state, Box::new(move || poster.post('n')));
+        }
+        fsm.set_policies(Policies{ cascade_limit: 10, ..Policies::default() });
+        fsm.enable_trace(32);
+
+        assert_eq!(fsm.trigger('n'), Err(TransitionError::CascadeOverflow{ state: 2 }));
+        assert_eq!(fsm.last_dispatch_stats().entries, 10);
+        assert_eq!(fsm.state(), 1);
+
+        // Nothing is left queued for the next dispatch.
+        fsm.set_policies(Policies::default());
+        fsm.set_entry_action(2, Box::new(|| {}));
+        fsm.trigger('n').unwrap();
+        assert_eq!(fsm.state(), 2);
+        assert_eq!(fsm.last_dispatch_stats().internal_events, 0);
+    }
+}
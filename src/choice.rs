@@ -0,0 +1,312 @@
+//! Transitions with several candidate targets.
+//!
+//! A choice transition is looked up, enabled, guarded and cooled down like
+//! any other; its target is then picked among the candidates whose own
+//! guard passes, according to its [`SelectionMode`].
+
+use std::{fmt::Debug, hash::Hash};
+
+use crate::{
+    error::TransitionError,
+    fsm::{Guard, StateMachine, Transition}
+};
+#[cfg(feature = "sim")]
+use crate::sim::SimRng;
+
+
+/// How a choice transition picks among its surviving candidates.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SelectionMode {
+    /// The first candidate, in declaration order, whose guard passes.
+    #[default]
+    FirstGuardWins,
+    /// A draw from the machine's RNG, proportional to the weights of the
+    /// candidates whose guard passes. Zero-weight candidates never win.
+    #[cfg(feature = "sim")]
+    WeightedRandom
+}
+
+
+pub struct Candidate<S> {
+    pub(crate) to: S,
+    pub(crate) weight: f64,
+    pub(crate) guard: Option<Guard>,
+    pub(crate) feature_flag: Option<String>
+}
+
+
+impl<S> Candidate<S> {
+    pub fn new(to: S) -> Self
+    {
+        Self{ to, weight: 1.0, guard: None, feature_flag: None }
+    }
+
+
+    pub fn with_weight(mut self, weight: f64) -> Self
+    {
+        self.weight = weight;
+        self
+    }
+
+
+    /// Excludes the candidate from selection while `guard` returns `false`.
+    pub fn with_guard(mut self, guard: Guard) -> Self
+    {
+        self.guard = Some(guard);
+        self
+    }
+}
+
+
+/// The targets a transition may still take once its own checks passed.
+pub(crate) enum Eligible<S> {
+    /// The only target, or the winner of a first-guard-wins choice; `None`
+    /// if no candidate passed.
+    One(Option<S>),
+    /// The weighted candidates that passed, to draw from.
+    #[cfg(feature = "sim")]
+    Weighted(Vec<(S, f64)>)
+}
+
+
+impl<S: Copy> Eligible<S> {
+    /// Returns the target to report without drawing: the only one, or the
+    /// first weighted candidate.
+    pub(crate) fn first(&self) -> Option<S>
+    {
+        match self {
+            Eligible::One(target) => *target,
+            #[cfg(feature = "sim")]
+            Eligible::Weighted(survivors) => survivors.first().map(|(to, _)| *to)
+        }
+    }
+}
+
+
+impl<S: Copy> Transition<S> {
+    /// Creates a transition choosing its target among `candidates`.
+    ///
+    /// Panics if `candidates` is empty.
+    pub fn choice(mode: SelectionMode, candidates: Vec<Candidate<S>>) -> Self
+    {
+        let first = candidates.first().expect("a choice needs at least one candidate").to;
+        let mut transition = Self::create(first, None);
+
+        transition.selection = mode;
+        transition.candidates = candidates;
+        transition
+    }
+
+
+    /// Returns every state the transition may lead to.
+    pub(crate) fn targets(&self) -> impl Iterator<Item = S> + '_
+    {
+        let single = self.candidates.is_empty().then_some(self.next_state);
+        single.into_iter().chain(self.candidates.iter().map(|c| c.to))
+    }
+}
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    /// Evaluates the candidates of `transition`, restricted to `order` and
+    /// in that order when given.
+    pub(crate) fn eligible_targets(
+        &self,
+        transition: &Transition<S>,
+        order: Option<&[usize]>,
+        catch: bool
+    ) -> Result<Eligible<S>, TransitionError<S, E>>
+    {
+        if transition.candidates.is_empty() {
+            return Ok(Eligible::One(Some(transition.next_state)));
+        }
+        // A frozen machine only visits the candidates that can be picked.
+        let all = order.is_none().then_some(0..transition.candidates.len());
+        let candidates = all
+            .into_iter()
+            .flatten()
+            .chain(order.into_iter().flatten().copied())
+            .map(|index| &transition.candidates[index]);
+
+        match transition.selection {
+            SelectionMode::FirstGuardWins => {
+                for candidate in candidates {
+                    if self.flag_on(candidate.feature_flag.as_deref())
+                        && passes(candidate, catch)?
+                    {
+                        return Ok(Eligible::One(Some(candidate.to)));
+                    }
+                }
+                Ok(Eligible::One(None))
+            }
+            #[cfg(feature = "sim")]
+            SelectionMode::WeightedRandom => {
+                let mut survivors = Vec::with_capacity(transition.candidates.len());
+
+                for candidate in candidates {
+                    if candidate.weight > 0.0
+                        && self.flag_on(candidate.feature_flag.as_deref())
+                        && passes(candidate, catch)?
+                    {
+                        survivors.push((candidate.to, candidate.weight));
+                    }
+                }
+                Ok(Eligible::Weighted(survivors))
+            }
+        }
+    }
+
+
+    /// Picks the target among `eligible`, drawing from the machine's RNG
+    /// for a weighted choice.
+    pub(crate) fn pick_target(&mut self, eligible: Eligible<S>) -> Option<S>
+    {
+        match eligible {
+            Eligible::One(target) => target,
+            #[cfg(feature = "sim")]
+            Eligible::Weighted(survivors) => draw(&mut self.rng, &survivors)
+        }
+    }
+
+
+    /// Lists the targets `trigger(event)` could reach right now with their
+    /// probabilities; empty if the event would be rejected.
+    ///
+    /// A single-target transition, or a first-guard-wins choice, yields
+    /// one target with probability 1. Guards are evaluated, so they must
+    /// be free of side effects.
+    pub fn peek(&self, event: E) -> Vec<(S, f64)>
+    {
+        self.refresh_context();
+
+        match self.preflight(event) {
+            Err(_) => Vec::new(),
+            Ok(Eligible::One(target)) => {
+                self.admit(target).map(|to| vec![(to, 1.0)]).unwrap_or_default()
+            }
+            #[cfg(feature = "sim")]
+            Ok(Eligible::Weighted(survivors)) => {
+                let total: f64 = survivors.iter().map(|(_, w)| w).sum();
+
+                survivors
+                    .into_iter()
+                    .filter(|(to, _)| self.admit(Some(*to)).is_ok())
+                    .map(|(to, w)| (to, w / total))
+                    .collect()
+            }
+        }
+    }
+
+
+    /// Replaces the generator used by weighted random choices.
+    #[cfg(feature = "sim")]
+    pub fn set_rng(&mut self, rng: SimRng)
+    {
+        self.rng = rng;
+    }
+}
+
+
+fn passes<S, E, T>(candidate: &Candidate<T>, catch: bool) -> Result<bool, TransitionError<S, E>>
+{
+    match &candidate.guard {
+        Some(guard) => crate::fsm::call(catch, guard),
+        None => Ok(true)
+    }
+}
+
+
+#[cfg(feature = "sim")]
+fn draw<S: Copy>(rng: &mut SimRng, survivors: &[(S, f64)]) -> Option<S>
+{
+    let total: f64 = survivors.iter().map(|(_, w)| w).sum();
+    let mut point = rng.next_f64() * total;
+
+    for &(to, weight) in survivors {
+        if point < weight {
+            return Some(to);
+        }
+        point -= weight;
+    }
+    survivors.last().map(|(to, _)| *to)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{builder::StateMachineBuilder, fsm::FSM};
+    use std::{cell::Cell, rc::Rc};
+
+
+    #[test]
+    fn test_first_guard_wins()
+    {
+        let open = Rc::new(Cell::new(false));
+        let o = open.clone();
+        let mut fsm = StateMachineBuilder::new('a').build().unwrap();
+
+        fsm.add_transition('a', 1, Transition::choice(SelectionMode::FirstGuardWins, vec![
+            Candidate::new('b').with_guard(Box::new(move || o.get())),
+            Candidate::new('c')
+        ]));
+        fsm.add_transition('b', 0, Transition::create('a', None));
+        fsm.add_transition('c', 0, Transition::create('a', None));
+
+        assert_eq!(fsm.peek(1), [('c', 1.0)]);
+        fsm.trigger(1).unwrap();
+        assert_eq!(fsm.state(), 'c');
+
+        fsm.trigger(0).unwrap();
+        open.set(true);
+        fsm.trigger(1).unwrap();
+        assert_eq!(fsm.state(), 'b');
+        assert_eq!(fsm.reachable_states(), ['a', 'b', 'c']);
+    }
+
+
+    #[cfg(feature = "sim")]
+    #[test]
+    fn test_weighted_random_is_reproducible()
+    {
+        #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+        enum Npc {
+            Deciding,
+            Patrol,
+            Idle,
+            Sleep,
+            Dance
+        }
+
+        let mut fsm = StateMachineBuilder::new(Npc::Deciding)
+            .transition(Npc::Deciding, "think", Npc::Patrol)
+            .weight(0.7)
+            .alternative(Npc::Idle)
+            .weight(0.3)
+            .alternative(Npc::Sleep)
+            .weight(0.0)
+            .alternative(Npc::Dance)
+            .weight(5.0)
+            .candidate_guard(|| false)
+            .select(SelectionMode::WeightedRandom)
+            .transition(Npc::Patrol, "done", Npc::Deciding)
+            .transition(Npc::Idle, "done", Npc::Deciding)
+            .build()
+            .unwrap();
+
+        fsm.set_rng(SimRng::new(42));
+        assert_eq!(fsm.peek("think"), [(Npc::Patrol, 0.7), (Npc::Idle, 0.3)]);
+
+        let mut chosen = Vec::new();
+        for _ in 0..10 {
+            fsm.trigger("think").unwrap();
+            chosen.push(fsm.state());
+            fsm.trigger("done").unwrap();
+        }
+
+        use Npc::{Idle as I, Patrol as P};
+        assert_eq!(chosen, [I, P, P, P, P, I, P, I, P, P]);
+    }
+}
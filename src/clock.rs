@@ -0,0 +1,81 @@
+use std::{sync::Arc, time::Instant};
+
+#[cfg(any(test, feature = "test-util"))]
+use std::{sync::Mutex, time::Duration};
+
+
+/// Source of monotonic time shared by every time-based feature of a machine.
+pub trait Clock: Send + Sync {
+    /// Returns the current reading of the clock.
+    fn now(&self) -> Instant;
+}
+
+
+/// Real monotonic clock backed by [`Instant::now`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant
+    {
+        Instant::now()
+    }
+}
+
+
+/// Returns the clock a machine uses unless told otherwise.
+pub fn default_clock() -> Arc<dyn Clock>
+{
+    Arc::new(SystemClock)
+}
+
+
+/// Manually driven clock for tests: time only moves on [`MockClock::advance`].
+#[cfg(any(test, feature = "test-util"))]
+#[derive(Debug)]
+pub struct MockClock {
+    origin: Instant,
+    elapsed: Mutex<Duration>
+}
+
+
+#[cfg(any(test, feature = "test-util"))]
+impl MockClock {
+    pub fn new() -> Self
+    {
+        Self{ origin: Instant::now(), elapsed: Mutex::new(Duration::ZERO) }
+    }
+
+
+    /// Moves the clock forward by `by`.
+    pub fn advance(&self, by: Duration)
+    {
+        *self.elapsed.lock().unwrap() += by;
+    }
+
+
+    /// Returns the total time advanced since creation.
+    pub fn elapsed(&self) -> Duration
+    {
+        *self.elapsed.lock().unwrap()
+    }
+}
+
+
+#[cfg(any(test, feature = "test-util"))]
+impl Default for MockClock {
+    fn default() -> Self
+    {
+        Self::new()
+    }
+}
+
+
+#[cfg(any(test, feature = "test-util"))]
+impl Clock for MockClock {
+    fn now(&self) -> Instant
+    {
+        self.origin + self.elapsed()
+    }
+}
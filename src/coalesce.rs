@@ -0,0 +1,255 @@
+//! Coalescing of identical posted events.
+//!
+//! With a [`CoalescePolicy`] configured for an event, posting it while an
+//! equal event is already queued does not grow the queue: the policy
+//! decides whether the new post is dropped, replaces the queued one, or
+//! is merged into it. The trace records how many posts each processed
+//! event stood for; under `Count`, actions read the number of merged
+//! posts through `EventPoster::coalesced` while the event is processed.
+
+use std::{collections::{HashMap, VecDeque}, fmt::Debug, hash::Hash};
+
+use crate::{fsm::StateMachine, origin::Origin};
+
+
+/// What posting an event does when an equal one is already queued.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CoalescePolicy {
+    /// Drop the new post; the queued event keeps its place and origin.
+    KeepFirst,
+    /// Drop the queued event and queue the new one at the back.
+    KeepLast,
+    /// Keep the queued event and add the new post to its count.
+    Count
+}
+
+
+pub(crate) struct Posted<E> {
+    pub(crate) event: E,
+    pub(crate) origin: Origin,
+    /// Posts this entry stands for, whatever the policy.
+    pub(crate) posts: usize,
+    /// Posts merged into this entry by `CoalescePolicy::Count`.
+    pub(crate) count: usize
+}
+
+
+/// The queue behind `EventPoster`, shared with the machine.
+pub(crate) struct PostQueue<E> {
+    entries: VecDeque<Posted<E>>,
+    coalesce: HashMap<E, CoalescePolicy>,
+    /// `posts` and `count` of the event being processed; 1 for triggered
+    /// events.
+    pub(crate) current: (usize, usize)
+}
+
+
+impl<E> Default for PostQueue<E> {
+    fn default() -> Self
+    {
+        Self{ entries: VecDeque::new(), coalesce: HashMap::new(), current: (1, 1) }
+    }
+}
+
+
+impl<E: Copy + Hash + Eq> PostQueue<E> {
+    pub(crate) fn push(&mut self, event: E, origin: Origin)
+    {
+        let policy = self.coalesce.get(&event).copied();
+        let queued = policy.and_then(|_| self.entries.iter().position(|p| p.event == event));
+
+        match (policy, queued) {
+            (Some(CoalescePolicy::KeepFirst), Some(index)) => self.entries[index].posts += 1,
+            (Some(CoalescePolicy::KeepLast), Some(index)) => {
+                let posts = self.entries.remove(index).unwrap().posts + 1;
+                self.entries.push_back(Posted{ event, origin, posts, count: 1 });
+            }
+            (Some(CoalescePolicy::Count), Some(index)) => {
+                self.entries[index].posts += 1;
+                self.entries[index].count += 1;
+            }
+            _ => self.entries.push_back(Posted{ event, origin, posts: 1, count: 1 })
+        }
+    }
+
+
+    /// Removes the oldest entry and makes it the current one.
+    pub(crate) fn pop_front(&mut self) -> Option<(E, Origin)>
+    {
+        let posted = self.entries.pop_front()?;
+
+        self.current = (posted.posts, posted.count);
+        Some((posted.event, posted.origin))
+    }
+
+
+    pub(crate) fn len(&self) -> usize
+    {
+        self.entries.len()
+    }
+
+
+    pub(crate) fn is_empty(&self) -> bool
+    {
+        self.entries.is_empty()
+    }
+
+
+    #[cfg(feature = "paranoid")]
+    pub(crate) fn events(&self) -> impl Iterator<Item = E> + '_
+    {
+        self.entries.iter().map(|posted| posted.event)
+    }
+
+
+    pub(crate) fn clear(&mut self)
+    {
+        self.entries.clear();
+    }
+
+
+    /// Replaces `old` by `new` in the queued events and coalescing
+    /// policies; returns how many it replaced.
+    pub(crate) fn rename_event(&mut self, old: E, new: E) -> usize
+    {
+        let mut count = 0;
+
+        for posted in self.entries.iter_mut().filter(|posted| posted.event == old) {
+            posted.event = new;
+            count += 1;
+        }
+        if let Some(policy) = self.coalesce.remove(&old) {
+            self.coalesce.insert(new, policy);
+            count += 1;
+        }
+        count
+    }
+
+
+    /// Moves `earlier`'s entries in front of this queue's, and its
+    /// coalescing policies over this queue's.
+    pub(crate) fn prepend(&mut self, earlier: PostQueue<E>)
+    {
+        let later = std::mem::replace(&mut self.entries, earlier.entries);
+
+        self.entries.extend(later);
+        self.coalesce.extend(earlier.coalesce);
+    }
+
+
+    /// Drops the queued events for which `keep` is false and returns them.
+    pub(crate) fn drop_unless(&mut self, keep: impl Fn(&E) -> bool) -> Vec<E>
+    {
+        let mut dropped = Vec::new();
+
+        self.entries.retain(|posted| {
+            let kept = keep(&posted.event);
+            if !kept {
+                dropped.push(posted.event);
+            }
+            kept
+        });
+        dropped
+    }
+}
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    /// Coalesces posts of `event` according to `policy`. Events already
+    /// queued are left as they are.
+    pub fn queue_coalesce(&mut self, event: E, policy: CoalescePolicy)
+    {
+        self.posted.borrow_mut().coalesce.insert(event, policy);
+    }
+
+
+    /// Stops coalescing posts of `event`; returns whether it was.
+    pub fn clear_queue_coalesce(&mut self, event: E) -> bool
+    {
+        self.posted.borrow_mut().coalesce.remove(&event).is_some()
+    }
+
+
+    /// Returns the number of posted events waiting to be processed.
+    pub fn queue_depth(&self) -> usize
+    {
+        self.posted.borrow().len()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{builder::StateMachineBuilder, fsm::FSM};
+    use std::{cell::RefCell, rc::Rc};
+
+
+    /// Posts five `'s'` with origin ids 0 to 4 from one action. Returns
+    /// the count the sensor action observed each time it fired, and the
+    /// posts and origin id traced for each processed `'s'`.
+    fn flood(policy: Option<CoalescePolicy>) -> (Vec<usize>, Vec<(usize, Option<u64>)>)
+    {
+        let observed = Rc::new(RefCell::new(Vec::new()));
+        let mut fsm = StateMachineBuilder::new(0)
+            .transition(0, 'g', 1)
+            .transition(1, 's', 1)
+            .build()
+            .unwrap();
+        let poster = fsm.poster();
+        let reader = fsm.poster();
+        let o = observed.clone();
+
+        fsm.transitions.get_mut(&(0, 'g')).unwrap().action = Some(Box::new(move || {
+            for id in 0..5 {
+                poster.post_from('s', Origin::new("sensor").with_id(id));
+            }
+        }));
+        fsm.transitions.get_mut(&(1, 's')).unwrap().action =
+            Some(Box::new(move || o.borrow_mut().push(reader.coalesced())));
+        if let Some(policy) = policy {
+            fsm.queue_coalesce('s', policy);
+        }
+        fsm.enable_trace(16);
+        fsm.trigger('g').unwrap();
+        assert_eq!(fsm.queue_depth(), 0);
+
+        let traced = fsm
+            .trace()
+            .filter(|entry| entry.event == 's')
+            .map(|entry| (entry.coalesced, entry.origin.id))
+            .collect();
+        (observed.take(), traced)
+    }
+
+
+    #[test]
+    fn test_flood_under_each_policy()
+    {
+        let one_by_one = (0..5).map(|id| (1, Some(id))).collect();
+
+        assert_eq!(flood(None), (vec![1; 5], one_by_one));
+        assert_eq!(flood(Some(CoalescePolicy::KeepFirst)), (vec![1], vec![(5, Some(0))]));
+        assert_eq!(flood(Some(CoalescePolicy::KeepLast)), (vec![1], vec![(5, Some(4))]));
+        assert_eq!(flood(Some(CoalescePolicy::Count)), (vec![5], vec![(5, Some(0))]));
+    }
+
+
+    #[test]
+    fn test_queue_depth_counts_coalesced_entries()
+    {
+        let mut fsm: StateMachine<u8, char> = StateMachineBuilder::new(0).build().unwrap();
+        let poster = fsm.poster();
+
+        fsm.queue_coalesce('a', CoalescePolicy::Count);
+        for event in ['a', 'b', 'a', 'b', 'a'] {
+            poster.post(event);
+        }
+        assert_eq!(fsm.queue_depth(), 3);
+        assert!(fsm.clear_queue_coalesce('a'));
+        poster.post('a');
+        assert_eq!(fsm.queue_depth(), 4);
+    }
+}
@@ -0,0 +1,329 @@
+//! Translating application commands into events.
+//!
+//! A [`CommandMapper`] holds the functions turning a command of the
+//! application's own type into an event, given a context such as the
+//! current user. They are tried in the order they were added: the first
+//! to return an event wins, one returning `MapError::Unmapped` passes the
+//! command on, and one returning `MapError::Invalid` rejects it outright.
+//! Registered on a machine, the mapper lets `handle_command` map, trigger
+//! and report either kind of failure as one [`CommandError`] carrying the
+//! command that caused it.
+
+use std::{
+    any::Any,
+    fmt::{self, Debug, Display},
+    hash::Hash
+};
+
+use crate::{error::TransitionError, fsm::StateMachine, origin::Origin};
+
+
+/// Returned by a mapping function that produces no event.
+///
+/// Variants may be added, so matches outside this crate need a wildcard:
+///
+/// ```compile_fail
+/// use pfsm::command::MapError;
+///
+/// fn retry(error: &MapError) -> bool
+/// {
+///     match error {
+///         MapError::Unmapped => true,
+///         MapError::Invalid(_) => false
+///     }
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MapError {
+    /// The function does not handle this command.
+    Unmapped,
+    /// The command must not reach the machine at all.
+    Invalid(String)
+}
+
+
+impl MapError {
+    /// Returns the numeric code listed in [`ERROR_CODES`](crate::error::ERROR_CODES).
+    pub fn code(&self) -> u16
+    {
+        match self {
+            MapError::Unmapped => 604,
+            MapError::Invalid(_) => 605
+        }
+    }
+}
+
+
+type MapFn<Cmd, E, C> = Box<dyn Fn(&Cmd, &C) -> Result<E, MapError>>;
+
+
+/// Mapping functions from commands `Cmd` to events `E` in a context `C`.
+pub struct CommandMapper<Cmd, E, C> {
+    maps: Vec<MapFn<Cmd, E, C>>
+}
+
+
+impl<Cmd, E, C> Default for CommandMapper<Cmd, E, C> {
+    fn default() -> Self
+    {
+        Self{ maps: Vec::new() }
+    }
+}
+
+
+impl<Cmd, E, C> CommandMapper<Cmd, E, C> {
+    pub fn new() -> Self
+    {
+        Self::default()
+    }
+
+
+    /// Adds a mapping function, tried after the ones added before.
+    pub fn map(mut self, map: impl Fn(&Cmd, &C) -> Result<E, MapError> + 'static) -> Self
+    {
+        self.maps.push(Box::new(map));
+        self
+    }
+
+
+    /// Returns the event for `command`, from the first function mapping
+    /// it.
+    pub fn event_for(&self, command: &Cmd, context: &C) -> Result<E, MapError>
+    {
+        for map in &self.maps {
+            match map(command, context) {
+                Err(MapError::Unmapped) => continue,
+                result => return result
+            }
+        }
+        Err(MapError::Unmapped)
+    }
+}
+
+
+/// A command that moved the machine.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Handled<S> {
+    pub from: S,
+    pub to: S
+}
+
+
+/// Why `handle_command` did not apply a command.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CommandError<Cmd, S, E> {
+    /// No mapper for this command and context type is registered.
+    NoMapper { command: Cmd },
+    /// No mapping function handles the command.
+    Unmapped { command: Cmd },
+    /// A mapping function rejected the command.
+    Invalid { command: Cmd, reason: String },
+    /// The command mapped to `event`, which the machine rejected.
+    Rejected { command: Cmd, event: E, error: TransitionError<S, E> }
+}
+
+
+impl<Cmd: Debug, S: Debug, E: Debug> Display for CommandError<Cmd, S, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        match self {
+            CommandError::NoMapper { command } => {
+                write!(f, "no command mapper registered for command {command:?}")
+            }
+            CommandError::Unmapped { command } => {
+                write!(f, "command {command:?} does not map to any event")
+            }
+            CommandError::Invalid { command, reason } => {
+                write!(f, "command {command:?} is invalid: {reason}")
+            }
+            CommandError::Rejected { command, event, error } => {
+                write!(f, "command {command:?} mapped to event {event:?}: {error}")
+            }
+        }
+    }
+}
+
+
+impl<Cmd: Debug, S: Debug, E: Debug> std::error::Error for CommandError<Cmd, S, E> {}
+
+
+impl<Cmd, S, E> CommandError<Cmd, S, E> {
+    /// Returns the numeric code listed in
+    /// [`ERROR_CODES`](crate::error::ERROR_CODES); a rejected command
+    /// reports the code of the transition error.
+    pub fn code(&self) -> u16
+    {
+        match self {
+            CommandError::NoMapper { .. } => 601,
+            CommandError::Unmapped { .. } => 602,
+            CommandError::Invalid { .. } => 603,
+            CommandError::Rejected { error, .. } => error.code()
+        }
+    }
+
+
+    pub fn command(&self) -> &Cmd
+    {
+        match self {
+            CommandError::NoMapper { command }
+            | CommandError::Unmapped { command }
+            | CommandError::Invalid { command, .. }
+            | CommandError::Rejected { command, .. } => command
+        }
+    }
+}
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug + 'static
+{
+    /// Registers the mapper used by `handle_command`, replacing any other.
+    pub fn set_command_mapper<Cmd: 'static, C: 'static>(
+        &mut self,
+        mapper: CommandMapper<Cmd, E, C>
+    )
+    {
+        self.command_mapper = Some(Box::new(mapper));
+    }
+
+
+    /// Maps `command` in `context` to an event and triggers it.
+    pub fn handle_command<Cmd: 'static, C: 'static>(
+        &mut self,
+        command: Cmd,
+        context: &mut C
+    ) -> Result<Handled<S>, CommandError<Cmd, S, E>>
+    {
+        let mapper = self
+            .command_mapper
+            .as_deref()
+            .and_then(<dyn Any>::downcast_ref::<CommandMapper<Cmd, E, C>>);
+        let Some(mapper) = mapper else {
+            return Err(CommandError::NoMapper{ command });
+        };
+        let event = match mapper.event_for(&command, context) {
+            Ok(event) => event,
+            Err(MapError::Unmapped) => return Err(CommandError::Unmapped{ command }),
+            Err(MapError::Invalid(reason)) => return Err(CommandError::Invalid{ command, reason })
+        };
+        let from = self.state;
+
+        match self.trigger_from(event, Origin::new("command")) {
+            Ok(()) => Ok(Handled{ from, to: self.state }),
+            Err(error) => Err(CommandError::Rejected{ command, event, error })
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{builder::StateMachineBuilder, fsm::FSM};
+
+
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    enum Command {
+        Submit,
+        Approve { by: u32 },
+        Reject { reason: String },
+        Archive
+    }
+
+
+    struct Session {
+        user: u32
+    }
+
+
+    fn review() -> StateMachine<&'static str, &'static str>
+    {
+        let mut fsm = StateMachineBuilder::new("draft")
+            .transition("draft", "submit", "pending")
+            .transition("pending", "approve", "approved")
+            .transition("pending", "reject", "draft")
+            .build()
+            .unwrap();
+        let mapper = CommandMapper::new()
+            .map(|command, _: &Session| match command {
+                Command::Submit => Ok("submit"),
+                Command::Reject { reason } if reason.is_empty() => {
+                    Err(MapError::Invalid("a rejection needs a reason".to_string()))
+                }
+                Command::Reject { .. } => Ok("reject"),
+                _ => Err(MapError::Unmapped)
+            })
+            .map(|command, session| match command {
+                Command::Approve { by } if *by == session.user => {
+                    Err(MapError::Invalid("authors cannot approve their own work".to_string()))
+                }
+                Command::Approve { .. } => Ok("approve"),
+                _ => Err(MapError::Unmapped)
+            });
+
+        fsm.set_command_mapper(mapper);
+        fsm
+    }
+
+
+    #[test]
+    fn test_mapped_commands_move_the_machine()
+    {
+        let mut fsm = review();
+        let mut session = Session{ user: 7 };
+
+        assert_eq!(
+            fsm.handle_command(Command::Submit, &mut session),
+            Ok(Handled{ from: "draft", to: "pending" })
+        );
+        let handled = fsm.handle_command(Command::Approve{ by: 8 }, &mut session);
+        assert_eq!(handled.unwrap().to, "approved");
+    }
+
+
+    #[test]
+    fn test_mapper_rejections()
+    {
+        let mut fsm = review();
+        let mut session = Session{ user: 7 };
+
+        fsm.handle_command(Command::Submit, &mut session).unwrap();
+        let error = fsm.handle_command(Command::Approve{ by: 7 }, &mut session).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "command Approve { by: 7 } is invalid: authors cannot approve their own work"
+        );
+        assert_eq!(error.code(), 603);
+        assert_eq!(
+            fsm.handle_command(Command::Archive, &mut session),
+            Err(CommandError::Unmapped{ command: Command::Archive })
+        );
+        assert_eq!(
+            fsm.handle_command(Command::Submit, &mut 0u8),
+            Err(CommandError::NoMapper{ command: Command::Submit })
+        );
+        assert_eq!(fsm.state(), "pending");
+    }
+
+
+    #[test]
+    fn test_mapped_command_rejected_by_machine()
+    {
+        let mut fsm = review();
+        let command = Command::Reject{ reason: "typos".to_string() };
+        let error = fsm.handle_command(command.clone(), &mut Session{ user: 1 }).unwrap_err();
+
+        assert_eq!(
+            error,
+            CommandError::Rejected{
+                command: command.clone(),
+                event: "reject",
+                error: TransitionError::NoTransition{ state: "draft", event: "reject" }
+            }
+        );
+        assert_eq!((error.command(), error.code()), (&command, 201));
+    }
+}
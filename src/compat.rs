@@ -0,0 +1,207 @@
+//! Deprecated shims keeping the pre-1.0 API compiling during migration.
+//!
+//! Before typed errors, `FSM::trigger` returned `Result<(), String>`.
+//! Code written against that signature can switch its import from
+//! `pfsm::fsm::FSM` to `pfsm::compat::FSM` and keep building, then move
+//! to `TransitionError` call site by call site. The shims will be removed
+//! two releases after their deprecation.
+#![allow(deprecated)]
+
+use std::{collections::HashMap, fmt::Debug, hash::Hash};
+
+pub use crate::fsm::{Action, StateMachine, Transition};
+use crate::fsm;
+
+
+/// The pre-1.0 machine trait, with string errors.
+#[deprecated(note = "use `pfsm::fsm::FSM`, whose `trigger` returns `TransitionError`")]
+pub trait FSM<S: Copy, E: Copy> {
+    fn initialize(initial: S,
+                  transitions: HashMap<(S, E), Transition<S>>) -> Self;
+
+
+    /// Triggers an event; the error is the typed error's `Display` text.
+    fn trigger(&mut self, event: E) -> Result<(), String>;
+
+
+    fn state(&self) -> S;
+}
+
+
+impl<S, E> FSM<S, E> for StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    fn initialize(
+        initial: S,
+        transitions: HashMap<(S, E), Transition<S>>
+    ) -> Self
+    {
+        <Self as fsm::FSM<S, E>>::initialize(initial, transitions)
+    }
+
+
+    fn trigger(&mut self, event: E) -> Result<(), String>
+    {
+        self.trigger_legacy(event)
+    }
+
+
+    fn state(&self) -> S
+    {
+        <Self as fsm::FSM<S, E>>::state(self)
+    }
+}
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    /// `trigger` with the error formatted as a string, as before 1.0.
+    #[deprecated(note = "use `FSM::trigger` and match on `TransitionError`")]
+    pub fn trigger_legacy(&mut self, event: E) -> Result<(), String>
+    {
+        <Self as fsm::FSM<S, E>>::trigger(self, event).map_err(|err| err.to_string())
+    }
+}
+
+
+/// The original `fsm` unit tests, unchanged, run against the shims.
+#[cfg(test)]
+#[allow(clippy::enum_variant_names, clippy::nonminimal_bool)]
+mod test {
+    use super::*;
+
+
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    enum TrafficLightState {
+        Red,
+        Yellow,
+        Green
+    }
+
+
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    enum TrafficLightEvent {
+        RedTimeout,
+        Yellow2GreenTimeout,
+        Yellow2RedTimeout,
+        GreenTimeout
+    }
+
+
+    type State = TrafficLightState;
+    type Event = TrafficLightEvent;
+
+
+    struct TrafficLight {
+        fsm: StateMachine<TrafficLightState, TrafficLightEvent>
+    }
+
+
+    impl TrafficLight {
+        fn init_fsm(
+            initial: State,
+            transitions: HashMap<(State, Event), Transition<State>>
+        ) -> Self
+        {
+            Self{ fsm: StateMachine::initialize(initial, transitions) }
+        }
+    }
+
+
+    fn create_traffic_light() -> TrafficLight
+    {
+        let initial = State::Red;
+        let mut transitions: HashMap<(State, Event), Transition<State>> =
+            HashMap::new();
+
+        transitions.insert(
+            (State::Red, Event::RedTimeout),
+            Transition::create(
+                State::Yellow,
+                Some(Box::new(|| action("Red -> Yellow")))
+            )
+        );
+
+        transitions.insert(
+            (State::Yellow, Event::Yellow2GreenTimeout),
+            Transition::create(
+                State::Green,
+                Some(Box::new(|| action("Yellow -> Green")))
+            )
+        );
+
+        transitions.insert(
+            (State::Green, Event::GreenTimeout),
+            Transition::create(
+                State::Yellow,
+                Some(Box::new(|| action("Green -> Yellow")))
+            )
+        );
+
+        transitions.insert(
+            (State::Yellow, Event::Yellow2RedTimeout),
+            Transition::create(
+                State::Red,
+                Some(Box::new(|| action("Yellow -> Red")))
+            )
+        );
+
+        TrafficLight::init_fsm(initial, transitions)
+    }
+
+
+    fn action(msg: &str)
+    {
+        println!("{msg}");
+    }
+
+
+    #[test]
+    fn test_initialize()
+    {
+        let tl = create_traffic_light();
+        assert_eq!(tl.fsm.state(), State::Red);
+    }
+
+
+    #[test]
+    fn test_trigger()
+    {
+        let mut tl = create_traffic_light();
+
+        assert!(tl.fsm.trigger(Event::RedTimeout).is_ok());
+        assert_eq!(tl.fsm.state(), State::Yellow);
+        
+        assert!(tl.fsm.trigger(Event::Yellow2GreenTimeout).is_ok());
+        assert_eq!(tl.fsm.state(), State::Green);
+
+        assert!(tl.fsm.trigger(Event::GreenTimeout).is_ok());
+        assert_eq!(tl.fsm.state(), State::Yellow);
+
+        assert!(tl.fsm.trigger(Event::Yellow2RedTimeout).is_ok());
+        assert_eq!(tl.fsm.state(), State::Red);
+    }
+
+
+    #[test]
+    fn test_incorrect_trigger()
+    {
+        let mut tl = create_traffic_light();
+
+        assert_eq!(tl.fsm.state(), State::Red);
+        assert!(!tl.fsm.trigger(Event::GreenTimeout).is_ok());
+    }
+
+
+    #[test]
+    fn test_incorrect_trigger_message()
+    {
+        let mut tl = create_traffic_light();
+
+        assert_eq!(
+            tl.fsm.trigger(Event::GreenTimeout),
+            Err("No transition found for event 'GreenTimeout' from state 'Red'".to_string())
+        );
+    }
+}
@@ -0,0 +1,137 @@
+use std::{fmt::{self, Debug, Display}, hash::Hash};
+
+use crate::fsm::StateMachine;
+
+
+/// The full set of active states of a machine.
+///
+/// Each region is one path of active states ordered from root to leaf.
+/// A flat machine has a single region holding a single state.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Configuration<S> {
+    regions: Vec<Vec<S>>
+}
+
+
+impl<S> Configuration<S> {
+    pub fn flat(state: S) -> Self
+    {
+        Self{ regions: vec![vec![state]] }
+    }
+
+
+    /// Builds a configuration from root-to-leaf paths, one per region.
+    pub fn new(regions: Vec<Vec<S>>) -> Self
+    {
+        Self{ regions }
+    }
+
+
+    pub fn regions(&self) -> &[Vec<S>]
+    {
+        &self.regions
+    }
+
+
+    /// Returns the innermost active state of every region.
+    pub fn leaves(&self) -> impl Iterator<Item = &S>
+    {
+        self.regions.iter().filter_map(|path| path.last())
+    }
+
+
+    /// Returns `true` if `state` is active at any level of any region.
+    pub fn contains(&self, state: &S) -> bool
+    where S: PartialEq
+    {
+        self.regions.iter().any(|path| path.contains(state))
+    }
+}
+
+
+/// Renders paths as `Active/Playing/Buffering` and separates regions
+/// with ` | `.
+impl<S: Debug> Display for Configuration<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        for (i, path) in self.regions.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" | ")?;
+            }
+            for (j, state) in path.iter().enumerate() {
+                if j > 0 {
+                    f.write_str("/")?;
+                }
+                write!(f, "{state:?}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    /// Returns the active configuration; always a single state for this
+    /// flat machine, so callers can code against the same API as
+    /// region-based machines.
+    pub fn configuration(&self) -> Configuration<S>
+    {
+        Configuration::flat(self.state)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{builder::StateMachineBuilder, fsm::FSM};
+
+
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    enum State {
+        Active,
+        Playing,
+        Buffering,
+        Power,
+        OnBattery
+    }
+
+
+    #[test]
+    fn test_flat_configuration()
+    {
+        let mut fsm = StateMachineBuilder::new(State::Playing)
+            .transition(State::Playing, 'b', State::Buffering)
+            .build()
+            .unwrap();
+
+        assert_eq!(fsm.configuration(), Configuration::flat(State::Playing));
+        fsm.trigger('b').unwrap();
+        assert_eq!(fsm.configuration().to_string(), "Buffering");
+        assert_eq!(fsm.configuration().leaves().collect::<Vec<_>>(), [&State::Buffering]);
+    }
+
+
+    #[test]
+    fn test_nested_and_parallel_rendering()
+    {
+        let nested = Configuration::new(vec![
+            vec![State::Active, State::Playing, State::Buffering]
+        ]);
+        let parallel = Configuration::new(vec![
+            vec![State::Active, State::Playing, State::Buffering],
+            vec![State::Power, State::OnBattery]
+        ]);
+
+        assert_eq!(nested.to_string(), "Active/Playing/Buffering");
+        assert_eq!(parallel.to_string(), "Active/Playing/Buffering | Power/OnBattery");
+        assert!(parallel.contains(&State::Power));
+        assert!(!nested.contains(&State::Power));
+        assert_eq!(
+            parallel.leaves().collect::<Vec<_>>(),
+            [&State::Buffering, &State::OnBattery]
+        );
+    }
+}
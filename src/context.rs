@@ -0,0 +1,151 @@
+//! Guard data looked up from outside the machine.
+//!
+//! When guard inputs are owned by another subsystem, a context provider
+//! is called at the start of every dispatch (and by `explain` and
+//! `peek`) to take a read-only snapshot. Guards built through the
+//! returned [`GuardContext`] read that snapshot, so every guard and choice
+//! candidate of one dispatch sees the same facts. Actions are unaffected
+//! and keep using whatever state they captured.
+
+use std::{cell::RefCell, fmt::Debug, hash::Hash, rc::Rc};
+
+use crate::fsm::{Guard, StateMachine};
+
+
+/// Source of guard snapshots.
+pub trait ContextProvider<C> {
+    fn snapshot(&self) -> C;
+}
+
+
+impl<C, F: Fn() -> C> ContextProvider<C> for F {
+    fn snapshot(&self) -> C
+    {
+        self()
+    }
+}
+
+
+struct Shared<C> {
+    provider: Box<dyn ContextProvider<C>>,
+    snapshot: RefCell<Option<C>>
+}
+
+
+/// Handle for building guards over a machine's provided context.
+pub struct GuardContext<C> {
+    shared: Rc<Shared<C>>
+}
+
+
+impl<C> Clone for GuardContext<C> {
+    fn clone(&self) -> Self
+    {
+        Self{ shared: self.shared.clone() }
+    }
+}
+
+
+impl<C: 'static> GuardContext<C> {
+    /// Builds a guard evaluating `f` against the current snapshot.
+    pub fn guard(&self, f: impl Fn(&C) -> bool + 'static) -> Guard
+    {
+        let context = self.clone();
+        Box::new(move || context.with(&f))
+    }
+
+
+    /// Runs `f` on the current snapshot, taking one first if needed.
+    pub fn with<T>(&self, f: impl FnOnce(&C) -> T) -> T
+    {
+        let mut snapshot = self.shared.snapshot.borrow_mut();
+        f(snapshot.get_or_insert_with(|| self.shared.provider.snapshot()))
+    }
+
+
+    fn refresh(&self)
+    {
+        let snapshot = self.shared.provider.snapshot();
+        *self.shared.snapshot.borrow_mut() = Some(snapshot);
+    }
+}
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    /// Installs `provider`, replacing any previous one, and returns the
+    /// handle guards should be built from.
+    pub fn set_context_provider<C: 'static>(
+        &mut self,
+        provider: impl ContextProvider<C> + 'static
+    ) -> GuardContext<C>
+    {
+        let context = GuardContext{
+            shared: Rc::new(Shared{ provider: Box::new(provider), snapshot: RefCell::new(None) })
+        };
+        let refreshed = context.clone();
+
+        self.refresh_context = Some(Box::new(move || refreshed.refresh()));
+        context
+    }
+
+
+    pub(crate) fn refresh_context(&self)
+    {
+        if let Some(refresh) = &self.refresh_context {
+            refresh();
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        builder::StateMachineBuilder,
+        choice::{Candidate, SelectionMode},
+        fsm::{Transition, FSM}
+    };
+    use std::{cell::Cell, rc::Rc};
+
+
+    #[derive(Clone, Debug)]
+    struct Facts {
+        load: u32
+    }
+
+
+    #[test]
+    fn test_snapshot_taken_per_dispatch()
+    {
+        let load = Rc::new(Cell::new(10));
+        let snapshots = Rc::new(Cell::new(0));
+        let mut fsm = StateMachineBuilder::new("idle")
+            .transition("scaled", "settle", "idle")
+            .transition("steady", "settle", "idle")
+            .build()
+            .unwrap();
+
+        let (l, n) = (load.clone(), snapshots.clone());
+        let facts = fsm.set_context_provider(move || {
+            n.set(n.get() + 1);
+            Facts{ load: l.get() }
+        });
+        fsm.add_transition("idle", "check", Transition::choice(SelectionMode::FirstGuardWins, vec![
+            Candidate::new("scaled").with_guard(facts.guard(|f| f.load > 50)),
+            Candidate::new("steady").with_guard(facts.guard(|f| f.load <= 50))
+        ]));
+
+        fsm.trigger("check").unwrap();
+        assert_eq!(fsm.state(), "steady");
+        assert_eq!(snapshots.get(), 1);
+
+        fsm.trigger("settle").unwrap();
+        load.set(80);
+        assert_eq!(fsm.peek("check"), [("scaled", 1.0)]);
+        fsm.trigger("check").unwrap();
+        assert_eq!(fsm.state(), "scaled");
+        assert_eq!(facts.with(|f| f.load), 80);
+    }
+}
@@ -0,0 +1,75 @@
+//! Application data attached to states.
+//!
+//! Each state has one slot holding a value of any type. Slots follow
+//! `rename_state`, are dropped by `remove_state` and show up in
+//! `describe` as presence flags only.
+
+use std::{any::Any, fmt::Debug, hash::Hash};
+
+use crate::fsm::StateMachine;
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    /// Stores `value` in the slot of `state`, replacing whatever was there.
+    pub fn set_state_data<T: Any>(&mut self, state: S, value: T)
+    {
+        self.state_data.insert(state, Box::new(value));
+    }
+
+
+    /// Returns the data of `state`, or `None` if it has none or it is not
+    /// a `T`.
+    pub fn state_data<T: Any>(&self, state: &S) -> Option<&T>
+    {
+        self.state_data.get(state)?.downcast_ref()
+    }
+
+
+    pub fn state_data_mut<T: Any>(&mut self, state: &S) -> Option<&mut T>
+    {
+        self.state_data.get_mut(state)?.downcast_mut()
+    }
+
+
+    /// Empties the slot of `state`, returning whether it held anything.
+    pub fn clear_state_data(&mut self, state: &S) -> bool
+    {
+        self.state_data.remove(state).is_some()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::builder::StateMachineBuilder;
+
+
+    #[test]
+    fn test_typed_slots_follow_renames()
+    {
+        let mut fsm = StateMachineBuilder::new('a')
+            .transition('a', 1, 'b')
+            .transition('b', 2, 'c')
+            .build()
+            .unwrap();
+
+        fsm.set_state_data('a', 7u32);
+        fsm.set_state_data('b', "chime.wav");
+        assert_eq!(fsm.state_data::<u32>(&'a'), Some(&7));
+        assert_eq!(fsm.state_data::<&str>(&'b'), Some(&"chime.wav"));
+        assert_eq!(fsm.state_data::<u64>(&'a'), None);
+        assert_eq!(fsm.state_data::<u32>(&'c'), None);
+        assert_eq!(fsm.describe().with_data, ['a', 'b']);
+
+        *fsm.state_data_mut::<u32>(&'a').unwrap() += 1;
+        fsm.rename_state(&'a', 'z').unwrap();
+        assert_eq!(fsm.state_data::<u32>(&'z'), Some(&8));
+        assert_eq!(fsm.state_data::<u32>(&'a'), None);
+
+        assert_eq!(fsm.remove_state(&'b'), Some(2));
+        assert_eq!(fsm.state_data::<&str>(&'b'), None);
+        assert!(!fsm.clear_state_data(&'b'));
+    }
+}
@@ -0,0 +1,256 @@
+//! Machine-wide expiry, independent of the current state.
+//!
+//! A deadline is a point in the machine's clock time and what to do once
+//! it has passed: post an expiry event, processed like any other, or
+//! enter an expiry state directly, running its entry action but no exit
+//! or transition action. It is checked by `tick`, before every triggered
+//! event, and as a timer step of `run_until_quiescent`, and handled at
+//! most once. Entering the expiry state takes no event, so it is counted
+//! and observed by `on_finish` but not traced, and subscriptions and
+//! observers, which are told the event responsible, are not notified.
+
+use std::{fmt::Debug, hash::Hash, time::{Duration, Instant}};
+
+use crate::{
+    cascade,
+    error::TransitionError,
+    fsm::{call, StateMachine},
+    origin::Origin
+};
+
+
+/// What a machine does once its deadline has passed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeadlineAction<S, E> {
+    /// Process this event, with `Origin::deadline()`.
+    Post(E),
+    /// Move to this state, running its entry action.
+    Enter(S)
+}
+
+
+/// When a captured deadline falls, as chosen by the
+/// `absolute_deadline_snapshots` policy.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeadlineTime {
+    /// Time left when the snapshot was taken; restoring counts it from
+    /// the restoring machine's clock.
+    Remaining(Duration),
+    /// The instant itself, only meaningful with the same clock.
+    At(Instant)
+}
+
+
+/// A deadline captured by `snapshot`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DeadlineSnapshot<S, E> {
+    pub time: DeadlineTime,
+    pub action: DeadlineAction<S, E>
+}
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    /// Sets the deadline, replacing any previous one.
+    pub fn set_deadline(&mut self, at: Instant, action: DeadlineAction<S, E>)
+    {
+        self.deadline = Some((at, action));
+    }
+
+
+    /// Removes the deadline; returns whether there was one.
+    pub fn clear_deadline(&mut self) -> bool
+    {
+        self.deadline.take().is_some()
+    }
+
+
+    /// Returns the time left before the deadline, zero once it has passed,
+    /// or `None` without a pending deadline.
+    pub fn time_to_deadline(&self) -> Option<Duration>
+    {
+        self.deadline.map(|(at, _)| at.saturating_duration_since(self.clock.now()))
+    }
+
+
+    pub(crate) fn deadline_snapshot(&self) -> Option<DeadlineSnapshot<S, E>>
+    {
+        self.deadline.map(|(at, action)| {
+            let time = if self.policies.absolute_deadline_snapshots {
+                DeadlineTime::At(at)
+            } else {
+                DeadlineTime::Remaining(at.saturating_duration_since(self.clock.now()))
+            };
+            DeadlineSnapshot{ time, action }
+        })
+    }
+
+
+    pub(crate) fn restore_deadline(&mut self, snapshot: Option<DeadlineSnapshot<S, E>>)
+    {
+        self.deadline = snapshot.map(|DeadlineSnapshot{ time, action }| match time {
+            DeadlineTime::Remaining(left) => (self.clock.now() + left, action),
+            DeadlineTime::At(at) => (at, action)
+        });
+    }
+
+
+    /// Removes and returns the deadline's action if the deadline has
+    /// passed.
+    pub(crate) fn take_due_deadline(&mut self) -> Option<DeadlineAction<S, E>>
+    {
+        match self.deadline {
+            Some((at, action)) if self.clock.now() >= at => {
+                self.deadline = None;
+                Some(action)
+            }
+            _ => None
+        }
+    }
+
+
+    /// Handles a passed deadline in a dispatch of its own; returns whether
+    /// it had passed.
+    pub(crate) fn expire_if_due(&mut self) -> Result<bool, TransitionError<S, E>>
+    {
+        let Some(action) = self.take_due_deadline() else {
+            return Ok(false);
+        };
+        self.dispatch(Origin::deadline(), |fsm| fsm.expire(action)).map(|_| true)
+    }
+
+
+    /// Takes the deadline's action within the current dispatch.
+    pub(crate) fn expire(
+        &mut self,
+        action: DeadlineAction<S, E>
+    ) -> Result<(), TransitionError<S, E>>
+    {
+        let target = match action {
+            DeadlineAction::Post(event) => return self.fire_one(event, true),
+            DeadlineAction::Enter(target) => target
+        };
+        let limit = self.policies.cascade_limit;
+
+        if let Some(entry) = self.entry_actions.get(&target) {
+            cascade::spend(&mut self.dispatch_stats, limit, target, true)?;
+            call(self.policies.catch_panics, entry)?;
+        }
+        self.state = target;
+        self.entered_at = self.clock.now();
+        self.sequence += 1;
+
+        if self.terminals.contains(&self.state)
+            && let Some(on_finish) = self.on_finish.take()
+        {
+            on_finish(&self.state);
+        }
+
+        #[cfg(feature = "paranoid")]
+        self.check_invariants("deadline");
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        builder::StateMachineBuilder,
+        clock::{Clock, MockClock},
+        fsm::FSM,
+        policy::Policies
+    };
+    use std::{cell::Cell, rc::Rc, sync::Arc};
+
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+
+    fn order(
+        clock: &Arc<MockClock>,
+        expired: &Rc<Cell<u32>>
+    ) -> StateMachine<&'static str, &'static str>
+    {
+        let e = expired.clone();
+        let mut fsm = StateMachineBuilder::new("placed")
+            .transition("placed", "pay", "paid")
+            .transition("paid", "ship", "shipped")
+            .transition("placed", "expire", "cancelled")
+            .transition("paid", "expire", "refunded")
+            .transition("placed", "abandon", "expired")
+            .on_enter("expired", move || e.set(e.get() + 1))
+            .build()
+            .unwrap();
+
+        fsm.set_clock(clock.clone());
+        fsm.enable_trace(8);
+        fsm
+    }
+
+
+    #[test]
+    fn test_expiry_event_and_expiry_state()
+    {
+        let clock = Arc::new(MockClock::new());
+        let expired = Rc::new(Cell::new(0));
+        let mut fsm = order(&clock, &expired);
+
+        fsm.set_deadline(clock.now() + 30 * DAY, DeadlineAction::Post("expire"));
+        fsm.trigger("pay").unwrap();
+        clock.advance(29 * DAY);
+        assert_eq!(fsm.time_to_deadline(), Some(DAY));
+        assert_eq!(fsm.tick(), Ok(false));
+
+        clock.advance(DAY);
+        assert_eq!(fsm.tick(), Ok(true));
+        assert_eq!(fsm.state(), "refunded");
+        assert_eq!(fsm.trace().last().unwrap().origin, Origin::deadline());
+        assert_eq!(fsm.time_to_deadline(), None);
+
+        let mut fsm = order(&clock, &expired);
+        fsm.set_deadline(clock.now() + 30 * DAY, DeadlineAction::Enter("expired"));
+        clock.advance(31 * DAY);
+        assert_eq!(
+            fsm.trigger("pay"),
+            Err(TransitionError::NoTransition{ state: "expired", event: "pay" })
+        );
+        assert_eq!(expired.get(), 1);
+        assert_eq!(fsm.trace().count(), 1);
+    }
+
+
+    #[test]
+    fn test_deadline_survives_restore()
+    {
+        let clock = Arc::new(MockClock::new());
+        let expired = Rc::new(Cell::new(0));
+        let mut fsm = order(&clock, &expired);
+
+        fsm.set_deadline(clock.now() + 30 * DAY, DeadlineAction::Enter("expired"));
+        clock.advance(10 * DAY);
+        let relative = fsm.snapshot();
+        fsm.set_policies(Policies::default().with_absolute_deadline_snapshots(true));
+        let absolute = fsm.snapshot();
+        assert_eq!(relative.deadline.unwrap().time, DeadlineTime::Remaining(20 * DAY));
+
+        let later = Arc::new(MockClock::new());
+        let mut restored = order(&later, &expired);
+        later.advance(100 * DAY);
+        restored.restore(relative);
+        assert_eq!(restored.time_to_deadline(), Some(20 * DAY));
+        later.advance(20 * DAY);
+        assert_eq!(restored.tick(), Ok(true));
+        assert_eq!(restored.state(), "expired");
+
+        fsm.clear_deadline();
+        fsm.restore(absolute);
+        assert_eq!(fsm.time_to_deadline(), Some(20 * DAY));
+        clock.advance(20 * DAY);
+        assert!(!fsm.is_quiescent());
+        assert_eq!(fsm.run_until_quiescent(4).unwrap().timer_events, 1);
+        assert_eq!((fsm.state(), expired.get()), ("expired", 2));
+    }
+}
@@ -0,0 +1,141 @@
+//! Retiring states without stranding machines already sitting in them.
+//!
+//! Nothing may enter a deprecated state, but transitions out of it keep
+//! working. The builder rejects transitions into one; a state deprecated
+//! on a live machine makes such transitions fail with
+//! `TransitionError::TargetDeprecated` instead.
+
+use std::{fmt::Debug, hash::Hash};
+
+use crate::fsm::StateMachine;
+
+
+/// Findings of `restore` worth telling the caller about.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ResumeReport<S> {
+    /// Set when the restored state is deprecated: the machine may leave
+    /// it, but should not be expected to return.
+    pub deprecated_state: Option<S>
+}
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    /// Forbids entering `state` from now on.
+    pub fn deprecate_state(&mut self, state: S)
+    {
+        if self.deprecated.insert(state) {
+            self.generation += 1;
+        }
+    }
+
+
+    pub fn is_deprecated(&self, state: &S) -> bool
+    {
+        self.deprecated.contains(state)
+    }
+
+
+    /// Returns the deprecated states no machine can reach any more from
+    /// the initial state, ordered by `Debug` rendering: once no persisted
+    /// machine sits in them, they can be removed from the definition.
+    ///
+    /// Transitions into deprecated states are left in place by
+    /// `deprecate_state` but fail, so they do not count, and neither do
+    /// transitions out of a state only reachable through them.
+    pub fn removable_deprecated_states(&self) -> Vec<S>
+    {
+        let reachable = self.reachable_avoiding_deprecated();
+        let mut states: Vec<S> = self.deprecated
+            .iter()
+            .copied()
+            .filter(|state| *state != self.initial && !reachable.contains(state))
+            .collect();
+
+        states.sort_by_cached_key(|state| format!("{state:?}"));
+        states
+    }
+
+
+    pub(crate) fn resume_report(&self) -> ResumeReport<S>
+    {
+        ResumeReport{ deprecated_state: Some(self.state).filter(|s| self.deprecated.contains(s)) }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        builder::{BuildError, StateMachineBuilder},
+        error::TransitionError,
+        fsm::FSM,
+        snapshot::Snapshot
+    };
+
+
+    #[test]
+    fn test_build_rejects_entering_deprecated()
+    {
+        let result = StateMachineBuilder::<u8, char>::new(0)
+            .transition(0, 'a', 1)
+            .transition(0, 'b', 2)
+            .deprecate_state(2)
+            .build();
+
+        assert_eq!(
+            result.err(),
+            Some(BuildError::EntersDeprecated{ from: 0, event: 'b', to: 2 })
+        );
+    }
+
+
+    #[test]
+    fn test_deprecated_state_can_be_left_and_resumed()
+    {
+        let mut fsm = StateMachineBuilder::new(0)
+            .transition(0, 'a', 1)
+            .transition(1, 'b', 0)
+            .build()
+            .unwrap();
+
+        fsm.deprecate_state(1);
+        assert_eq!(
+            fsm.trigger('a'),
+            Err(TransitionError::TargetDeprecated{ state: 0, event: 'a', target: 1 })
+        );
+        assert_eq!(fsm.removable_deprecated_states(), [1]);
+
+        let report = fsm.restore(Snapshot{ state: 1, trace: Vec::new(), deadline: None });
+        assert_eq!(report.deprecated_state, Some(1));
+        fsm.trigger('b').unwrap();
+        assert_eq!(fsm.state(), 0);
+        assert_eq!(fsm.restore(fsm.snapshot()).deprecated_state, None);
+
+        fsm.remove_transition(0, 'a');
+        assert_eq!(fsm.removable_deprecated_states(), [1]);
+    }
+
+
+    #[test]
+    fn test_removable_through_deprecated_edges_only()
+    {
+        let mut fsm = StateMachineBuilder::new(0)
+            .transition(0, 'a', 1)
+            .transition(1, 'b', 2)
+            .transition(0, 'c', 3)
+            .transition(3, 'd', 2)
+            .build()
+            .unwrap();
+
+        fsm.deprecate_state(1);
+        assert_eq!(fsm.removable_deprecated_states(), [1]);
+
+        fsm.deprecate_state(2);
+        assert_eq!(fsm.removable_deprecated_states(), [1, 2]);
+        fsm.deprecate_state(3);
+        assert_eq!(fsm.removable_deprecated_states(), [1, 2, 3]);
+    }
+}
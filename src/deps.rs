@@ -0,0 +1,188 @@
+//! Guards with declared dependencies, cached across `available` calls.
+//!
+//! A guard registered with `guard_dep` names the pieces of context it
+//! reads. `available` reuses its last result until the application calls
+//! `invalidate` with one of those names. Guards without declared
+//! dependencies are evaluated on every call, and dispatches always
+//! evaluate every guard: the cache only serves `available`.
+//!
+//! The declaration must be complete; a guard reading anything it did not
+//! declare will go stale.
+
+use std::{cell::{Cell, RefCell}, fmt::Debug, hash::Hash, rc::Rc};
+
+use crate::fsm::{Guard, StateMachine};
+
+
+struct Slot {
+    deps: Vec<String>,
+    result: Option<bool>
+}
+
+
+#[derive(Default)]
+pub(crate) struct DepCache {
+    active: Cell<bool>,
+    slots: RefCell<Vec<Slot>>
+}
+
+
+/// Wraps `guard` so that `available` caches its result until one of
+/// `deps` is invalidated on `cache`'s machine.
+pub(crate) fn depend(
+    cache: &Rc<DepCache>,
+    deps: &[&str],
+    guard: impl Fn() -> bool + 'static
+) -> Guard
+{
+    let cache = cache.clone();
+    let id = {
+        let mut slots = cache.slots.borrow_mut();
+        slots.push(Slot{ deps: deps.iter().map(|d| d.to_string()).collect(), result: None });
+        slots.len() - 1
+    };
+
+    Box::new(move || {
+        if !cache.active.get() {
+            return guard();
+        }
+        if let Some(result) = cache.slots.borrow()[id].result {
+            return result;
+        }
+        let result = guard();
+        cache.slots.borrow_mut()[id].result = Some(result);
+        result
+    })
+}
+
+
+/// Serves cached results while alive, even if a guard unwinds.
+struct Active<'a>(&'a DepCache);
+
+
+impl<'a> Active<'a> {
+    fn set(cache: &'a DepCache) -> Self
+    {
+        cache.active.set(true);
+        Self(cache)
+    }
+}
+
+
+impl Drop for Active<'_> {
+    fn drop(&mut self)
+    {
+        self.0.active.set(false);
+    }
+}
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    /// Returns a guard depending on `deps` for this machine, for use with
+    /// `Transition::with_guard` or `Candidate::with_guard`.
+    pub fn dependent_guard(&self, deps: &[&str], guard: impl Fn() -> bool + 'static) -> Guard
+    {
+        depend(&self.guard_deps, deps, guard)
+    }
+
+
+    /// Marks the context named `key` as changed: guards depending on it
+    /// are evaluated again by the next `available`.
+    pub fn invalidate(&mut self, key: &str)
+    {
+        for slot in self.guard_deps.slots.borrow_mut().iter_mut() {
+            if slot.deps.iter().any(|dep| dep == key) {
+                slot.result = None;
+            }
+        }
+    }
+
+
+    /// Returns the events that would fire from the current state right
+    /// now, ordered by their `Debug` rendering.
+    ///
+    /// The checks are those of `explain`: an event passes if `explain`
+    /// would report it as firing, whether or not a breakpoint applies.
+    pub fn available(&self) -> Vec<E>
+    {
+        if self.is_finished() {
+            return Vec::new();
+        }
+        self.refresh_context();
+
+        let _active = Active::set(&self.guard_deps);
+        let mut events = self.valid_events();
+
+        events.retain(|event| self.would_fire(*event));
+        events
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::{builder::StateMachineBuilder, error::TransitionError, fsm::FSM, policy::Policies};
+    use std::{cell::Cell, panic::{self, AssertUnwindSafe}, rc::Rc};
+
+
+    #[test]
+    fn test_only_invalidated_guards_rerun()
+    {
+        let [credit, inventory, plain] = [(); 3].map(|_| Rc::new(Cell::new(0)));
+        let (c, i, p) = (credit.clone(), inventory.clone(), plain.clone());
+        let mut fsm = StateMachineBuilder::new(0)
+            .transition(0, 'a', 1)
+            .guard_dep(&["credit"], move || { c.set(c.get() + 1); true })
+            .transition(0, 'b', 1)
+            .guard_dep(&["inventory"], move || { i.set(i.get() + 1); false })
+            .transition(0, 'c', 1)
+            .guard(move || { p.set(p.get() + 1); true })
+            .build()
+            .unwrap();
+        let counts = || (credit.get(), inventory.get(), plain.get());
+
+        assert_eq!(fsm.available(), ['a', 'c']);
+        assert_eq!(fsm.available(), ['a', 'c']);
+        assert_eq!(counts(), (1, 1, 2));
+
+        fsm.invalidate("credit");
+        fsm.available();
+        assert_eq!(counts(), (2, 1, 3));
+
+        fsm.trigger('a').unwrap();
+        assert_eq!(counts(), (3, 1, 3));
+    }
+
+
+    #[test]
+    fn test_available_matches_trigger()
+    {
+        let mut fsm = StateMachineBuilder::new(0)
+            .transition(0, 'a', 1)
+            .feature_flag("beta")
+            .transition(0, 'b', 1)
+            .candidate_guard(|| false)
+            .alternative(2)
+            .candidate_guard(|| false)
+            .transition(0, 'c', 2)
+            .guard_dep(&["credit"], || panic!("guard failed"))
+            .transition(0, 'd', 2)
+            .build()
+            .unwrap();
+
+        fsm.set_policies(Policies{ flags_without_provider: false, ..Policies::default() });
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| fsm.available())).is_err());
+        assert!(!fsm.guard_deps.active.get());
+
+        fsm.set_policies(Policies{
+            flags_without_provider: false,
+            catch_panics: true,
+            ..Policies::default()
+        });
+        assert_eq!(fsm.available(), ['d']);
+        assert_eq!(fsm.trigger('a'), Err(TransitionError::GuardRejected{ state: 0, event: 'a' }));
+        assert_eq!(fsm.trigger('b'), Err(TransitionError::GuardRejected{ state: 0, event: 'b' }));
+    }
+}
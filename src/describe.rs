@@ -0,0 +1,320 @@
+use std::{cell::RefCell, fmt::{self, Debug}, hash::Hash, rc::Rc, time::Duration};
+
+use crate::fsm::{StateMachine, Transition};
+
+
+/// Structural summary of one transition.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct TransitionDescription<S, E> {
+    pub from: S,
+    pub event: E,
+    pub to: S,
+    pub guarded: bool,
+    pub has_action: bool,
+    pub cooldown: Option<Duration>,
+    pub enabled: bool,
+    pub group: Option<String>,
+    pub feature_flag: Option<String>,
+    /// Candidates of a choice, in declaration order; empty otherwise.
+    pub candidates: Vec<CandidateDescription<S>>
+}
+
+
+/// Structural summary of one candidate target of a choice.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct CandidateDescription<S> {
+    pub to: S,
+    pub weight: f64,
+    pub guarded: bool,
+    pub feature_flag: Option<String>
+}
+
+
+type Keys<S, E> = Rc<[(S, E)]>;
+
+
+/// Transition keys in the order used by `describe`, sorted once per
+/// structure generation.
+pub(crate) struct KeyOrder<S, E> {
+    cached: RefCell<Option<(u64, Keys<S, E>)>>
+}
+
+
+impl<S, E> Default for KeyOrder<S, E> {
+    fn default() -> Self
+    {
+        Self{ cached: RefCell::new(None) }
+    }
+}
+
+
+/// Structural summary of a machine, in a deterministic order.
+///
+/// States and transitions are ordered by their `Debug` rendering, so two
+/// descriptions of equivalent machines compare equal. Descriptions are
+/// only produced by `describe`:
+///
+/// ```compile_fail
+/// use pfsm::describe::MachineDescription;
+///
+/// let description: MachineDescription<u8, char> = MachineDescription{
+///     initial: 0,
+///     states: vec![0],
+///     transitions: Vec::new(),
+///     tags: Vec::new(),
+///     terminals: Vec::new(),
+///     with_data: Vec::new()
+/// };
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct MachineDescription<S, E> {
+    pub initial: S,
+    pub states: Vec<S>,
+    pub transitions: Vec<TransitionDescription<S, E>>,
+    pub tags: Vec<(S, Vec<String>)>,
+    pub terminals: Vec<S>,
+    /// States with something in their data slot.
+    pub with_data: Vec<S>
+}
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    /// Returns every state the machine knows about: the initial state,
+    /// transition endpoints, tagged and terminal states, ordered by `Debug`
+    /// rendering.
+    pub fn states(&self) -> Vec<S>
+    {
+        let mut states = vec![self.initial];
+
+        for ((from, _), transition) in &self.transitions {
+            states.push(*from);
+            states.extend(transition.targets());
+        }
+        states.extend(self.tags.keys().copied());
+        states.extend(self.terminals.iter().copied());
+
+        states.sort_by_cached_key(|state| format!("{state:?}"));
+        states.dedup();
+        states
+    }
+
+
+    pub fn describe(&self) -> MachineDescription<S, E>
+    {
+        let mut transitions = Vec::with_capacity(self.transitions.len());
+        self.transitions_sorted_into(&mut transitions);
+
+        let mut tags: Vec<(S, Vec<String>)> = self.tags
+            .iter()
+            .filter(|(_, tags)| !tags.is_empty())
+            .map(|(state, tags)| (*state, tags.clone()))
+            .collect();
+        tags.sort_by_cached_key(|(state, _)| format!("{state:?}"));
+
+        let mut terminals: Vec<S> = self.terminals.iter().copied().collect();
+        terminals.sort_by_cached_key(|state| format!("{state:?}"));
+
+        let mut with_data: Vec<S> = self.state_data.keys().copied().collect();
+        with_data.sort_by_cached_key(|state| format!("{state:?}"));
+
+        MachineDescription{
+            initial: self.initial,
+            states: self.states(),
+            transitions,
+            tags,
+            terminals,
+            with_data
+        }
+    }
+
+
+    /// Replaces the contents of `buffer` with every transition, in the
+    /// order used by `describe`, reusing its allocation.
+    pub fn transitions_sorted_into(&self, buffer: &mut Vec<TransitionDescription<S, E>>)
+    {
+        buffer.clear();
+        buffer.extend(
+            self.sorted_keys().iter().map(|key| describe_transition(key, &self.transitions[key]))
+        );
+    }
+
+
+    /// Returns up to `len` transitions starting at `offset`, in the order
+    /// used by `describe`. Only the requested page is materialised, and
+    /// the ordering is only recomputed after the structure changes.
+    pub fn transitions_page(&self, offset: usize, len: usize) -> Vec<TransitionDescription<S, E>>
+    {
+        self.sorted_keys()
+            .iter()
+            .skip(offset)
+            .take(len)
+            .map(|key| describe_transition(key, &self.transitions[key]))
+            .collect()
+    }
+
+
+    fn sorted_keys(&self) -> Keys<S, E>
+    {
+        let mut slot = self.key_order.cached.borrow_mut();
+
+        if let Some((generation, keys)) = &*slot
+            && *generation == self.generation
+        {
+            return keys.clone();
+        }
+        let mut keys: Vec<(S, E)> = self.transitions.keys().copied().collect();
+
+        keys.sort_by_cached_key(|(from, event)| format!("{from:?}\0{event:?}"));
+        let keys: Keys<S, E> = keys.into();
+        *slot = Some((self.generation, keys.clone()));
+        keys
+    }
+}
+
+
+pub(crate) fn describe_transition<S: Copy, E: Copy>(
+    &(from, event): &(S, E),
+    t: &Transition<S>
+) -> TransitionDescription<S, E>
+{
+    TransitionDescription{
+        from,
+        event,
+        to: t.next_state,
+        guarded: t.guard.is_some(),
+        has_action: t.action.is_some(),
+        cooldown: t.cooldown,
+        enabled: t.enabled,
+        group: t.group.clone(),
+        feature_flag: t.feature_flag.clone(),
+        candidates: t.candidates
+            .iter()
+            .map(|c| CandidateDescription{
+                to: c.to,
+                weight: c.weight,
+                guarded: c.guard.is_some(),
+                feature_flag: c.feature_flag.clone()
+            })
+            .collect()
+    }
+}
+
+
+/// Lists at most `debug_edge_limit` transitions, in the order used by
+/// `describe`, so that huge machines do not produce huge strings.
+impl<S, E> Debug for StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        struct Edges<'a, S: Copy, E: Copy>(&'a StateMachine<S, E>);
+
+        impl<S, E> Debug for Edges<'_, S, E>
+        where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+        {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+            {
+                let limit = self.0.policies.debug_edge_limit;
+                let mut list = f.debug_list();
+
+                for t in self.0.transitions_page(0, limit) {
+                    list.entry(&format_args!("{:?} -{:?}-> {:?}", t.from, t.event, t.to));
+                }
+                if self.0.transitions.len() > limit {
+                    list.entry(&format_args!("... {} more", self.0.transitions.len() - limit));
+                }
+                list.finish()
+            }
+        }
+
+        f.debug_struct("StateMachine")
+            .field("initial", &self.initial)
+            .field("state", &self.state)
+            .field("transitions", &Edges(self))
+            .finish_non_exhaustive()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::builder::StateMachineBuilder;
+
+
+    #[test]
+    fn test_describe_is_ordered()
+    {
+        let fsm = StateMachineBuilder::new('b')
+            .transition('c', 1, 'a')
+            .guard(|| true)
+            .transition('a', 2, 'b')
+            .action(|| {})
+            .tag('c', "cancellable")
+            .build()
+            .unwrap();
+        let description = fsm.describe();
+
+        assert_eq!(description.initial, 'b');
+        assert_eq!(description.states, ['a', 'b', 'c']);
+        assert_eq!(
+            description.transitions.iter().map(|t| (t.from, t.event, t.to)).collect::<Vec<_>>(),
+            [('a', 2, 'b'), ('c', 1, 'a')]
+        );
+        assert!(description.transitions[1].guarded);
+        assert!(description.transitions[0].has_action);
+        assert_eq!(description.tags, [('c', vec!["cancellable".to_string()])]);
+    }
+
+
+    #[test]
+    fn test_large_machine_debug_and_paging()
+    {
+        let fsm = crate::fixtures::complete_graph(45).build().unwrap();
+
+        let debug = format!("{fsm:?}");
+        assert!(debug.len() < 1024, "{} bytes", debug.len());
+        assert!(debug.contains("... 1948 more"));
+
+        let all = fsm.describe().transitions;
+        let mut paged = Vec::new();
+        let mut offset = 0;
+
+        loop {
+            let page = fsm.transitions_page(offset, 300);
+            if page.is_empty() {
+                break;
+            }
+            offset += page.len();
+            paged.extend(page);
+        }
+        assert_eq!(paged.len(), 1980);
+        assert_eq!(paged, all);
+
+        let mut buffer = Vec::with_capacity(4096);
+        let capacity = buffer.capacity();
+        fsm.transitions_sorted_into(&mut buffer);
+        assert_eq!(buffer, all);
+        assert_eq!(buffer.capacity(), capacity);
+    }
+
+
+    #[test]
+    fn test_page_order_follows_structure_changes()
+    {
+        let mut fsm = StateMachineBuilder::new('a')
+            .transition('b', 1, 'a')
+            .transition('a', 1, 'b')
+            .build()
+            .unwrap();
+
+        assert_eq!(fsm.transitions_page(0, 1)[0].from, 'a');
+        fsm.remove_transition('a', 1);
+        assert_eq!(fsm.transitions_page(0, 5).len(), 1);
+        assert_eq!(fsm.transitions_page(0, 1)[0].from, 'b');
+    }
+}
@@ -0,0 +1,237 @@
+//! Checking that a machine reproduces its behaviour exactly.
+//!
+//! [`determinism_check`] builds several fresh machines from one factory
+//! and seed, feeds each the same events and compares what every step did,
+//! including the internal events traced along the way. Timestamps are
+//! left out: they are expected to differ.
+
+use std::{fmt::{self, Debug, Display}, hash::Hash};
+
+use crate::{
+    error::TransitionError,
+    fsm::{StateMachine, FSM},
+    origin::Origin,
+    trace::TraceOutcome
+};
+
+
+/// The feature most likely to explain a divergence, judged from what the
+/// machines use.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LikelyCause {
+    /// The fresh machines did not start with the same generator state.
+    UnseededRng,
+    /// Cooldowns or timeouts make the behaviour depend on the clock.
+    WallClock,
+    /// Neither of the above is in use, which leaves closures iterating
+    /// hash maps, or reading other state the factory does not reset.
+    HashIterationOrder
+}
+
+
+/// One traced event, without its timestamp.
+pub type TracedStep<S, E> = (S, E, TraceOutcome<S, E>, Origin);
+
+
+/// What one triggered event did. Fields may be added, so it cannot be
+/// built outside this crate:
+///
+/// ```compile_fail
+/// use pfsm::determinism::StepRecord;
+///
+/// let record: StepRecord<u8, char> = StepRecord{ result: Ok(1), trace: Vec::new() };
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct StepRecord<S, E> {
+    /// The state afterwards, or the rejection.
+    pub result: Result<S, TransitionError<S, E>>,
+    pub trace: Vec<TracedStep<S, E>>
+}
+
+
+/// First step at which run `run` did something else than run 0.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct DivergencePoint<S, E> {
+    pub run: usize,
+    pub step: usize,
+    pub expected: StepRecord<S, E>,
+    pub actual: StepRecord<S, E>,
+    pub likely_cause: LikelyCause
+}
+
+
+impl<S: Debug, E: Debug> Display for DivergencePoint<S, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(
+            f,
+            "Run {} diverged at step {} (likely cause: {:?})\n  expected: {:?}\n  actual:   {:?}",
+            self.run, self.step, self.likely_cause, self.expected, self.actual
+        )
+    }
+}
+
+
+/// Runs `events` through `runs` machines built by `table(seed)` and
+/// compares every step against the first run.
+pub fn determinism_check<S, E>(
+    table: impl Fn(u64) -> StateMachine<S, E>,
+    seed: u64,
+    events: &[E],
+    runs: usize
+) -> Result<(), DivergencePoint<S, E>>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    let machines: Vec<StateMachine<S, E>> = (0..runs).map(|_| table(seed)).collect();
+    let likely_cause = likely_cause(&machines);
+    let mut machines = machines.into_iter();
+    let Some(first) = machines.next() else {
+        return Ok(());
+    };
+    let expected = record(first, events);
+
+    for (run, fsm) in machines.enumerate() {
+        let actual = record(fsm, events);
+
+        if let Some(step) = (0..events.len()).find(|&i| expected[i] != actual[i]) {
+            return Err(DivergencePoint{
+                run: run + 1,
+                step,
+                expected: expected[step].clone(),
+                actual: actual[step].clone(),
+                likely_cause
+            });
+        }
+    }
+    Ok(())
+}
+
+
+fn record<S, E>(mut fsm: StateMachine<S, E>, events: &[E]) -> Vec<StepRecord<S, E>>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    let capacity = fsm.policies().internal_event_limit + 1;
+
+    events
+        .iter()
+        .map(|&event| {
+            fsm.enable_trace(capacity);
+            let result = fsm.trigger(event).map(|()| fsm.state());
+            let trace = fsm.trace()
+                .map(|e| (e.state, e.event, e.outcome.clone(), e.origin.clone()))
+                .collect();
+
+            StepRecord{ result, trace }
+        })
+        .collect()
+}
+
+
+fn likely_cause<S, E>(machines: &[StateMachine<S, E>]) -> LikelyCause
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    #[cfg(feature = "sim")]
+    if machines.windows(2).any(|m| format!("{:?}", m[0].rng) != format!("{:?}", m[1].rng)) {
+        return LikelyCause::UnseededRng;
+    }
+    let timed = |fsm: &StateMachine<S, E>| {
+        !fsm.timeouts.is_empty() || fsm.transitions.values().any(|t| t.cooldown.is_some())
+    };
+
+    if machines.iter().any(timed) {
+        LikelyCause::WallClock
+    } else {
+        LikelyCause::HashIterationOrder
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::builder::StateMachineBuilder;
+    use std::{cell::Cell, rc::Rc};
+
+
+    #[test]
+    fn test_deterministic_machine_passes()
+    {
+        let table = |_| {
+            let mut fsm = StateMachineBuilder::new(0)
+                .transition(0, 'a', 1)
+                .transition(1, 'b', 0)
+                .build()
+                .unwrap();
+            let poster = fsm.poster();
+
+            fsm.set_entry_action(1, Box::new(move || poster.post('b')));
+            fsm
+        };
+
+        assert_eq!(determinism_check(table, 7, &['a', 'a', 'b'], 3), Ok(()));
+    }
+
+
+    #[test]
+    fn test_shared_state_diverges()
+    {
+        let calls = Rc::new(Cell::new(0));
+        let table = |_| {
+            let calls = calls.clone();
+
+            StateMachineBuilder::new(0)
+                .transition(0, 'a', 1)
+                .guard(move || {
+                    calls.set(calls.get() + 1);
+                    calls.get() < 3
+                })
+                .transition(1, 'b', 0)
+                .build()
+                .unwrap()
+        };
+
+        let divergence = determinism_check(table, 0, &['a', 'b', 'a', 'b'], 2).unwrap_err();
+        assert_eq!((divergence.run, divergence.step), (1, 0));
+        assert_eq!(divergence.likely_cause, LikelyCause::HashIterationOrder);
+    }
+
+
+    #[cfg(feature = "sim")]
+    fn coin(rng_seed: u64) -> StateMachine<u8, char>
+    {
+        use crate::{choice::SelectionMode, sim::SimRng};
+
+        let mut fsm = StateMachineBuilder::new(0)
+            .transition(0, 'a', 1)
+            .alternative(2)
+            .select(SelectionMode::WeightedRandom)
+            .transition(1, 'b', 0)
+            .transition(2, 'b', 0)
+            .build()
+            .unwrap();
+
+        fsm.set_rng(SimRng::new(rng_seed));
+        fsm
+    }
+
+
+    #[cfg(feature = "sim")]
+    #[test]
+    fn test_unseeded_rng_flagged()
+    {
+        let events: Vec<char> = "ab".repeat(32).chars().collect();
+        assert_eq!(determinism_check(coin, 42, &events, 4), Ok(()));
+
+        let next = Cell::new(0);
+        let unseeded = |_| {
+            next.set(next.get() + 1);
+            coin(next.get())
+        };
+        let divergence = determinism_check(unseeded, 42, &events, 4).unwrap_err();
+
+        assert_eq!(divergence.likely_cause, LikelyCause::UnseededRng);
+        assert_ne!(divergence.expected.result, divergence.actual.result);
+    }
+}
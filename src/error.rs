@@ -0,0 +1,445 @@
+use std::{borrow::Cow, fmt::{self, Debug, Display}, time::Duration};
+
+use crate::trace::TraceEntry;
+
+
+/// Reason a triggered event did not move the machine.
+///
+/// Variants may be added, so matches outside this crate need a wildcard:
+///
+/// ```compile_fail
+/// use pfsm::error::TransitionError::{self, *};
+///
+/// fn retry(error: TransitionError<u8, char>) -> bool
+/// {
+///     match error {
+///         GuardRejected { .. } | CoolingDown { .. } | Cancelled { .. } => true,
+///         NoTransition { .. } | UnknownEvent { .. } | Disabled { .. } => false,
+///         MachineFinished { .. } | TargetDeprecated { .. } | CascadeOverflow { .. } => false,
+///         BreakpointAborted { .. } | TransitionPending { .. } | ActionPanicked { .. } => false,
+///         StaleFreeze { .. } | Closed | WithContext { .. } => false
+///     }
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TransitionError<S, E> {
+    NoTransition { state: S, event: E },
+    /// The event is not in the machine's declared alphabet.
+    UnknownEvent { state: S, name: String },
+    Disabled { state: S, event: E },
+    GuardRejected { state: S, event: E },
+    CoolingDown { state: S, event: E, remaining: Duration },
+    /// The machine is in a terminal state and refuses further events.
+    MachineFinished { state: S, event: E },
+    /// The transition leads into a deprecated state.
+    TargetDeprecated { state: S, event: E, target: S },
+    /// A dispatch exhausted its cascade budget; `state` is the one whose
+    /// entry or exit action, or posted event, went over it.
+    CascadeOverflow { state: S },
+    /// The transition action gave up after a cancellation request.
+    Cancelled { state: S, event: E },
+    /// A breakpoint aborted the transition before any action ran.
+    BreakpointAborted { state: S, event: E },
+    /// A prepared transition is still awaiting `commit` or `abort`.
+    TransitionPending { state: S, event: E },
+    /// A guard or action panicked while the `catch_panics` policy was on.
+    ActionPanicked { message: String },
+    /// The machine was frozen and its structure has changed since; only
+    /// under `FreezePolicy::Reject`.
+    StaleFreeze { state: S, event: E },
+    /// The machine behind a `FairTrigger` was dropped before processing
+    /// the event.
+    Closed,
+    /// `error`, with what the machine had been doing; only returned under
+    /// the `rejection_context` policy. `code`, `root` and `TriggerCode`
+    /// look through it.
+    WithContext { error: Box<TransitionError<S, E>>, context: Box<RejectionContext<S, E>> }
+}
+
+
+impl<S: Debug, E: Debug> Display for TransitionError<S, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        match self {
+            TransitionError::NoTransition { state, event } => write!(
+                f, "No transition found for event '{event:?}' from state '{state:?}'"
+            ),
+            TransitionError::UnknownEvent { state, name } => write!(
+                f, "Unknown event '{name}' in state '{state:?}'"
+            ),
+            TransitionError::Disabled { state, event } => write!(
+                f, "Transition for event '{event:?}' from state '{state:?}' is disabled"
+            ),
+            TransitionError::GuardRejected { state, event } => write!(
+                f, "Guard rejected event '{event:?}' in state '{state:?}'"
+            ),
+            TransitionError::CoolingDown { state, event, remaining } => write!(
+                f,
+                "Transition for event '{event:?}' from state '{state:?}' \
+                 is cooling down for {remaining:?}"
+            ),
+            TransitionError::MachineFinished { state, event } => write!(
+                f, "Machine finished in state '{state:?}' and refuses event '{event:?}'"
+            ),
+            TransitionError::TargetDeprecated { state, event, target } => write!(
+                f,
+                "Event '{event:?}' from state '{state:?}' leads into \
+                 deprecated state '{target:?}'"
+            ),
+            TransitionError::CascadeOverflow { state } => write!(
+                f, "Cascade budget exhausted in state '{state:?}'"
+            ),
+            TransitionError::Cancelled { state, event } => write!(
+                f, "Action for event '{event:?}' in state '{state:?}' was cancelled"
+            ),
+            TransitionError::BreakpointAborted { state, event } => write!(
+                f, "Breakpoint aborted event '{event:?}' in state '{state:?}'"
+            ),
+            TransitionError::TransitionPending { state, event } => write!(
+                f,
+                "Event '{event:?}' in state '{state:?}' must wait for the prepared \
+                 transition to be committed or aborted"
+            ),
+            TransitionError::ActionPanicked { message } => {
+                write!(f, "Action panicked: {message}")
+            }
+            TransitionError::StaleFreeze { state, event } => write!(
+                f,
+                "Event '{event:?}' in state '{state:?}' refused: the machine changed \
+                 since it was frozen"
+            ),
+            TransitionError::Closed => {
+                write!(f, "The machine was dropped before processing the event")
+            }
+            TransitionError::WithContext { error, context } => write!(f, "{error}; {context}")
+        }
+    }
+}
+
+
+impl<S: Debug, E: Debug> std::error::Error for TransitionError<S, E> {}
+
+
+impl<S, E> TransitionError<S, E> {
+    /// Returns the error without any rejection context.
+    pub fn root(&self) -> &Self
+    {
+        match self {
+            TransitionError::WithContext { error, .. } => error.root(),
+            error => error
+        }
+    }
+
+
+    pub fn context(&self) -> Option<&RejectionContext<S, E>>
+    {
+        match self {
+            TransitionError::WithContext { context, .. } => Some(context),
+            _ => None
+        }
+    }
+}
+
+
+/// What a machine had been doing when it rejected an event, as carried
+/// by `TransitionError::WithContext`. Nothing is formatted until the
+/// error is displayed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RejectionContext<S, E> {
+    /// The name set with `set_name`, if any.
+    pub machine: Option<Cow<'static, str>>,
+    /// The most recent trace entries, oldest first, ending with the
+    /// rejection itself.
+    pub recent: Vec<TraceEntry<S, E>>,
+    /// For `NoTransition`, the states one transition away from the
+    /// rejecting state, capped by the `listed_events_limit` policy.
+    pub next_states: Option<Vec<S>>,
+    /// Next states left out by the cap.
+    pub more_next_states: usize,
+    /// Transitions in the whole machine.
+    pub transition_count: usize
+}
+
+
+impl<S: Debug, E: Debug> Display for RejectionContext<S, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        match &self.machine {
+            Some(name) => write!(f, "recent trace of '{name}':")?,
+            None => write!(f, "recent trace:")?
+        }
+        for (index, entry) in self.recent.iter().enumerate() {
+            let separator = if index == 0 { " " } else { ", " };
+            write!(f, "{separator}{entry}")?;
+        }
+        match &self.next_states {
+            None => Ok(()),
+            Some(states) if self.more_next_states == 0 => write!(
+                f, "; next states: {states:?} of {} transitions", self.transition_count
+            ),
+            Some(states) => write!(
+                f,
+                "; next states: {states:?} and {} more of {} transitions",
+                self.more_next_states,
+                self.transition_count
+            )
+        }
+    }
+}
+
+
+/// Payload-free summary of a [`TransitionError`], cheap to return and log.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum TriggerCode {
+    NoTransition,
+    UnknownEvent,
+    Disabled,
+    GuardRejected,
+    CoolingDown,
+    MachineFinished,
+    TargetDeprecated,
+    CascadeOverflow,
+    Cancelled,
+    BreakpointAborted,
+    TransitionPending,
+    ActionPanicked,
+    StaleFreeze,
+    Closed
+}
+
+
+impl<S, E> From<&TransitionError<S, E>> for TriggerCode {
+    fn from(err: &TransitionError<S, E>) -> Self
+    {
+        match err {
+            TransitionError::NoTransition { .. } => TriggerCode::NoTransition,
+            TransitionError::UnknownEvent { .. } => TriggerCode::UnknownEvent,
+            TransitionError::Disabled { .. } => TriggerCode::Disabled,
+            TransitionError::GuardRejected { .. } => TriggerCode::GuardRejected,
+            TransitionError::CoolingDown { .. } => TriggerCode::CoolingDown,
+            TransitionError::MachineFinished { .. } => TriggerCode::MachineFinished,
+            TransitionError::TargetDeprecated { .. } => TriggerCode::TargetDeprecated,
+            TransitionError::CascadeOverflow { .. } => TriggerCode::CascadeOverflow,
+            TransitionError::Cancelled { .. } => TriggerCode::Cancelled,
+            TransitionError::BreakpointAborted { .. } => TriggerCode::BreakpointAborted,
+            TransitionError::TransitionPending { .. } => TriggerCode::TransitionPending,
+            TransitionError::ActionPanicked { .. } => TriggerCode::ActionPanicked,
+            TransitionError::StaleFreeze { .. } => TriggerCode::StaleFreeze,
+            TransitionError::Closed => TriggerCode::Closed,
+            TransitionError::WithContext { error, .. } => TriggerCode::from(&**error)
+        }
+    }
+}
+
+
+/// Every numeric error code ever assigned, with the variant it stands
+/// for. Codes are grouped by the hundred — 1xx building, 2xx triggering,
+/// 3xx restoring from a peer, 4xx schema files and names, 5xx structural
+/// edits, 6xx commands, 7xx test tooling — and are never reused: a code
+/// whose variant is removed stays here.
+pub const ERROR_CODES: &[(u16, &str)] = &[
+    (101, "BuildError::DuplicateTransition"),
+    (102, "BuildError::InvalidAlias"),
+    (103, "BuildError::EntersDeprecated"),
+    (104, "BuildError::UnknownResumeState"),
+    (105, "BuildError::TransitionOnAlias"),
+    (201, "TransitionError::NoTransition"),
+    (202, "TransitionError::UnknownEvent"),
+    (203, "TransitionError::Disabled"),
+    (204, "TransitionError::GuardRejected"),
+    (205, "TransitionError::CoolingDown"),
+    (206, "TransitionError::MachineFinished"),
+    (207, "TransitionError::TargetDeprecated"),
+    (208, "TransitionError::CascadeOverflow"),
+    (209, "TransitionError::Cancelled"),
+    (210, "TransitionError::BreakpointAborted"),
+    (211, "TransitionError::TransitionPending"),
+    (212, "TransitionError::ActionPanicked"),
+    (213, "TransitionError::StaleFreeze"),
+    (214, "TransitionError::Closed"),
+    (215, "BudgetExceeded"),
+    (301, "SyncError::FingerprintMismatch"),
+    (302, "SyncError::GenerationMismatch"),
+    (303, "SyncError::Stale"),
+    (401, "SchemaError::Json"),
+    (402, "SchemaError::Invalid"),
+    (403, "SchemaError::UnknownState"),
+    (404, "SchemaError::UnknownEvent"),
+    (405, "SchemaError::UnknownName"),
+    (406, "SchemaError::DuplicateTransition"),
+    (407, "SchemaError::Scenarios"),
+    (408, "NameCollision::States"),
+    (409, "NameCollision::Events"),
+    (410, "JsonError"),
+    (411, "UnknownName"),
+    (501, "RenameError::NotFound"),
+    (502, "RenameError::AlreadyExists"),
+    (503, "SwapError::StateRemoved"),
+    (504, "SwapError::UnknownFallback"),
+    (505, "ConflictError"),
+    (601, "CommandError::NoMapper"),
+    (602, "CommandError::Unmapped"),
+    (603, "CommandError::Invalid"),
+    (604, "MapError::Unmapped"),
+    (605, "MapError::Invalid"),
+    (701, "TourError"),
+    (702, "ScenarioFailure")
+];
+
+
+/// Returns the variant a numeric error code stands for, for decoding
+/// logged codes on the host.
+pub const fn error_name(code: u16) -> Option<&'static str>
+{
+    let mut i = 0;
+
+    while i < ERROR_CODES.len() {
+        if ERROR_CODES[i].0 == code {
+            return Some(ERROR_CODES[i].1);
+        }
+        i += 1;
+    }
+    None
+}
+
+
+impl TriggerCode {
+    /// Returns the numeric code listed in [`ERROR_CODES`].
+    pub const fn code(self) -> u16
+    {
+        match self {
+            TriggerCode::NoTransition => 201,
+            TriggerCode::UnknownEvent => 202,
+            TriggerCode::Disabled => 203,
+            TriggerCode::GuardRejected => 204,
+            TriggerCode::CoolingDown => 205,
+            TriggerCode::MachineFinished => 206,
+            TriggerCode::TargetDeprecated => 207,
+            TriggerCode::CascadeOverflow => 208,
+            TriggerCode::Cancelled => 209,
+            TriggerCode::BreakpointAborted => 210,
+            TriggerCode::TransitionPending => 211,
+            TriggerCode::ActionPanicked => 212,
+            TriggerCode::StaleFreeze => 213,
+            TriggerCode::Closed => 214
+        }
+    }
+}
+
+
+impl<S, E> TransitionError<S, E> {
+    /// Returns the numeric code listed in [`ERROR_CODES`].
+    pub fn code(&self) -> u16
+    {
+        TriggerCode::from(self).code()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        algebra::ConflictError,
+        builder::BuildError,
+        command::{CommandError, MapError},
+        fsm::BatchError,
+        json::JsonError,
+        naming::NameCollision,
+        quiescence::{BudgetExceeded, QuiescenceReport},
+        registry::UnknownName,
+        rename::RenameError,
+        scenario::ScenarioFailure,
+        schema::SchemaError,
+        swap::SwapError,
+        sync::SyncError,
+        tour::TourError
+    };
+    use std::collections::HashSet;
+
+
+    /// The codes as first assigned; a line may be added, never changed.
+    const ASSIGNED: &str = include_str!("../tests/snapshots/error_codes.txt");
+
+
+    #[test]
+    fn test_codes_are_unique_and_stable()
+    {
+        let codes: HashSet<u16> = ERROR_CODES.iter().map(|(code, _)| *code).collect();
+        let names: HashSet<&str> = ERROR_CODES.iter().map(|(_, name)| *name).collect();
+        let listed: Vec<String> = ERROR_CODES
+            .iter()
+            .map(|(code, name)| format!("{code} {name}"))
+            .collect();
+
+        assert_eq!((codes.len(), names.len()), (ERROR_CODES.len(), ERROR_CODES.len()));
+        assert_eq!(listed, ASSIGNED.lines().collect::<Vec<_>>());
+        assert_eq!(error_name(208), Some("TransitionError::CascadeOverflow"));
+        assert_eq!(error_name(200), None);
+    }
+
+
+    #[test]
+    fn test_every_variant_has_its_listed_code()
+    {
+        let json = JsonError{ message: String::new(), offset: 0 };
+        let unknown = UnknownName{ kind: "action", name: String::new(), available: Vec::new() };
+        let codes = [
+            BuildError::DuplicateTransition{ from: 0, event: 'a' }.code(),
+            BuildError::<u8, char>::InvalidAlias{ alias: 'a', canonical: 'b' }.code(),
+            BuildError::EntersDeprecated{ from: 0, event: 'a', to: 1 }.code(),
+            BuildError::<u8, char>::UnknownResumeState{ state: 0 }.code(),
+            BuildError::TransitionOnAlias{ from: 0, alias: 'a' }.code(),
+            TransitionError::NoTransition{ state: 0, event: 'a' }.code(),
+            TransitionError::<u8, char>::UnknownEvent{ state: 0, name: String::new() }.code(),
+            TransitionError::Disabled{ state: 0, event: 'a' }.code(),
+            TransitionError::GuardRejected{ state: 0, event: 'a' }.code(),
+            TransitionError::CoolingDown{ state: 0, event: 'a', remaining: Duration::ZERO }
+                .code(),
+            TransitionError::MachineFinished{ state: 0, event: 'a' }.code(),
+            TransitionError::TargetDeprecated{ state: 0, event: 'a', target: 1 }.code(),
+            TransitionError::<u8, char>::CascadeOverflow{ state: 0 }.code(),
+            TransitionError::Cancelled{ state: 0, event: 'a' }.code(),
+            TransitionError::BreakpointAborted{ state: 0, event: 'a' }.code(),
+            TransitionError::TransitionPending{ state: 0, event: 'a' }.code(),
+            TransitionError::<u8, char>::ActionPanicked{ message: String::new() }.code(),
+            TransitionError::StaleFreeze{ state: 0, event: 'a' }.code(),
+            TransitionError::<u8, char>::Closed.code(),
+            BudgetExceeded{ report: QuiescenceReport::default() }.code(),
+            SyncError::FingerprintMismatch{ local: 0, remote: 1 }.code(),
+            SyncError::GenerationMismatch{ local: 0, remote: 1 }.code(),
+            SyncError::Stale{ local: 0, remote: 1 }.code(),
+            SchemaError::Json(json.clone()).code(),
+            SchemaError::Invalid(String::new()).code(),
+            SchemaError::UnknownState(String::new()).code(),
+            SchemaError::UnknownEvent(String::new()).code(),
+            SchemaError::UnknownName(unknown.clone()).code(),
+            SchemaError::DuplicateTransition{ from: String::new(), event: String::new() }.code(),
+            SchemaError::Scenarios(Vec::new()).code(),
+            NameCollision::<u8, char>::States{ name: String::new(), states: Vec::new() }.code(),
+            NameCollision::<u8, char>::Events{ name: String::new(), events: Vec::new() }.code(),
+            json.code(),
+            unknown.code(),
+            RenameError::NotFound(0).code(),
+            RenameError::AlreadyExists(0).code(),
+            SwapError::StateRemoved{ state: 0 }.code(),
+            SwapError::UnknownFallback{ fallback: 0 }.code(),
+            ConflictError{ from: 0, event: 'a', ours: vec![1], theirs: vec![2] }.code(),
+            CommandError::<(), u8, char>::NoMapper{ command: () }.code(),
+            CommandError::<(), u8, char>::Unmapped{ command: () }.code(),
+            CommandError::<(), u8, char>::Invalid{ command: (), reason: String::new() }.code(),
+            MapError::Unmapped.code(),
+            MapError::Invalid(String::new()).code(),
+            TourError::<u8, char>{ uncovered: Vec::new() }.code(),
+            ScenarioFailure{ scenario: String::new(), step: 0, reason: String::new() }.code()
+        ];
+        let disabled = TransitionError::Disabled{ state: 0, event: 'a' };
+        let batch = BatchError{ index: 3, error: disabled };
+
+        assert!(codes.iter().eq(ERROR_CODES.iter().map(|(code, _)| code)));
+        assert_eq!(batch.code(), 203);
+    }
+}
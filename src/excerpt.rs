@@ -0,0 +1,171 @@
+//! Recent history carried inside rejection errors.
+//!
+//! Under the `rejection_context` policy, an event rejected by `trigger`,
+//! `trigger_from`, `trigger_str`, `prepare`, `commit` or any other public
+//! entry point comes back as `TransitionError::WithContext`, holding the
+//! machine's name and clones of its last `rejection_excerpt_len` trace
+//! entries, the rejection being the last one when it was traced. The
+//! excerpt needs the trace to be enabled; without a trace, or with the
+//! policy off, errors are returned unwrapped and nothing is cloned.
+//!
+//! A `NoTransition` context also lists the states one transition away
+//! from the rejecting state, up to `listed_events_limit` of them, and the
+//! size of the machine, so that a rejection deep inside a loop says what
+//! could have happened instead.
+
+use std::{borrow::Cow, fmt::Debug, hash::Hash};
+
+use crate::{
+    error::{RejectionContext, TransitionError},
+    fsm::StateMachine
+};
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    /// Names the machine in rejection contexts.
+    pub fn set_name(&mut self, name: impl Into<Cow<'static, str>>)
+    {
+        self.name = Some(name.into());
+    }
+
+
+    pub fn name(&self) -> Option<&str>
+    {
+        self.name.as_deref()
+    }
+
+
+    /// Wraps `error` in a rejection context if the policy asks for one.
+    pub(crate) fn with_context(&self, error: TransitionError<S, E>) -> TransitionError<S, E>
+    {
+        let Some(trace) = self.trace.as_ref().filter(|_| self.policies.rejection_context) else {
+            return error;
+        };
+        let skip = trace.entries.len().saturating_sub(self.policies.rejection_excerpt_len);
+        let mut more_next_states = 0;
+        let next_states = match error {
+            TransitionError::NoTransition { state, .. } => {
+                let mut states = self.next_states_from(state);
+
+                more_next_states = states.len().saturating_sub(self.policies.listed_events_limit);
+                states.truncate(self.policies.listed_events_limit);
+                Some(states)
+            }
+            _ => None
+        };
+        let context = RejectionContext{
+            machine: self.name.clone(),
+            recent: trace.entries.iter().skip(skip).cloned().collect(),
+            next_states,
+            more_next_states,
+            transition_count: self.transitions.len()
+        };
+
+        TransitionError::WithContext{ error: Box::new(error), context: Box::new(context) }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        error::TransitionError,
+        fixtures::traffic_light::{self, Event::*, State::*},
+        fsm::FSM,
+        policy::Policies
+    };
+
+
+    #[test]
+    fn test_excerpt_of_recent_trace()
+    {
+        let mut fsm = traffic_light::machine();
+
+        fsm.enable_trace(16);
+        fsm.set_name("crossing");
+        fsm.set_policies(
+            Policies::default().with_rejection_context(true).with_rejection_excerpt_len(3)
+        );
+        for event in [RedTimeout, Yellow2GreenTimeout, GreenTimeout] {
+            fsm.trigger(event).unwrap();
+        }
+
+        let error = fsm.trigger(RedTimeout).unwrap_err();
+        let context = error.context().unwrap();
+        assert_eq!(
+            *error.root(),
+            TransitionError::NoTransition{ state: Yellow, event: RedTimeout }
+        );
+        assert_eq!(error.code(), 201);
+        assert_eq!(context.machine.as_deref(), Some("crossing"));
+        assert_eq!(
+            context.recent.iter().map(|entry| entry.event).collect::<Vec<_>>(),
+            [Yellow2GreenTimeout, GreenTimeout, RedTimeout]
+        );
+        assert_eq!(context.next_states.as_deref(), Some(&[Green, Red][..]));
+        assert_eq!(context.transition_count, 4);
+        assert_eq!(
+            error.to_string(),
+            "No transition found for event 'RedTimeout' from state 'Yellow'; recent trace of \
+             'crossing': Yellow -Yellow2GreenTimeout-> Green, Green -GreenTimeout-> Yellow, \
+             Yellow -RedTimeout-> rejected; next states: [Green, Red] of 4 transitions"
+        );
+    }
+
+
+    #[test]
+    fn test_next_states_are_capped()
+    {
+        let mut fsm = traffic_light::machine();
+
+        fsm.enable_trace(1);
+        fsm.set_policies(
+            Policies::default().with_rejection_context(true).with_listed_events_limit(1)
+        );
+        fsm.trigger(RedTimeout).unwrap();
+
+        let error = fsm.trigger(GreenTimeout).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "No transition found for event 'GreenTimeout' from state 'Yellow'; recent trace: \
+             Yellow -GreenTimeout-> rejected; next states: [Green] and 1 more of 4 transitions"
+        );
+
+        let error = fsm.trigger_str("Flash").unwrap_err();
+        assert_eq!(error.context().unwrap().next_states, None);
+    }
+
+
+    #[test]
+    fn test_context_from_other_entry_points()
+    {
+        let mut fsm = traffic_light::machine();
+
+        fsm.enable_trace(4);
+        fsm.set_policies(Policies::default().with_rejection_context(true));
+        let error = fsm.trigger_str("Flash").unwrap_err();
+        assert!(error.context().is_some());
+        assert!(matches!(error.root(), TransitionError::UnknownEvent { .. }));
+
+        let error = fsm.prepare(GreenTimeout).err().unwrap();
+        assert!(error.context().is_some());
+        assert_eq!(*error.root(), TransitionError::NoTransition{ state: Red, event: GreenTimeout });
+    }
+
+
+    #[test]
+    fn test_no_context_without_trace_or_policy()
+    {
+        let mut fsm = traffic_light::machine();
+        let plain = TransitionError::NoTransition{ state: Red, event: GreenTimeout };
+
+        fsm.set_policies(Policies::default().with_rejection_context(true));
+        assert_eq!(fsm.trigger(GreenTimeout), Err(plain.clone()));
+
+        fsm.enable_trace(4);
+        fsm.set_policies(Policies::default());
+        assert_eq!(fsm.trigger(GreenTimeout), Err(plain));
+    }
+}
@@ -0,0 +1,310 @@
+use std::{
+    fmt::{self, Debug, Display},
+    hash::Hash,
+    time::Duration
+};
+
+use crate::fsm::{Blocked, StateMachine};
+
+
+/// What triggering an event would do right now.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum Verdict<S, E> {
+    Fires { to: S },
+    /// The transition would fire unless a breakpoint aborts it.
+    AtBreakpoint { to: S },
+    Disabled { to: S },
+    TargetDeprecated { to: S },
+    GuardRejected { to: S },
+    /// The transition's feature flag is off; see the `flag` module.
+    FlagOff { to: S, flag: String },
+    CoolingDown { to: S, remaining: Duration },
+    /// `valid` is capped by the `listed_events_limit` policy; `more`
+    /// counts the events left out.
+    NoTransition { valid: Vec<E>, more: usize },
+    /// The event is outside the declared alphabet.
+    UnknownEvent,
+    Finished,
+    /// A prepared transition is still awaiting `commit` or `abort`.
+    TransitionPending,
+    /// A guard panicked while the `catch_panics` policy was on.
+    Panicked { message: String },
+    /// The structure changed since `freeze`, under the `Reject` freeze
+    /// policy.
+    StaleFreeze
+}
+
+
+/// Answer to "why would (or wouldn't) this event fire?".
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct Explanation<S, E> {
+    pub state: S,
+    pub event: E,
+    pub verdict: Verdict<S, E>
+}
+
+
+impl<S: Debug, E: Debug> Display for Explanation<S, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "{:?} in {:?}: ", self.event, self.state)?;
+        match &self.verdict {
+            Verdict::Fires { to } => write!(f, "fires to {to:?}"),
+            Verdict::AtBreakpoint { to } => {
+                write!(f, "fires to {to:?} unless a breakpoint aborts it")
+            }
+            Verdict::Disabled { to } => write!(f, "transition to {to:?} is disabled"),
+            Verdict::TargetDeprecated { to } => write!(f, "target {to:?} is deprecated"),
+            Verdict::GuardRejected { to } => {
+                write!(f, "guard rejects transition to {to:?}")
+            }
+            Verdict::FlagOff { to, flag } => {
+                write!(f, "feature flag '{flag}' is off for transition to {to:?}")
+            }
+            Verdict::CoolingDown { to, remaining } => write!(
+                f, "transition to {to:?} is cooling down for {remaining:?}"
+            ),
+            Verdict::NoTransition { valid, more: 0 } => {
+                write!(f, "no transition (valid events: {valid:?})")
+            }
+            Verdict::NoTransition { valid, more } => {
+                write!(f, "no transition (valid events: {valid:?} and {more} more)")
+            }
+            Verdict::UnknownEvent => write!(f, "event is not in the alphabet"),
+            Verdict::Finished => write!(f, "machine has finished"),
+            Verdict::TransitionPending => write!(f, "a prepared transition is pending"),
+            Verdict::Panicked { message } => write!(f, "guard panicked: {message}"),
+            Verdict::StaleFreeze => write!(f, "structure changed since the machine was frozen")
+        }
+    }
+}
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    /// Describes what `trigger(event)` would do, without doing it.
+    ///
+    /// The checks are the ones `trigger` makes, in the same order. Guards
+    /// are evaluated, under the `catch_panics` policy, so they must be free
+    /// of side effects; breakpoints are not consulted, and a weighted
+    /// choice reports its first eligible candidate.
+    pub fn explain(&self, event: E) -> Explanation<S, E>
+    {
+        self.refresh_context();
+
+        let key = (self.state, self.resolve(self.state, event));
+        let checked = self.preflight(key.1).and_then(|eligible| self.admit(eligible.first()));
+        let to = || self.transitions[&key].next_state;
+        let verdict = match checked {
+            Ok(to) if self.breakpoint_applies(key.0, key.1) => Verdict::AtBreakpoint{ to },
+            Ok(to) => Verdict::Fires{ to },
+            Err(Blocked::StaleFreeze) => Verdict::StaleFreeze,
+            Err(Blocked::Pending) => Verdict::TransitionPending,
+            Err(Blocked::UnknownEvent) => Verdict::UnknownEvent,
+            Err(Blocked::Finished) => Verdict::Finished,
+            Err(Blocked::NoTransition) => {
+                let mut valid = self.valid_events();
+                let more = valid.len().saturating_sub(self.policies.listed_events_limit);
+
+                valid.truncate(self.policies.listed_events_limit);
+                Verdict::NoTransition{ valid, more }
+            }
+            Err(Blocked::Disabled) => Verdict::Disabled{ to: to() },
+            Err(Blocked::FlagOff) => Verdict::FlagOff{
+                to: to(),
+                flag: self.transitions[&key].feature_flag.clone().unwrap_or_default()
+            },
+            Err(Blocked::GuardRejected) => Verdict::GuardRejected{ to: to() },
+            Err(Blocked::CoolingDown(remaining)) => Verdict::CoolingDown{ to: to(), remaining },
+            Err(Blocked::TargetDeprecated(to)) => Verdict::TargetDeprecated{ to },
+            Err(Blocked::Panicked(message)) => Verdict::Panicked{ message }
+        };
+
+        Explanation{ state: self.state, event, verdict }
+    }
+
+
+    /// Whether `trigger(event)` would pass every check made before the
+    /// first action right now; breakpoints are not consulted.
+    pub(crate) fn would_fire(&self, event: E) -> bool
+    {
+        self.preflight(self.resolve(self.state, event))
+            .and_then(|eligible| self.admit(eligible.first()))
+            .is_ok()
+    }
+
+
+    /// Returns the events with a transition out of the current state,
+    /// ordered by their `Debug` rendering.
+    pub fn valid_events(&self) -> Vec<E>
+    {
+        let mut events: Vec<E> = self.transitions
+            .keys()
+            .filter(|(state, _)| *state == self.state)
+            .map(|(_, event)| *event)
+            .collect();
+
+        events.sort_by_cached_key(|event| format!("{event:?}"));
+        events
+    }
+
+
+    /// Returns the states one transition away from the current state,
+    /// including every candidate of a choice, without duplicates and
+    /// ordered by their `Debug` rendering.
+    pub fn valid_next_states(&self) -> Vec<S>
+    {
+        self.next_states_from(self.state)
+    }
+
+
+    pub(crate) fn next_states_from(&self, from: S) -> Vec<S>
+    {
+        let mut states: Vec<S> = Vec::new();
+
+        for ((state, _), transition) in &self.transitions {
+            if *state != from {
+                continue;
+            }
+            for target in transition.targets() {
+                if !states.contains(&target) {
+                    states.push(target);
+                }
+            }
+        }
+        states.sort_by_cached_key(|state| format!("{state:?}"));
+        states
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        breakpoint::BreakpointDecision,
+        builder::StateMachineBuilder,
+        clock::MockClock,
+        error::TransitionError,
+        fsm::{Transition, FSM},
+        policy::Policies
+    };
+    use std::{cell::Cell, collections::HashMap, rc::Rc, sync::Arc};
+
+
+    #[test]
+    fn test_guard_and_cooldown_verdicts()
+    {
+        let open = Rc::new(Cell::new(false));
+        let clock = Arc::new(MockClock::new());
+        let mut transitions = HashMap::new();
+        let o = open.clone();
+
+        transitions.insert(
+            (0, 'a'),
+            Transition::create(1, None).with_guard(Box::new(move || o.get()))
+        );
+        transitions.insert(
+            (1, 'b'),
+            Transition::create(0, None).with_cooldown(Duration::from_secs(3))
+        );
+        let mut fsm = StateMachine::initialize(0, transitions);
+        fsm.set_clock(clock.clone());
+
+        assert_eq!(fsm.explain('a').verdict, Verdict::GuardRejected{ to: 1 });
+        open.set(true);
+        assert_eq!(fsm.explain('a').verdict, Verdict::Fires{ to: 1 });
+        assert_eq!(
+            fsm.explain('b').to_string(),
+            "'b' in 0: no transition (valid events: ['a'])"
+        );
+
+        fsm.trigger('a').unwrap();
+        fsm.trigger('b').unwrap();
+        fsm.trigger('a').unwrap();
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(
+            fsm.explain('b').verdict,
+            Verdict::CoolingDown{ to: 0, remaining: Duration::from_secs(2) }
+        );
+    }
+
+
+    #[test]
+    fn test_verdicts_follow_trigger_checks()
+    {
+        let mut fsm = StateMachineBuilder::new(0)
+            .transition(0, 'a', 1)
+            .guard(|| false)
+            .transition(0, 'b', 1)
+            .candidate_guard(|| false)
+            .alternative(2)
+            .transition(0, 'c', 2)
+            .guard(|| panic!("guard failed"))
+            .build()
+            .unwrap();
+
+        fsm.deprecate_state(1);
+        fsm.set_policies(Policies{ catch_panics: true, ..Policies::default() });
+        assert_eq!(fsm.explain('a').verdict, Verdict::GuardRejected{ to: 1 });
+        assert_eq!(fsm.trigger('a'), Err(TransitionError::GuardRejected{ state: 0, event: 'a' }));
+        assert_eq!(fsm.explain('b').verdict, Verdict::Fires{ to: 2 });
+        assert_eq!(
+            fsm.explain('c').verdict,
+            Verdict::Panicked{ message: "guard failed".to_string() }
+        );
+
+        std::mem::forget(fsm.prepare('b').unwrap());
+        assert_eq!(fsm.explain('b').verdict, Verdict::TransitionPending);
+        fsm.release_prepared();
+
+        fsm.set_breakpoint_on(Some(0), Some('b'), |_| BreakpointDecision::Abort);
+        assert_eq!(fsm.explain('b').verdict, Verdict::AtBreakpoint{ to: 2 });
+        assert_eq!(fsm.explain('a').verdict, Verdict::GuardRejected{ to: 1 });
+    }
+
+
+    #[test]
+    fn test_valid_next_states_of_traffic_light()
+    {
+        use crate::fixtures::traffic_light::{self, Event::*, State::*};
+
+        let mut fsm = traffic_light::machine();
+
+        assert_eq!(fsm.valid_next_states(), [Yellow]);
+        fsm.trigger(RedTimeout).unwrap();
+        assert_eq!(fsm.valid_next_states(), [Green, Red]);
+        fsm.trigger(Yellow2GreenTimeout).unwrap();
+        assert_eq!(fsm.valid_next_states(), [Yellow]);
+
+        let fsm = StateMachineBuilder::new(0)
+            .transition(0, 'a', 1)
+            .alternative(3)
+            .transition(0, 'b', 2)
+            .build()
+            .unwrap();
+        assert_eq!(fsm.valid_next_states(), [1, 2, 3]);
+    }
+
+
+    #[test]
+    fn test_valid_events_capped_by_policy()
+    {
+        let transitions = ('a'..='z').map(|e| ((0, e), Transition::create(1, None))).collect();
+        let mut fsm: StateMachine<u8, char> = StateMachine::initialize(0, transitions);
+
+        assert_eq!(
+            fsm.explain('!').verdict,
+            Verdict::NoTransition{ valid: ('a'..='p').collect(), more: 10 }
+        );
+
+        fsm.set_policies(crate::policy::Policies{ listed_events_limit: 2, ..Default::default() });
+        assert_eq!(
+            fsm.explain('!').to_string(),
+            "'!' in 0: no transition (valid events: ['a', 'b'] and 24 more)"
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use std::{fmt::{Debug, Write}, hash::Hash};
+
+use crate::{
+    fsm::StateMachine,
+    naming::{NameCollision, Names}
+};
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    /// Renders the machine as a Graphviz DOT digraph.
+    ///
+    /// Output is deterministic. Choices get one edge per candidate,
+    /// labelled with its weight. Guarded edges are labelled `[guarded]`
+    /// and edges behind a feature flag `[flag: name]`, naming the
+    /// transition's flag and then the candidate's. Disabled edges are
+    /// dashed, terminal states are double circles and state tags become
+    /// node tooltips. A `__current` note, labelled with the rendering of
+    /// `configuration()`, points at the active state.
+    ///
+    /// States and events are named by their `Debug` rendering; see
+    /// `to_dot_with` when some render alike.
+    pub fn to_dot(&self) -> Result<String, NameCollision<S, E>>
+    {
+        self.to_dot_with(&Names::default())
+    }
+
+
+    /// Like `to_dot`, naming states and events with `names`.
+    pub fn to_dot_with(&self, names: &Names<S, E>) -> Result<String, NameCollision<S, E>>
+    {
+        let description = self.describe();
+        let events: Vec<E> = description.transitions.iter().map(|t| t.event).collect();
+        let quote = |state: &S| quote_str(&names.state(state));
+        let mut dot = String::from("digraph {\n    __start [shape=point];\n");
+
+        names.state_map(&description.states)?;
+        names.event_map(&events)?;
+
+        let _ = writeln!(dot, "    __start -> {};", quote(&description.initial));
+        let configuration = self.configuration();
+        let _ = writeln!(
+            dot,
+            "    __current [shape=plaintext, label={}];",
+            quote_str(&format!("current: {configuration}"))
+        );
+        for state in configuration.leaves() {
+            let _ = writeln!(dot, "    __current -> {} [style=dotted];", quote(state));
+        }
+        for state in &description.states {
+            let _ = write!(dot, "    {}", quote(state));
+            let mut attributes = Vec::new();
+
+            if let Some((_, tags)) = description.tags.iter().find(|(s, _)| s == state) {
+                attributes.push(format!("tooltip={}", quote_str(&tags.join(", "))));
+            }
+            if description.terminals.contains(state) {
+                attributes.push("shape=doublecircle".to_string());
+            }
+            if !attributes.is_empty() {
+                let _ = write!(dot, " [{}]", attributes.join(", "));
+            }
+            dot.push_str(";\n");
+        }
+        for t in &description.transitions {
+            let single = [(t.to, None, false, None)];
+            let candidates: Vec<_> = t.candidates
+                .iter()
+                .map(|c| (c.to, Some(c.weight), c.guarded, c.feature_flag.as_deref()))
+                .collect();
+            let targets = if candidates.is_empty() { &single[..] } else { &candidates[..] };
+
+            for (to, weight, candidate_guarded, candidate_flag) in targets {
+                let mut label = names.event(&t.event);
+
+                if let Some(weight) = weight {
+                    let _ = write!(label, " (w={weight})");
+                }
+                if t.guarded || *candidate_guarded {
+                    label.push_str(" [guarded]");
+                }
+                for flag in t.feature_flag.as_deref().iter().chain(candidate_flag) {
+                    let _ = write!(label, " [flag: {flag}]");
+                }
+                let _ = write!(
+                    dot,
+                    "    {} -> {} [label={}",
+                    quote(&t.from), quote(to), quote_str(&label)
+                );
+                if !t.enabled {
+                    dot.push_str(", style=dashed");
+                }
+                dot.push_str("];\n");
+            }
+        }
+        dot.push_str("}\n");
+        Ok(dot)
+    }
+}
+
+
+fn quote_str(s: &str) -> String
+{
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::{builder::StateMachineBuilder, fsm::FSM};
+
+
+    #[test]
+    fn test_current_state_is_marked()
+    {
+        let mut fsm = StateMachineBuilder::new(0).transition(0, 'a', 1).build().unwrap();
+
+        fsm.trigger('a').unwrap();
+        let dot = fsm.to_dot().unwrap();
+
+        assert!(dot.contains(r#"__current [shape=plaintext, label="current: 1"];"#), "{dot}");
+        assert!(dot.contains(r#"__current -> "1" [style=dotted];"#), "{dot}");
+    }
+
+
+    #[test]
+    fn test_flags_are_labelled()
+    {
+        let fsm = StateMachineBuilder::new(0)
+            .transition(0, 'a', 1)
+            .feature_flag("beta")
+            .transition(1, 'b', 0)
+            .alternative(2)
+            .candidate_flag("canary")
+            .build()
+            .unwrap();
+        let dot = fsm.to_dot().unwrap();
+
+        assert!(dot.contains(r#""0" -> "1" [label="'a' [flag: beta]"];"#), "{dot}");
+        assert!(dot.contains(r#""1" -> "0" [label="'b' (w=1)"];"#), "{dot}");
+        assert!(dot.contains(r#""1" -> "2" [label="'b' (w=1) [flag: canary]"];"#), "{dot}");
+
+        let matrix = fsm.to_matrix();
+        assert!(matrix.get(&0, &'a').unwrap().flagged);
+        assert!(matrix.get(&1, &'b').unwrap().flagged);
+        let table = matrix.to_markdown_table();
+        assert!(table.contains("| 1?  ") && table.contains("| 0/2? "), "{table}");
+    }
+}
@@ -0,0 +1,325 @@
+//! Fair, first-come first-served triggering from other threads.
+//!
+//! A machine holds closures and handles that cannot leave its thread, so
+//! other threads trigger it through a [`FairTrigger`]: each call queues
+//! the event, its origin and a slot for the result in one shared FIFO,
+//! and the owning thread processes the queue with `pump`. The queue is
+//! numbered under its lock, so events are processed strictly in the
+//! order they arrived, whichever thread sent them, and a thread sending
+//! in bursts cannot overtake one that sent earlier. Each caller gets the
+//! result of its own event. Dropping the machine closes the queue: the
+//! events still in it and any sent afterwards are never processed, and
+//! their callers get `TransitionError::Closed`.
+
+use std::{
+    collections::VecDeque,
+    fmt::Debug,
+    hash::Hash,
+    sync::{mpsc, Arc, Mutex, MutexGuard, PoisonError}
+};
+
+use crate::{error::TransitionError, fsm::StateMachine, origin::Origin};
+
+
+type Slot<S, E> = mpsc::Sender<Result<(), TransitionError<S, E>>>;
+
+
+pub(crate) struct FairInbox<S, E> {
+    entries: VecDeque<(E, Origin, Slot<S, E>)>,
+    next_sequence: u64,
+    closed: bool
+}
+
+
+/// Locks `inbox`; a caller that panicked while holding the lock cannot
+/// have left the queue half-updated.
+fn lock<S, E>(inbox: &Mutex<FairInbox<S, E>>) -> MutexGuard<'_, FairInbox<S, E>>
+{
+    inbox.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+
+impl<S, E> FairInbox<S, E> {
+    pub(crate) fn len(&self) -> usize
+    {
+        self.entries.len()
+    }
+}
+
+
+impl<S, E> Default for FairInbox<S, E> {
+    fn default() -> Self
+    {
+        Self{ entries: VecDeque::new(), next_sequence: 0, closed: false }
+    }
+}
+
+
+/// The machine's end of the queue, closing it when the machine is
+/// dropped.
+pub(crate) struct FairQueue<S, E> {
+    pub(crate) inbox: Arc<Mutex<FairInbox<S, E>>>
+}
+
+
+#[cfg(feature = "paranoid")]
+impl<S, E: Copy> FairQueue<S, E> {
+    /// The events waiting in the queue, oldest first.
+    pub(crate) fn events(&self) -> Vec<E>
+    {
+        lock(&self.inbox).entries.iter().map(|(event, ..)| *event).collect()
+    }
+}
+
+
+impl<S, E> Default for FairQueue<S, E> {
+    fn default() -> Self
+    {
+        Self{ inbox: Arc::default() }
+    }
+}
+
+
+impl<S, E> Drop for FairQueue<S, E> {
+    fn drop(&mut self)
+    {
+        let mut inbox = lock(&self.inbox);
+
+        // Dropping the slots wakes their waiting callers.
+        inbox.closed = true;
+        inbox.entries.clear();
+    }
+}
+
+
+/// Handle for triggering a machine from any thread; clones share the
+/// queue.
+pub struct FairTrigger<S, E> {
+    inbox: Arc<Mutex<FairInbox<S, E>>>
+}
+
+
+impl<S, E> Clone for FairTrigger<S, E> {
+    fn clone(&self) -> Self
+    {
+        Self{ inbox: self.inbox.clone() }
+    }
+}
+
+
+/// An event waiting in the queue of a [`FairTrigger`].
+pub struct FairTicket<S, E> {
+    sequence: u64,
+    result: mpsc::Receiver<Result<(), TransitionError<S, E>>>
+}
+
+
+impl<S, E> FairTicket<S, E> {
+    /// Returns the event's position in the arrival order, counted from 0
+    /// over all senders.
+    pub fn sequence(&self) -> u64
+    {
+        self.sequence
+    }
+
+
+    /// Blocks until the owning thread has processed the event and
+    /// returns its result, or `TransitionError::Closed` if the machine was
+    /// dropped before processing it, or before the event was submitted.
+    pub fn wait(self) -> Result<(), TransitionError<S, E>>
+    {
+        self.result.recv().unwrap_or(Err(TransitionError::Closed))
+    }
+}
+
+
+impl<S, E> FairTrigger<S, E> {
+    /// Queues `event` without waiting for it to be processed.
+    pub fn submit(&self, event: E, origin: Origin) -> FairTicket<S, E>
+    {
+        let (slot, result) = mpsc::channel();
+        let mut inbox = lock(&self.inbox);
+        let sequence = inbox.next_sequence;
+
+        inbox.next_sequence += 1;
+        if !inbox.closed {
+            inbox.entries.push_back((event, origin, slot));
+        }
+        FairTicket{ sequence, result }
+    }
+
+
+    /// Queues `event` and blocks until the owning thread has processed it.
+    pub fn trigger(&self, event: E) -> Result<(), TransitionError<S, E>>
+    {
+        self.submit(event, Origin::unspecified()).wait()
+    }
+
+
+    pub fn trigger_from(&self, event: E, origin: Origin) -> Result<(), TransitionError<S, E>>
+    {
+        self.submit(event, origin).wait()
+    }
+
+
+    /// Returns the number of events waiting to be pumped.
+    pub fn pending(&self) -> usize
+    {
+        lock(&self.inbox).len()
+    }
+}
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    pub fn fair_trigger(&self) -> FairTrigger<S, E>
+    {
+        FairTrigger{ inbox: self.fair.inbox.clone() }
+    }
+
+
+    /// Processes the events queued through `fair_trigger` in arrival
+    /// order, including the ones that arrive meanwhile, until the queue is
+    /// empty; returns how many were processed. The queue's lock is not
+    /// held while an event is processed.
+    pub fn pump(&mut self) -> usize
+    {
+        let mut processed = 0;
+
+        while self.pump_one().is_some() {
+            processed += 1;
+        }
+        processed
+    }
+
+
+    /// Processes the oldest queued event, if any; returns whether it
+    /// succeeded, or `None` if the queue was empty.
+    pub(crate) fn pump_one(&mut self) -> Option<bool>
+    {
+        let (event, origin, slot) = lock(&self.fair.inbox).entries.pop_front()?;
+        let result = self.trigger_from(event, origin);
+        let ok = result.is_ok();
+
+        // The caller may have dropped its ticket; the event still counts.
+        let _ = slot.send(result);
+        Some(ok)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{builder::StateMachineBuilder, fsm::FSM};
+    use std::{cell::RefCell, rc::Rc, thread};
+
+
+    const PRODUCERS: u8 = 4;
+    const PER_PRODUCER: u16 = 250;
+
+
+    #[test]
+    fn test_global_fifo_under_contention()
+    {
+        let events = (0..PRODUCERS).flat_map(|p| (0..PER_PRODUCER).map(move |n| (p, n)));
+        let mut fsm = events
+            .fold(StateMachineBuilder::new(0), |builder, event| builder.transition(0, event, 0))
+            .build()
+            .unwrap();
+        let processed = Rc::new(RefCell::new(Vec::new()));
+        let log = processed.clone();
+
+        fsm.add_observer(Box::new(move |_, event, _| log.borrow_mut().push(*event)));
+        let producers: Vec<_> = (0..PRODUCERS)
+            .map(|p| {
+                let trigger = fsm.fair_trigger();
+                thread::spawn(move || {
+                    let tickets: Vec<_> = (0..PER_PRODUCER)
+                        .map(|n| (trigger.submit((p, n), Origin::new("producer")), (p, n)))
+                        .collect();
+                    tickets
+                        .into_iter()
+                        .map(|(ticket, event)| {
+                            let sequence = ticket.sequence();
+                            assert_eq!(ticket.wait(), Ok(()));
+                            (sequence, event)
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+
+        let total = PRODUCERS as usize * PER_PRODUCER as usize;
+        let mut pumped = 0;
+        while pumped < total {
+            pumped += fsm.pump();
+            thread::yield_now();
+        }
+
+        let mut arrivals: Vec<_> = producers.into_iter().flat_map(|p| p.join().unwrap()).collect();
+        arrivals.sort();
+        assert!(arrivals.iter().map(|(sequence, _)| *sequence).eq(0..total as u64));
+        assert_eq!(
+            *processed.borrow(),
+            arrivals.into_iter().map(|(_, event)| event).collect::<Vec<_>>()
+        );
+    }
+
+
+    #[test]
+    fn test_each_caller_gets_its_own_result()
+    {
+        let mut fsm = StateMachineBuilder::new(0).transition(0, 'a', 1).build().unwrap();
+        let trigger = fsm.fair_trigger();
+
+        let rejected = trigger.submit('b', Origin::new("telemetry"));
+        let accepted = trigger.submit('a', Origin::new("operator"));
+        assert_eq!((rejected.sequence(), accepted.sequence()), (0, 1));
+        assert_eq!(trigger.pending(), 2);
+
+        assert_eq!(fsm.pump(), 2);
+        assert_eq!(rejected.wait(), Err(TransitionError::NoTransition{ state: 0, event: 'b' }));
+        assert_eq!(accepted.wait(), Ok(()));
+        assert_eq!(fsm.state(), 1);
+        assert_eq!(fsm.pump(), 0);
+    }
+
+
+    #[test]
+    fn test_waiting_on_a_dropped_machine_fails()
+    {
+        let fsm = StateMachineBuilder::new(0).transition(0, 'a', 1).build().unwrap();
+        let trigger = fsm.fair_trigger();
+        let queued = trigger.submit('a', Origin::new("remote"));
+        let waiter = thread::spawn(move || queued.wait());
+
+        drop(fsm);
+        assert_eq!(waiter.join().unwrap(), Err(TransitionError::Closed));
+        assert_eq!(trigger.pending(), 0);
+
+        let late = trigger.submit('a', Origin::new("remote"));
+        let late = thread::spawn(move || late.wait());
+        assert_eq!(late.join().unwrap(), Err(TransitionError::Closed));
+        assert_eq!(trigger.trigger('a').unwrap_err().code(), 214);
+    }
+
+
+    #[test]
+    fn test_poisoned_queue_stays_usable()
+    {
+        let mut fsm = StateMachineBuilder::new(0).transition(0, 'a', 1).build().unwrap();
+        let trigger = fsm.fair_trigger();
+        let inbox = fsm.fair.inbox.clone();
+
+        let _ = thread::spawn(move || {
+            let _held = inbox.lock().unwrap();
+            panic!("poisoning the queue");
+        }).join();
+        let ticket = trigger.submit('a', Origin::new("remote"));
+        assert_eq!(trigger.pending(), 1);
+        assert_eq!(fsm.pump(), 1);
+        assert_eq!(ticket.wait(), Ok(()));
+    }
+}
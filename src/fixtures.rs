@@ -0,0 +1,394 @@
+//! Reference machines for tests and benchmarks.
+//!
+//! Each reference machine lives in its own module with its `State` and
+//! `Event` types, its transition table, and constructors for a builder,
+//! a built machine, and a built machine whose actions record every
+//! transition taken in a [`Log`] as `"From -> To"`. The invariants listed
+//! in each module's documentation are checked by this module's tests, so
+//! callers may rely on them.
+//!
+//! [`chain`] and [`complete_graph`] generate machines of any size for
+//! stress tests.
+//!
+//! ```
+//! use pfsm::{assert_fsm_path, fixtures::traffic_light::{self, Event::*, State::*}};
+//!
+//! let mut fsm = traffic_light::machine();
+//! assert_fsm_path!(fsm, Red -RedTimeout-> Yellow -Yellow2GreenTimeout-> Green);
+//! ```
+
+use std::{cell::RefCell, collections::HashMap, fmt::Debug, hash::Hash, rc::Rc};
+
+use crate::{builder::StateMachineBuilder, fsm::Transition};
+
+
+/// Transitions taken by a logged machine, as `"From -> To"`.
+pub type Log = Rc<RefCell<Vec<String>>>;
+
+
+/// Returns the transitions of `table`, keyed as `FSM::initialize` expects,
+/// with logging actions if `log` is given.
+pub fn transition_map<S, E>(
+    table: &[(S, E, S)],
+    log: Option<&Log>
+) -> HashMap<(S, E), Transition<S>>
+where S: Copy + Hash + Eq + Debug + 'static, E: Copy + Hash + Eq + Debug
+{
+    table
+        .iter()
+        .map(|&(from, event, to)| {
+            let action = log.map(|log| logger(log, from, to));
+            ((from, event), Transition::create(to, action))
+        })
+        .collect()
+}
+
+
+/// Returns a builder declaring the transitions of `table` in order, with
+/// logging actions if `log` is given.
+pub fn builder_from<S, E>(
+    initial: S,
+    table: &[(S, E, S)],
+    log: Option<&Log>
+) -> StateMachineBuilder<S, E>
+where S: Copy + Hash + Eq + Debug + 'static, E: Copy + Hash + Eq + Debug
+{
+    table.iter().fold(StateMachineBuilder::new(initial), |builder, &(from, event, to)| {
+        let builder = builder.transition(from, event, to);
+        match log {
+            Some(log) => builder.action(logger(log, from, to)),
+            None => builder
+        }
+    })
+}
+
+
+fn logger<S: Debug>(log: &Log, from: S, to: S) -> Box<dyn Fn()>
+{
+    let log = log.clone();
+    let line = format!("{from:?} -> {to:?}");
+
+    Box::new(move || log.borrow_mut().push(line.clone()))
+}
+
+
+/// A three-colour traffic light.
+///
+/// - 3 states, 4 events, 4 transitions; starts in `Red`.
+/// - Every transition lies on the cycle `Red -RedTimeout-> Yellow
+///   -Yellow2GreenTimeout-> Green -GreenTimeout-> Yellow
+///   -Yellow2RedTimeout-> Red`, which is also a transition tour.
+/// - Only `RedTimeout` is accepted in `Red`.
+pub mod traffic_light {
+    use super::Log;
+    use crate::{builder::StateMachineBuilder, fsm::StateMachine};
+
+
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    pub enum State {
+        Red,
+        Yellow,
+        Green
+    }
+
+
+    #[allow(clippy::enum_variant_names)]
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    pub enum Event {
+        RedTimeout,
+        Yellow2GreenTimeout,
+        Yellow2RedTimeout,
+        GreenTimeout
+    }
+
+
+    pub const INITIAL: State = State::Red;
+
+
+    pub const TRANSITIONS: [(State, Event, State); 4] = [
+        (State::Red, Event::RedTimeout, State::Yellow),
+        (State::Yellow, Event::Yellow2GreenTimeout, State::Green),
+        (State::Green, Event::GreenTimeout, State::Yellow),
+        (State::Yellow, Event::Yellow2RedTimeout, State::Red)
+    ];
+
+
+    pub fn builder() -> StateMachineBuilder<State, Event>
+    {
+        super::builder_from(INITIAL, &TRANSITIONS, None)
+    }
+
+
+    pub fn machine() -> StateMachine<State, Event>
+    {
+        builder().build().unwrap()
+    }
+
+
+    pub fn logged(log: &Log) -> StateMachine<State, Event>
+    {
+        super::builder_from(INITIAL, &TRANSITIONS, Some(log)).build().unwrap()
+    }
+}
+
+
+/// A coin-operated turnstile.
+///
+/// - 2 states, 2 events, 2 transitions; starts in `Locked`.
+/// - `Locked -Coin-> Unlocked -Push-> Locked`; `Push` is rejected in
+///   `Locked` and `Coin` in `Unlocked`.
+pub mod turnstile {
+    use super::Log;
+    use crate::{builder::StateMachineBuilder, fsm::StateMachine};
+
+
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    pub enum State {
+        Locked,
+        Unlocked
+    }
+
+
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    pub enum Event {
+        Coin,
+        Push
+    }
+
+
+    pub const INITIAL: State = State::Locked;
+
+
+    pub const TRANSITIONS: [(State, Event, State); 2] = [
+        (State::Locked, Event::Coin, State::Unlocked),
+        (State::Unlocked, Event::Push, State::Locked)
+    ];
+
+
+    pub fn builder() -> StateMachineBuilder<State, Event>
+    {
+        super::builder_from(INITIAL, &TRANSITIONS, None)
+    }
+
+
+    pub fn machine() -> StateMachine<State, Event>
+    {
+        builder().build().unwrap()
+    }
+
+
+    pub fn logged(log: &Log) -> StateMachine<State, Event>
+    {
+        super::builder_from(INITIAL, &TRANSITIONS, Some(log)).build().unwrap()
+    }
+}
+
+
+/// A simplified TCP connection.
+///
+/// - 6 states, 8 events, 11 transitions; starts in `Closed`.
+/// - Active open: `Closed -ActiveOpen-> SynSent -SynAck-> Established
+///   -Close-> FinWait -Ack-> Closed`.
+/// - Passive open: `Closed -PassiveOpen-> Listen -Syn-> SynReceived
+///   -Ack-> Established`.
+/// - Every state can reach `Closed`, and `Closed` can reach every state.
+pub mod tcp {
+    use super::Log;
+    use crate::{builder::StateMachineBuilder, fsm::StateMachine};
+
+
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    pub enum State {
+        Closed,
+        Listen,
+        SynSent,
+        SynReceived,
+        Established,
+        FinWait
+    }
+
+
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    pub enum Event {
+        PassiveOpen,
+        ActiveOpen,
+        Syn,
+        SynAck,
+        Ack,
+        Close,
+        Reset,
+        Timeout
+    }
+
+
+    pub const INITIAL: State = State::Closed;
+
+
+    pub const TRANSITIONS: [(State, Event, State); 11] = [
+        (State::Closed, Event::PassiveOpen, State::Listen),
+        (State::Closed, Event::ActiveOpen, State::SynSent),
+        (State::Listen, Event::Syn, State::SynReceived),
+        (State::Listen, Event::Close, State::Closed),
+        (State::SynSent, Event::SynAck, State::Established),
+        (State::SynSent, Event::Timeout, State::Closed),
+        (State::SynReceived, Event::Ack, State::Established),
+        (State::SynReceived, Event::Reset, State::Listen),
+        (State::Established, Event::Close, State::FinWait),
+        (State::Established, Event::Reset, State::Closed),
+        (State::FinWait, Event::Ack, State::Closed)
+    ];
+
+
+    pub fn builder() -> StateMachineBuilder<State, Event>
+    {
+        super::builder_from(INITIAL, &TRANSITIONS, None)
+    }
+
+
+    pub fn machine() -> StateMachine<State, Event>
+    {
+        builder().build().unwrap()
+    }
+
+
+    pub fn logged(log: &Log) -> StateMachine<State, Event>
+    {
+        super::builder_from(INITIAL, &TRANSITIONS, Some(log)).build().unwrap()
+    }
+}
+
+
+/// The only event of a [`chain`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Next;
+
+
+/// States `0..n` linked by `Next`, starting in 0: `n - 1` transitions,
+/// and `n - 1` triggers of `Next` reach the last state, which has none.
+///
+/// Panics if `n` is 0.
+pub fn chain(n: usize) -> StateMachineBuilder<usize, Next>
+{
+    assert!(n > 0, "a chain needs at least one state");
+
+    (0..n - 1).fold(StateMachineBuilder::new(0), |builder, state| {
+        builder.transition(state, Next, state + 1)
+    })
+}
+
+
+/// States `0..n`, starting in 0, where event `j` leads from every other
+/// state to `j`: `n * (n - 1)` transitions, and no self-loops.
+///
+/// Panics if `n` is 0.
+pub fn complete_graph(n: usize) -> StateMachineBuilder<usize, usize>
+{
+    assert!(n > 0, "a complete graph needs at least one state");
+
+    let mut builder = StateMachineBuilder::new(0);
+
+    for from in 0..n {
+        for to in (0..n).filter(|&to| to != from) {
+            builder = builder.transition(from, to, to);
+        }
+    }
+    builder
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{assert_fsm_path, fsm::{StateMachine, FSM}};
+
+
+    /// Returns the number of states, distinct events and transitions.
+    fn counts<S, E>(fsm: &StateMachine<S, E>) -> (usize, usize, usize)
+    where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+    {
+        let transitions = fsm.describe().transitions;
+        let mut events: Vec<_> = transitions.iter().map(|t| format!("{:?}", t.event)).collect();
+
+        events.sort();
+        events.dedup();
+        (fsm.states().len(), events.len(), transitions.len())
+    }
+
+
+    #[test]
+    fn test_traffic_light_invariants()
+    {
+        use traffic_light::{Event::*, State::*};
+
+        let log = Log::default();
+        let mut fsm = traffic_light::logged(&log);
+
+        assert_eq!(counts(&fsm), (3, 4, 4));
+        assert_eq!(fsm.transition_tour().unwrap(), traffic_light::TRANSITIONS.map(|t| t.1));
+        assert_fsm_path!(
+            fsm,
+            Red -RedTimeout-> Yellow -Yellow2GreenTimeout-> Green
+                -GreenTimeout-> Yellow -Yellow2RedTimeout-> Red
+        );
+        assert_eq!(fsm.available(), [RedTimeout]);
+        assert_eq!(
+            *log.borrow(),
+            ["Red -> Yellow", "Yellow -> Green", "Green -> Yellow", "Yellow -> Red"]
+        );
+    }
+
+
+    #[test]
+    fn test_turnstile_invariants()
+    {
+        use turnstile::{Event::*, State::*};
+
+        let log = Log::default();
+        let mut fsm = turnstile::logged(&log);
+
+        assert_eq!(counts(&fsm), (2, 2, 2));
+        assert_fsm_path!(fsm, Locked -Coin-> Unlocked !> Coin);
+        assert_fsm_path!(fsm, Unlocked -Push-> Locked !> Push);
+        assert_eq!(*log.borrow(), ["Locked -> Unlocked", "Unlocked -> Locked"]);
+    }
+
+
+    #[test]
+    fn test_tcp_invariants()
+    {
+        use tcp::{Event::*, State::*};
+
+        let mut fsm = tcp::machine();
+
+        assert_eq!(counts(&fsm), (6, 8, 11));
+        assert_eq!(fsm.strongly_connected_components().len(), 1);
+        assert_fsm_path!(
+            fsm,
+            Closed -ActiveOpen-> SynSent -SynAck-> Established -Close-> FinWait -Ack-> Closed
+        );
+        assert_fsm_path!(fsm, Closed -PassiveOpen-> Listen -Syn-> SynReceived -Ack-> Established);
+
+        assert_eq!(transition_map(&tcp::TRANSITIONS, None).len(), 11);
+    }
+
+
+    #[test]
+    fn test_generated_machines()
+    {
+        let mut line = chain(100).build().unwrap();
+
+        assert_eq!(counts(&line), (100, 1, 99));
+        for _ in 0..99 {
+            line.trigger(Next).unwrap();
+        }
+        assert_eq!(line.state(), 99);
+        assert!(line.trigger(Next).is_err());
+        assert_eq!(counts(&chain(1).build().unwrap()), (1, 0, 0));
+
+        let mut graph = complete_graph(5).build().unwrap();
+        assert_eq!(counts(&graph), (5, 5, 20));
+        graph.trigger(3).unwrap();
+        assert!(graph.trigger(3).is_err());
+        assert_eq!(graph.available(), [0, 1, 2, 4]);
+    }
+}
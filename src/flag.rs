@@ -0,0 +1,136 @@
+//! Transitions gated by an external feature-flag system.
+//!
+//! A transition or choice candidate names its flag with `feature_flag` or
+//! `candidate_flag` on the builder. The machine asks its flag provider at
+//! decision time, so flipping a flag needs no mutation of the machine. A
+//! transition whose flag is off is rejected as if its guard had failed; a
+//! candidate whose flag is off is skipped, and selection falls through to
+//! the next one. Flags are checked before guards, and while no provider
+//! is set they read as the `flags_without_provider` policy says.
+
+use std::{fmt::Debug, hash::Hash};
+
+use crate::{
+    choice::Candidate,
+    fsm::{StateMachine, Transition}
+};
+
+
+pub type FlagProvider = Box<dyn Fn(&str) -> bool>;
+
+
+impl<S: Copy> Transition<S> {
+    /// Gates the transition on `flag`.
+    pub fn with_feature_flag(mut self, flag: &str) -> Self
+    {
+        self.feature_flag = Some(flag.to_string());
+        self
+    }
+}
+
+
+impl<S> Candidate<S> {
+    /// Excludes the candidate from selection while `flag` is off.
+    pub fn with_feature_flag(mut self, flag: &str) -> Self
+    {
+        self.feature_flag = Some(flag.to_string());
+        self
+    }
+}
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    pub fn set_flag_provider(&mut self, provider: FlagProvider)
+    {
+        self.flag_provider = Some(provider);
+    }
+
+
+    pub fn clear_flag_provider(&mut self)
+    {
+        self.flag_provider = None;
+    }
+
+
+    /// Whether a transition or candidate gated on `flag` may be taken.
+    pub(crate) fn flag_on(&self, flag: Option<&str>) -> bool
+    {
+        match (flag, &self.flag_provider) {
+            (None, _) => true,
+            (Some(flag), Some(provider)) => provider(flag),
+            (Some(_), None) => self.policies.flags_without_provider
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        builder::StateMachineBuilder,
+        error::TransitionError,
+        explain::Verdict,
+        fsm::FSM,
+        policy::Policies
+    };
+    use std::{cell::Cell, rc::Rc};
+
+
+    #[test]
+    fn test_flag_flipped_between_triggers()
+    {
+        let on = Rc::new(Cell::new(false));
+        let provider_on = on.clone();
+        let mut fsm = StateMachineBuilder::new("cart")
+            .transition("cart", "checkout", "new_checkout")
+            .candidate_flag("new_checkout_flow")
+            .alternative("legacy_checkout")
+            .transition("new_checkout", "back", "cart")
+            .transition("legacy_checkout", "back", "cart")
+            .build()
+            .unwrap();
+
+        fsm.set_flag_provider(Box::new(move |flag| {
+            flag == "new_checkout_flow" && provider_on.get()
+        }));
+        fsm.trigger("checkout").unwrap();
+        assert_eq!(fsm.state(), "legacy_checkout");
+
+        fsm.trigger("back").unwrap();
+        on.set(true);
+        fsm.trigger("checkout").unwrap();
+        assert_eq!(fsm.state(), "new_checkout");
+    }
+
+
+    #[test]
+    fn test_flagged_transition_rejected_like_a_guard()
+    {
+        let mut fsm = StateMachineBuilder::new(0)
+            .transition(0, 'a', 1)
+            .feature_flag("beta")
+            .guard(|| false)
+            .build()
+            .unwrap();
+
+        assert_eq!(fsm.explain('a').verdict, Verdict::GuardRejected{ to: 1 });
+        assert_eq!(fsm.describe().transitions[0].feature_flag.as_deref(), Some("beta"));
+
+        fsm.set_policies(Policies{ flags_without_provider: false, ..Policies::default() });
+        assert_eq!(
+            fsm.explain('a').to_string(),
+            "'a' in 0: feature flag 'beta' is off for transition to 1"
+        );
+        assert_eq!(
+            fsm.trigger('a'),
+            Err(TransitionError::GuardRejected{ state: 0, event: 'a' })
+        );
+
+        fsm.set_flag_provider(Box::new(|_| true));
+        assert_eq!(fsm.explain('a').verdict, Verdict::GuardRejected{ to: 1 });
+        fsm.clear_flag_provider();
+        assert!(matches!(fsm.explain('a').verdict, Verdict::FlagOff { .. }));
+    }
+}
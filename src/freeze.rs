@@ -0,0 +1,325 @@
+//! Precomputed event routing for latency-sensitive loops.
+//!
+//! `freeze` resolves, for every state and every event it accepts
+//! (aliases included), the canonical transition key up front, along with
+//! the candidates of a choice that can still be picked, in selection
+//! order. A trigger then finds its route with one probe of a flat table:
+//! an event missing from it has no transition in the current state, and
+//! is rejected without consulting the aliases. Transitions without
+//! guard, flag, cooldown or candidates are decided by the table alone.
+//! The outcome of every trigger is the same as without freezing.
+//!
+//! The table is only valid for the structure generation it was built
+//! for. What a structural change does to a frozen machine is chosen by
+//! the `freeze` policy: it either unfreezes transparently, or rejects
+//! events with `TransitionError::StaleFreeze` until `freeze` or
+//! `unfreeze` is called again; structural edits themselves have no error
+//! to report.
+
+use std::{cell::Cell, collections::HashMap, fmt::Debug, hash::Hash};
+
+use crate::{
+    choice::SelectionMode,
+    fsm::{StateMachine, Transition}
+};
+
+
+/// What a structural change does to a frozen machine.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FreezePolicy {
+    /// Drop back to the regular lookup.
+    #[default]
+    Unfreeze,
+    /// Reject every event with `TransitionError::StaleFreeze`.
+    Reject
+}
+
+
+pub(crate) struct Frozen<S, E> {
+    generation: u64,
+    /// Index into `plans`; an alias shares the entry of its canonical
+    /// event.
+    routes: HashMap<(S, E), usize>,
+    plans: Vec<Route<S, E>>,
+    /// The entry `resolve` found last, so that the checks that follow in
+    /// the same dispatch need not probe `routes` again.
+    resolved: Cell<Option<usize>>
+}
+
+
+/// Where an event received in a state leads.
+pub(crate) enum Route<S, E> {
+    /// The transition stored under `key`.
+    Found { key: (S, E), plan: Plan<S> },
+    /// An alias of `canonical`, which has no transition in this state.
+    Missing { canonical: E }
+}
+
+
+pub(crate) enum Plan<S> {
+    /// Enabled, and without guard, flag, cooldown or candidates.
+    Direct(S),
+    /// Checked against the transition; for a choice, the indices of the
+    /// candidates that can be picked, in selection order.
+    Checked(Box<[usize]>)
+}
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    /// Precomputes the route of every (state, event) pair that has a
+    /// transition, aliases included, and switches `trigger` to it.
+    pub fn freeze(&mut self)
+    {
+        let mut routes = HashMap::with_capacity(self.transitions.len());
+        let mut plans = Vec::with_capacity(self.transitions.len());
+        let mut aliases: HashMap<E, Vec<E>> = HashMap::new();
+
+        for (alias, canonical) in &self.aliases {
+            aliases.entry(*canonical).or_default().push(*alias);
+        }
+        for (&key, transition) in &self.transitions {
+            let index = plans.len();
+
+            plans.push(Route::Found{ key, plan: plan(transition) });
+            routes.insert(key, index);
+            for alias in aliases.get(&key.1).into_iter().flatten() {
+                routes.insert((key.0, *alias), index);
+            }
+        }
+        let states = self.states();
+
+        for (alias, canonical) in &self.aliases {
+            let index = plans.len();
+
+            plans.push(Route::Missing{ canonical: *canonical });
+            for state in &states {
+                routes.entry((*state, *alias)).or_insert(index);
+            }
+        }
+        self.frozen = Some(Frozen{
+            generation: self.generation,
+            routes,
+            plans,
+            resolved: Cell::new(None)
+        });
+    }
+
+
+    /// Drops the table built by `freeze`.
+    pub fn unfreeze(&mut self)
+    {
+        self.frozen = None;
+    }
+
+
+    /// Whether `trigger` uses the table built by `freeze`; under the
+    /// `Unfreeze` policy, structural changes since then unfreeze the
+    /// machine.
+    pub fn is_frozen(&self) -> bool
+    {
+        self.frozen.as_ref().is_some_and(|frozen| frozen.generation == self.generation)
+    }
+
+
+    /// Whether the machine is frozen and rejects events because its
+    /// structure changed since, under the `Reject` policy.
+    pub(crate) fn freeze_is_stale(&self) -> bool
+    {
+        self.policies.freeze == FreezePolicy::Reject
+            && self.frozen.as_ref().is_some_and(|frozen| frozen.generation != self.generation)
+    }
+
+
+    fn fresh_frozen(&self) -> Option<&Frozen<S, E>>
+    {
+        self.frozen.as_ref().filter(|frozen| frozen.generation == self.generation)
+    }
+
+
+    /// The canonical event `event` stands for when received in `state`.
+    pub(crate) fn resolve(&self, state: S, event: E) -> E
+    {
+        let Some(frozen) = self.fresh_frozen() else {
+            return self.canonical(event);
+        };
+        let index = frozen.routes.get(&(state, event)).copied();
+
+        frozen.resolved.set(index);
+        match index.map(|index| &frozen.plans[index]) {
+            None => event,
+            Some(Route::Found { key, .. }) => key.1,
+            Some(Route::Missing { canonical }) => *canonical
+        }
+    }
+
+
+    /// Returns how the transition stored under `key` is checked if the
+    /// machine is frozen; `Some(None)` means there is no such transition.
+    pub(crate) fn frozen_plan(&self, key: (S, E)) -> Option<Option<&Plan<S>>>
+    {
+        let frozen = self.fresh_frozen()?;
+        let found = |index: usize| match &frozen.plans[index] {
+            Route::Found { key: found, plan } if *found == key => Some(plan),
+            _ => None
+        };
+
+        if let Some(plan) = frozen.resolved.take().and_then(found) {
+            return Some(Some(plan));
+        }
+        Some(frozen.routes.get(&key).and_then(|index| found(*index)))
+    }
+}
+
+
+fn plan<S: Copy>(transition: &Transition<S>) -> Plan<S>
+{
+    let unconditional = transition.enabled
+        && transition.guard.is_none()
+        && transition.feature_flag.is_none()
+        && transition.cooldown.is_none();
+
+    if transition.candidates.is_empty() {
+        return match unconditional {
+            true => Plan::Direct(transition.next_state),
+            false => Plan::Checked(Box::new([]))
+        };
+    }
+
+    let mut order = Vec::with_capacity(transition.candidates.len());
+
+    for (index, candidate) in transition.candidates.iter().enumerate() {
+        match transition.selection {
+            SelectionMode::FirstGuardWins => {
+                order.push(index);
+                // Nothing after a candidate that always passes can win.
+                if candidate.guard.is_none() && candidate.feature_flag.is_none() {
+                    break;
+                }
+            }
+            #[cfg(feature = "sim")]
+            SelectionMode::WeightedRandom => {
+                if candidate.weight > 0.0 {
+                    order.push(index);
+                }
+            }
+        }
+    }
+    Plan::Checked(order.into())
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        builder::StateMachineBuilder,
+        error::TransitionError,
+        fsm::{StateMachine, FSM},
+        policy::Policies
+    };
+    use super::FreezePolicy;
+
+
+    fn machine(current: u8) -> StateMachine<u8, char>
+    {
+        StateMachineBuilder::new(0)
+            .transition(0, 'a', 1)
+            .transition(1, 'a', 2)
+            .guard(|| false)
+            .transition(1, 'b', 3)
+            .transition(2, 'b', 0)
+            .transition(2, 'c', 1)
+            .candidate_guard(|| false)
+            .alternative(0)
+            .alternative(2)
+            .alias('a', 'x')
+            .alias('b', 'y')
+            .terminal(3)
+            .build_resuming(current)
+            .unwrap()
+    }
+
+
+    fn outcome(mut fsm: StateMachine<u8, char>, event: char)
+        -> (Result<(), TransitionError<u8, char>>, u8)
+    {
+        let result = fsm.trigger(event);
+
+        (result, fsm.state())
+    }
+
+
+    #[test]
+    fn test_frozen_matches_unfrozen_for_every_pair()
+    {
+        for state in 0..4 {
+            for event in ['a', 'b', 'c', 'x', 'y', 'z'] {
+                let mut frozen = machine(state);
+
+                frozen.freeze();
+                assert!(frozen.is_frozen());
+                assert_eq!(
+                    frozen.explain(event),
+                    machine(state).explain(event),
+                    "{state} on {event:?}"
+                );
+                assert_eq!(
+                    outcome(frozen, event),
+                    outcome(machine(state), event),
+                    "{state} on {event:?}"
+                );
+            }
+        }
+    }
+
+
+    #[test]
+    fn test_choice_order_is_precomputed()
+    {
+        let mut fsm = machine(2);
+
+        fsm.freeze();
+        fsm.trigger('c').unwrap();
+        assert_eq!(fsm.state(), 0);
+    }
+
+
+    #[test]
+    fn test_structural_change_unfreezes()
+    {
+        let mut fsm = machine(0);
+
+        assert!(!fsm.is_frozen());
+        fsm.freeze();
+        fsm.trigger('x').unwrap();
+        assert!(fsm.is_frozen());
+
+        fsm.remove_transition(1, 'b');
+        assert!(!fsm.is_frozen());
+        assert_eq!(fsm.trigger('y'), Err(TransitionError::NoTransition{ state: 1, event: 'b' }));
+
+        fsm.freeze();
+        assert!(fsm.is_frozen());
+        fsm.unfreeze();
+        assert!(!fsm.is_frozen());
+    }
+
+
+    #[test]
+    fn test_reject_policy_refuses_until_refrozen()
+    {
+        let mut fsm = machine(0);
+
+        fsm.set_policies(Policies::default().with_freeze(FreezePolicy::Reject));
+        fsm.freeze();
+        fsm.set_enabled(1, 'b', false);
+        assert!(!fsm.is_frozen());
+        assert_eq!(fsm.trigger('a'), Err(TransitionError::StaleFreeze{ state: 0, event: 'a' }));
+        assert_eq!(fsm.state(), 0);
+
+        fsm.freeze();
+        fsm.trigger('a').unwrap();
+        assert_eq!(fsm.trigger('b'), Err(TransitionError::Disabled{ state: 1, event: 'b' }));
+    }
+}
@@ -1,19 +1,101 @@
-use std::{collections::HashMap, fmt::Debug, hash::Hash};
+use std::{
+    any::Any,
+    borrow::Cow,
+    cell::RefCell,
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt::{self, Debug},
+    hash::Hash,
+    panic::{self, AssertUnwindSafe},
+    rc::Rc,
+    sync::Arc,
+    time::{Duration, Instant}
+};
+
+use crate::{
+    alarm::RejectionAlarm,
+    alphabet::Alphabet,
+    breakpoint::Breakpoint,
+    cancel::CancellationToken,
+    analysis::AnalysisCache,
+    cascade::{self, DispatchStats},
+    choice::{Candidate, Eligible, SelectionMode},
+    deadline::DeadlineAction,
+    coalesce::PostQueue,
+    clock::{default_clock, Clock},
+    deps::DepCache,
+    describe::KeyOrder,
+    flag::FlagProvider,
+    freeze::{Frozen, Plan},
+    error::{TransitionError, TriggerCode},
+    fair::FairQueue,
+    initial::{InitialSelection, Selector},
+    memo::GuardMemo,
+    middleware::Middleware,
+    observe::Subscriptions,
+    origin::Origin,
+    policy::{CancellationPolicy, FinishedPolicy, Policies, ResetPolicy},
+    trace::{Trace, TraceEntry, TraceOutcome}
+};
 
 
 pub type Action = Box<dyn Fn()>;
+pub type Guard = Box<dyn Fn() -> bool>;
+pub type FinishCallback<S> = Box<dyn FnOnce(&S)>;
 
 
 pub struct Transition<S: Copy> {
-    next_state: S,
-    action: Option<Action>
+    pub(crate) next_state: S,
+    pub(crate) action: Option<Action>,
+    pub(crate) guard: Option<Guard>,
+    pub(crate) cooldown: Option<Duration>,
+    pub(crate) enabled: bool,
+    pub(crate) group: Option<String>,
+    pub(crate) feature_flag: Option<String>,
+    pub(crate) candidates: Vec<Candidate<S>>,
+    pub(crate) selection: SelectionMode
 }
 
 
 impl<S: Copy> Transition<S> {
     pub fn create(next_state: S, action: Option<Action>) -> Self
     {
-        Self{ next_state, action }
+        Self{
+            next_state,
+            action,
+            guard: None,
+            cooldown: None,
+            enabled: true,
+            group: None,
+            feature_flag: None,
+            candidates: Vec::new(),
+            selection: SelectionMode::default()
+        }
+    }
+
+
+    /// Allows the transition to fire only while `guard` returns `true`.
+    pub fn with_guard(mut self, guard: Guard) -> Self
+    {
+        self.guard = Some(guard);
+        self
+    }
+
+
+    /// Forbids firing this transition again until `cooldown` has elapsed
+    /// on the machine's clock since it last fired.
+    pub fn with_cooldown(mut self, cooldown: Duration) -> Self
+    {
+        self.cooldown = Some(cooldown);
+        self
+    }
+
+
+    /// Makes the transition a member of `group`; see
+    /// `StateMachine::set_group_enabled`.
+    pub fn in_group(mut self, group: &str) -> Self
+    {
+        self.group = Some(group.to_string());
+        self
     }
 }
 
@@ -26,7 +108,7 @@ pub trait FSM<S: Copy, E: Copy> {
     
     /// Triggers an event, causing the state machine to transition
     /// if a valid transition exists.
-    fn trigger(&mut self, event: E) -> Result<(), String>;
+    fn trigger(&mut self, event: E) -> Result<(), TransitionError<S, E>>;
 
     
     /// Returns the current state of the state machine.
@@ -34,9 +116,128 @@ pub trait FSM<S: Copy, E: Copy> {
 }
 
 
+/// Error returned by `trigger_batch`: the event at `index` was rejected.
+///
+/// Fields may be added, so it cannot be built outside this crate:
+///
+/// ```compile_fail
+/// use pfsm::{error::TransitionError, fsm::BatchError};
+///
+/// let rejected = TransitionError::NoTransition{ state: 0, event: 'a' };
+/// let error = BatchError{ index: 0, error: rejected };
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct BatchError<S, E> {
+    pub index: usize,
+    pub error: TransitionError<S, E>
+}
+
+
+impl<S: Debug, E: Debug> fmt::Display for BatchError<S, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "Event {} of the batch was rejected: {}", self.index, self.error)
+    }
+}
+
+
+impl<S: Debug, E: Debug> std::error::Error for BatchError<S, E> {}
+
+
+impl<S, E> BatchError<S, E> {
+    /// Returns the code of the rejection, as listed in
+    /// [`ERROR_CODES`](crate::error::ERROR_CODES).
+    pub fn code(&self) -> u16
+    {
+        self.error.code()
+    }
+}
+
+
+/// Why the checks made before a transition's first action stopped it,
+/// in the order they are made.
+pub(crate) enum Blocked<S> {
+    StaleFreeze,
+    Pending,
+    UnknownEvent,
+    Finished,
+    NoTransition,
+    Disabled,
+    FlagOff,
+    /// The transition's guard, or every candidate of a choice, rejected it.
+    GuardRejected,
+    CoolingDown(Duration),
+    TargetDeprecated(S),
+    /// A guard panicked under the `catch_panics` policy.
+    Panicked(String)
+}
+
+
+impl<S> Blocked<S> {
+    /// Takes the message of the `ActionPanicked` error returned by `call`.
+    fn panicked<E>(error: TransitionError<S, E>) -> Self
+    {
+        match error {
+            TransitionError::ActionPanicked { message } => Blocked::Panicked(message),
+            _ => unreachable!("call only reports panics")
+        }
+    }
+}
+
+
 pub struct StateMachine<S: Copy, E: Copy> {
-    state: S,
-    transitions: HashMap<(S, E), Transition<S>>
+    pub(crate) initial: S,
+    pub(crate) state: S,
+    pub(crate) transitions: HashMap<(S, E), Transition<S>>,
+    pub(crate) clock: Arc<dyn Clock>,
+    pub(crate) entered_at: Instant,
+    pub(crate) last_fired: HashMap<(S, E), Instant>,
+    pub(crate) timeouts: HashMap<S, (Duration, E)>,
+    pub(crate) tags: HashMap<S, Vec<String>>,
+    pub(crate) entry_actions: HashMap<S, Action>,
+    pub(crate) exit_actions: HashMap<S, Action>,
+    pub(crate) terminals: HashSet<S>,
+    pub(crate) deprecated: HashSet<S>,
+    /// Every group a transition was ever put in; see `empty_groups`.
+    pub(crate) groups: BTreeSet<String>,
+    pub(crate) state_data: HashMap<S, Box<dyn Any>>,
+    pub(crate) initial_choice: Option<Selector<S>>,
+    pub(crate) initial_selection: Option<InitialSelection<S>>,
+    pub(crate) rejection_alarm: Option<RejectionAlarm<S, E>>,
+    pub(crate) flag_provider: Option<FlagProvider>,
+    pub(crate) prepared: Option<(S, E)>,
+    pub(crate) abandoned_prepares: u64,
+    pub(crate) on_finish: Option<FinishCallback<S>>,
+    pub(crate) deadline: Option<(Instant, DeadlineAction<S, E>)>,
+    pub(crate) name: Option<Cow<'static, str>>,
+    pub(crate) command_mapper: Option<Box<dyn Any>>,
+    pub(crate) generation: u64,
+    pub(crate) sequence: u64,
+    pub(crate) analysis: AnalysisCache<S>,
+    pub(crate) key_order: KeyOrder<S, E>,
+    pub(crate) frozen: Option<Frozen<S, E>>,
+    pub(crate) policies: Policies,
+    pub(crate) guard_memo: Rc<GuardMemo>,
+    pub(crate) guard_deps: Rc<DepCache>,
+    pub(crate) middleware: Vec<Middleware<S, E>>,
+    pub(crate) posted: Rc<RefCell<PostQueue<E>>>,
+    pub(crate) origin: Rc<RefCell<Origin>>,
+    pub(crate) cancel: CancellationToken,
+    pub(crate) fair: FairQueue<S, E>,
+    pub(crate) dispatch_stats: DispatchStats,
+    pub(crate) refresh_context: Option<Box<dyn Fn()>>,
+    pub(crate) subscriptions: Subscriptions<S, E>,
+    pub(crate) breakpoints: Vec<Breakpoint<S, E>>,
+    pub(crate) next_breakpoint_id: u64,
+    pub(crate) aliases: HashMap<E, E>,
+    pub(crate) name_aliases: HashMap<String, E>,
+    pub(crate) alphabet: Option<Alphabet<E>>,
+    pub(crate) quarantine_enabled: bool,
+    pub(crate) quarantine: HashMap<String, u64>,
+    pub(crate) trace: Option<Trace<S, E>>,
+    #[cfg(feature = "sim")]
+    pub(crate) rng: crate::sim::SimRng
 }
 
 
@@ -48,26 +249,68 @@ where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
         transitions: HashMap<(S, E), Transition<S>>
     ) -> Self
     {
-        Self{ state: initial, transitions }
+        let clock = default_clock();
+        let entered_at = clock.now();
+        let groups = transitions.values().filter_map(|t| t.group.clone()).collect();
+
+        Self{
+            initial,
+            state: initial,
+            transitions,
+            clock,
+            entered_at,
+            last_fired: HashMap::new(),
+            timeouts: HashMap::new(),
+            tags: HashMap::new(),
+            entry_actions: HashMap::new(),
+            exit_actions: HashMap::new(),
+            terminals: HashSet::new(),
+            deprecated: HashSet::new(),
+            groups,
+            state_data: HashMap::new(),
+            initial_choice: None,
+            initial_selection: None,
+            rejection_alarm: None,
+            flag_provider: None,
+            prepared: None,
+            abandoned_prepares: 0,
+            on_finish: None,
+            deadline: None,
+            name: None,
+            command_mapper: None,
+            generation: 0,
+            sequence: 0,
+            analysis: AnalysisCache::default(),
+            key_order: KeyOrder::default(),
+            frozen: None,
+            policies: Policies::default(),
+            guard_memo: Rc::default(),
+            guard_deps: Rc::default(),
+            middleware: Vec::new(),
+            posted: Rc::default(),
+            origin: Rc::default(),
+            cancel: CancellationToken::default(),
+            fair: FairQueue::default(),
+            dispatch_stats: DispatchStats::default(),
+            refresh_context: None,
+            subscriptions: Subscriptions::default(),
+            breakpoints: Vec::new(),
+            next_breakpoint_id: 0,
+            aliases: HashMap::new(),
+            name_aliases: HashMap::new(),
+            alphabet: None,
+            quarantine_enabled: false,
+            quarantine: HashMap::new(),
+            trace: None,
+            #[cfg(feature = "sim")]
+            rng: crate::sim::SimRng::new(0)
+        }
     }
 
 
-    fn trigger(&mut self, event: E) -> Result<(), String>
+    fn trigger(&mut self, event: E) -> Result<(), TransitionError<S, E>>
     {
-        let key = (self.state, event);
-        
-        if let Some(transition) = self.transitions.get(&key) {
-            if let Some(action) = &transition.action {
-                action();
-            }
-            self.state = transition.next_state;
-            return Ok(());
-        }
-        
-        Err(format!(
-            "No transition found for event '{:?}' from state '{:?}'",
-            event, self.state
-        ))
+        self.fire(event, Origin::unspecified(), true)
     }
 
 
@@ -78,34 +321,590 @@ where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
 }
 
 
-#[cfg(test)]
-mod test {
-    use super::*;
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    /// Dispatches `event`, optionally skipping its transition action,
+    /// then processes any events posted meanwhile.
+    pub(crate) fn fire(
+        &mut self,
+        event: E,
+        origin: Origin,
+        run_action: bool
+    ) -> Result<(), TransitionError<S, E>>
+    {
+        let result = match self.expire_if_due() {
+            Ok(_) => self.dispatch(origin, |fsm| fsm.fire_one(event, run_action)),
+            Err(err) => Err(err)
+        };
+        result.map_err(|err| self.with_context(err))
+    }
 
 
-    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
-    enum TrafficLightState {
-        Red,
-        Yellow,
-        Green
+    /// Prepares a dispatch, runs `first` and then any posted events.
+    pub(crate) fn dispatch(
+        &mut self,
+        origin: Origin,
+        first: impl FnOnce(&mut Self) -> Result<(), TransitionError<S, E>>
+    ) -> Result<(), TransitionError<S, E>>
+    {
+        *self.origin.borrow_mut() = origin;
+        self.cancel.reset(self.policies.sticky_cancel);
+        self.dispatch_stats = DispatchStats::default();
+        self.posted.borrow_mut().current = (1, 1);
+        self.refresh_context();
+
+        let result = first(self);
+
+        if self.posted.borrow().is_empty() {
+            return result;
+        }
+        if result.is_err() {
+            self.posted.borrow_mut().clear();
+            return result;
+        }
+        self.drain_posted()
     }
 
 
-    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
-    enum TrafficLightEvent {
-        RedTimeout,
-        Yellow2GreenTimeout,
-        Yellow2RedTimeout,
-        GreenTimeout
+    /// Looks up and takes the transition for one event and traces it.
+    ///
+    /// Actions run in order: exit action of the source state, transition
+    /// action, entry action of the target state. The new state is only
+    /// committed once all of them have returned.
+    pub(crate) fn fire_one(
+        &mut self,
+        event: E,
+        run_action: bool
+    ) -> Result<(), TransitionError<S, E>>
+    {
+        let from = self.state;
+        let received = event;
+        let event = self.resolve(from, event);
+
+        if self.policies.finished == FinishedPolicy::Ignore && self.is_finished() {
+            self.note_outcome(received, false);
+            return Ok(());
+        }
+        let sequence = self.sequence;
+        let result = self.take_through_middleware(event, run_action);
+
+        self.record_outcome(from, received, sequence, &result);
+        result
+    }
+
+
+    /// Traces one processed event and feeds the rejection alarm;
+    /// `sequence` is the sequence number from before processing it.
+    pub(crate) fn record_outcome(
+        &mut self,
+        from: S,
+        received: E,
+        sequence: u64,
+        result: &Result<(), TransitionError<S, E>>
+    )
+    {
+        if let Some(trace) = &mut self.trace {
+            let outcome = match result {
+                Ok(()) => TraceOutcome::Transitioned{ to: self.state },
+                Err(err) => TraceOutcome::Rejected(err.clone())
+            };
+            trace.record(TraceEntry{
+                state: from,
+                event: received,
+                outcome,
+                origin: self.origin.borrow().clone(),
+                coalesced: self.posted.borrow().current.0,
+                at: self.clock.now()
+            });
+        }
+        self.note_outcome(received, self.sequence != sequence);
+
+        #[cfg(feature = "paranoid")]
+        self.check_invariants("trigger");
+    }
+
+
+    pub(crate) fn take(&mut self, event: E, run_action: bool) -> Result<(), TransitionError<S, E>>
+    {
+        let target = self.check_transition(event)?;
+
+        self.run_transition(event, target, run_action)
+    }
+
+
+    /// Runs every check `take` makes before the first action, returning
+    /// the selected target.
+    pub(crate) fn check_transition(&mut self, event: E) -> Result<S, TransitionError<S, E>>
+    {
+        let state = self.state;
+        let blocked = match self.preflight(event) {
+            Ok(eligible) => {
+                let target = self.pick_target(eligible);
+                match self.admit(target) {
+                    Ok(target) => {
+                        self.check_breakpoints(state, event, target)?;
+                        return Ok(target);
+                    }
+                    Err(blocked) => blocked
+                }
+            }
+            Err(blocked) => blocked
+        };
+        Err(self.rejection(event, blocked))
+    }
+
+
+    /// Runs the checks shared by `check_transition`, `explain` and
+    /// `available`, up to the targets a choice may still pick from.
+    pub(crate) fn preflight(&self, event: E) -> Result<Eligible<S>, Blocked<S>>
+    {
+        let key = (self.state, event);
+        let catch = self.policies.catch_panics;
+
+        if self.freeze_is_stale() {
+            return Err(Blocked::StaleFreeze);
+        }
+        if self.prepared.is_some() {
+            return Err(Blocked::Pending);
+        }
+        if self.alphabet.as_ref().is_some_and(|alphabet| !alphabet.events.contains(&event)) {
+            return Err(Blocked::UnknownEvent);
+        }
+        if self.terminals.contains(&self.state) {
+            return Err(Blocked::Finished);
+        }
+        let order = match self.frozen_plan(key) {
+            None => None,
+            Some(None) => return Err(Blocked::NoTransition),
+            Some(Some(Plan::Direct(to))) => return Ok(Eligible::One(Some(*to))),
+            Some(Some(Plan::Checked(order))) => Some(&order[..])
+        };
+        let Some(transition) = self.transitions.get(&key) else {
+            return Err(Blocked::NoTransition);
+        };
+        if !transition.enabled {
+            return Err(Blocked::Disabled);
+        }
+        if !self.flag_on(transition.feature_flag.as_deref()) {
+            return Err(Blocked::FlagOff);
+        }
+        if let Some(guard) = &transition.guard
+            && !call(catch, guard).map_err(Blocked::panicked::<E>)?
+        {
+            return Err(Blocked::GuardRejected);
+        }
+
+        let now = self.clock.now();
+
+        if let (Some(cooldown), Some(fired)) =
+            (transition.cooldown, self.last_fired.get(&key))
+            && now.duration_since(*fired) < cooldown
+        {
+            return Err(Blocked::CoolingDown(cooldown - now.duration_since(*fired)));
+        }
+        self.eligible_targets(transition, order, catch).map_err(Blocked::panicked)
+    }
+
+
+    /// Checks the target a choice picked, `None` if none was eligible.
+    pub(crate) fn admit(&self, target: Option<S>) -> Result<S, Blocked<S>>
+    {
+        match target {
+            None => Err(Blocked::GuardRejected),
+            Some(target) if self.deprecated.contains(&target) => {
+                Err(Blocked::TargetDeprecated(target))
+            }
+            Some(target) => Ok(target)
+        }
+    }
+
+
+    fn rejection(&mut self, event: E, blocked: Blocked<S>) -> TransitionError<S, E>
+    {
+        let state = self.state;
+
+        match blocked {
+            Blocked::StaleFreeze => TransitionError::StaleFreeze{ state, event },
+            Blocked::Pending => TransitionError::TransitionPending{ state, event },
+            Blocked::UnknownEvent => self.unknown_event(format!("{event:?}")),
+            Blocked::Finished => TransitionError::MachineFinished{ state, event },
+            Blocked::NoTransition => TransitionError::NoTransition{ state, event },
+            Blocked::Disabled => TransitionError::Disabled{ state, event },
+            Blocked::FlagOff | Blocked::GuardRejected => {
+                TransitionError::GuardRejected{ state, event }
+            }
+            Blocked::CoolingDown(remaining) => {
+                TransitionError::CoolingDown{ state, event, remaining }
+            }
+            Blocked::TargetDeprecated(target) => {
+                TransitionError::TargetDeprecated{ state, event, target }
+            }
+            Blocked::Panicked(message) => TransitionError::ActionPanicked{ message }
+        }
+    }
+
+
+    /// Runs the actions of a checked transition and commits it.
+    pub(crate) fn run_transition(
+        &mut self,
+        event: E,
+        target: S,
+        run_action: bool
+    ) -> Result<(), TransitionError<S, E>>
+    {
+        let state = self.state;
+        let key = (state, event);
+        let catch = self.policies.catch_panics;
+        let now = self.clock.now();
+        let transition = &self.transitions[&key];
+        let limit = self.policies.cascade_limit;
+
+        if let Some(exit) = self.exit_actions.get(&state) {
+            cascade::spend(&mut self.dispatch_stats, limit, state, false)?;
+            call(catch, exit)?;
+        }
+        if run_action && let Some(action) = &transition.action {
+            call(catch, action)?;
+            if self.cancel.take_observed()
+                && self.policies.cancellation == CancellationPolicy::Fail
+            {
+                return Err(TransitionError::Cancelled{ state, event });
+            }
+        }
+        if let Some(entry) = self.entry_actions.get(&target) {
+            cascade::spend(&mut self.dispatch_stats, limit, target, true)?;
+            call(catch, entry)?;
+        }
+
+        if transition.cooldown.is_some() {
+            self.last_fired.insert(key, now);
+        }
+        self.state = target;
+        self.entered_at = now;
+        self.sequence += 1;
+        self.subscriptions.notify(&state, &event, &target, now);
+
+        if self.terminals.contains(&self.state)
+            && let Some(on_finish) = self.on_finish.take()
+        {
+            on_finish(&self.state);
+        }
+        Ok(())
+    }
+
+
+    pub fn policies(&self) -> &Policies
+    {
+        &self.policies
+    }
+
+
+    pub fn set_policies(&mut self, policies: Policies)
+    {
+        self.policies = policies;
+    }
+
+
+    /// Starts recording the last `capacity` triggers, discarding any
+    /// previous trace.
+    pub fn enable_trace(&mut self, capacity: usize)
+    {
+        self.trace = Some(Trace::new(capacity));
+    }
+
+
+    pub fn disable_trace(&mut self)
+    {
+        self.trace = None;
+    }
+
+
+    /// Returns the recorded triggers, oldest first; empty unless enabled.
+    pub fn trace(&self) -> impl Iterator<Item = &TraceEntry<S, E>>
+    {
+        self.trace.iter().flat_map(|trace| trace.entries.iter())
+    }
+
+
+    /// Triggers `events` in order, stopping at the first rejected one.
+    ///
+    /// Returns how many events were processed.
+    pub fn trigger_batch(
+        &mut self,
+        events: impl IntoIterator<Item = E>
+    ) -> Result<usize, BatchError<S, E>>
+    {
+        let mut processed = 0;
+
+        for event in events {
+            self.trigger(event)
+                .map_err(|error| BatchError{ index: processed, error })?;
+            processed += 1;
+        }
+        Ok(processed)
+    }
+
+
+    /// Like `trigger`, but reports failures as a payload-free code.
+    ///
+    /// Neither a successful nor a rejected call allocates unless the trace
+    /// or the `catch_panics` policy is enabled.
+    pub fn try_trigger_quiet(&mut self, event: E) -> Result<(), TriggerCode>
+    {
+        self.fire(event, Origin::unspecified(), true).map_err(|err| TriggerCode::from(&err))
+    }
+
+
+    /// Returns to the initial state without running any action.
+    ///
+    /// Cooldowns are forgotten and time in state restarts. An initial
+    /// choice is evaluated again under `ResetPolicy::Reevaluate`.
+    pub fn reset(&mut self)
+    {
+        if self.policies.reset == ResetPolicy::Reevaluate
+            && let Some(choose) = &self.initial_choice
+        {
+            let selection = choose();
+
+            if selection.state != self.initial {
+                self.initial = selection.state;
+                self.generation += 1;
+            }
+            self.initial_selection = Some(selection);
+        }
+        self.state = self.initial;
+        self.entered_at = self.clock.now();
+        self.last_fired.clear();
+    }
+
+
+    /// Sets the action run whenever a transition enters `state`.
+    pub fn set_entry_action(&mut self, state: S, action: Action)
+    {
+        self.entry_actions.insert(state, action);
+    }
+
+
+    /// Sets the action run whenever a transition leaves `state`.
+    pub fn set_exit_action(&mut self, state: S, action: Action)
+    {
+        self.exit_actions.insert(state, action);
+    }
+
+
+    /// Marks `state` as terminal: once entered, the machine is finished.
+    pub fn set_terminal(&mut self, state: S)
+    {
+        if self.terminals.insert(state) {
+            self.generation += 1;
+        }
+    }
+
+
+    /// Returns `true` while the current state is terminal.
+    pub fn is_finished(&self) -> bool
+    {
+        self.terminals.contains(&self.state)
+    }
+
+
+    /// Sets the callback run, with the terminal state, the first time a
+    /// transition enters any terminal state. It runs at most once; a
+    /// machine already finished when it is set does not run it.
+    pub fn set_on_finish(&mut self, on_finish: FinishCallback<S>)
+    {
+        self.on_finish = Some(on_finish);
+    }
+
+
+    /// Returns a counter bumped on every structural change.
+    pub fn generation(&self) -> u64
+    {
+        self.generation
+    }
+
+
+    /// Returns how many transitions the machine has taken.
+    pub fn sequence(&self) -> u64
+    {
+        self.sequence
+    }
+
+
+    /// Adds a transition, returning the one it replaced, if any.
+    pub fn add_transition(
+        &mut self,
+        from: S,
+        event: E,
+        transition: Transition<S>
+    ) -> Option<Transition<S>>
+    {
+        self.generation += 1;
+        self.groups.extend(transition.group.clone());
+        self.transitions.insert((from, event), transition)
+    }
+
+
+    pub fn remove_transition(&mut self, from: S, event: E) -> Option<Transition<S>>
+    {
+        let removed = self.transitions.remove(&(from, event));
+
+        if removed.is_some() {
+            self.generation += 1;
+        }
+        removed
+    }
+
+
+    /// Removes `state` with every transition out of or into it and
+    /// everything attached to it: tags, actions, subscriptions, timeout,
+    /// terminal and deprecation marks and data.
+    ///
+    /// Returns how many transitions were removed, or `None` if `state`
+    /// is the initial or current state, which cannot be removed.
+    pub fn remove_state(&mut self, state: &S) -> Option<usize>
+    {
+        if *state == self.initial || *state == self.state {
+            return None;
+        }
+
+        let before = self.transitions.len();
+
+        self.transitions.retain(|(from, _), t| from != state && t.targets().all(|to| to != *state));
+        self.last_fired.retain(|key, _| self.transitions.contains_key(key));
+        self.tags.remove(state);
+        self.entry_actions.remove(state);
+        self.exit_actions.remove(state);
+        self.timeouts.remove(state);
+        self.terminals.remove(state);
+        self.deprecated.remove(state);
+        self.state_data.remove(state);
+        self.subscriptions.remove_state(state);
+        self.generation += 1;
+
+        Some(before - self.transitions.len())
+    }
+
+
+    /// Enables or disables a transition without removing it.
+    ///
+    /// Returns `false` if there is no such transition.
+    pub fn set_enabled(&mut self, from: S, event: E, enabled: bool) -> bool
+    {
+        match self.transitions.get_mut(&(from, event)) {
+            Some(transition) => {
+                if transition.enabled != enabled {
+                    transition.enabled = enabled;
+                    self.generation += 1;
+                }
+                true
+            }
+            None => false
+        }
+    }
+
+
+    /// Replaces the clock read by every time-based feature of the machine.
+    ///
+    /// Time in the current state restarts from the new clock's reading and
+    /// cooldowns recorded against the old clock are forgotten.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>)
+    {
+        self.entered_at = clock.now();
+        self.last_fired.clear();
+        self.clock = clock;
+    }
+
+
+    /// Returns how long the machine has been in its current state.
+    pub fn time_in_state(&self) -> Duration
+    {
+        self.clock.now().duration_since(self.entered_at)
+    }
+
+
+    /// Makes `tick` trigger `event` once the machine has spent `after`
+    /// in `state`.
+    pub fn set_timeout(&mut self, state: S, after: Duration, event: E)
+    {
+        self.timeouts.insert(state, (after, event));
+    }
+
+
+    /// Returns `true` if the current state carries `tag`.
+    pub fn has_tag(&self, tag: &str) -> bool
+    {
+        self.tags
+            .get(&self.state)
+            .is_some_and(|tags| tags.iter().any(|t| t == tag))
+    }
+
+
+    /// Returns every state carrying `tag`, ordered by `Debug` rendering.
+    pub fn states_with_tag(&self, tag: &str) -> Vec<S>
+    {
+        let mut states: Vec<S> = self.tags
+            .iter()
+            .filter(|(_, tags)| tags.iter().any(|t| t == tag))
+            .map(|(state, _)| *state)
+            .collect();
+
+        states.sort_by_cached_key(|state| format!("{state:?}"));
+        states
+    }
+
+
+    /// Handles a passed deadline, or else fires the current state's
+    /// timeout if it is due.
+    ///
+    /// Returns `Ok(true)` if either happened.
+    pub fn tick(&mut self) -> Result<bool, TransitionError<S, E>>
+    {
+        if self.expire_if_due()? {
+            return Ok(true);
+        }
+        match self.timeouts.get(&self.state) {
+            Some(&(after, event)) if self.time_in_state() >= after => {
+                self.trigger_from(event, Origin::timer()).map(|_| true)
+            }
+            _ => Ok(false)
+        }
     }
+}
 
 
-    type State = TrafficLightState;
-    type Event = TrafficLightEvent;
+/// Invokes a user closure, turning a panic into `ActionPanicked` if
+/// `catch` is set.
+pub(crate) fn call<T, S, E>(catch: bool, f: impl Fn() -> T) -> Result<T, TransitionError<S, E>>
+{
+    if !catch {
+        return Ok(f());
+    }
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "non-string panic payload".to_string());
+
+        TransitionError::ActionPanicked{ message }
+    })
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{assert_fsm_path, builder::StateMachineBuilder, clock::MockClock};
+    use std::{cell::RefCell, rc::Rc};
+    use crate::fixtures::{self, traffic_light::{self, Event, State}};
+    use Event::*;
+    use State::*;
 
 
     struct TrafficLight {
-        fsm: StateMachine<TrafficLightState, TrafficLightEvent>
+        fsm: StateMachine<State, Event>
     }
 
 
@@ -122,85 +921,207 @@ mod test {
 
     fn create_traffic_light() -> TrafficLight
     {
-        let initial = State::Red;
-        let mut transitions: HashMap<(State, Event), Transition<State>> =
-            HashMap::new();
+        let log = fixtures::Log::default();
 
-        transitions.insert(
-            (State::Red, Event::RedTimeout),
-            Transition::create(
-                State::Yellow,
-                Some(Box::new(|| action("Red -> Yellow")))
-            )
-        );
+        TrafficLight::init_fsm(
+            traffic_light::INITIAL,
+            fixtures::transition_map(&traffic_light::TRANSITIONS, Some(&log))
+        )
+    }
 
-        transitions.insert(
-            (State::Yellow, Event::Yellow2GreenTimeout),
-            Transition::create(
-                State::Green,
-                Some(Box::new(|| action("Yellow -> Green")))
-            )
+
+    #[test]
+    fn test_initialize()
+    {
+        let tl = create_traffic_light();
+        assert_eq!(tl.fsm.state(), State::Red);
+    }
+
+
+    #[test]
+    fn test_trigger()
+    {
+        let mut tl = create_traffic_light();
+
+        assert_fsm_path!(
+            tl.fsm,
+            Red -RedTimeout-> Yellow -Yellow2GreenTimeout-> Green
+                -GreenTimeout-> Yellow -Yellow2RedTimeout-> Red
         );
+    }
+
+
+    #[test]
+    fn test_incorrect_trigger()
+    {
+        let mut tl = create_traffic_light();
+
+        assert_fsm_path!(tl.fsm, Red !> GreenTimeout);
+    }
+
+
+    #[test]
+    fn test_clock_shared_by_timeout_and_cooldown()
+    {
+        let clock = Arc::new(MockClock::new());
+        let mut transitions = HashMap::new();
 
         transitions.insert(
-            (State::Green, Event::GreenTimeout),
-            Transition::create(
-                State::Yellow,
-                Some(Box::new(|| action("Green -> Yellow")))
-            )
+            (State::Red, Event::RedTimeout),
+            Transition::create(State::Yellow, None)
+                .with_cooldown(Duration::from_secs(10))
         );
-
         transitions.insert(
             (State::Yellow, Event::Yellow2RedTimeout),
-            Transition::create(
-                State::Red,
-                Some(Box::new(|| action("Yellow -> Red")))
-            )
+            Transition::create(State::Red, None)
         );
 
-        TrafficLight::init_fsm(initial, transitions)
+        let mut fsm: StateMachine<State, Event> =
+            StateMachine::initialize(State::Red, transitions);
+        fsm.set_clock(clock.clone());
+        fsm.set_timeout(State::Yellow, Duration::from_secs(5), Event::Yellow2RedTimeout);
+
+        assert!(fsm.trigger(Event::RedTimeout).is_ok());
+        assert_eq!(fsm.tick(), Ok(false));
+
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(fsm.time_in_state(), Duration::from_secs(5));
+        assert_eq!(fsm.tick(), Ok(true));
+        assert_eq!(fsm.state(), State::Red);
+        assert_eq!(fsm.time_in_state(), Duration::ZERO);
+
+        // The same five seconds are only half of the cooldown.
+        assert!(fsm.trigger(Event::RedTimeout).is_err());
+        assert_eq!(fsm.state(), State::Red);
+
+        clock.advance(Duration::from_secs(5));
+        assert!(fsm.trigger(Event::RedTimeout).is_ok());
+        assert_eq!(fsm.state(), State::Yellow);
     }
 
 
-    fn action(msg: &str)
+    fn panicking_light() -> StateMachine<State, Event>
     {
-        println!("{msg}");
+        StateMachineBuilder::new(Red)
+            .transition(Red, RedTimeout, Yellow)
+            .action(|| panic!("bulb burnt out"))
+            .transition(Red, GreenTimeout, Green)
+            .guard(|| panic!("sensor offline"))
+            .transition(Yellow, Yellow2RedTimeout, Red)
+            .build()
+            .unwrap()
     }
 
 
     #[test]
-    fn test_initialize()
+    fn test_panicking_action_propagates_by_default()
     {
-        let tl = create_traffic_light();
-        assert_eq!(tl.fsm.state(), State::Red);
+        let mut fsm = panicking_light();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| fsm.trigger(RedTimeout)));
+
+        assert!(result.is_err());
+        assert_eq!(fsm.state(), Red);
     }
 
 
     #[test]
-    fn test_trigger()
+    fn test_panicking_action_caught_by_policy()
     {
-        let mut tl = create_traffic_light();
+        let mut fsm = panicking_light();
 
-        assert!(tl.fsm.trigger(Event::RedTimeout).is_ok());
-        assert_eq!(tl.fsm.state(), State::Yellow);
-        
-        assert!(tl.fsm.trigger(Event::Yellow2GreenTimeout).is_ok());
-        assert_eq!(tl.fsm.state(), State::Green);
+        fsm.set_policies(Policies{ catch_panics: true, ..Policies::default() });
+        fsm.enable_trace(8);
 
-        assert!(tl.fsm.trigger(Event::GreenTimeout).is_ok());
-        assert_eq!(tl.fsm.state(), State::Yellow);
+        assert_eq!(
+            fsm.trigger(RedTimeout),
+            Err(TransitionError::ActionPanicked{ message: "bulb burnt out".to_string() })
+        );
+        assert_eq!(fsm.state(), Red);
+        assert_eq!(
+            fsm.trigger(GreenTimeout),
+            Err(TransitionError::ActionPanicked{ message: "sensor offline".to_string() })
+        );
+        assert_eq!(fsm.state(), Red);
 
-        assert!(tl.fsm.trigger(Event::Yellow2RedTimeout).is_ok());
-        assert_eq!(tl.fsm.state(), State::Red);
+        let outcomes: Vec<_> = fsm.trace().map(|entry| entry.outcome.clone()).collect();
+        assert_eq!(outcomes, [
+            TraceOutcome::Rejected(TransitionError::ActionPanicked{
+                message: "bulb burnt out".to_string()
+            }),
+            TraceOutcome::Rejected(TransitionError::ActionPanicked{
+                message: "sensor offline".to_string()
+            })
+        ]);
+    }
+
+
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    enum Job {
+        Queued,
+        Running,
+        Completed,
+        Cancelled
+    }
+
+
+    fn job(finished: Rc<RefCell<Vec<Job>>>) -> StateMachine<Job, char>
+    {
+        let mut fsm = StateMachineBuilder::new(Job::Queued)
+            .transition(Job::Queued, 's', Job::Running)
+            .transition(Job::Queued, 'c', Job::Cancelled)
+            .transition(Job::Running, 'd', Job::Completed)
+            .transition(Job::Completed, 'r', Job::Queued)
+            .terminal(Job::Completed)
+            .terminal(Job::Cancelled)
+            .build()
+            .unwrap();
+
+        fsm.set_on_finish(Box::new(move |state| finished.borrow_mut().push(*state)));
+        fsm
     }
 
 
     #[test]
-    fn test_incorrect_trigger()
+    fn test_on_finish_runs_exactly_once()
     {
-        let mut tl = create_traffic_light();
+        let finished = Rc::new(RefCell::new(Vec::new()));
+        let mut fsm = job(finished.clone());
 
-        assert_eq!(tl.fsm.state(), State::Red);
-        assert!(!tl.fsm.trigger(Event::GreenTimeout).is_ok());
+        assert!(!fsm.is_finished());
+        fsm.trigger('s').unwrap();
+        assert!(finished.borrow().is_empty());
+        fsm.trigger('d').unwrap();
+        assert!(fsm.is_finished());
+        assert_eq!(*finished.borrow(), [Job::Completed]);
+
+        fsm.reset();
+        fsm.trigger('c').unwrap();
+        assert!(fsm.is_finished());
+        assert_eq!(*finished.borrow(), [Job::Completed]);
+        assert_eq!(fsm.unexpected_sinks(), Vec::<Job>::new());
+    }
+
+
+    #[test]
+    fn test_finished_machine_refuses_or_ignores_events()
+    {
+        let mut fsm = job(Rc::default());
+
+        fsm.enable_trace(8);
+        fsm.trigger_batch(['s', 'd']).unwrap();
+
+        // Completed has an outgoing transition, but terminal wins.
+        assert_eq!(
+            fsm.trigger('r'),
+            Err(TransitionError::MachineFinished{ state: Job::Completed, event: 'r' })
+        );
+        assert_eq!(fsm.try_trigger_quiet('x'), Err(TriggerCode::MachineFinished));
+        assert_eq!(fsm.trace().count(), 4);
+
+        fsm.set_policies(Policies{ finished: FinishedPolicy::Ignore, ..Policies::default() });
+        assert_eq!(fsm.trigger('r'), Ok(()));
+        assert_eq!(fsm.trigger('x'), Ok(()));
+        assert_eq!(fsm.state(), Job::Completed);
+        assert_eq!(fsm.trace().count(), 4);
     }
 }
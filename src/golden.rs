@@ -0,0 +1,335 @@
+//! Golden-file snapshots of a machine's observable behaviour.
+//!
+//! A [`BehaviorSnapshot`] gathers what a test run leaves behind — state,
+//! counters, transition coverage, the stats of the last dispatch, the
+//! trace, quarantined events and the structural violations — as a JSON
+//! document whose object keys and lists are sorted, so equal runs give
+//! byte-identical files. Durations are the
+//! only values that depend on the wall clock; [`BehaviorSnapshot::redact`]
+//! replaces them by `null`.
+//!
+//! [`assert_behavior_snapshot!`] compares a machine against a checked-in
+//! file, and fails when the file is missing. Setting
+//! `PFSM_UPDATE_SNAPSHOTS` writes every file a test run compares against
+//! instead, to be reviewed in the diff.
+//!
+//! ```
+//! use pfsm::{fixtures::traffic_light::{self, Event}, fsm::FSM};
+//!
+//! let mut fsm = traffic_light::machine();
+//! fsm.enable_trace(8);
+//! fsm.trigger(Event::RedTimeout).unwrap();
+//!
+//! let snapshot = fsm.behavior_snapshot().redact();
+//! assert_eq!(snapshot.value().get("state").unwrap().as_str(), Some("Yellow"));
+//! ```
+
+use std::{fmt::Debug, hash::Hash, path::Path, time::Duration};
+
+use crate::{fsm::StateMachine, json::Value, trace::TraceOutcome};
+
+
+/// Compares `$fsm`'s behaviour snapshot against the file at `$path`,
+/// relative to the crate being tested; with `redact`, durations are
+/// compared as `null`.
+#[macro_export]
+macro_rules! assert_behavior_snapshot {
+    ($fsm:expr, $path:expr) => {
+        $crate::golden::check_snapshot(
+            &$fsm.behavior_snapshot(),
+            ::std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join($path)
+        )
+    };
+    ($fsm:expr, $path:expr, redact) => {
+        $crate::golden::check_snapshot(
+            &$fsm.behavior_snapshot().redact(),
+            ::std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join($path)
+        )
+    };
+}
+
+
+/// A machine's observable behaviour; see the module documentation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BehaviorSnapshot {
+    value: Value
+}
+
+
+impl BehaviorSnapshot {
+    pub fn value(&self) -> &Value
+    {
+        &self.value
+    }
+
+
+    /// Replaces every duration, the members whose key ends in `_ms`, by
+    /// `null`.
+    pub fn redact(mut self) -> Self
+    {
+        redact(&mut self.value);
+        self
+    }
+
+
+    /// Returns the snapshot as indented JSON ending with a newline, the
+    /// format of the snapshot files.
+    pub fn to_json(&self) -> String
+    {
+        self.value.to_pretty_string() + "\n"
+    }
+}
+
+
+fn redact(value: &mut Value)
+{
+    match value {
+        Value::Array(items) => items.iter_mut().for_each(redact),
+        Value::Object(members) => {
+            for (key, value) in members {
+                if key.ends_with("_ms") {
+                    *value = Value::Null;
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+
+/// Implementation of [`assert_behavior_snapshot!`].
+#[track_caller]
+pub fn check_snapshot(snapshot: &BehaviorSnapshot, path: impl AsRef<Path>)
+{
+    compare(snapshot, path.as_ref(), std::env::var_os("PFSM_UPDATE_SNAPSHOTS").is_some());
+}
+
+
+#[track_caller]
+fn compare(snapshot: &BehaviorSnapshot, path: &Path, update: bool)
+{
+    let actual = snapshot.to_json();
+
+    if update {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+        std::fs::write(path, actual).unwrap();
+        return;
+    }
+    if !path.exists() {
+        panic!(
+            "snapshot {} is missing; rerun with PFSM_UPDATE_SNAPSHOTS=1 to write it\n\
+             --- actual\n{actual}",
+            path.display()
+        );
+    }
+
+    let expected = std::fs::read_to_string(path).unwrap();
+    if expected != actual {
+        panic!(
+            "behaviour differs from snapshot {}; rerun with PFSM_UPDATE_SNAPSHOTS=1 to \
+             accept it\n--- expected\n{expected}--- actual\n{actual}",
+            path.display()
+        );
+    }
+}
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    /// Captures the machine's observable behaviour so far.
+    pub fn behavior_snapshot(&self) -> BehaviorSnapshot
+    {
+        let now = self.clock.now();
+        let entries = self.trace.as_ref().map(|trace| &trace.entries);
+
+        let declared: Vec<String> = self
+            .describe()
+            .transitions
+            .iter()
+            .map(|t| format!("{:?} -{:?}-> {:?}", t.from, t.event, t.to))
+            .collect();
+        let taken: Vec<String> = entries
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| match &entry.outcome {
+                TraceOutcome::Transitioned{ to } => {
+                    Some(format!("{:?} -{:?}-> {:?}", entry.state, entry.event, to))
+                }
+                TraceOutcome::Rejected(_) => None
+            })
+            .collect();
+        let (covered, uncovered) = declared.into_iter().partition(|t| taken.contains(t));
+
+        let rejections = entries.map_or(Value::Null, |entries| {
+            let count = entries
+                .iter()
+                .filter(|entry| matches!(entry.outcome, TraceOutcome::Rejected(_)))
+                .count();
+            Value::Number(count as f64)
+        });
+        let trace = entries.map_or(Value::Null, |entries| {
+            Value::Array(entries
+                .iter()
+                .map(|entry| object(vec![
+                    ("age_ms", millis(now.duration_since(entry.at))),
+                    ("coalesced", Value::Number(entry.coalesced as f64)),
+                    ("event", debug(&entry.event)),
+                    ("origin", Value::from(entry.origin.to_string())),
+                    ("outcome", match &entry.outcome {
+                        TraceOutcome::Transitioned{ to } => object(vec![("to", debug(to))]),
+                        TraceOutcome::Rejected(error) => {
+                            object(vec![("rejected", Value::from(error.to_string()))])
+                        }
+                    }),
+                    ("state", debug(&entry.state))
+                ]))
+                .collect())
+        });
+        let unknown_events = if self.quarantine_enabled {
+            let mut counts: Vec<_> = self.quarantine.iter().collect();
+            counts.sort();
+            object(counts
+                .into_iter()
+                .map(|(name, count)| (name.as_str(), Value::Number(*count as f64)))
+                .collect())
+        } else {
+            Value::Null
+        };
+
+        let stats = self.last_dispatch_stats();
+        let value = object(vec![
+            ("configuration", Value::from(self.configuration().to_string())),
+            ("counters", object(vec![
+                ("generation", Value::Number(self.generation as f64)),
+                ("rejections", rejections),
+                ("transitions", Value::Number(self.sequence as f64))
+            ])),
+            ("coverage", object(vec![
+                ("covered", sorted(covered)),
+                ("uncovered", sorted(uncovered))
+            ])),
+            ("dispatch_stats", object(vec![
+                ("entries", Value::Number(stats.entries as f64)),
+                ("exits", Value::Number(stats.exits as f64)),
+                ("internal_events", Value::Number(stats.internal_events as f64))
+            ])),
+            ("finished", Value::Bool(self.is_finished())),
+            ("state", debug(&self.state)),
+            ("time_in_state_ms", millis(self.time_in_state())),
+            ("trace", trace),
+            ("unknown_events", unknown_events),
+            ("violations", object(vec![
+                ("empty_groups", sorted(self.empty_groups().into_iter().map(Into::into).collect())),
+                ("unexpected_sinks", sorted(debugs(self.unexpected_sinks()))),
+                ("unreachable_states", sorted(debugs(self.unreachable_states())))
+            ]))
+        ]);
+        BehaviorSnapshot{ value }
+    }
+}
+
+
+fn object(members: Vec<(&str, Value)>) -> Value
+{
+    Value::Object(members.into_iter().map(|(key, value)| (key.to_string(), value)).collect())
+}
+
+
+fn sorted(mut items: Vec<String>) -> Value
+{
+    items.sort();
+    Value::Array(items.into_iter().map(Value::from).collect())
+}
+
+
+fn debugs<T: Debug>(items: Vec<T>) -> Vec<String>
+{
+    items.iter().map(|item| format!("{item:?}")).collect()
+}
+
+
+fn debug(value: &impl Debug) -> Value
+{
+    Value::from(format!("{value:?}"))
+}
+
+
+fn millis(duration: Duration) -> Value
+{
+    Value::Number(duration.as_millis() as f64)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        clock::MockClock,
+        fixtures::traffic_light::{self, Event::*},
+        fsm::FSM
+    };
+    use std::sync::Arc;
+
+
+    fn run() -> StateMachine<traffic_light::State, traffic_light::Event>
+    {
+        let mut fsm = traffic_light::builder()
+            .on_exit(traffic_light::State::Green, || {})
+            .on_enter(traffic_light::State::Yellow, || {})
+            .build()
+            .unwrap();
+
+        fsm.enable_trace(8);
+        fsm.enable_quarantine();
+        for event in [RedTimeout, Yellow2GreenTimeout, Yellow2RedTimeout, GreenTimeout] {
+            let _ = fsm.trigger(event);
+        }
+        fsm
+    }
+
+
+    #[test]
+    fn test_run_matches_checked_in_snapshot()
+    {
+        assert_eq!(run().behavior_snapshot().redact(), run().behavior_snapshot().redact());
+        assert_behavior_snapshot!(run(), "tests/snapshots/traffic_light.json", redact);
+    }
+
+
+    #[test]
+    #[should_panic(expected = "is missing")]
+    fn test_missing_snapshot_fails()
+    {
+        compare(&run().behavior_snapshot(), Path::new("tests/snapshots/missing.json"), false);
+    }
+
+
+    #[test]
+    fn test_redaction_strips_durations_only()
+    {
+        let clock = Arc::new(MockClock::new());
+        let mut fsm = traffic_light::machine();
+
+        fsm.set_clock(clock.clone());
+        fsm.enable_trace(8);
+        fsm.trigger(RedTimeout).unwrap();
+        clock.advance(std::time::Duration::from_millis(250));
+
+        let snapshot = fsm.behavior_snapshot();
+        let entry = &snapshot.value().get("trace").unwrap().as_array().unwrap()[0];
+        assert_eq!(snapshot.value().get("time_in_state_ms"), Some(&Value::Number(250.0)));
+        assert_eq!(entry.get("age_ms"), Some(&Value::Number(250.0)));
+
+        let redacted = fsm.behavior_snapshot().redact();
+        let entry = &redacted.value().get("trace").unwrap().as_array().unwrap()[0];
+        assert_eq!(redacted.value().get("time_in_state_ms"), Some(&Value::Null));
+        assert_eq!(entry.get("age_ms"), Some(&Value::Null));
+        assert_eq!(entry.get("event").unwrap().as_str(), Some("RedTimeout"));
+        assert_eq!(redacted.to_json().lines().count(), snapshot.to_json().lines().count());
+    }
+}
@@ -0,0 +1,134 @@
+//! Named groups of transitions toggled together.
+//!
+//! Group membership is set per transition with `group` on the builder or
+//! `Transition::in_group`. Toggling a group sets the `enabled` flag of
+//! every member, so its transitions behave exactly as if disabled one by
+//! one, and a later `set_enabled` on a member overrides the group.
+//!
+//! A group exists once a transition is put in it. Removing transitions,
+//! or hot-swapping in a structure without them, can leave a group with
+//! no member, which `empty_groups` reports: toggling it silently changes
+//! nothing.
+
+use std::{fmt::Debug, hash::Hash};
+
+use crate::fsm::StateMachine;
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    /// Enables or disables every transition of `group` and returns how
+    /// many of them changed; 0 for a group without members.
+    pub fn set_group_enabled(&mut self, group: &str, enabled: bool) -> usize
+    {
+        let mut changed = 0;
+
+        for transition in self.transitions.values_mut() {
+            if transition.group.as_deref() == Some(group) && transition.enabled != enabled {
+                transition.enabled = enabled;
+                changed += 1;
+            }
+        }
+
+        if changed > 0 {
+            self.generation += 1;
+        }
+        changed
+    }
+
+
+    /// Returns the transitions in `group`, ordered like `describe`.
+    pub fn group_members(&self, group: &str) -> Vec<(S, E)>
+    {
+        self.describe()
+            .transitions
+            .into_iter()
+            .filter(|t| t.group.as_deref() == Some(group))
+            .map(|t| (t.from, t.event))
+            .collect()
+    }
+
+
+    /// Returns the groups that no transition is in anymore, sorted.
+    pub fn empty_groups(&self) -> Vec<&str>
+    {
+        self.groups
+            .iter()
+            .filter(|group| {
+                !self.transitions.values().any(|t| t.group.as_deref() == Some(group.as_str()))
+            })
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        builder::StateMachineBuilder,
+        error::TransitionError,
+        fsm::{Transition, FSM}
+    };
+
+
+    #[test]
+    fn test_group_toggled_as_a_unit()
+    {
+        let mut fsm = StateMachineBuilder::new("cart")
+            .transition("cart", "express", "paid")
+            .group("experimental_checkout")
+            .transition("paid", "undo", "cart")
+            .group("experimental_checkout")
+            .transition("cart", "pay", "paid")
+            .transition("paid", "back", "cart")
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            fsm.group_members("experimental_checkout"),
+            [("cart", "express"), ("paid", "undo")]
+        );
+        assert_eq!(fsm.set_group_enabled("experimental_checkout", false), 2);
+        assert_eq!(fsm.set_group_enabled("experimental_checkout", false), 0);
+        assert_eq!(
+            fsm.trigger("express"),
+            Err(TransitionError::Disabled{ state: "cart", event: "express" })
+        );
+        assert_eq!(fsm.available(), ["pay"]);
+        fsm.trigger("pay").unwrap();
+        assert_eq!(
+            fsm.trigger("undo"),
+            Err(TransitionError::Disabled{ state: "paid", event: "undo" })
+        );
+
+        assert_eq!(fsm.set_group_enabled("experimental_checkout", true), 2);
+        fsm.trigger("undo").unwrap();
+        fsm.trigger("express").unwrap();
+        assert_eq!(fsm.state(), "paid");
+        assert_eq!(fsm.set_group_enabled("missing", true), 0);
+        assert!(fsm.empty_groups().is_empty());
+    }
+
+
+    #[test]
+    fn test_emptied_group_is_reported()
+    {
+        let mut fsm = StateMachineBuilder::new("cart")
+            .transition("cart", "express", "paid")
+            .group("experimental_checkout")
+            .transition("cart", "pay", "paid")
+            .group("legacy")
+            .build()
+            .unwrap();
+
+        fsm.remove_transition("cart", "express");
+        assert_eq!(fsm.empty_groups(), ["experimental_checkout"]);
+        assert_eq!(fsm.set_group_enabled("experimental_checkout", false), 0);
+
+        fsm.add_transition("cart", "express", Transition::create("paid", None).in_group("beta"));
+        fsm.remove_state(&"paid");
+        assert_eq!(fsm.empty_groups(), ["beta", "experimental_checkout", "legacy"]);
+    }
+}
@@ -0,0 +1,278 @@
+//! Record-by-record loading of large transition lists.
+//!
+//! Unlike `Schema::build`, a [`SchemaImporter`] keeps going past bad
+//! records: each one is reported with its index while the rest still
+//! build. The caller decides how many failures `finish` tolerates.
+
+use std::{collections::HashMap, fmt::{self, Debug, Display}, hash::Hash};
+
+use crate::{
+    fsm::{StateMachine, Transition, FSM},
+    json::Value,
+    registry::{ActionRegistry, GuardRegistry},
+    schema::{Resolver, SchemaError, TransitionSpec}
+};
+
+
+/// Counts reported to the progress callback.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ImportProgress {
+    pub processed: usize,
+    pub imported: usize,
+    pub failed: usize
+}
+
+
+/// A record that could not be imported; `index` counts from 0 in the
+/// order records were fed.
+///
+/// Fields may be added, so it cannot be built outside this crate:
+///
+/// ```compile_fail
+/// use pfsm::{import::RecordError, schema::SchemaError};
+///
+/// let error = RecordError{ index: 0, error: SchemaError::Invalid(String::new()) };
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct RecordError {
+    pub index: usize,
+    pub error: SchemaError
+}
+
+
+#[derive(Clone, Debug, Default, PartialEq)]
+#[non_exhaustive]
+pub struct ImportReport {
+    pub progress: ImportProgress,
+    pub errors: Vec<RecordError>
+}
+
+
+impl Display for ImportReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "{} of {} record(s) failed", self.errors.len(), self.progress.processed)?;
+        for RecordError { index, error } in &self.errors {
+            write!(f, "\n  record {index}: {error}")?;
+        }
+        Ok(())
+    }
+}
+
+
+impl std::error::Error for ImportReport {}
+
+
+type ProgressCallback = Box<dyn FnMut(ImportProgress)>;
+
+
+/// Incremental importer for transition records.
+pub struct SchemaImporter<'a, S: Copy, E> {
+    initial: S,
+    resolver: &'a Resolver<S, E>,
+    actions: &'a ActionRegistry,
+    guards: &'a GuardRegistry,
+    transitions: HashMap<(S, E), Transition<S>>,
+    report: ImportReport,
+    tolerated: usize,
+    progress: Option<(usize, ProgressCallback)>
+}
+
+
+impl<'a, S, E> SchemaImporter<'a, S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    pub fn new(
+        initial: S,
+        resolver: &'a Resolver<S, E>,
+        actions: &'a ActionRegistry,
+        guards: &'a GuardRegistry
+    ) -> Self
+    {
+        Self{
+            initial,
+            resolver,
+            actions,
+            guards,
+            transitions: HashMap::new(),
+            report: ImportReport::default(),
+            tolerated: 0,
+            progress: None
+        }
+    }
+
+
+    /// Calls `callback` after every `every` records.
+    pub fn on_progress(
+        mut self,
+        every: usize,
+        callback: impl FnMut(ImportProgress) + 'static
+    ) -> Self
+    {
+        self.progress = Some((every.max(1), Box::new(callback)));
+        self
+    }
+
+
+    /// Lets `finish` succeed with up to `errors` failed records.
+    pub fn tolerate(mut self, errors: usize) -> Self
+    {
+        self.tolerated = errors;
+        self
+    }
+
+
+    /// Parses and imports one JSON transition record.
+    pub fn push_value(&mut self, value: &Value)
+    {
+        let result = TransitionSpec::from_value(value).and_then(|spec| self.resolve(&spec));
+        self.record(result);
+    }
+
+
+    pub fn push(&mut self, spec: &TransitionSpec)
+    {
+        let result = self.resolve(spec);
+        self.record(result);
+    }
+
+
+    /// Imports every JSON record of `values`, in order.
+    pub fn extend<'v>(&mut self, values: impl IntoIterator<Item = &'v Value>)
+    {
+        for value in values {
+            self.push_value(value);
+        }
+    }
+
+
+    /// Returns what has been imported and rejected so far.
+    pub fn report(&self) -> &ImportReport
+    {
+        &self.report
+    }
+
+
+    /// Builds the machine from the imported records, unless more of them
+    /// failed than tolerated.
+    pub fn finish(self) -> Result<StateMachine<S, E>, ImportReport>
+    {
+        if self.report.errors.len() > self.tolerated {
+            return Err(self.report);
+        }
+        Ok(StateMachine::initialize(self.initial, self.transitions))
+    }
+
+
+    fn resolve(&mut self, spec: &TransitionSpec) -> Result<(), SchemaError>
+    {
+        let key = (self.resolver.state(&spec.from)?, self.resolver.event(&spec.event)?);
+
+        if self.transitions.contains_key(&key) {
+            return Err(SchemaError::DuplicateTransition{
+                from: spec.from.clone(),
+                event: spec.event.clone()
+            });
+        }
+        let transition = spec.resolve(self.resolver, self.actions, self.guards)?;
+
+        self.transitions.insert(key, transition);
+        Ok(())
+    }
+
+
+    fn record(&mut self, result: Result<(), SchemaError>)
+    {
+        let progress = &mut self.report.progress;
+
+        match result {
+            Ok(()) => progress.imported += 1,
+            Err(error) => {
+                progress.failed += 1;
+                self.report.errors.push(RecordError{ index: progress.processed, error });
+            }
+        }
+        progress.processed += 1;
+
+        if let Some((every, callback)) = &mut self.progress
+            && progress.processed.is_multiple_of(*every)
+        {
+            callback(*progress);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::json;
+    use std::{cell::RefCell, rc::Rc};
+
+
+    fn resolver() -> Resolver<u32, u32>
+    {
+        let number = |prefix: char| move |name: &str| name.strip_prefix(prefix)?.parse().ok();
+
+        Resolver::new(number('s'), number('e'))
+    }
+
+
+    fn fixture() -> Value
+    {
+        let records: Vec<String> = (0..100)
+            .map(|i| match i {
+                7 => r#"{"from": "s7", "event": "e7", "to": "nowhere"}"#.to_string(),
+                42 => r#"{"from": "s3", "event": "e3", "to": "s9"}"#.to_string(),
+                63 => r#"{"from": "s63", "event": "e63"}"#.to_string(),
+                _ => format!(r#"{{"from": "s{i}", "event": "e{i}", "to": "s{}"}}"#, i + 1)
+            })
+            .collect();
+
+        json::parse(&format!("[{}]", records.join(","))).unwrap()
+    }
+
+
+    #[test]
+    fn test_bad_records_reported_by_index()
+    {
+        let (resolver, actions, guards) = (resolver(), ActionRegistry::new(), GuardRegistry::new());
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let s = seen.clone();
+        let mut importer = SchemaImporter::new(0, &resolver, &actions, &guards)
+            .on_progress(25, move |progress| s.borrow_mut().push(progress.processed))
+            .tolerate(3);
+
+        importer.extend(fixture().as_array().unwrap());
+        let indices: Vec<usize> = importer.report().errors.iter().map(|e| e.index).collect();
+
+        assert_eq!(indices, [7, 42, 63]);
+        assert_eq!(importer.report().errors[0].error, SchemaError::UnknownState("nowhere".into()));
+        assert_eq!(
+            importer.report().errors[1].error,
+            SchemaError::DuplicateTransition{ from: "s3".into(), event: "e3".into() }
+        );
+        assert_eq!(*seen.borrow(), [25, 50, 75, 100]);
+
+        let mut fsm = importer.finish().unwrap();
+        assert_eq!(fsm.transitions.len(), 97);
+        fsm.trigger(0).unwrap();
+        assert_eq!(fsm.state(), 1);
+    }
+
+
+    #[test]
+    fn test_too_many_errors()
+    {
+        let (resolver, actions, guards) = (resolver(), ActionRegistry::new(), GuardRegistry::new());
+        let mut importer = SchemaImporter::new(0, &resolver, &actions, &guards).tolerate(2);
+
+        importer.extend(fixture().as_array().unwrap());
+        let report = importer.finish().err().unwrap();
+
+        assert_eq!(report.progress, ImportProgress{ processed: 100, imported: 97, failed: 3 });
+        assert!(report.to_string().starts_with("3 of 100 record(s) failed\n  record 7: "));
+    }
+}
@@ -0,0 +1,157 @@
+//! Initial state chosen at construction from runtime conditions.
+//!
+//! An initial choice works like a choice transition taken once, when the
+//! machine is built: a context snapshot is taken and the first branch
+//! whose guard passes on it names the initial state, or the fallback if
+//! none does. `Policies::reset` decides whether `reset` keeps that state
+//! or chooses again.
+
+use std::{fmt::Debug, hash::Hash, rc::Rc};
+
+use crate::{builder::StateMachineBuilder, context::ContextProvider, fsm::StateMachine};
+
+
+pub type InitialGuard<C> = Box<dyn Fn(&C) -> bool>;
+pub(crate) type Selector<S> = Box<dyn Fn() -> InitialSelection<S>>;
+
+
+/// The outcome of an initial choice.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InitialSelection<S> {
+    pub state: S,
+    /// Index of the branch taken, `None` for the fallback.
+    pub branch: Option<usize>
+}
+
+
+pub(crate) fn select<C, S: Copy>(
+    branches: &[(InitialGuard<C>, S)],
+    context: &C,
+    fallback: S
+) -> InitialSelection<S>
+{
+    branches
+        .iter()
+        .position(|(guard, _)| guard(context))
+        .map_or(
+            InitialSelection{ state: fallback, branch: None },
+            |i| InitialSelection{ state: branches[i].1, branch: Some(i) }
+        )
+}
+
+
+impl<S, E> StateMachineBuilder<S, E>
+where S: Copy + Hash + Eq + Debug + 'static, E: Copy + Hash + Eq + Debug
+{
+    /// Chooses the initial state when the machine is built, replacing the
+    /// one given to `new`, from a snapshot of `context`.
+    pub fn initial_choice<C: 'static>(
+        mut self,
+        context: impl ContextProvider<C> + 'static,
+        branches: Vec<(InitialGuard<C>, S)>,
+        fallback: S
+    ) -> Self
+    {
+        self.initial_choice = Some(Box::new(move || {
+            select(&branches, &context.snapshot(), fallback)
+        }));
+        self
+    }
+}
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    /// Returns how the initial state was chosen, if by an initial choice:
+    /// at build time, or at the last reevaluating `reset`.
+    pub fn initial_selection(&self) -> Option<InitialSelection<S>>
+    {
+        self.initial_selection
+    }
+}
+
+
+pub(crate) fn template_selector<S: Copy + 'static, P: 'static>(
+    branches: &Rc<Vec<(InitialGuard<P>, S)>>,
+    params: &Rc<P>,
+    fallback: S
+) -> Selector<S>
+{
+    let (branches, params) = (branches.clone(), params.clone());
+    Box::new(move || select(&branches, &params, fallback))
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        builder::StateMachineBuilder,
+        fsm::{StateMachine, FSM},
+        policy::{Policies, ResetPolicy}
+    };
+    use std::{cell::Cell, rc::Rc};
+
+
+    #[derive(Clone, Copy, Debug)]
+    struct Install {
+        resumable: bool,
+        configured: bool
+    }
+
+
+    fn machine(install: &Rc<Cell<Install>>) -> StateMachine<&'static str, &'static str>
+    {
+        let install = install.clone();
+
+        StateMachineBuilder::new("fresh")
+            .initial_choice(
+                move || install.get(),
+                vec![
+                    (Box::new(|i: &Install| i.resumable), "resuming"),
+                    (Box::new(|i: &Install| i.configured), "ready")
+                ],
+                "fresh"
+            )
+            .transition("resuming", "done", "ready")
+            .transition("fresh", "configure", "ready")
+            .build()
+            .unwrap()
+    }
+
+
+    #[test]
+    fn test_context_selects_initial_state()
+    {
+        let install = Rc::new(Cell::new(Install{ resumable: true, configured: true }));
+        let fsm = machine(&install);
+
+        assert_eq!(fsm.state(), "resuming");
+        assert_eq!(fsm.initial_selection().unwrap().branch, Some(0));
+
+        install.set(Install{ resumable: false, configured: false });
+        let fsm = machine(&install);
+        assert_eq!(fsm.state(), "fresh");
+        assert_eq!(fsm.initial_selection().unwrap().branch, None);
+        assert!(!fsm.is_reachable(&"resuming"));
+    }
+
+
+    #[test]
+    fn test_reset_policies()
+    {
+        let install = Rc::new(Cell::new(Install{ resumable: true, configured: false }));
+        let mut fsm = machine(&install);
+
+        fsm.trigger("done").unwrap();
+        install.set(Install{ resumable: false, configured: true });
+        fsm.reset();
+        assert_eq!(fsm.state(), "resuming");
+
+        fsm.set_policies(Policies{ reset: ResetPolicy::Reevaluate, ..Policies::default() });
+        fsm.reset();
+        assert_eq!(fsm.state(), "ready");
+        assert_eq!(fsm.initial_selection().unwrap().branch, Some(1));
+        assert!(!fsm.is_reachable(&"resuming"));
+    }
+}
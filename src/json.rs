@@ -0,0 +1,542 @@
+//! Minimal JSON reader and writer used by the schema file format.
+
+use std::fmt::{self, Display, Write};
+
+
+/// Parsed JSON document. Object members keep their file order.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>)
+}
+
+
+impl Value {
+    /// Returns the member `key` if this is an object containing it.
+    pub fn get(&self, key: &str) -> Option<&Value>
+    {
+        match self {
+            Value::Object(members) => members
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None
+        }
+    }
+
+
+    pub fn as_str(&self) -> Option<&str>
+    {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None
+        }
+    }
+
+
+    pub fn as_f64(&self) -> Option<f64>
+    {
+        match self {
+            Value::Number(n) => Some(*n),
+            _ => None
+        }
+    }
+
+
+    pub fn as_bool(&self) -> Option<bool>
+    {
+        match self {
+            Value::Bool(b) => Some(*b),
+            _ => None
+        }
+    }
+
+
+    pub fn as_array(&self) -> Option<&[Value]>
+    {
+        match self {
+            Value::Array(items) => Some(items),
+            _ => None
+        }
+    }
+
+
+    pub fn as_object(&self) -> Option<&[(String, Value)]>
+    {
+        match self {
+            Value::Object(members) => Some(members),
+            _ => None
+        }
+    }
+
+
+    /// Renders the value with one member or item per line, indented by
+    /// two spaces per level, for files meant to be read and diffed.
+    pub fn to_pretty_string(&self) -> String
+    {
+        let mut out = String::new();
+        self.write_pretty(&mut out, 0);
+        out
+    }
+
+
+    fn write_pretty(&self, out: &mut String, depth: usize)
+    {
+        let indent = |out: &mut String, depth: usize| out.push_str(&"  ".repeat(depth));
+
+        match self {
+            Value::Array(items) if !items.is_empty() => {
+                out.push_str("[\n");
+                for (i, item) in items.iter().enumerate() {
+                    indent(out, depth + 1);
+                    item.write_pretty(out, depth + 1);
+                    out.push_str(if i + 1 < items.len() { ",\n" } else { "\n" });
+                }
+                indent(out, depth);
+                out.push(']');
+            }
+            Value::Object(members) if !members.is_empty() => {
+                out.push_str("{\n");
+                for (i, (name, value)) in members.iter().enumerate() {
+                    indent(out, depth + 1);
+                    write!(out, "{}: ", Value::from(name.as_str())).unwrap();
+                    value.write_pretty(out, depth + 1);
+                    out.push_str(if i + 1 < members.len() { ",\n" } else { "\n" });
+                }
+                indent(out, depth);
+                out.push('}');
+            }
+            scalar => write!(out, "{scalar}").unwrap()
+        }
+    }
+}
+
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self
+    {
+        Value::String(s.to_string())
+    }
+}
+
+
+impl From<String> for Value {
+    fn from(s: String) -> Self
+    {
+        Value::String(s)
+    }
+}
+
+
+impl Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        match self {
+            Value::Null => f.write_str("null"),
+            Value::Bool(b) => write!(f, "{b}"),
+            Value::Number(n) => write!(f, "{n}"),
+            Value::String(s) => write_string(f, s),
+            Value::Array(items) => {
+                f.write_char('[')?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                f.write_char(']')
+            }
+            Value::Object(members) => {
+                f.write_char('{')?;
+                for (i, (name, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write_string(f, name)?;
+                    write!(f, ":{value}")?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+
+fn write_string(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result
+{
+    f.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?
+        }
+    }
+    f.write_char('"')
+}
+
+
+/// Syntax error with the byte offset where parsing stopped. Fields may
+/// be added; build one with `new`:
+///
+/// ```compile_fail
+/// let error = pfsm::json::JsonError{ message: "expected ','".into(), offset: 4 };
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct JsonError {
+    pub message: String,
+    pub offset: usize
+}
+
+
+impl JsonError {
+    pub fn new(message: impl Into<String>, offset: usize) -> Self
+    {
+        Self{ message: message.into(), offset }
+    }
+}
+
+
+impl Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "{} at byte {}", self.message, self.offset)
+    }
+}
+
+
+impl std::error::Error for JsonError {}
+
+
+impl JsonError {
+    /// Returns the numeric code listed in [`ERROR_CODES`](crate::error::ERROR_CODES).
+    pub fn code(&self) -> u16
+    {
+        410
+    }
+}
+
+
+/// How deeply arrays and objects may nest before parsing fails, so that
+/// hostile input cannot overflow the stack.
+pub const MAX_DEPTH: usize = 128;
+
+
+/// Parses a complete JSON document.
+pub fn parse(input: &str) -> Result<Value, JsonError>
+{
+    let mut parser = Parser{ bytes: input.as_bytes(), pos: 0, depth: 0 };
+    let value = parser.value()?;
+
+    parser.skip_whitespace();
+    if parser.pos != parser.bytes.len() {
+        return Err(parser.error("trailing characters"));
+    }
+    Ok(value)
+}
+
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    depth: usize
+}
+
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> JsonError
+    {
+        JsonError{ message: message.to_string(), offset: self.pos }
+    }
+
+
+    fn skip_whitespace(&mut self)
+    {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.bytes.get(self.pos) {
+            self.pos += 1;
+        }
+    }
+
+
+    fn expect(&mut self, byte: u8) -> Result<(), JsonError>
+    {
+        if self.bytes.get(self.pos) == Some(&byte) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", byte as char)))
+        }
+    }
+
+
+    fn literal(&mut self, word: &str, value: Value) -> Result<Value, JsonError>
+    {
+        if self.bytes[self.pos..].starts_with(word.as_bytes()) {
+            self.pos += word.len();
+            Ok(value)
+        } else {
+            Err(self.error("invalid literal"))
+        }
+    }
+
+
+    fn value(&mut self) -> Result<Value, JsonError>
+    {
+        self.skip_whitespace();
+        match self.bytes.get(self.pos) {
+            Some(b'{') => self.nested(Self::object),
+            Some(b'[') => self.nested(Self::array),
+            Some(b'"') => self.string().map(Value::String),
+            Some(b't') => self.literal("true", Value::Bool(true)),
+            Some(b'f') => self.literal("false", Value::Bool(false)),
+            Some(b'n') => self.literal("null", Value::Null),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end of input"))
+        }
+    }
+
+
+    fn nested(
+        &mut self,
+        parse: fn(&mut Self) -> Result<Value, JsonError>
+    ) -> Result<Value, JsonError>
+    {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error("nesting too deep"));
+        }
+        self.depth += 1;
+        let value = parse(self);
+
+        self.depth -= 1;
+        value
+    }
+
+
+    fn object(&mut self) -> Result<Value, JsonError>
+    {
+        let mut members = Vec::new();
+
+        self.expect(b'{')?;
+        self.skip_whitespace();
+        if self.bytes.get(self.pos) == Some(&b'}') {
+            self.pos += 1;
+            return Ok(Value::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            let name = self.string()?;
+            self.skip_whitespace();
+            self.expect(b':')?;
+            members.push((name, self.value()?));
+            self.skip_whitespace();
+            match self.bytes.get(self.pos) {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(Value::Object(members));
+                }
+                _ => return Err(self.error("expected ',' or '}'"))
+            }
+        }
+    }
+
+
+    fn array(&mut self) -> Result<Value, JsonError>
+    {
+        let mut items = Vec::new();
+
+        self.expect(b'[')?;
+        self.skip_whitespace();
+        if self.bytes.get(self.pos) == Some(&b']') {
+            self.pos += 1;
+            return Ok(Value::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            self.skip_whitespace();
+            match self.bytes.get(self.pos) {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(Value::Array(items));
+                }
+                _ => return Err(self.error("expected ',' or ']'"))
+            }
+        }
+    }
+
+
+    fn string(&mut self) -> Result<String, JsonError>
+    {
+        let mut out = String::new();
+
+        self.expect(b'"')?;
+        loop {
+            let start = self.pos;
+            while let Some(&b) = self.bytes.get(self.pos) {
+                if b == b'"' || b == b'\\' {
+                    break;
+                }
+                self.pos += 1;
+            }
+            // Slicing at ASCII delimiters keeps UTF-8 sequences intact.
+            out.push_str(std::str::from_utf8(&self.bytes[start..self.pos])
+                .map_err(|_| self.error("invalid UTF-8"))?);
+
+            match self.bytes.get(self.pos) {
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(out);
+                }
+                Some(b'\\') => {
+                    self.pos += 1;
+                    out.push(self.escape()?);
+                }
+                _ => return Err(self.error("unterminated string"))
+            }
+        }
+    }
+
+
+    fn escape(&mut self) -> Result<char, JsonError>
+    {
+        let c = match self.bytes.get(self.pos) {
+            Some(b'"') => '"',
+            Some(b'\\') => '\\',
+            Some(b'/') => '/',
+            Some(b'b') => '\u{8}',
+            Some(b'f') => '\u{c}',
+            Some(b'n') => '\n',
+            Some(b'r') => '\r',
+            Some(b't') => '\t',
+            Some(b'u') => {
+                let unit = self.hex4(self.pos + 1)?;
+                self.pos += 4;
+                self.surrogate_pair(unit)?
+            }
+            _ => return Err(self.error("invalid escape"))
+        };
+        self.pos += 1;
+        Ok(c)
+    }
+
+
+    fn hex4(&self, at: usize) -> Result<u32, JsonError>
+    {
+        self.bytes.get(at..at + 4)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u32::from_str_radix(h, 16).ok())
+            .ok_or_else(|| self.error("invalid unicode escape"))
+    }
+
+
+    /// Combines a high surrogate at `self.pos` with an immediately
+    /// following `\u` low surrogate; lone surrogates become U+FFFD.
+    fn surrogate_pair(&mut self, unit: u32) -> Result<char, JsonError>
+    {
+        if !(0xd800..0xdc00).contains(&unit) {
+            return Ok(char::from_u32(unit).unwrap_or('\u{fffd}'));
+        }
+        if self.bytes.get(self.pos + 1..self.pos + 3) != Some(&b"\\u"[..]) {
+            return Ok('\u{fffd}');
+        }
+        let low = self.hex4(self.pos + 3)?;
+
+        if !(0xdc00..0xe000).contains(&low) {
+            return Ok('\u{fffd}');
+        }
+        self.pos += 6;
+        Ok(char::from_u32(0x10000 + ((unit - 0xd800) << 10) + (low - 0xdc00)).unwrap())
+    }
+
+
+    fn number(&mut self) -> Result<Value, JsonError>
+    {
+        let start = self.pos;
+
+        while let Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') =
+            self.bytes.get(self.pos)
+        {
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.bytes[start..self.pos])
+            .ok()
+            .and_then(|n| n.parse().ok())
+            .map(Value::Number)
+            .ok_or_else(|| JsonError{
+                message: "invalid number".to_string(),
+                offset: start
+            })
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+
+    #[test]
+    fn test_pretty_string()
+    {
+        let value = parse(r#"{"a": [1, {"b": "x"}], "c": {}}"#).unwrap();
+
+        assert_eq!(
+            value.to_pretty_string(),
+            "{\n  \"a\": [\n    1,\n    {\n      \"b\": \"x\"\n    }\n  ],\n  \"c\": {}\n}"
+        );
+        assert_eq!(parse(&value.to_pretty_string()), Ok(value));
+    }
+
+
+    #[test]
+    fn test_round_trip()
+    {
+        let text = r#"{"name":"a \"b\"","list":[1,2.5,true,null],"empty":{}}"#;
+        let value = parse(text).unwrap();
+
+        assert_eq!(value.get("name").and_then(Value::as_str), Some("a \"b\""));
+        assert_eq!(value.get("list").and_then(Value::as_array).map(|l| l.len()), Some(4));
+        assert_eq!(value.to_string(), text);
+    }
+
+
+    #[test]
+    fn test_syntax_error_offset()
+    {
+        let err = parse("{\"a\": [1, 2,]}").unwrap_err();
+
+        assert_eq!(err.offset, 12);
+    }
+
+
+    #[test]
+    fn test_nesting_limit()
+    {
+        let deep = |n| format!("{}{}", "[".repeat(n), "]".repeat(n));
+
+        assert!(parse(&deep(MAX_DEPTH)).is_ok());
+        assert_eq!(parse(&deep(MAX_DEPTH + 1)).unwrap_err().message, "nesting too deep");
+        assert!(parse(&"[".repeat(1_000_000)).is_err());
+    }
+
+
+    #[test]
+    fn test_surrogate_pairs()
+    {
+        assert_eq!(parse(r#""\ud83d\ude00""#), Ok(Value::from("\u{1f600}")));
+        assert_eq!(parse(r#""\ud83dx""#), Ok(Value::from("\u{fffd}x")));
+        assert_eq!(parse(r#""\ude00\u0041""#), Ok(Value::from("\u{fffd}A")));
+        assert_eq!(parse(r#""\ud83d\u0041""#), Ok(Value::from("\u{fffd}A")));
+    }
+}
@@ -1 +1,71 @@
+pub mod acceptor;
+pub mod alarm;
+pub mod algebra;
+pub mod alias;
+pub mod alphabet;
+pub mod analysis;
+pub mod breakpoint;
+pub mod builder;
+pub mod cancel;
+pub mod cascade;
+pub mod choice;
+pub mod clock;
+pub mod coalesce;
+pub mod command;
+pub mod compat;
+pub mod configuration;
+pub mod context;
+pub mod data;
+pub mod deadline;
+pub mod deprecate;
+pub mod deps;
+pub mod describe;
+#[cfg(any(test, feature = "test-util"))]
+pub mod determinism;
+pub mod error;
+pub mod excerpt;
+pub mod explain;
+pub mod export;
+pub mod fair;
+#[cfg(any(test, feature = "test-util"))]
+pub mod fixtures;
+pub mod flag;
+pub mod freeze;
 pub mod fsm;
+#[cfg(any(test, feature = "test-util"))]
+pub mod golden;
+pub mod group;
+pub mod import;
+pub mod initial;
+pub mod json;
+pub mod maintain;
+pub mod matrix;
+pub mod memo;
+pub mod middleware;
+pub mod naming;
+pub mod observe;
+pub mod origin;
+#[cfg(feature = "paranoid")]
+mod paranoid;
+pub mod parallel;
+#[cfg(any(test, feature = "test-util"))]
+pub mod playground;
+pub mod policy;
+pub mod prepare;
+pub mod quiescence;
+pub mod registry;
+pub mod rename;
+pub mod scenario;
+pub mod replay;
+pub mod schema;
+pub mod snapshot;
+pub mod status;
+pub mod swap;
+pub mod sync;
+pub mod template;
+#[cfg(feature = "sim")]
+pub mod sim;
+#[cfg(any(test, feature = "test-util"))]
+pub mod testing;
+pub mod tour;
+pub mod trace;
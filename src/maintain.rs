@@ -0,0 +1,313 @@
+//! Housekeeping within a budget, for callers driving a machine from a
+//! frame or poll loop.
+//!
+//! `maintain` does one unit of pending work at a time, always the most
+//! urgent one left, in this order:
+//!
+//! 1. the passed deadline;
+//! 2. the current state's due timeout;
+//! 3. one posted event, oldest first;
+//! 4. one event queued through `fair_trigger`, in arrival order, its
+//!    result being sent to its caller;
+//! 5. rebuilding analysis results invalidated by a structural change.
+//!
+//! It stops once nothing is pending or the budget is spent: each unit
+//! is one step, whether it succeeds or fails, and time is measured on the
+//! machine's clock before each step. Rejection alarms need no
+//! housekeeping, as they are evaluated while events are processed. A
+//! timeout whose event is rejected stays due, but is not retried within
+//! the same call until the machine changes state.
+
+use std::{fmt::Debug, hash::Hash, time::{Duration, Instant}};
+
+use crate::{fsm::StateMachine, origin::Origin};
+
+
+/// How much work one `maintain` call may do; unlimited by default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MaintenanceBudget {
+    pub steps: Option<usize>,
+    pub time: Option<Duration>
+}
+
+
+impl MaintenanceBudget {
+    pub fn steps(steps: usize) -> Self
+    {
+        Self::default().with_steps(steps)
+    }
+
+
+    pub fn time(time: Duration) -> Self
+    {
+        Self::default().with_time(time)
+    }
+
+
+    pub fn with_steps(mut self, steps: usize) -> Self
+    {
+        self.steps = Some(steps);
+        self
+    }
+
+
+    pub fn with_time(mut self, time: Duration) -> Self
+    {
+        self.time = Some(time);
+        self
+    }
+}
+
+
+/// Units of work by category, done or still pending.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MaintenanceWork {
+    pub deadlines: usize,
+    pub timeouts: usize,
+    pub posted_events: usize,
+    pub fair_events: usize,
+    pub cache_refreshes: usize
+}
+
+
+impl MaintenanceWork {
+    pub fn total(&self) -> usize
+    {
+        self.deadlines
+            + self.timeouts
+            + self.posted_events
+            + self.fair_events
+            + self.cache_refreshes
+    }
+}
+
+
+/// What `maintain` did and what it left.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MaintenanceReport {
+    /// Units that succeeded.
+    pub done: MaintenanceWork,
+    /// Units whose event or expiry failed; a failed posted or fair event
+    /// is consumed all the same.
+    pub failed: MaintenanceWork,
+    /// Work pending when `maintain` returned; a passed deadline, a due
+    /// timeout and stale analysis results count as one unit each, and a
+    /// timeout rejected during the call is left out.
+    pub remaining: MaintenanceWork,
+    /// Whether the budget ran out with work remaining.
+    pub exhausted: bool
+}
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    /// Does pending housekeeping by priority until none is left or
+    /// `budget` is spent; see the module documentation.
+    pub fn maintain(&mut self, budget: MaintenanceBudget) -> MaintenanceReport
+    {
+        let start = self.clock.now();
+        let mut done = MaintenanceWork::default();
+        let mut failed = MaintenanceWork::default();
+        // The state and entry time in which a timeout was rejected.
+        let mut rejected_timeout = None;
+
+        loop {
+            let remaining = self.pending_maintenance(rejected_timeout);
+            let steps_taken = done.total() + failed.total();
+            let out_of_steps = budget.steps.is_some_and(|steps| steps_taken >= steps);
+            let out_of_time = budget
+                .time
+                .is_some_and(|time| self.clock.now().duration_since(start) >= time);
+
+            if remaining.total() == 0 || out_of_steps || out_of_time {
+                let exhausted = remaining.total() > 0;
+
+                #[cfg(feature = "paranoid")]
+                self.check_invariants("maintain");
+                return MaintenanceReport{ done, failed, remaining, exhausted };
+            }
+
+            if let Some(action) = self.take_due_deadline() {
+                self.start_step(Origin::deadline());
+                let ok = self.expire(action).is_ok();
+                tally(&mut done, &mut failed, ok).deadlines += 1;
+            } else if remaining.timeouts > 0 {
+                let event = self.due_timeout().unwrap();
+                self.start_step(Origin::timer());
+                self.posted.borrow_mut().current = (1, 1);
+                let ok = self.fire_one(event, true).is_ok();
+                if !ok {
+                    rejected_timeout = Some((self.state, self.entered_at));
+                }
+                tally(&mut done, &mut failed, ok).timeouts += 1;
+            } else if remaining.posted_events > 0 {
+                let (event, origin) = self.posted.borrow_mut().pop_front().unwrap();
+                self.start_step(origin);
+                let ok = self.fire_one(event, true).is_ok();
+                tally(&mut done, &mut failed, ok).posted_events += 1;
+            } else if let Some(ok) = self.pump_one() {
+                tally(&mut done, &mut failed, ok).fair_events += 1;
+            } else {
+                self.refresh_analysis();
+                done.cache_refreshes += 1;
+            }
+        }
+    }
+
+
+    /// Counts pending work, leaving out a timeout already rejected in the
+    /// current state and entry.
+    fn pending_maintenance(&self, rejected_timeout: Option<(S, Instant)>) -> MaintenanceWork
+    {
+        let timeout_due = self.due_timeout().is_some()
+            && rejected_timeout != Some((self.state, self.entered_at));
+
+        MaintenanceWork{
+            deadlines: usize::from(self.deadline_due()),
+            timeouts: usize::from(timeout_due),
+            posted_events: self.queue_depth(),
+            fair_events: self.fair.inbox.lock().unwrap().len(),
+            cache_refreshes: usize::from(self.analysis_stale())
+        }
+    }
+}
+
+
+fn tally<'a>(
+    done: &'a mut MaintenanceWork,
+    failed: &'a mut MaintenanceWork,
+    ok: bool
+) -> &'a mut MaintenanceWork
+{
+    if ok { done } else { failed }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        builder::StateMachineBuilder,
+        clock::{Clock, MockClock},
+        deadline::DeadlineAction,
+        fsm::FSM
+    };
+    use std::{cell::RefCell, rc::Rc, sync::Arc};
+
+
+    type Log = Rc<RefCell<Vec<&'static str>>>;
+
+
+    /// A machine with one unit of work pending in every category, whose
+    /// actions log which category they belong to and take a second each.
+    fn backlogged() -> (StateMachine<u8, char>, Arc<MockClock>, Log)
+    {
+        let clock = Arc::new(MockClock::new());
+        let log = Log::default();
+        let logger = |name: &'static str| {
+            let (log, clock) = (log.clone(), clock.clone());
+            move || {
+                log.borrow_mut().push(name);
+                clock.advance(Duration::from_secs(1));
+            }
+        };
+        let mut fsm = StateMachineBuilder::new(0)
+            .transition(0, 'd', 1)
+            .action(logger("deadline"))
+            .transition(1, 't', 2)
+            .action(logger("timeout"))
+            .timeout(1, Duration::ZERO, 't')
+            .transition(2, 'p', 3)
+            .action(logger("posted"))
+            .transition(3, 'f', 4)
+            .action(logger("fair"))
+            .transition(4, 'z', 0)
+            .build()
+            .unwrap();
+
+        fsm.set_clock(clock.clone());
+        fsm.strongly_connected_components();
+        fsm.set_enabled(4, 'z', false);
+        fsm.poster().post('p');
+        let _ticket = fsm.fair_trigger().submit('f', Origin::new("remote"));
+        fsm.set_deadline(clock.now(), DeadlineAction::Post('d'));
+        clock.advance(Duration::from_secs(5));
+        (fsm, clock, log)
+    }
+
+
+    #[test]
+    fn test_priority_order()
+    {
+        let (mut fsm, _, log) = backlogged();
+        let report = fsm.maintain(MaintenanceBudget::default());
+        let computations = fsm.analysis.computations.get();
+
+        assert_eq!(*log.borrow(), ["deadline", "timeout", "posted", "fair"]);
+        assert_eq!(
+            report.done,
+            MaintenanceWork{
+                deadlines: 1,
+                timeouts: 1,
+                posted_events: 1,
+                fair_events: 1,
+                cache_refreshes: 1
+            }
+        );
+        assert_eq!((report.remaining.total(), report.exhausted), (0, false));
+        assert_eq!(fsm.state(), 4);
+        assert!(fsm.sink_states().contains(&4));
+        assert_eq!(fsm.analysis.computations.get(), computations);
+        assert_eq!(fsm.maintain(MaintenanceBudget::steps(1)), MaintenanceReport::default());
+    }
+
+
+    #[test]
+    fn test_budget_cutoff()
+    {
+        let (mut fsm, _, log) = backlogged();
+        let report = fsm.maintain(MaintenanceBudget::steps(2));
+
+        assert_eq!(*log.borrow(), ["deadline", "timeout"]);
+        assert!(report.exhausted);
+        assert_eq!(report.done.total(), 2);
+        assert_eq!((report.remaining.posted_events, report.remaining.fair_events), (1, 1));
+
+        let report = fsm.maintain(MaintenanceBudget::time(Duration::from_millis(1500)));
+        assert_eq!(*log.borrow(), ["deadline", "timeout", "posted", "fair"]);
+        assert_eq!(report.done.total(), 2);
+        assert_eq!(report.remaining.cache_refreshes, 1);
+
+        let report = fsm.maintain(MaintenanceBudget::time(Duration::ZERO).with_steps(5));
+        assert_eq!((report.done.total(), report.exhausted), (0, true));
+    }
+
+
+    #[test]
+    fn test_rejected_timeout_and_posted_event()
+    {
+        let mut fsm = StateMachineBuilder::new(0)
+            .transition(0, 't', 1)
+            .transition(1, 'x', 0)
+            .timeout(0, Duration::ZERO, 't')
+            .build()
+            .unwrap();
+
+        fsm.set_enabled(0, 't', false);
+        fsm.poster().post('x');
+        let report = fsm.maintain(MaintenanceBudget::default());
+
+        assert_eq!((report.done.total(), report.failed.total()), (0, 2));
+        assert_eq!((report.failed.timeouts, report.failed.posted_events), (1, 1));
+        assert_eq!((report.remaining.total(), report.exhausted), (0, false));
+        assert_eq!(fsm.queue_depth(), 0);
+
+        fsm.set_enabled(0, 't', true);
+        let report = fsm.maintain(MaintenanceBudget::default());
+        assert_eq!((report.done.timeouts, fsm.state()), (1, 1));
+    }
+}
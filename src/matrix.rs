@@ -0,0 +1,264 @@
+//! State-transition matrix: one row per state, one column per event.
+
+use std::{fmt::{Debug, Write}, hash::Hash};
+
+use crate::fsm::StateMachine;
+
+
+/// The transition found in one row and column of a matrix.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct MatrixCell<S> {
+    /// The target, or every candidate target of a choice.
+    pub targets: Vec<S>,
+    pub guarded: bool,
+    pub enabled: bool,
+    /// Whether the transition or one of its candidates is behind a
+    /// feature flag.
+    pub flagged: bool
+}
+
+
+/// Transition table laid out as rows of states and columns of events,
+/// both ordered by `Debug` rendering.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransitionMatrix<S, E> {
+    states: Vec<S>,
+    events: Vec<E>,
+    cells: Vec<Option<MatrixCell<S>>>
+}
+
+
+impl<S, E> TransitionMatrix<S, E>
+where S: Copy + Eq + Debug, E: Copy + Eq + Debug
+{
+    pub fn states(&self) -> &[S]
+    {
+        &self.states
+    }
+
+
+    pub fn events(&self) -> &[E]
+    {
+        &self.events
+    }
+
+
+    /// Returns the cell at `row` and `col`, or `None` if it is blank or
+    /// out of range.
+    pub fn cell(&self, row: usize, col: usize) -> Option<&MatrixCell<S>>
+    {
+        if row >= self.states.len() || col >= self.events.len() {
+            return None;
+        }
+        self.cells[row * self.events.len() + col].as_ref()
+    }
+
+
+    /// Returns the cell for `event` in `state`.
+    pub fn get(&self, state: &S, event: &E) -> Option<&MatrixCell<S>>
+    {
+        let row = self.states.iter().position(|s| s == state)?;
+        let col = self.events.iter().position(|e| e == event)?;
+
+        self.cell(row, col)
+    }
+
+
+    /// Returns the blank cells, row by row.
+    pub fn blanks(&self) -> Vec<(S, E)>
+    {
+        self.states
+            .iter()
+            .enumerate()
+            .flat_map(|(row, state)| {
+                self.events
+                    .iter()
+                    .enumerate()
+                    .filter(move |(col, _)| self.cell(row, *col).is_none())
+                    .map(move |(_, event)| (*state, *event))
+            })
+            .collect()
+    }
+
+
+    /// Renders the matrix as a GitHub-flavoured markdown table with
+    /// aligned columns. Guarded cells are marked with `*`, flagged cells
+    /// with `?`.
+    pub fn to_markdown_table(&self) -> String
+    {
+        let grid = self.grid();
+        let widths = column_widths(&grid);
+        let mut out = String::new();
+
+        for (i, row) in grid.iter().enumerate() {
+            push_row(&mut out, row, &widths);
+            if i == 0 {
+                let dashes: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+                push_row(&mut out, &dashes, &widths);
+            }
+        }
+        out
+    }
+
+
+    /// Renders the matrix as a boxed plain-text table.
+    pub fn to_ascii_table(&self) -> String
+    {
+        let grid = self.grid();
+        let widths = column_widths(&grid);
+        let rule: String = widths.iter().map(|w| format!("+{}", "-".repeat(w + 2))).collect();
+        let rule = rule + "+\n";
+        let mut out = rule.clone();
+
+        for (i, row) in grid.iter().enumerate() {
+            push_row(&mut out, row, &widths);
+            if i == 0 {
+                out.push_str(&rule);
+            }
+        }
+        out.push_str(&rule);
+        out
+    }
+
+
+    /// Header row followed by one row of rendered cells per state.
+    fn grid(&self) -> Vec<Vec<String>>
+    {
+        let header = std::iter::once("state".to_string())
+            .chain(self.events.iter().map(|e| format!("{e:?}")))
+            .collect();
+        let rows = self.states.iter().enumerate().map(|(row, state)| {
+            std::iter::once(format!("{state:?}"))
+                .chain((0..self.events.len()).map(|col| match self.cell(row, col) {
+                    None => String::new(),
+                    Some(cell) => {
+                        let targets: Vec<String> =
+                            cell.targets.iter().map(|t| format!("{t:?}")).collect();
+                        targets.join("/")
+                            + if cell.guarded { "*" } else { "" }
+                            + if cell.flagged { "?" } else { "" }
+                    }
+                }))
+                .collect()
+        });
+
+        std::iter::once(header).chain(rows).collect()
+    }
+}
+
+
+fn column_widths(grid: &[Vec<String>]) -> Vec<usize>
+{
+    (0..grid[0].len())
+        .map(|col| grid.iter().map(|row| row[col].chars().count()).max().unwrap_or(0))
+        .collect()
+}
+
+
+fn push_row(out: &mut String, row: &[String], widths: &[usize])
+{
+    for (cell, width) in row.iter().zip(widths) {
+        let _ = write!(out, "| {cell:<width$} ");
+    }
+    out.push_str("|\n");
+}
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    pub fn to_matrix(&self) -> TransitionMatrix<S, E>
+    {
+        let description = self.describe();
+        let mut events: Vec<E> = description.transitions.iter().map(|t| t.event).collect();
+
+        events.sort_by_cached_key(|event| format!("{event:?}"));
+        events.dedup();
+
+        let states = description.states;
+        let mut cells = vec![None; states.len() * events.len()];
+
+        for t in description.transitions {
+            let row = states.iter().position(|s| *s == t.from).unwrap();
+            let col = events.iter().position(|e| *e == t.event).unwrap();
+            let targets = if t.candidates.is_empty() {
+                vec![t.to]
+            } else {
+                t.candidates.iter().map(|c| c.to).collect()
+            };
+            let flagged = t.feature_flag.is_some()
+                || t.candidates.iter().any(|c| c.feature_flag.is_some());
+
+            cells[row * events.len() + col] =
+                Some(MatrixCell{ targets, guarded: t.guarded, enabled: t.enabled, flagged });
+        }
+
+        TransitionMatrix{ states, events, cells }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        builder::StateMachineBuilder,
+        fixtures::traffic_light::{self, Event, State}
+    };
+
+
+    /// The reference traffic light, with `Yellow2RedTimeout` guarded.
+    fn guarded_light() -> StateMachine<State, Event>
+    {
+        traffic_light::builder().guard(|| true).build().unwrap()
+    }
+
+
+    #[test]
+    fn test_markdown_table()
+    {
+        assert_eq!(guarded_light().to_matrix().to_markdown_table(), "\
+| state  | GreenTimeout | RedTimeout | Yellow2GreenTimeout | Yellow2RedTimeout |
+| ------ | ------------ | ---------- | ------------------- | ----------------- |
+| Green  | Yellow       |            |                     |                   |
+| Red    |              | Yellow     |                     |                   |
+| Yellow |              |            | Green               | Red*              |
+");
+    }
+
+
+    #[test]
+    fn test_ascii_table()
+    {
+        let fsm = StateMachineBuilder::new(0).transition(0, 'a', 10).build().unwrap();
+
+        assert_eq!(fsm.to_matrix().to_ascii_table(), "\
++-------+-----+
+| state | 'a' |
++-------+-----+
+| 0     | 10  |
+| 10    |     |
++-------+-----+
+");
+    }
+
+
+    #[test]
+    fn test_cell_lookup()
+    {
+        let matrix = guarded_light().to_matrix();
+
+        assert_eq!(matrix.states(), [State::Green, State::Red, State::Yellow]);
+        assert_eq!(matrix.cell(1, 1).unwrap().targets, [State::Yellow]);
+        assert!(!matrix.cell(0, 0).unwrap().guarded);
+        assert!(matrix.cell(2, 3).unwrap().guarded);
+        assert_eq!(matrix.cell(0, 1), None);
+        assert_eq!(matrix.cell(3, 0), None);
+        assert_eq!(
+            matrix.get(&State::Yellow, &Event::Yellow2RedTimeout).unwrap().targets,
+            [State::Red]
+        );
+        assert_eq!(matrix.blanks().len(), 8);
+    }
+}
@@ -0,0 +1,199 @@
+//! Per-dispatch guard memoization.
+//!
+//! A memoized guard is identified by a key. While one `trigger` is being
+//! processed, the first evaluation of a key is cached and every later
+//! evaluation of the same key returns the cached result; the cache is
+//! cleared when the dispatch ends. Outside a dispatch (`explain`, `peek`)
+//! guards are evaluated normally.
+//!
+//! This is only sound for pure guards: a guard whose result changes
+//! because an earlier guard or action of the same dispatch had a side
+//! effect will see a stale value.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fmt::Debug,
+    hash::Hash,
+    rc::Rc
+};
+
+use crate::fsm::{Guard, StateMachine};
+
+
+#[derive(Default)]
+pub(crate) struct GuardMemo {
+    active: Cell<bool>,
+    results: RefCell<HashMap<String, bool>>,
+    saved: Cell<u64>
+}
+
+
+impl GuardMemo {
+    /// Caches guard results until the returned value is dropped, even if
+    /// the dispatch unwinds.
+    pub(crate) fn begin(&self) -> Memoizing<'_>
+    {
+        self.clear();
+        self.active.set(true);
+        Memoizing(self)
+    }
+
+
+    fn clear(&self)
+    {
+        if !self.results.borrow().is_empty() {
+            self.results.borrow_mut().clear();
+        }
+    }
+}
+
+
+pub(crate) struct Memoizing<'a>(&'a GuardMemo);
+
+
+impl Drop for Memoizing<'_> {
+    fn drop(&mut self)
+    {
+        self.0.active.set(false);
+        self.0.clear();
+    }
+}
+
+
+/// Wraps `guard` so that its result is shared by every guard registered
+/// under `key` for the rest of the current dispatch of `memo`'s machine.
+pub(crate) fn memoize(
+    memo: &Rc<GuardMemo>,
+    key: &str,
+    guard: impl Fn() -> bool + 'static
+) -> Guard
+{
+    let memo = memo.clone();
+    let key = key.to_string();
+
+    Box::new(move || {
+        if !memo.active.get() {
+            return guard();
+        }
+        if let Some(&result) = memo.results.borrow().get(&key) {
+            memo.saved.set(memo.saved.get() + 1);
+            return result;
+        }
+        let result = guard();
+        memo.results.borrow_mut().insert(key.clone(), result);
+        result
+    })
+}
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    /// Returns a guard memoized under `key` for this machine, for use
+    /// with `Transition::with_guard` or `Candidate::with_guard`.
+    ///
+    /// See the module documentation: `guard` must be pure.
+    pub fn memoized_guard(&self, key: &str, guard: impl Fn() -> bool + 'static) -> Guard
+    {
+        memoize(&self.guard_memo, key, guard)
+    }
+
+
+    /// Returns how many guard evaluations memoization has skipped.
+    pub fn saved_guard_evaluations(&self) -> u64
+    {
+        self.guard_memo.saved.get()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::{builder::StateMachineBuilder, fsm::{StateMachine, FSM}};
+    use std::{
+        cell::Cell,
+        panic::{self, AssertUnwindSafe},
+        rc::Rc
+    };
+
+
+    /// Three candidates guarded by the same counting guard.
+    fn router(
+        memoized: bool,
+        calls: &Rc<Cell<u32>>,
+        open: &Rc<Cell<bool>>
+    ) -> StateMachine<u8, char>
+    {
+        let guard = |calls: Rc<Cell<u32>>, open: Rc<Cell<bool>>| move || {
+            calls.set(calls.get() + 1);
+            open.get()
+        };
+        let mut builder = StateMachineBuilder::new(0).transition(0, 'r', 1);
+
+        for to in [1, 2, 3] {
+            if to > 1 {
+                builder = builder.alternative(to);
+            }
+            let guard = guard(calls.clone(), open.clone());
+            builder = if memoized {
+                builder.candidate_guard_memoized("open", guard)
+            } else {
+                builder.candidate_guard(guard)
+            };
+        }
+        builder.build().unwrap()
+    }
+
+
+    #[test]
+    fn test_memoized_guard_runs_once_per_dispatch()
+    {
+        let (calls, open) = (Rc::new(Cell::new(0)), Rc::new(Cell::new(false)));
+        let mut fsm = router(true, &calls, &open);
+
+        assert!(fsm.trigger('r').is_err());
+        assert_eq!(calls.get(), 1);
+        assert_eq!(fsm.saved_guard_evaluations(), 2);
+
+        // The cache does not outlive the dispatch.
+        open.set(true);
+        fsm.trigger('r').unwrap();
+        assert_eq!(calls.get(), 2);
+        assert_eq!(fsm.state(), 1);
+    }
+
+
+    #[test]
+    fn test_plain_guard_runs_per_candidate()
+    {
+        let (calls, open) = (Rc::new(Cell::new(0)), Rc::new(Cell::new(false)));
+        let mut fsm = router(false, &calls, &open);
+
+        assert!(fsm.trigger('r').is_err());
+        assert_eq!(calls.get(), 3);
+        assert_eq!(fsm.saved_guard_evaluations(), 0);
+    }
+
+
+    #[test]
+    fn test_cache_ends_with_a_panicking_dispatch()
+    {
+        let (calls, open) = (Rc::new(Cell::new(0)), Rc::new(Cell::new(true)));
+        let (c, o) = (calls.clone(), open.clone());
+        let mut fsm = StateMachineBuilder::new(0)
+            .transition(0, 'r', 1)
+            .guard_memoized("open", move || {
+                c.set(c.get() + 1);
+                o.get()
+            })
+            .action(|| panic!("action failed"))
+            .build()
+            .unwrap();
+
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| fsm.trigger('r'))).is_err());
+        open.set(false);
+        assert!(fsm.peek('r').is_empty());
+        assert_eq!((calls.get(), fsm.saved_guard_evaluations()), (2, 0));
+    }
+}
@@ -0,0 +1,183 @@
+//! Layers wrapping the processing of every triggered event.
+//!
+//! Each middleware receives the current state, the event and a handle to
+//! the rest of the chain. Calling `next()` runs the inner layers and,
+//! innermost, the transition itself (lookup, guards, cooldown, actions);
+//! not calling it short-circuits with the middleware's own result.
+//! `next` may be called more than once, e.g. to retry.
+//!
+//! Middleware runs outermost-first in registration order. It sits inside
+//! the finished-machine `Ignore` policy (ignored events never reach it)
+//! and outside the trace, which records the chain's final result once
+//! per trigger. Subscriptions and observers run within `next()`, once
+//! the transition has committed, so a layer sees their effects when
+//! `next()` returns. Events posted by actions are processed after the
+//! whole chain has returned, each through the chain again.
+//!
+//! A panic escaping the chain leaves the middleware registered.
+
+use std::{
+    fmt::Debug,
+    hash::Hash,
+    panic::{self, AssertUnwindSafe}
+};
+
+use crate::{error::TransitionError, fsm::StateMachine};
+
+
+pub type TriggerResult<S, E> = Result<(), TransitionError<S, E>>;
+pub type Next<'a, S, E> = dyn FnMut() -> TriggerResult<S, E> + 'a;
+pub type Middleware<S, E> = Box<dyn Fn(&S, &E, &mut Next<'_, S, E>) -> TriggerResult<S, E>>;
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    /// Appends a layer inside all previously added ones.
+    pub fn add_middleware(&mut self, middleware: Middleware<S, E>)
+    {
+        self.middleware.push(middleware);
+    }
+
+
+    pub(crate) fn take_through_middleware(
+        &mut self,
+        event: E,
+        run_action: bool
+    ) -> TriggerResult<S, E>
+    {
+        if self.middleware.is_empty() {
+            return self.take_memoized(event, run_action);
+        }
+
+        let chain = std::mem::take(&mut self.middleware);
+        let state = self.state;
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            run(&chain, &state, &event, &mut || self.take_memoized(event, run_action))
+        }));
+
+        self.middleware = chain;
+        result.unwrap_or_else(|payload| panic::resume_unwind(payload))
+    }
+
+
+    fn take_memoized(&mut self, event: E, run_action: bool) -> TriggerResult<S, E>
+    {
+        let memo = self.guard_memo.clone();
+        let _memoizing = memo.begin();
+
+        self.take(event, run_action)
+    }
+}
+
+
+fn run<S, E>(
+    chain: &[Middleware<S, E>],
+    state: &S,
+    event: &E,
+    inner: &mut Next<'_, S, E>
+) -> TriggerResult<S, E>
+{
+    match chain.split_first() {
+        None => inner(),
+        Some((layer, rest)) => layer(state, event, &mut || run(rest, state, event, inner))
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{builder::StateMachineBuilder, fsm::FSM};
+    use std::{cell::RefCell, rc::Rc};
+
+
+    #[test]
+    fn test_timing_and_auth_middleware()
+    {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut fsm = StateMachineBuilder::new("locked")
+            .transition("locked", "unlock", "open")
+            .transition("open", "lock", "locked")
+            .transition("open", "erase", "wiped")
+            .build()
+            .unwrap();
+
+        let l = log.clone();
+        fsm.add_middleware(Box::new(move |_, event, next| {
+            l.borrow_mut().push(format!("timing start {event}"));
+            let result = next();
+            l.borrow_mut().push(format!("timing end {event}: {}", result.is_ok()));
+            result
+        }));
+        let l = log.clone();
+        fsm.add_middleware(Box::new(move |state, event, next| {
+            if *event == "erase" {
+                l.borrow_mut().push("auth denied".to_string());
+                return Err(TransitionError::GuardRejected{ state: *state, event: *event });
+            }
+            next()
+        }));
+
+        fsm.enable_trace(4);
+        fsm.trigger("unlock").unwrap();
+        assert_eq!(fsm.state(), "open");
+        assert!(fsm.trigger("erase").is_err());
+        assert_eq!(fsm.state(), "open");
+
+        assert_eq!(*log.borrow(), [
+            "timing start unlock",
+            "timing end unlock: true",
+            "timing start erase",
+            "auth denied",
+            "timing end erase: false"
+        ]);
+        assert_eq!(fsm.trace().count(), 2);
+    }
+
+
+    #[test]
+    fn test_retry_middleware_calls_through_again()
+    {
+        let attempts = Rc::new(RefCell::new(0));
+        let a = attempts.clone();
+        let mut fsm = StateMachineBuilder::new(0)
+            .transition(0, 'a', 1)
+            .guard(move || {
+                *a.borrow_mut() += 1;
+                *a.borrow() > 2
+            })
+            .build()
+            .unwrap();
+
+        fsm.add_middleware(Box::new(|_, _, next| next().or_else(|_| next()).or_else(|_| next())));
+        fsm.trigger('a').unwrap();
+        assert_eq!(*attempts.borrow(), 3);
+    }
+
+
+    #[test]
+    fn test_chain_survives_panic()
+    {
+        let calls = Rc::new(RefCell::new(0));
+        let c = calls.clone();
+        let mut fsm = StateMachineBuilder::new(0)
+            .transition(0, 'a', 1)
+            .transition(0, 'b', 1)
+            .build()
+            .unwrap();
+
+        fsm.add_middleware(Box::new(move |_, event, next| {
+            *c.borrow_mut() += 1;
+            if *event == 'a' {
+                panic!("layer failed");
+            }
+            next()
+        }));
+
+        let panicked = panic::catch_unwind(AssertUnwindSafe(|| fsm.trigger('a')));
+        assert!(panicked.is_err());
+        fsm.trigger('b').unwrap();
+        assert_eq!((*calls.borrow(), fsm.state()), (2, 1));
+    }
+}
@@ -0,0 +1,252 @@
+//! Names given to states and events in exports and name lookups.
+//!
+//! By default a value is named by its `Debug` rendering, but two values
+//! can render alike (tuple variants whose payloads print the same, say).
+//! Every name map is therefore checked, and a [`NameCollision`] reported
+//! instead of silently merging the values; [`Names`] with a custom namer
+//! tells them apart.
+
+use std::{
+    collections::HashMap,
+    fmt::{self, Debug, Display}
+};
+
+
+pub type Namer<T> = Box<dyn Fn(&T) -> String>;
+
+
+/// Several states, or several events, received the same name.
+///
+/// Variants may be added, so matches outside this crate need a wildcard:
+///
+/// ```compile_fail
+/// use pfsm::naming::NameCollision;
+///
+/// fn name(collision: &NameCollision<u8, char>) -> &str
+/// {
+///     match collision {
+///         NameCollision::States { name, .. } | NameCollision::Events { name, .. } => name
+///     }
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum NameCollision<S, E> {
+    States { name: String, states: Vec<S> },
+    Events { name: String, events: Vec<E> }
+}
+
+
+impl<S, E> Display for NameCollision<S, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        let (kind, name, count) = match self {
+            NameCollision::States { name, states } => ("states", name, states.len()),
+            NameCollision::Events { name, events } => ("events", name, events.len())
+        };
+        write!(f, "{count} {kind} are named '{name}'; supply a namer that tells them apart")
+    }
+}
+
+
+impl<S: Debug, E: Debug> std::error::Error for NameCollision<S, E> {}
+
+
+impl<S, E> NameCollision<S, E> {
+    /// Returns the numeric code listed in [`ERROR_CODES`](crate::error::ERROR_CODES).
+    pub fn code(&self) -> u16
+    {
+        match self {
+            NameCollision::States { .. } => 408,
+            NameCollision::Events { .. } => 409
+        }
+    }
+}
+
+
+/// How states and events are named; `Debug` rendering by default.
+pub struct Names<S, E> {
+    state: Namer<S>,
+    event: Namer<E>
+}
+
+
+impl<S: Debug, E: Debug> Default for Names<S, E> {
+    fn default() -> Self
+    {
+        Self{
+            state: Box::new(|state| format!("{state:?}")),
+            event: Box::new(|event| format!("{event:?}"))
+        }
+    }
+}
+
+
+impl<S, E> Names<S, E> {
+    pub fn with_state_namer(mut self, namer: impl Fn(&S) -> String + 'static) -> Self
+    {
+        self.state = Box::new(namer);
+        self
+    }
+
+
+    pub fn with_event_namer(mut self, namer: impl Fn(&E) -> String + 'static) -> Self
+    {
+        self.event = Box::new(namer);
+        self
+    }
+
+
+    pub fn state(&self, state: &S) -> String
+    {
+        (self.state)(state)
+    }
+
+
+    pub fn event(&self, event: &E) -> String
+    {
+        (self.event)(event)
+    }
+
+
+    /// Maps the name of each state to the state, failing on a collision.
+    pub fn state_map(&self, states: &[S]) -> Result<HashMap<String, S>, NameCollision<S, E>>
+    where S: Copy + PartialEq
+    {
+        unique(states, &self.state)
+            .map_err(|(name, states)| NameCollision::States{ name, states })
+    }
+
+
+    /// Maps the name of each event to the event, failing on a collision.
+    pub fn event_map(&self, events: &[E]) -> Result<HashMap<String, E>, NameCollision<S, E>>
+    where E: Copy + PartialEq
+    {
+        unique(events, &self.event)
+            .map_err(|(name, events)| NameCollision::Events{ name, events })
+    }
+}
+
+
+/// Repeated values are not collisions. The collision reported is the one
+/// with the smallest name, so the error is deterministic.
+fn unique<T: Copy + PartialEq>(
+    values: &[T],
+    namer: &Namer<T>
+) -> Result<HashMap<String, T>, (String, Vec<T>)>
+{
+    let mut named: HashMap<String, Vec<T>> = HashMap::with_capacity(values.len());
+
+    for value in values {
+        let same = named.entry(namer(value)).or_default();
+        if !same.contains(value) {
+            same.push(*value);
+        }
+    }
+
+    let collision = named
+        .iter()
+        .filter(|(_, values)| values.len() > 1)
+        .min_by(|a, b| a.0.cmp(b.0));
+
+    match collision {
+        Some((name, values)) => Err((name.clone(), values.clone())),
+        None => Ok(named.into_iter().map(|(name, values)| (name, values[0])).collect())
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{builder::StateMachineBuilder, fsm::{StateMachine, FSM}, schema::Resolver};
+
+
+    /// Renders without its payload, so distinct ids collide.
+    #[derive(Clone, Copy, PartialEq, Eq, Hash)]
+    struct Id(u8);
+
+
+    impl Debug for Id {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+        {
+            f.write_str("Id")
+        }
+    }
+
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    enum Slot {
+        Empty,
+        Held(Id)
+    }
+
+
+    fn machine() -> StateMachine<Slot, Id>
+    {
+        StateMachineBuilder::new(Slot::Empty)
+            .transition(Slot::Empty, Id(1), Slot::Held(Id(1)))
+            .transition(Slot::Empty, Id(2), Slot::Held(Id(2)))
+            .build()
+            .unwrap()
+    }
+
+
+    fn names() -> Names<Slot, Id>
+    {
+        Names::default()
+            .with_state_namer(|slot| match slot {
+                Slot::Empty => "Empty".to_string(),
+                Slot::Held(Id(n)) => format!("Held{n}")
+            })
+            .with_event_namer(|Id(n)| format!("Take{n}"))
+    }
+
+
+    #[test]
+    fn test_collision_reported()
+    {
+        let held = [Slot::Held(Id(1)), Slot::Held(Id(2))];
+        let mut fsm = machine();
+
+        match fsm.to_dot() {
+            Err(NameCollision::States{ name, mut states }) => {
+                states.sort_by_key(|slot| matches!(slot, Slot::Held(Id(2))));
+                assert_eq!((name.as_str(), states), ("Held(Id)", held.to_vec()));
+            }
+            other => panic!("expected a state collision, got {other:?}")
+        }
+        assert_eq!(
+            Resolver::<Slot, Id>::from_variants(&held, &[]).err().map(|err| err.to_string()),
+            Some("2 states are named 'Held(Id)'; supply a namer that tells them apart".to_string())
+        );
+        match fsm.set_alphabet([Id(1), Id(1), Id(2)]) {
+            Err(NameCollision::Events{ name, events }) => {
+                assert_eq!((name.as_str(), events.len()), ("Id", 2));
+            }
+            other => panic!("expected an event collision, got {other:?}")
+        }
+    }
+
+
+    #[test]
+    fn test_custom_namer_disambiguates()
+    {
+        let mut fsm = machine();
+        let dot = fsm.to_dot_with(&names()).unwrap();
+
+        assert!(dot.contains(r#""Empty" -> "Held2" [label="Take2"];"#));
+
+        let resolver = Resolver::from_variants_with(
+            &[Slot::Empty, Slot::Held(Id(1)), Slot::Held(Id(2))],
+            &[Id(1), Id(2)],
+            &names()
+        ).unwrap();
+        assert_eq!(resolver.state("Held2").ok(), Some(Slot::Held(Id(2))));
+        assert_eq!(resolver.event("Take1").ok(), Some(Id(1)));
+
+        fsm.set_alphabet_with([Id(1), Id(2)], &names()).unwrap();
+        fsm.trigger_str("Take2").unwrap();
+        assert_eq!(fsm.state(), Slot::Held(Id(2)));
+    }
+}
@@ -0,0 +1,381 @@
+//! Observational callbacks run after a transition has been committed.
+//!
+//! Unlike entry/exit actions, subscriptions cannot veto a transition and
+//! any number may be registered per state. After the state changes they
+//! run in this order: exit subscriptions of the source state, entry
+//! subscriptions of the target state, then global observers, each group
+//! in registration order. All actions of the transition have run by then.
+//!
+//! Load-aware observers also receive a [`LoadHint`], so that expensive
+//! ones can do less during event storms, and steer their own delivery
+//! with the [`ObserverControl`] they return. They run among the global
+//! observers, in registration order.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Debug,
+    hash::Hash,
+    time::{Duration, Instant}
+};
+
+use crate::fsm::StateMachine;
+
+
+/// Called with the state entered or left and the event responsible.
+pub type StateListener<S, E> = Box<dyn Fn(&S, &E)>;
+/// Called with the source state, event and target state of a transition.
+pub type Observer<S, E> = Box<dyn Fn(&S, &E, &S)>;
+/// Like [`Observer`], with the machine's current load.
+pub type LoadAwareObserver<S, E> = Box<dyn Fn(&S, &E, &S, LoadHint) -> ObserverControl>;
+
+
+/// How busy the machine is; see `StateMachine::set_burst_threshold`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoadHint {
+    Normal,
+    /// More transitions than the threshold were taken within the window.
+    Burst
+}
+
+
+/// What a load-aware observer wants done after it was called.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ObserverControl {
+    Continue,
+    /// Remove the observer, as `unsubscribe` would.
+    Unsubscribe,
+    /// Do not call the observer for the next `n` transitions.
+    Skip(usize)
+}
+
+
+type Listeners<S, E> = HashMap<S, Vec<(SubscriptionId, StateListener<S, E>)>>;
+
+
+/// Identifies a registration for `unsubscribe`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(u64);
+
+
+enum GlobalObserver<S, E> {
+    Plain(Observer<S, E>),
+    LoadAware { observer: LoadAwareObserver<S, E>, skip: usize }
+}
+
+
+/// Times of the transitions taken within the burst window.
+struct LoadMeter {
+    threshold: usize,
+    window: Duration,
+    recent: VecDeque<Instant>
+}
+
+
+impl LoadMeter {
+    fn record(&mut self, now: Instant) -> LoadHint
+    {
+        while self.recent.front().is_some_and(|at| now.duration_since(*at) >= self.window) {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(now);
+
+        if self.recent.len() > self.threshold {
+            LoadHint::Burst
+        } else {
+            LoadHint::Normal
+        }
+    }
+}
+
+
+pub(crate) struct Subscriptions<S, E> {
+    next_id: u64,
+    pub(crate) enter: Listeners<S, E>,
+    pub(crate) exit: Listeners<S, E>,
+    observers: Vec<(SubscriptionId, GlobalObserver<S, E>)>,
+    load: Option<LoadMeter>
+}
+
+
+impl<S, E> Default for Subscriptions<S, E> {
+    fn default() -> Self
+    {
+        Self{
+            next_id: 0,
+            enter: HashMap::new(),
+            exit: HashMap::new(),
+            observers: Vec::new(),
+            load: None
+        }
+    }
+}
+
+
+impl<S: Hash + Eq, E> Subscriptions<S, E> {
+    fn next_id(&mut self) -> SubscriptionId
+    {
+        self.next_id += 1;
+        SubscriptionId(self.next_id)
+    }
+
+
+    pub(crate) fn remove_state(&mut self, state: &S)
+    {
+        self.enter.remove(state);
+        self.exit.remove(state);
+    }
+
+
+    pub(crate) fn notify(&mut self, from: &S, event: &E, to: &S, now: Instant)
+    {
+        let hint = self.load.as_mut().map_or(LoadHint::Normal, |load| load.record(now));
+
+        for (_, listener) in self.exit.get(from).into_iter().flatten() {
+            listener(from, event);
+        }
+        for (_, listener) in self.enter.get(to).into_iter().flatten() {
+            listener(to, event);
+        }
+        self.observers.retain_mut(|(_, observer)| match observer {
+            GlobalObserver::Plain(observer) => {
+                observer(from, event, to);
+                true
+            }
+            GlobalObserver::LoadAware { skip, .. } if *skip > 0 => {
+                *skip -= 1;
+                true
+            }
+            GlobalObserver::LoadAware { observer, skip } => {
+                match observer(from, event, to, hint) {
+                    ObserverControl::Continue => true,
+                    ObserverControl::Unsubscribe => false,
+                    ObserverControl::Skip(n) => {
+                        *skip = n;
+                        true
+                    }
+                }
+            }
+        });
+    }
+}
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    /// Calls `listener` every time a transition enters `state`.
+    pub fn on_enter_state(&mut self, state: S, listener: StateListener<S, E>) -> SubscriptionId
+    {
+        let id = self.subscriptions.next_id();
+        self.subscriptions.enter.entry(state).or_default().push((id, listener));
+        id
+    }
+
+
+    /// Calls `listener` every time a transition leaves `state`.
+    pub fn on_exit_state(&mut self, state: S, listener: StateListener<S, E>) -> SubscriptionId
+    {
+        let id = self.subscriptions.next_id();
+        self.subscriptions.exit.entry(state).or_default().push((id, listener));
+        id
+    }
+
+
+    /// Calls `observer` after every transition.
+    pub fn add_observer(&mut self, observer: Observer<S, E>) -> SubscriptionId
+    {
+        let id = self.subscriptions.next_id();
+        self.subscriptions.observers.push((id, GlobalObserver::Plain(observer)));
+        id
+    }
+
+
+    /// Calls `observer` after every transition with the current load,
+    /// unless it asked to be skipped.
+    pub fn add_load_aware_observer(&mut self, observer: LoadAwareObserver<S, E>) -> SubscriptionId
+    {
+        let id = self.subscriptions.next_id();
+        self.subscriptions.observers.push((id, GlobalObserver::LoadAware{ observer, skip: 0 }));
+        id
+    }
+
+
+    /// Reports `LoadHint::Burst` to load-aware observers while more than
+    /// `threshold` transitions were taken within the last `window` of the
+    /// machine's clock. Without a threshold the load is always `Normal`.
+    pub fn set_burst_threshold(&mut self, threshold: usize, window: Duration)
+    {
+        self.subscriptions.load = Some(LoadMeter{ threshold, window, recent: VecDeque::new() });
+    }
+
+
+    pub fn clear_burst_threshold(&mut self)
+    {
+        self.subscriptions.load = None;
+    }
+
+
+    /// Removes a subscription or observer; returns `false` if `id` is not
+    /// registered.
+    pub fn unsubscribe(&mut self, id: SubscriptionId) -> bool
+    {
+        let subscriptions = &mut self.subscriptions;
+        let before = subscriptions.observers.len();
+
+        subscriptions.observers.retain(|(i, _)| *i != id);
+        if subscriptions.observers.len() != before {
+            return true;
+        }
+        for listeners in subscriptions.enter.values_mut().chain(subscriptions.exit.values_mut()) {
+            if let Some(index) = listeners.iter().position(|(i, _)| *i == id) {
+                drop(listeners.remove(index));
+                return true;
+            }
+        }
+        false
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{builder::StateMachineBuilder, clock::MockClock, fsm::FSM};
+    use std::{cell::RefCell, rc::Rc, sync::Arc};
+
+
+    type Log = Rc<RefCell<Vec<String>>>;
+
+
+    fn machine(log: &Log) -> StateMachine<char, u8>
+    {
+        let push = |msg: &'static str| {
+            let log = log.clone();
+            move || log.borrow_mut().push(msg.to_string())
+        };
+
+        StateMachineBuilder::new('a')
+            .transition('a', 1, 'b')
+            .action(push("transition action"))
+            .transition('b', 2, 'a')
+            .on_exit('a', push("exit action a"))
+            .on_enter('b', push("entry action b"))
+            .build()
+            .unwrap()
+    }
+
+
+    #[test]
+    fn test_subscription_order()
+    {
+        let log: Log = Rc::default();
+        let mut fsm = machine(&log);
+        let listener = |name: &'static str| {
+            let log = log.clone();
+            Box::new(move |state: &char, event: &u8| {
+                log.borrow_mut().push(format!("{name} {state} on {event}"));
+            })
+        };
+
+        fsm.on_enter_state('b', listener("enter#1"));
+        fsm.on_enter_state('b', listener("enter#2"));
+        fsm.on_exit_state('a', listener("exit"));
+        let l = log.clone();
+        fsm.add_observer(Box::new(move |from, event, to| {
+            l.borrow_mut().push(format!("observer {from} -{event}-> {to}"));
+        }));
+
+        fsm.trigger(1).unwrap();
+        assert_eq!(*log.borrow(), [
+            "exit action a",
+            "transition action",
+            "entry action b",
+            "exit a on 1",
+            "enter#1 b on 1",
+            "enter#2 b on 1",
+            "observer a -1-> b"
+        ]);
+
+        log.borrow_mut().clear();
+        fsm.trigger(2).unwrap();
+        assert_eq!(*log.borrow(), ["observer b -2-> a"]);
+    }
+
+
+    #[test]
+    fn test_unsubscribe()
+    {
+        let log: Log = Rc::default();
+        let mut fsm = machine(&log);
+        let l = log.clone();
+        let id = fsm.on_enter_state('b', Box::new(move |_, _| l.borrow_mut().push("entered".into())));
+
+        assert!(fsm.unsubscribe(id));
+        assert!(!fsm.unsubscribe(id));
+        fsm.trigger(1).unwrap();
+        assert!(!log.borrow().iter().any(|msg| msg == "entered"));
+    }
+
+
+    #[test]
+    fn test_load_hint_and_skip()
+    {
+        let clock = Arc::new(MockClock::new());
+        let hints = Rc::new(RefCell::new(Vec::new()));
+        let calls = Rc::new(RefCell::new(Vec::new()));
+        let mut fsm = StateMachineBuilder::new('a')
+            .transition('a', 1, 'b')
+            .transition('b', 1, 'a')
+            .build()
+            .unwrap();
+        let (h, c) = (hints.clone(), calls.clone());
+
+        fsm.set_clock(clock.clone());
+        fsm.set_burst_threshold(3, Duration::from_secs(1));
+        fsm.add_load_aware_observer(Box::new(move |_, _, _, hint| {
+            h.borrow_mut().push(hint);
+            ObserverControl::Continue
+        }));
+        let skipper = fsm.add_load_aware_observer(Box::new(move |_, _, to, _| {
+            c.borrow_mut().push(*to);
+            ObserverControl::Skip(2)
+        }));
+
+        for _ in 0..5 {
+            fsm.trigger(1).unwrap();
+            clock.advance(Duration::from_millis(100));
+        }
+        clock.advance(Duration::from_secs(1));
+        fsm.trigger(1).unwrap();
+
+        use LoadHint::*;
+        assert_eq!(*hints.borrow(), [Normal, Normal, Normal, Burst, Burst, Normal]);
+        // Called on the first and fourth transitions, skipped on the two
+        // after each call.
+        assert_eq!(*calls.borrow(), ['b', 'a']);
+        assert!(fsm.unsubscribe(skipper));
+    }
+
+
+    #[test]
+    fn test_observer_unsubscribes_itself()
+    {
+        let calls = Rc::new(RefCell::new(0));
+        let c = calls.clone();
+        let mut fsm = StateMachineBuilder::new('a')
+            .transition('a', 1, 'b')
+            .transition('b', 1, 'a')
+            .build()
+            .unwrap();
+        let id = fsm.add_load_aware_observer(Box::new(move |_, _, _, hint| {
+            assert_eq!(hint, LoadHint::Normal);
+            *c.borrow_mut() += 1;
+            ObserverControl::Unsubscribe
+        }));
+
+        fsm.trigger(1).unwrap();
+        fsm.trigger(1).unwrap();
+        assert_eq!(*calls.borrow(), 1);
+        assert!(!fsm.unsubscribe(id));
+    }
+}
@@ -0,0 +1,189 @@
+//! Provenance of triggered events.
+//!
+//! Every dispatch carries an [`Origin`]: `trigger` uses
+//! `Origin::unspecified()`, `tick` uses `Origin::timer()` and
+//! `trigger_from` takes one explicitly. Events posted from actions
+//! inherit the origin of the dispatch that posted them unless posted with
+//! `EventPoster::post_from`. The origin is recorded in every trace entry,
+//! rejected or not, and closures can read it through an [`OriginReader`].
+
+use std::{borrow::Cow, cell::RefCell, fmt::{self, Debug, Display}, hash::Hash, rc::Rc};
+
+use crate::{error::TransitionError, fsm::StateMachine};
+
+
+/// Where an event came from: a label plus an optional numeric id, such
+/// as a user or request id.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Origin {
+    pub label: Cow<'static, str>,
+    pub id: Option<u64>
+}
+
+
+impl Origin {
+    pub fn new(label: impl Into<Cow<'static, str>>) -> Self
+    {
+        Self{ label: label.into(), id: None }
+    }
+
+
+    pub fn unspecified() -> Self
+    {
+        Self::new("unspecified")
+    }
+
+
+    /// Origin of events triggered by `tick`.
+    pub fn timer() -> Self
+    {
+        Self::new("timer")
+    }
+
+
+    /// Origin of the expiry event posted when a deadline passes.
+    pub fn deadline() -> Self
+    {
+        Self::new("deadline")
+    }
+
+
+    pub fn with_id(mut self, id: u64) -> Self
+    {
+        self.id = Some(id);
+        self
+    }
+}
+
+
+impl Default for Origin {
+    fn default() -> Self
+    {
+        Self::unspecified()
+    }
+}
+
+
+impl Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        match self.id {
+            Some(id) => write!(f, "{}#{id}", self.label),
+            None => write!(f, "{}", self.label)
+        }
+    }
+}
+
+
+/// Handle for reading the origin of the event being dispatched.
+#[derive(Clone)]
+pub struct OriginReader {
+    pub(crate) origin: Rc<RefCell<Origin>>
+}
+
+
+impl OriginReader {
+    /// Returns the origin of the current dispatch, or of the last one
+    /// outside of a dispatch.
+    pub fn get(&self) -> Origin
+    {
+        self.origin.borrow().clone()
+    }
+}
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    /// Like `trigger`, with `origin` recorded as the event's provenance.
+    pub fn trigger_from(&mut self, event: E, origin: Origin) -> Result<(), TransitionError<S, E>>
+    {
+        self.fire(event, origin, true)
+    }
+
+
+    pub fn origin_reader(&self) -> OriginReader
+    {
+        OriginReader{ origin: self.origin.clone() }
+    }
+
+
+    /// Like `add_observer`, for observers that also want the origin of
+    /// the event.
+    pub fn add_origin_observer(
+        &mut self,
+        observer: impl Fn(&S, &E, &S, &Origin) + 'static
+    ) -> crate::observe::SubscriptionId
+    {
+        let reader = self.origin_reader();
+
+        self.add_observer(Box::new(move |from, event, to| {
+            observer(from, event, to, &reader.origin.borrow());
+        }))
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{builder::StateMachineBuilder, clock::MockClock, fsm::FSM};
+    use std::{sync::Arc, time::Duration};
+
+
+    fn origins(fsm: &StateMachine<u8, char>) -> Vec<String>
+    {
+        fsm.trace().map(|entry| format!("{} {}", entry.event, entry.origin)).collect()
+    }
+
+
+    #[test]
+    fn test_origin_of_external_internal_and_timer_events()
+    {
+        let mut fsm = StateMachineBuilder::new(0)
+            .transition(0, 'a', 1)
+            .transition(1, 'b', 2)
+            .transition(2, 'c', 3)
+            .transition(3, 't', 0)
+            .build()
+            .unwrap();
+        let clock = Arc::new(MockClock::new());
+        let poster = fsm.poster();
+        let other = fsm.poster();
+
+        fsm.set_clock(clock.clone());
+        fsm.set_timeout(3, Duration::from_secs(5), 't');
+        fsm.set_entry_action(1, Box::new(move || poster.post('b')));
+        fsm.set_entry_action(2, Box::new(move || other.post_from('c', Origin::new("retry"))));
+        fsm.enable_trace(8);
+
+        fsm.trigger_from('a', Origin::new("user").with_id(7)).unwrap();
+        assert!(fsm.trigger('x').is_err());
+        clock.advance(Duration::from_secs(5));
+        assert!(fsm.tick().unwrap());
+
+        assert_eq!(origins(&fsm), ["a user#7", "b user#7", "c retry", "x unspecified", "t timer"]);
+    }
+
+
+    #[test]
+    fn test_closures_see_origin()
+    {
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let (s1, s2) = (seen.clone(), seen.clone());
+        let mut fsm = StateMachineBuilder::new(0)
+            .transition(0, 'a', 1)
+            .guard_with_origin(|origin| origin.label != "bot")
+            .action_with_origin(move |origin| s1.borrow_mut().push(format!("action {origin}")))
+            .build()
+            .unwrap();
+
+        fsm.add_origin_observer(move |_, _, to, origin| {
+            s2.borrow_mut().push(format!("observer {to} {origin}"));
+        });
+
+        assert!(fsm.trigger_from('a', Origin::new("bot")).is_err());
+        fsm.trigger_from('a', Origin::new("user")).unwrap();
+        assert_eq!(*seen.borrow(), ["action user", "observer 1 user"]);
+    }
+}
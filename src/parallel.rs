@@ -0,0 +1,256 @@
+//! Orthogonal regions: independent machines driven by the same events.
+
+use std::{fmt::Debug, hash::Hash};
+
+use crate::{
+    configuration::Configuration,
+    error::TransitionError,
+    fsm::{StateMachine, FSM},
+    policy::RegionFailurePolicy
+};
+
+
+/// What one region did with a broadcast event.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RegionOutcome<S, E> {
+    /// The region transitioned to `to`.
+    Handled { to: S },
+    /// The region has no transition for the event in its current state.
+    Unhandled,
+    /// Any other rejection, including a caught panic.
+    Failed(TransitionError<S, E>),
+    /// An earlier region failed under `RegionFailurePolicy::Abort`.
+    Skipped
+}
+
+
+/// Per-region outcomes of one `ParallelMachine::trigger`, in registration
+/// order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RegionReport<S, E> {
+    pub event: E,
+    pub outcomes: Vec<RegionOutcome<S, E>>
+}
+
+
+impl<S, E> RegionReport<S, E> {
+    /// Returns `true` if at least one region transitioned.
+    pub fn handled(&self) -> bool
+    {
+        self.outcomes.iter().any(|o| matches!(o, RegionOutcome::Handled { .. }))
+    }
+
+
+    /// Returns the index and error of every failed region.
+    pub fn failures(&self) -> impl Iterator<Item = (usize, &TransitionError<S, E>)>
+    {
+        self.outcomes.iter().enumerate().filter_map(|(i, outcome)| match outcome {
+            RegionOutcome::Failed(err) => Some((i, err)),
+            _ => None
+        })
+    }
+}
+
+
+/// A set of regions that each receive every event, in the order they
+/// were added.
+///
+/// A failing region never rolls back the regions visited before it.
+pub struct ParallelMachine<S: Copy, E: Copy> {
+    regions: Vec<(String, StateMachine<S, E>)>,
+    failure_policy: RegionFailurePolicy
+}
+
+
+impl<S, E> Default for ParallelMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    fn default() -> Self
+    {
+        Self::new()
+    }
+}
+
+
+impl<S, E> ParallelMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    pub fn new() -> Self
+    {
+        Self{ regions: Vec::new(), failure_policy: RegionFailurePolicy::default() }
+    }
+
+
+    /// Appends a region; regions are visited in the order they are added.
+    pub fn add_region(&mut self, name: &str, machine: StateMachine<S, E>) -> &mut Self
+    {
+        self.regions.push((name.to_string(), machine));
+        self
+    }
+
+
+    pub fn set_failure_policy(&mut self, policy: RegionFailurePolicy)
+    {
+        self.failure_policy = policy;
+    }
+
+
+    pub fn region_names(&self) -> impl Iterator<Item = &str>
+    {
+        self.regions.iter().map(|(name, _)| name.as_str())
+    }
+
+
+    pub fn region(&self, name: &str) -> Option<&StateMachine<S, E>>
+    {
+        self.regions.iter().find(|(n, _)| n == name).map(|(_, machine)| machine)
+    }
+
+
+    pub fn region_mut(&mut self, name: &str) -> Option<&mut StateMachine<S, E>>
+    {
+        self.regions.iter_mut().find(|(n, _)| n == name).map(|(_, machine)| machine)
+    }
+
+
+    /// Delivers `event` to every region and reports what each one did.
+    pub fn trigger(&mut self, event: E) -> RegionReport<S, E>
+    {
+        let mut outcomes = Vec::with_capacity(self.regions.len());
+        let mut aborted = false;
+
+        for (_, machine) in &mut self.regions {
+            let outcome = if aborted {
+                RegionOutcome::Skipped
+            } else {
+                match machine.trigger(event) {
+                    Ok(()) => RegionOutcome::Handled{ to: machine.state() },
+                    Err(err) if matches!(err.root(), TransitionError::NoTransition { .. }) => {
+                        RegionOutcome::Unhandled
+                    }
+                    Err(err) => RegionOutcome::Failed(err)
+                }
+            };
+
+            if matches!(outcome, RegionOutcome::Failed(_))
+                && self.failure_policy == RegionFailurePolicy::Abort
+            {
+                aborted = true;
+            }
+            outcomes.push(outcome);
+        }
+        RegionReport{ event, outcomes }
+    }
+
+
+    /// Returns the current state of every region, in registration order.
+    pub fn configuration(&self) -> Configuration<S>
+    {
+        Configuration::new(self.regions.iter().map(|(_, m)| vec![m.state()]).collect())
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{builder::StateMachineBuilder, policy::Policies};
+    use std::{cell::RefCell, rc::Rc};
+
+
+    fn region(id: u8, log: &Rc<RefCell<Vec<u8>>>) -> StateMachine<u8, char>
+    {
+        let log = log.clone();
+        let mut fsm = StateMachineBuilder::new(0)
+            .transition(0, 'g', 1)
+            .action(move || {
+                log.borrow_mut().push(id);
+                if id == 2 {
+                    panic!("region 2 failed");
+                }
+            })
+            .build()
+            .unwrap();
+
+        fsm.set_policies(Policies{ catch_panics: true, ..Policies::default() });
+        fsm
+    }
+
+
+    fn machine(log: &Rc<RefCell<Vec<u8>>>) -> ParallelMachine<u8, char>
+    {
+        let mut parallel = ParallelMachine::new();
+
+        parallel
+            .add_region("one", region(1, log))
+            .add_region("two", region(2, log))
+            .add_region("three", region(3, log));
+        parallel
+    }
+
+
+    #[test]
+    fn test_failing_region_is_isolated()
+    {
+        let log = Rc::default();
+        let mut parallel = machine(&log);
+        let report = parallel.trigger('g');
+
+        assert_eq!(report.outcomes, [
+            RegionOutcome::Handled{ to: 1 },
+            RegionOutcome::Failed(TransitionError::ActionPanicked{
+                message: "region 2 failed".to_string()
+            }),
+            RegionOutcome::Handled{ to: 1 }
+        ]);
+        assert_eq!(report.failures().map(|(i, _)| i).collect::<Vec<_>>(), [1]);
+        assert_eq!(*log.borrow(), [1, 2, 3]);
+        assert_eq!(parallel.configuration().to_string(), "1 | 0 | 1");
+
+        let report = parallel.trigger('x');
+        assert!(!report.handled());
+        assert_eq!(report.outcomes[0], RegionOutcome::Unhandled);
+    }
+
+
+    #[test]
+    fn test_abort_policy_skips_later_regions()
+    {
+        let log = Rc::default();
+        let mut parallel = machine(&log);
+
+        parallel.set_failure_policy(RegionFailurePolicy::Abort);
+        let report = parallel.trigger('g');
+
+        assert_eq!(report.outcomes[0], RegionOutcome::Handled{ to: 1 });
+        assert!(matches!(report.outcomes[1], RegionOutcome::Failed(_)));
+        assert_eq!(report.outcomes[2], RegionOutcome::Skipped);
+        assert_eq!(*log.borrow(), [1, 2]);
+        assert_eq!(parallel.region("three").unwrap().state(), 0);
+        assert_eq!(parallel.region_names().collect::<Vec<_>>(), ["one", "two", "three"]);
+    }
+
+
+    #[test]
+    fn test_unhandled_with_rejection_context_does_not_abort()
+    {
+        let log = Rc::default();
+        let mut parallel = machine(&log);
+
+        parallel.set_failure_policy(RegionFailurePolicy::Abort);
+        for name in ["one", "two", "three"] {
+            let region = parallel.region_mut(name).unwrap();
+            region.enable_trace(4);
+            region.set_policies(Policies{
+                catch_panics: true,
+                rejection_context: true,
+                ..Policies::default()
+            });
+        }
+        let report = parallel.trigger('x');
+
+        assert_eq!(report.outcomes.len(), 3);
+        assert!(report.outcomes.iter().all(|outcome| *outcome == RegionOutcome::Unhandled));
+    }
+}
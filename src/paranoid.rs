@@ -0,0 +1,220 @@
+//! Internal consistency checks compiled in by the `paranoid` feature.
+//!
+//! After every trigger, restore, forced state change, rename, hot swap,
+//! deadline expiry, maintenance run and quiescence run the machine
+//! verifies that its current state, its trace, the posted queue and the
+//! fair queue only reference states and events it knows about, and panics
+//! with a report otherwise. An event is known if a transition, timeout,
+//! alias or the alphabet names it. Trace entries recorded before a hot
+//! swap are exempt.
+//! Nothing here is compiled without the feature.
+
+use std::{collections::HashSet, fmt::{Debug, Write}, hash::Hash};
+
+use crate::{fsm::StateMachine, trace::TraceOutcome};
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    /// Panics if any invariant is broken; `after` names the mutation.
+    pub(crate) fn check_invariants(&self, after: &str)
+    {
+        let states: HashSet<S> = self.states().into_iter().collect();
+        let events: HashSet<E> = self.transitions
+            .keys()
+            .map(|(_, event)| *event)
+            .chain(self.timeouts.values().map(|(_, event)| *event))
+            .chain(self.aliases.keys().copied())
+            .chain(self.alphabet.iter().flat_map(|alphabet| alphabet.events.iter().copied()))
+            .collect();
+        let mut report = String::new();
+
+        if !states.contains(&self.state) {
+            let _ = writeln!(report, "  current state {:?} is unknown", self.state);
+        }
+        let exempt = self.trace.as_ref().map_or(0, |trace| trace.predating_swap);
+
+        for (index, entry) in self.trace().enumerate().skip(exempt) {
+            if !states.contains(&entry.state) {
+                let _ = writeln!(report, "  trace[{index}] source {:?} is unknown", entry.state);
+            }
+            // Rejected entries may legitimately carry events nobody handles.
+            if let TraceOutcome::Transitioned { to } = &entry.outcome {
+                if !states.contains(to) {
+                    let _ = writeln!(report, "  trace[{index}] target {to:?} is unknown");
+                }
+                if !events.contains(&entry.event) {
+                    let _ = writeln!(report, "  trace[{index}] event {:?} is unknown", entry.event);
+                }
+            }
+        }
+
+        for (index, event) in self.posted.borrow().events().enumerate() {
+            if !events.contains(&event) {
+                let _ = writeln!(report, "  posted[{index}] event {event:?} is unknown");
+            }
+        }
+        for (index, event) in self.fair.events().into_iter().enumerate() {
+            if !events.contains(&event) {
+                let _ = writeln!(report, "  fair[{index}] event {event:?} is unknown");
+            }
+        }
+
+        if !report.is_empty() {
+            let mut known = self.states();
+            known.sort_by_cached_key(|state| format!("{state:?}"));
+            panic!("machine invariants violated after {after}:\n{report}  known states: {known:?}");
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use std::time::Instant;
+
+    use crate::{
+        builder::StateMachineBuilder,
+        deadline::DeadlineAction,
+        fsm::{StateMachine, FSM},
+        maintain::MaintenanceBudget,
+        origin::Origin,
+        trace::{TraceEntry, TraceOutcome}
+    };
+
+
+    fn machine() -> StateMachine<u8, char>
+    {
+        let mut fsm = StateMachineBuilder::new(0)
+            .transition(0, 'a', 1)
+            .transition(1, 'b', 0)
+            .build()
+            .unwrap();
+
+        fsm.enable_trace(4);
+        fsm
+    }
+
+
+    #[test]
+    fn test_consistent_machine_passes()
+    {
+        let mut fsm = machine();
+
+        fsm.trigger('a').unwrap();
+        fsm.force_state(0);
+        fsm.restore(fsm.snapshot());
+        fsm.rename_state(&1, 7).unwrap();
+        fsm.check_invariants("test");
+    }
+
+
+    #[test]
+    #[should_panic(expected = "machine invariants violated after force_state:\n  current state 9 is unknown")]
+    fn test_forced_unknown_state()
+    {
+        machine().force_state(9);
+    }
+
+
+    #[test]
+    #[should_panic(expected = "after restore:\n  current state 5 is unknown")]
+    fn test_restored_unknown_state()
+    {
+        let mut fsm = machine();
+        let mut snapshot = fsm.snapshot();
+
+        snapshot.state = 5;
+        fsm.restore(snapshot);
+    }
+
+
+    #[test]
+    #[should_panic(expected = "  trace[0] target 8 is unknown\n  trace[0] event 'z' is unknown")]
+    fn test_restored_trace_with_unknown_references()
+    {
+        let mut fsm = machine();
+        let mut snapshot = fsm.snapshot();
+
+        snapshot.trace.push(TraceEntry{
+            state: 0,
+            event: 'z',
+            outcome: TraceOutcome::Transitioned{ to: 8 },
+            origin: crate::origin::Origin::unspecified(),
+            coalesced: 1,
+            at: std::time::Instant::now()
+        });
+        fsm.restore(snapshot);
+    }
+
+
+    #[test]
+    #[should_panic(expected = "after trigger:\n  current state 1 is unknown")]
+    fn test_trigger_checks_after_removal()
+    {
+        let mut fsm = machine();
+
+        fsm.trigger('a').unwrap();
+        fsm.remove_transition(0, 'a');
+        fsm.remove_transition(1, 'b');
+        let _ = fsm.trigger('b');
+    }
+
+
+    #[test]
+    #[should_panic(expected = "after force_state:\n  posted[1] event 'z' is unknown")]
+    fn test_posted_unknown_event()
+    {
+        let mut fsm = machine();
+        let poster = fsm.poster();
+
+        poster.post('a');
+        poster.post('z');
+        fsm.force_state(0);
+    }
+
+
+    #[test]
+    #[should_panic(expected = "after force_state:\n  fair[0] event 'z' is unknown")]
+    fn test_fair_queue_unknown_event()
+    {
+        let mut fsm = machine();
+        let _ticket = fsm.fair_trigger().submit('z', Origin::new("remote"));
+
+        fsm.force_state(0);
+    }
+
+
+    #[test]
+    #[should_panic(expected = "after deadline:\n  current state 9 is unknown")]
+    fn test_deadline_checks_after_expiry()
+    {
+        let mut fsm = machine();
+
+        fsm.set_deadline(Instant::now(), DeadlineAction::Enter(9));
+        let _ = fsm.trigger('a');
+    }
+
+
+    #[test]
+    #[should_panic(expected = "after maintain:\n  posted[0] event 'z' is unknown")]
+    fn test_maintain_checks_when_out_of_budget()
+    {
+        let mut fsm = machine();
+
+        fsm.poster().post('z');
+        fsm.maintain(MaintenanceBudget::steps(0));
+    }
+
+
+    #[test]
+    #[should_panic(expected = "after run_until_quiescent:\n  posted[0] event 'z' is unknown")]
+    fn test_quiescence_checks_when_out_of_budget()
+    {
+        let mut fsm = machine();
+
+        fsm.poster().post('z');
+        let _ = fsm.run_until_quiescent(0);
+    }
+}
@@ -0,0 +1,189 @@
+//! Interactive poking at a machine, for design sessions and snapshot tests.
+//!
+//! A [`Playground`] applies [`Command`]s to a wrapped machine and renders
+//! what each one did as a line of text. Every command that moves the
+//! machine pushes a snapshot, so `Undo` can step back; undoing restores
+//! the state and trace only, not whatever the actions did.
+
+use std::{fmt::{Debug, Write}, hash::Hash};
+
+use crate::{
+    fsm::{StateMachine, FSM},
+    snapshot::Snapshot
+};
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command<S, E> {
+    Trigger(E),
+    Undo,
+    Reset,
+    /// Force the machine into a state, bypassing transitions.
+    Jump(S),
+    Explain(E)
+}
+
+
+pub struct Playground<S: Copy, E: Copy> {
+    machine: StateMachine<S, E>,
+    undo: Vec<Snapshot<S, E>>
+}
+
+
+impl<S, E> Playground<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    pub fn new(machine: StateMachine<S, E>) -> Self
+    {
+        Self{ machine, undo: Vec::new() }
+    }
+
+
+    pub fn machine(&self) -> &StateMachine<S, E>
+    {
+        &self.machine
+    }
+
+
+    pub fn into_machine(self) -> StateMachine<S, E>
+    {
+        self.machine
+    }
+
+
+    /// Applies `command` and describes its effect.
+    pub fn apply(&mut self, command: Command<S, E>) -> String
+    {
+        let from = self.machine.state();
+
+        match command {
+            Command::Trigger(event) => {
+                self.undo.push(self.machine.snapshot());
+                match self.machine.trigger(event) {
+                    Ok(()) => format!("{event:?}: {from:?} -> {:?}", self.machine.state()),
+                    Err(err) => {
+                        self.undo.pop();
+                        format!("{event:?} rejected: {err}")
+                    }
+                }
+            }
+            Command::Undo => match self.undo.pop() {
+                Some(snapshot) => {
+                    self.machine.restore(snapshot);
+                    format!("undo: {from:?} -> {:?}", self.machine.state())
+                }
+                None => "nothing to undo".to_string()
+            },
+            Command::Reset => {
+                self.undo.push(self.machine.snapshot());
+                self.machine.reset();
+                format!("reset: {from:?} -> {:?}", self.machine.state())
+            }
+            Command::Jump(state) => {
+                self.undo.push(self.machine.snapshot());
+                self.machine.force_state(state);
+                format!("jump: {from:?} -> {state:?}")
+            }
+            Command::Explain(event) => self.machine.explain(event).to_string()
+        }
+    }
+
+
+    /// Applies every command and returns the transcript: each command on
+    /// a `> ` line, followed by its effect.
+    pub fn run_script(&mut self, commands: impl IntoIterator<Item = Command<S, E>>) -> String
+    {
+        let mut transcript = String::new();
+
+        for command in commands {
+            let effect = self.apply(command);
+            writeln!(transcript, "> {command:?}\n{effect}").unwrap();
+        }
+        transcript
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::builder::StateMachineBuilder;
+
+
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    enum State {
+        Draft,
+        Review,
+        Published
+    }
+
+
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    enum Event {
+        Submit,
+        Approve,
+        Reject
+    }
+
+
+    fn playground() -> Playground<State, Event>
+    {
+        let fsm = StateMachineBuilder::new(State::Draft)
+            .transition(State::Draft, Event::Submit, State::Review)
+            .transition(State::Review, Event::Approve, State::Published)
+            .transition(State::Review, Event::Reject, State::Draft)
+            .build()
+            .unwrap();
+
+        Playground::new(fsm)
+    }
+
+
+    #[test]
+    fn test_scripted_session_transcript()
+    {
+        let mut playground = playground();
+        let transcript = playground.run_script([
+            Command::Explain(Event::Approve),
+            Command::Trigger(Event::Submit),
+            Command::Trigger(Event::Submit),
+            Command::Trigger(Event::Approve),
+            Command::Undo,
+            Command::Jump(State::Published),
+            Command::Reset,
+            Command::Undo,
+            Command::Undo,
+            Command::Undo,
+            Command::Undo,
+            Command::Undo
+        ]);
+
+        assert_eq!(transcript, "\
+> Explain(Approve)
+Approve in Draft: no transition (valid events: [Submit])
+> Trigger(Submit)
+Submit: Draft -> Review
+> Trigger(Submit)
+Submit rejected: No transition found for event 'Submit' from state 'Review'
+> Trigger(Approve)
+Approve: Review -> Published
+> Undo
+undo: Published -> Review
+> Jump(Published)
+jump: Review -> Published
+> Reset
+reset: Published -> Draft
+> Undo
+undo: Draft -> Published
+> Undo
+undo: Published -> Review
+> Undo
+undo: Review -> Draft
+> Undo
+nothing to undo
+> Undo
+nothing to undo
+");
+        assert_eq!(playground.machine().state(), State::Draft);
+    }
+}
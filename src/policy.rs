@@ -0,0 +1,277 @@
+use crate::freeze::FreezePolicy;
+
+
+/// What a finished machine does with further events.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FinishedPolicy {
+    /// Reject them with `TransitionError::MachineFinished`.
+    #[default]
+    Refuse,
+    /// Accept and discard them: `trigger` returns `Ok` and nothing is traced.
+    Ignore
+}
+
+
+/// What happens to a transition whose cancellable action returned
+/// `Cancelled`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CancellationPolicy {
+    /// Fail with `TransitionError::Cancelled`; the state is unchanged, but
+    /// the exit action has already run.
+    #[default]
+    Fail,
+    /// Take the transition anyway.
+    Complete
+}
+
+
+/// Where `reset` returns a machine whose initial state was chosen by an
+/// `initial_choice`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ResetPolicy {
+    /// The state selected when the machine was built.
+    #[default]
+    KeepSelected,
+    /// Take a fresh snapshot and evaluate the choice again.
+    Reevaluate
+}
+
+
+/// What a `ParallelMachine` does after one region fails an event.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RegionFailurePolicy {
+    /// Deliver the event to the remaining regions anyway.
+    #[default]
+    Continue,
+    /// Skip the remaining regions.
+    Abort
+}
+
+
+/// Runtime switches changing how a machine processes events.
+///
+/// New switches may be added, so outside this crate policies are built
+/// from the defaults with the `with_*` methods:
+///
+/// ```
+/// use pfsm::policy::{FinishedPolicy, Policies};
+///
+/// let policies = Policies::default()
+///     .with_catch_panics(true)
+///     .with_finished(FinishedPolicy::Ignore);
+/// assert!(policies.catch_panics);
+/// ```
+///
+/// ```compile_fail
+/// use pfsm::policy::Policies;
+///
+/// let policies = Policies{ catch_panics: true, ..Policies::default() };
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Policies {
+    /// Catch panics raised by guards and actions and report them as
+    /// `TransitionError::ActionPanicked`, leaving the state unchanged.
+    ///
+    /// Closures are invoked through `AssertUnwindSafe`: whatever they
+    /// captured may be left half-updated by the panic.
+    pub catch_panics: bool,
+    /// Handling of events triggered once a terminal state is reached.
+    pub finished: FinishedPolicy,
+    /// Most transitions listed by the machine's `Debug` output.
+    pub debug_edge_limit: usize,
+    /// Most valid events listed by an explanation of a missing transition.
+    pub listed_events_limit: usize,
+    /// Most entry and exit actions one dispatch may run.
+    pub cascade_limit: usize,
+    /// Most posted events one dispatch may process.
+    pub internal_event_limit: usize,
+    pub cancellation: CancellationPolicy,
+    /// Keep a cancellation request across dispatches instead of clearing
+    /// it when the next one starts.
+    pub sticky_cancel: bool,
+    pub reset: ResetPolicy,
+    /// Whether flagged transitions are enabled while no flag provider is
+    /// set.
+    pub flags_without_provider: bool,
+    /// Capture deadlines in snapshots as instants rather than as the time
+    /// left, for restoring into a machine sharing the same clock.
+    pub absolute_deadline_snapshots: bool,
+    /// Return rejections as `TransitionError::WithContext`, with an
+    /// excerpt of the trace, while tracing is enabled.
+    pub rejection_context: bool,
+    /// Most trace entries in a rejection context.
+    pub rejection_excerpt_len: usize,
+    /// What a structural change does to a frozen machine.
+    pub freeze: FreezePolicy
+}
+
+
+impl Policies {
+    pub fn with_catch_panics(mut self, catch_panics: bool) -> Self
+    {
+        self.catch_panics = catch_panics;
+        self
+    }
+
+
+    pub fn with_finished(mut self, finished: FinishedPolicy) -> Self
+    {
+        self.finished = finished;
+        self
+    }
+
+
+    pub fn with_debug_edge_limit(mut self, limit: usize) -> Self
+    {
+        self.debug_edge_limit = limit;
+        self
+    }
+
+
+    pub fn with_listed_events_limit(mut self, limit: usize) -> Self
+    {
+        self.listed_events_limit = limit;
+        self
+    }
+
+
+    pub fn with_cascade_limit(mut self, limit: usize) -> Self
+    {
+        self.cascade_limit = limit;
+        self
+    }
+
+
+    pub fn with_internal_event_limit(mut self, limit: usize) -> Self
+    {
+        self.internal_event_limit = limit;
+        self
+    }
+
+
+    pub fn with_cancellation(mut self, cancellation: CancellationPolicy) -> Self
+    {
+        self.cancellation = cancellation;
+        self
+    }
+
+
+    pub fn with_sticky_cancel(mut self, sticky_cancel: bool) -> Self
+    {
+        self.sticky_cancel = sticky_cancel;
+        self
+    }
+
+
+    pub fn with_reset(mut self, reset: ResetPolicy) -> Self
+    {
+        self.reset = reset;
+        self
+    }
+
+
+    pub fn with_flags_without_provider(mut self, enabled: bool) -> Self
+    {
+        self.flags_without_provider = enabled;
+        self
+    }
+
+
+    pub fn with_absolute_deadline_snapshots(mut self, absolute: bool) -> Self
+    {
+        self.absolute_deadline_snapshots = absolute;
+        self
+    }
+
+
+    pub fn with_rejection_context(mut self, enabled: bool) -> Self
+    {
+        self.rejection_context = enabled;
+        self
+    }
+
+
+    pub fn with_rejection_excerpt_len(mut self, len: usize) -> Self
+    {
+        self.rejection_excerpt_len = len;
+        self
+    }
+
+
+    pub fn with_freeze(mut self, freeze: FreezePolicy) -> Self
+    {
+        self.freeze = freeze;
+        self
+    }
+}
+
+
+impl Default for Policies {
+    fn default() -> Self
+    {
+        Self{
+            catch_panics: false,
+            finished: FinishedPolicy::default(),
+            debug_edge_limit: 32,
+            listed_events_limit: 16,
+            cascade_limit: 1000,
+            internal_event_limit: 1000,
+            cancellation: CancellationPolicy::default(),
+            sticky_cancel: false,
+            reset: ResetPolicy::default(),
+            flags_without_provider: true,
+            absolute_deadline_snapshots: false,
+            rejection_context: false,
+            rejection_excerpt_len: 5,
+            freeze: FreezePolicy::default()
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+
+    #[test]
+    fn test_with_methods_cover_every_switch()
+    {
+        // Listing every field makes this fail to compile when one is added
+        // without a matching `with_*` method.
+        let expected = Policies{
+            catch_panics: true,
+            finished: FinishedPolicy::Ignore,
+            debug_edge_limit: 1,
+            listed_events_limit: 2,
+            cascade_limit: 3,
+            internal_event_limit: 4,
+            cancellation: CancellationPolicy::Complete,
+            sticky_cancel: true,
+            reset: ResetPolicy::Reevaluate,
+            flags_without_provider: false,
+            absolute_deadline_snapshots: true,
+            rejection_context: true,
+            rejection_excerpt_len: 6,
+            freeze: FreezePolicy::Reject
+        };
+        let built = Policies::default()
+            .with_catch_panics(true)
+            .with_finished(FinishedPolicy::Ignore)
+            .with_debug_edge_limit(1)
+            .with_listed_events_limit(2)
+            .with_cascade_limit(3)
+            .with_internal_event_limit(4)
+            .with_cancellation(CancellationPolicy::Complete)
+            .with_sticky_cancel(true)
+            .with_reset(ResetPolicy::Reevaluate)
+            .with_flags_without_provider(false)
+            .with_absolute_deadline_snapshots(true)
+            .with_rejection_context(true)
+            .with_rejection_excerpt_len(6)
+            .with_freeze(FreezePolicy::Reject);
+
+        assert_eq!(built, expected);
+    }
+}
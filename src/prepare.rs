@@ -0,0 +1,252 @@
+//! Two-phase transitions for coordinating with external transactions.
+//!
+//! `prepare` runs every check a trigger would, guards included, and
+//! reserves the transition without running anything. The returned
+//! [`PreparedTransition`] then either commits it, running the actions
+//! and moving the machine, or aborts it. Middleware is not consulted:
+//! the outcome was decided at prepare time. As with `trigger`, a passed
+//! deadline is handled before the event is checked. A rejected `prepare`
+//! is traced and fed to the rejection alarm like a rejected trigger.
+
+use std::{fmt::Debug, hash::Hash};
+
+use crate::{error::TransitionError, fsm::StateMachine, origin::Origin};
+
+
+/// A checked transition awaiting `commit` or `abort`.
+///
+/// Dropping it undecided aborts it and counts it in
+/// `StateMachine::abandoned_prepares`. Leaking it leaves the reservation in
+/// place: the machine then fails every event with `TransitionPending`
+/// until `release_prepared` is called.
+pub struct PreparedTransition<'a, S: Copy + Debug, E: Copy + Debug> {
+    fsm: &'a mut StateMachine<S, E>,
+    from: S,
+    received: E,
+    canonical: E,
+    to: S,
+    decided: bool
+}
+
+
+impl<S, E> PreparedTransition<'_, S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    pub fn from(&self) -> S
+    {
+        self.from
+    }
+
+
+    pub fn event(&self) -> E
+    {
+        self.received
+    }
+
+
+    pub fn to(&self) -> S
+    {
+        self.to
+    }
+
+
+    /// Runs the transition's actions and commits it, then processes any
+    /// events they posted. A failing or panicking action leaves the
+    /// state unchanged, as with `trigger`.
+    pub fn commit(mut self) -> Result<(), TransitionError<S, E>>
+    {
+        let (from, received, canonical, to) = (self.from, self.received, self.canonical, self.to);
+
+        self.decided = true;
+        self.fsm.prepared = None;
+        let result = self.fsm.dispatch(Origin::unspecified(), |fsm| {
+            let sequence = fsm.sequence;
+            let result = fsm.run_transition(canonical, to, true);
+
+            fsm.record_outcome(from, received, sequence, &result);
+            result
+        });
+        result.map_err(|err| self.fsm.with_context(err))
+    }
+
+
+    /// Releases the reservation; nothing has run.
+    pub fn abort(mut self)
+    {
+        self.decided = true;
+        self.fsm.prepared = None;
+    }
+}
+
+
+impl<S: Copy + Debug, E: Copy + Debug> Drop for PreparedTransition<'_, S, E> {
+    fn drop(&mut self)
+    {
+        if !self.decided {
+            self.fsm.prepared = None;
+            self.fsm.abandoned_prepares += 1;
+        }
+    }
+}
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    /// Checks and reserves the transition `event` would take.
+    pub fn prepare(
+        &mut self,
+        event: E
+    ) -> Result<PreparedTransition<'_, S, E>, TransitionError<S, E>>
+    {
+        if let Err(err) = self.expire_if_due() {
+            return Err(self.with_context(err));
+        }
+        let from = self.state;
+        let canonical = self.resolve(from, event);
+
+        self.refresh_context();
+        let sequence = self.sequence;
+        let to = match self.check_transition(canonical) {
+            Ok(to) => to,
+            Err(err) => {
+                self.record_outcome(from, event, sequence, &Err(err.clone()));
+                return Err(self.with_context(err));
+            }
+        };
+        self.prepared = Some((from, event));
+
+        Ok(PreparedTransition{ fsm: self, from, received: event, canonical, to, decided: false })
+    }
+
+
+    /// How many prepared transitions were dropped without being
+    /// committed or aborted.
+    pub fn abandoned_prepares(&self) -> u64
+    {
+        self.abandoned_prepares
+    }
+
+
+    /// Returns the reservation of a prepared transition that was leaked
+    /// instead of being committed or aborted, releasing it.
+    pub fn release_prepared(&mut self) -> Option<(S, E)>
+    {
+        self.prepared.take()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        builder::StateMachineBuilder,
+        deadline::DeadlineAction,
+        fsm::FSM,
+        trace::TraceOutcome
+    };
+    use std::{cell::Cell, rc::Rc, time::Instant};
+
+
+    fn order(charged: &Rc<Cell<u32>>) -> StateMachine<&'static str, &'static str>
+    {
+        let charged = charged.clone();
+
+        StateMachineBuilder::new("cart")
+            .transition("cart", "checkout", "paid")
+            .action(move || charged.set(charged.get() + 1))
+            .transition("cart", "empty", "cart")
+            .build()
+            .unwrap()
+    }
+
+
+    #[test]
+    fn test_commit_and_abort()
+    {
+        let charged = Rc::new(Cell::new(0));
+        let mut fsm = order(&charged);
+
+        let prepared = fsm.prepare("checkout").unwrap();
+        assert_eq!((prepared.from(), prepared.to()), ("cart", "paid"));
+        prepared.abort();
+        assert_eq!((fsm.state(), charged.get()), ("cart", 0));
+
+        let prepared = fsm.prepare("checkout").unwrap();
+        prepared.commit().unwrap();
+        assert_eq!((fsm.state(), charged.get()), ("paid", 1));
+        assert_eq!(
+            fsm.prepare("checkout").err(),
+            Some(TransitionError::NoTransition{ state: "paid", event: "checkout" })
+        );
+    }
+
+
+    #[test]
+    fn test_pending_reservation_blocks_events()
+    {
+        let charged = Rc::new(Cell::new(0));
+        let mut fsm = order(&charged);
+
+        std::mem::forget(fsm.prepare("checkout").unwrap());
+        assert_eq!(
+            fsm.trigger("empty"),
+            Err(TransitionError::TransitionPending{ state: "cart", event: "empty" })
+        );
+        assert!(matches!(
+            fsm.prepare("checkout"),
+            Err(TransitionError::TransitionPending { .. })
+        ));
+
+        assert_eq!(fsm.release_prepared(), Some(("cart", "checkout")));
+        fsm.trigger("checkout").unwrap();
+        assert_eq!(charged.get(), 1);
+    }
+
+
+    #[test]
+    fn test_rejection_is_traced()
+    {
+        let mut fsm = order(&Rc::new(Cell::new(0)));
+
+        fsm.enable_trace(4);
+        fsm.trigger("checkout").unwrap();
+        assert!(fsm.prepare("checkout").is_err());
+
+        let last = fsm.trace().last().unwrap();
+        assert_eq!((last.state, last.event), ("paid", "checkout"));
+        let rejection = TransitionError::NoTransition{ state: "paid", event: "checkout" };
+        assert_eq!(last.outcome, TraceOutcome::Rejected(rejection));
+    }
+
+
+    #[test]
+    fn test_drop_aborts()
+    {
+        let charged = Rc::new(Cell::new(0));
+        let mut fsm = order(&charged);
+
+        drop(fsm.prepare("checkout").unwrap());
+        fsm.prepare("checkout").unwrap().abort();
+        assert_eq!(fsm.abandoned_prepares(), 1);
+        assert_eq!(fsm.release_prepared(), None);
+        fsm.trigger("empty").unwrap();
+        assert_eq!((fsm.state(), charged.get()), ("cart", 0));
+    }
+
+
+    #[test]
+    fn test_passed_deadline_expires_first()
+    {
+        let charged = Rc::new(Cell::new(0));
+        let mut fsm = order(&charged);
+
+        fsm.set_deadline(Instant::now(), DeadlineAction::Enter("paid"));
+        assert_eq!(
+            fsm.prepare("checkout").err(),
+            Some(TransitionError::NoTransition{ state: "paid", event: "checkout" })
+        );
+        assert_eq!((fsm.state(), charged.get()), ("paid", 0));
+    }
+}
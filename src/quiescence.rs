@@ -0,0 +1,195 @@
+//! Running a machine until it has nothing left to react to.
+//!
+//! A machine is quiescent when no posted event is queued and neither the
+//! current state's timeout nor the deadline is due at the current clock
+//! reading. Each micro-step handles the passed deadline, or else processes
+//! one posted event, oldest first, or else the due timeout. Rejected
+//! events are traced and count as a step; a timeout whose event is
+//! rejected stays due and will use up the budget.
+
+use std::{fmt::{self, Debug, Display}, hash::Hash};
+
+use crate::{cascade::DispatchStats, fsm::StateMachine, origin::Origin};
+
+
+/// What `run_until_quiescent` processed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct QuiescenceReport {
+    pub steps: usize,
+    pub internal_events: usize,
+    pub timer_events: usize
+}
+
+
+/// The budget ran out before the machine became quiescent.
+///
+/// Fields may be added, so it cannot be built outside this crate:
+///
+/// ```compile_fail
+/// use pfsm::quiescence::{BudgetExceeded, QuiescenceReport};
+///
+/// let error = BudgetExceeded{ report: QuiescenceReport::default() };
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct BudgetExceeded {
+    pub report: QuiescenceReport
+}
+
+
+impl Display for BudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "Machine still not quiescent after {} step(s)", self.report.steps)
+    }
+}
+
+
+impl std::error::Error for BudgetExceeded {}
+
+
+impl BudgetExceeded {
+    /// Returns the numeric code listed in [`ERROR_CODES`](crate::error::ERROR_CODES).
+    pub fn code(&self) -> u16
+    {
+        215
+    }
+}
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    pub fn is_quiescent(&self) -> bool
+    {
+        self.posted.borrow().is_empty() && self.due_timeout().is_none() && !self.deadline_due()
+    }
+
+
+    /// Processes micro-steps until the machine is quiescent, running at
+    /// most `budget` of them.
+    pub fn run_until_quiescent(&mut self, budget: usize) -> Result<QuiescenceReport, BudgetExceeded>
+    {
+        let mut report = QuiescenceReport::default();
+
+        loop {
+            let done = self.is_quiescent();
+
+            if done || report.steps == budget {
+                #[cfg(feature = "paranoid")]
+                self.check_invariants("run_until_quiescent");
+
+                return match done {
+                    true => Ok(report),
+                    false => Err(BudgetExceeded{ report })
+                };
+            }
+
+            if let Some(action) = self.take_due_deadline() {
+                report.timer_events += 1;
+                report.steps += 1;
+                self.start_step(Origin::deadline());
+                let _ = self.expire(action);
+                continue;
+            }
+
+            let posted = self.posted.borrow_mut().pop_front();
+            let (event, origin) = match posted {
+                Some(posted) => {
+                    report.internal_events += 1;
+                    posted
+                }
+                None => {
+                    report.timer_events += 1;
+                    self.posted.borrow_mut().current = (1, 1);
+                    (self.due_timeout().unwrap(), Origin::timer())
+                }
+            };
+
+            self.start_step(origin);
+            let _ = self.fire_one(event, true);
+            report.steps += 1;
+        }
+    }
+
+
+    /// Prepares one micro-step on behalf of `origin`.
+    pub(crate) fn start_step(&mut self, origin: Origin)
+    {
+        *self.origin.borrow_mut() = origin;
+        self.dispatch_stats = DispatchStats::default();
+        self.refresh_context();
+    }
+
+
+    pub(crate) fn deadline_due(&self) -> bool
+    {
+        self.deadline.is_some_and(|(at, _)| self.clock.now() >= at)
+    }
+
+
+    /// Returns the event of the current state's timeout, if it is due and
+    /// the machine has not finished.
+    pub(crate) fn due_timeout(&self) -> Option<E>
+    {
+        match self.timeouts.get(&self.state) {
+            Some(&(after, event)) if !self.is_finished() && self.time_in_state() >= after => {
+                Some(event)
+            }
+            _ => None
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{builder::StateMachineBuilder, clock::MockClock, fsm::FSM};
+    use std::{sync::Arc, time::Duration};
+
+
+    #[test]
+    fn test_cascade_quiesces()
+    {
+        let mut fsm = StateMachineBuilder::new(0)
+            .transition(0, 'a', 1)
+            .transition(1, 'b', 2)
+            .transition(2, 'c', 3)
+            .transition(3, 'd', 4)
+            .build()
+            .unwrap();
+        let (first, second) = (fsm.poster(), fsm.poster());
+
+        fsm.set_clock(Arc::new(MockClock::new()));
+        fsm.set_entry_action(1, Box::new(move || first.post('b')));
+        fsm.set_entry_action(2, Box::new(move || second.post('c')));
+        fsm.set_timeout(3, Duration::ZERO, 'd');
+        fsm.poster().post('a');
+
+        assert!(!fsm.is_quiescent());
+        assert_eq!(
+            fsm.run_until_quiescent(10),
+            Ok(QuiescenceReport{ steps: 4, internal_events: 3, timer_events: 1 })
+        );
+        assert_eq!(fsm.state(), 4);
+        assert!(fsm.is_quiescent());
+    }
+
+
+    #[test]
+    fn test_self_feeding_loop_exhausts_budget()
+    {
+        let mut fsm = StateMachineBuilder::new(0).transition(0, 'a', 0).build().unwrap();
+        let poster = fsm.poster();
+
+        fsm.set_entry_action(0, Box::new(move || poster.post('a')));
+        fsm.poster().post('a');
+
+        let err = fsm.run_until_quiescent(5).unwrap_err();
+        assert_eq!(err.report.steps, 5);
+        assert_eq!(err.to_string(), "Machine still not quiescent after 5 step(s)");
+        assert!(!fsm.is_quiescent());
+    }
+}
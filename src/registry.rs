@@ -0,0 +1,148 @@
+use std::{collections::BTreeMap, fmt::{self, Display}, rc::Rc};
+
+use crate::fsm::{Action, Guard};
+
+
+/// Error returned when a name is not present in a registry. Fields may
+/// be added; resolvers outside this crate build it with `new`:
+///
+/// ```compile_fail
+/// use pfsm::registry::UnknownName;
+///
+/// let error = UnknownName{ kind: "action", name: "beep".into(), available: Vec::new() };
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct UnknownName {
+    pub kind: &'static str,
+    pub name: String,
+    pub available: Vec<String>
+}
+
+
+impl UnknownName {
+    pub fn new(kind: &'static str, name: impl Into<String>, available: Vec<String>) -> Self
+    {
+        Self{ kind, name: name.into(), available }
+    }
+}
+
+
+impl Display for UnknownName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(
+            f,
+            "Unknown {} '{}' (available: {})",
+            self.kind, self.name, self.available.join(", ")
+        )
+    }
+}
+
+
+impl std::error::Error for UnknownName {}
+
+
+impl UnknownName {
+    /// Returns the numeric code listed in [`ERROR_CODES`](crate::error::ERROR_CODES).
+    pub fn code(&self) -> u16
+    {
+        411
+    }
+}
+
+
+/// Named actions that data-driven machines can refer to.
+#[derive(Default)]
+pub struct ActionRegistry {
+    actions: BTreeMap<String, Rc<dyn Fn()>>
+}
+
+
+impl ActionRegistry {
+    pub fn new() -> Self
+    {
+        Self::default()
+    }
+
+
+    /// Registers `action` under `name`, replacing any previous entry.
+    pub fn register(&mut self, name: &str, action: impl Fn() + 'static) -> &mut Self
+    {
+        self.actions.insert(name.to_string(), Rc::new(action));
+        self
+    }
+
+
+    /// Returns a fresh action calling the one registered under `name`.
+    pub fn resolve(&self, name: &str) -> Result<Action, UnknownName>
+    {
+        match self.actions.get(name) {
+            Some(action) => {
+                let action = Rc::clone(action);
+                Ok(Box::new(move || action()))
+            }
+            None => Err(unknown("action", name, self.names()))
+        }
+    }
+
+
+    /// Returns the registered names in sorted order.
+    pub fn names(&self) -> Vec<&str>
+    {
+        self.actions.keys().map(String::as_str).collect()
+    }
+}
+
+
+/// Named guards that data-driven machines can refer to.
+#[derive(Default)]
+pub struct GuardRegistry {
+    guards: BTreeMap<String, Rc<dyn Fn() -> bool>>
+}
+
+
+impl GuardRegistry {
+    pub fn new() -> Self
+    {
+        Self::default()
+    }
+
+
+    /// Registers `guard` under `name`, replacing any previous entry.
+    pub fn register(&mut self, name: &str, guard: impl Fn() -> bool + 'static) -> &mut Self
+    {
+        self.guards.insert(name.to_string(), Rc::new(guard));
+        self
+    }
+
+
+    /// Returns a fresh guard calling the one registered under `name`.
+    pub fn resolve(&self, name: &str) -> Result<Guard, UnknownName>
+    {
+        match self.guards.get(name) {
+            Some(guard) => {
+                let guard = Rc::clone(guard);
+                Ok(Box::new(move || guard()))
+            }
+            None => Err(unknown("guard", name, self.names()))
+        }
+    }
+
+
+    /// Returns the registered names in sorted order.
+    pub fn names(&self) -> Vec<&str>
+    {
+        self.guards.keys().map(String::as_str).collect()
+    }
+}
+
+
+fn unknown(kind: &'static str, name: &str, available: Vec<&str>) -> UnknownName
+{
+    UnknownName{
+        kind,
+        name: name.to_string(),
+        available: available.into_iter().map(str::to_string).collect()
+    }
+}
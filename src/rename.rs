@@ -0,0 +1,538 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Debug, Display},
+    hash::Hash
+};
+
+use crate::{
+    deadline::DeadlineAction,
+    fsm::StateMachine,
+    schema::Schema,
+    trace::TraceOutcome
+};
+
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RenameError<T> {
+    /// The old name is not used anywhere in the machine.
+    NotFound(T),
+    /// The new name is already in use.
+    AlreadyExists(T)
+}
+
+
+impl<T: Debug> Display for RenameError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        match self {
+            RenameError::NotFound(old) => write!(f, "'{old:?}' does not exist"),
+            RenameError::AlreadyExists(new) => write!(f, "'{new:?}' already exists")
+        }
+    }
+}
+
+
+impl<T: Debug> std::error::Error for RenameError<T> {}
+
+
+impl<T> RenameError<T> {
+    /// Returns the numeric code listed in [`ERROR_CODES`](crate::error::ERROR_CODES).
+    pub fn code(&self) -> u16
+    {
+        match self {
+            RenameError::NotFound(_) => 501,
+            RenameError::AlreadyExists(_) => 502
+        }
+    }
+}
+
+
+/// Replaces `old` by `new` in place, returning 1 if it did.
+fn swap<T: PartialEq + Copy>(value: &mut T, old: T, new: T) -> usize
+{
+    if *value == old {
+        *value = new;
+        1
+    } else {
+        0
+    }
+}
+
+
+/// Moves the entry stored under `old` to `new`, returning 1 if it did.
+fn rekey<K: Hash + Eq, V>(map: &mut HashMap<K, V>, old: &K, new: K) -> usize
+{
+    match map.remove(old) {
+        Some(value) => {
+            map.insert(new, value);
+            1
+        }
+        None => 0
+    }
+}
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    /// Renames a state everywhere it is referenced: transition sources and
+    /// targets, the initial and current state, tags, terminal and
+    /// deprecation marks, entry/exit actions and subscriptions, timeouts,
+    /// data, breakpoint filters, cooldown bookkeeping, the deadline's
+    /// expiry state and the trace.
+    ///
+    /// Returns how many references were rewritten.
+    pub fn rename_state(&mut self, old: &S, new: S) -> Result<usize, RenameError<S>>
+    {
+        let states = self.states();
+
+        if states.contains(&new) {
+            return Err(RenameError::AlreadyExists(new));
+        }
+        if !states.contains(old) {
+            return Err(RenameError::NotFound(*old));
+        }
+
+        let old = *old;
+        let mut count = swap(&mut self.initial, old, new) + swap(&mut self.state, old, new);
+
+        self.transitions = std::mem::take(&mut self.transitions)
+            .into_iter()
+            .map(|((mut from, event), mut transition)| {
+                count += swap(&mut from, old, new);
+                count += swap(&mut transition.next_state, old, new);
+                for candidate in &mut transition.candidates {
+                    count += swap(&mut candidate.to, old, new);
+                }
+                ((from, event), transition)
+            })
+            .collect();
+        self.last_fired = std::mem::take(&mut self.last_fired)
+            .into_iter()
+            .map(|((mut from, event), at)| {
+                count += swap(&mut from, old, new);
+                ((from, event), at)
+            })
+            .collect();
+
+        count += rekey(&mut self.tags, &old, new);
+        count += rekey(&mut self.entry_actions, &old, new);
+        count += rekey(&mut self.exit_actions, &old, new);
+        count += rekey(&mut self.timeouts, &old, new);
+        count += rekey(&mut self.state_data, &old, new);
+        count += rekey(&mut self.subscriptions.enter, &old, new);
+        count += rekey(&mut self.subscriptions.exit, &old, new);
+        for marks in [&mut self.terminals, &mut self.deprecated] {
+            if marks.remove(&old) {
+                marks.insert(new);
+                count += 1;
+            }
+        }
+        for breakpoint in &mut self.breakpoints {
+            if let Some(state) = &mut breakpoint.state {
+                count += swap(state, old, new);
+            }
+        }
+        if let Some((_, DeadlineAction::Enter(state))) = &mut self.deadline {
+            count += swap(state, old, new);
+        }
+
+        if let Some(trace) = &mut self.trace {
+            for entry in &mut trace.entries {
+                count += swap(&mut entry.state, old, new);
+                if let TraceOutcome::Transitioned { to } = &mut entry.outcome {
+                    count += swap(to, old, new);
+                }
+            }
+        }
+
+        self.generation += 1;
+
+        #[cfg(feature = "paranoid")]
+        self.check_invariants("rename_state");
+        Ok(count)
+    }
+
+
+    /// Renames an event everywhere it is referenced: transition keys,
+    /// timeouts, aliases, the alphabet, the deadline, queued events and
+    /// coalescing policies, breakpoint filters, cooldown bookkeeping,
+    /// quarantine counts and the trace. An alphabet or quarantine name
+    /// that was the event's `Debug` rendering becomes the new one's.
+    ///
+    /// Returns how many references were rewritten.
+    pub fn rename_event(&mut self, old: &E, new: E) -> Result<usize, RenameError<E>>
+    {
+        let known = |event: &E| {
+            self.transitions.keys().any(|(_, e)| e == event)
+                || self.timeouts.values().any(|(_, e)| e == event)
+                || self.alphabet.as_ref().is_some_and(|alphabet| alphabet.events.contains(event))
+        };
+
+        if known(&new) {
+            return Err(RenameError::AlreadyExists(new));
+        }
+        if !known(old) {
+            return Err(RenameError::NotFound(*old));
+        }
+
+        let old = *old;
+        let mut count = 0;
+
+        self.transitions = std::mem::take(&mut self.transitions)
+            .into_iter()
+            .map(|((from, mut event), transition)| {
+                count += swap(&mut event, old, new);
+                ((from, event), transition)
+            })
+            .collect();
+        self.last_fired = std::mem::take(&mut self.last_fired)
+            .into_iter()
+            .map(|((from, mut event), at)| {
+                count += swap(&mut event, old, new);
+                ((from, event), at)
+            })
+            .collect();
+        for (_, event) in self.timeouts.values_mut() {
+            count += swap(event, old, new);
+        }
+        self.aliases = std::mem::take(&mut self.aliases)
+            .into_iter()
+            .map(|(mut alias, mut canonical)| {
+                count += swap(&mut alias, old, new) + swap(&mut canonical, old, new);
+                (alias, canonical)
+            })
+            .collect();
+        for canonical in self.name_aliases.values_mut() {
+            count += swap(canonical, old, new);
+        }
+
+        let (old_name, new_name) = (format!("{old:?}"), format!("{new:?}"));
+
+        if let Some(alphabet) = &mut self.alphabet {
+            if alphabet.events.remove(&old) {
+                alphabet.events.insert(new);
+                count += 1;
+            }
+            alphabet.names = std::mem::take(&mut alphabet.names)
+                .into_iter()
+                .map(|(mut name, mut event)| {
+                    if swap(&mut event, old, new) == 1 {
+                        count += 1;
+                        if name == old_name {
+                            name.clone_from(&new_name);
+                        }
+                    }
+                    (name, event)
+                })
+                .collect();
+        }
+        count += rekey(&mut self.quarantine, &old_name, new_name);
+        if let Some((_, DeadlineAction::Post(event))) = &mut self.deadline {
+            count += swap(event, old, new);
+        }
+        count += self.posted.borrow_mut().rename_event(old, new);
+        for breakpoint in &mut self.breakpoints {
+            if let Some(event) = &mut breakpoint.event {
+                count += swap(event, old, new);
+            }
+        }
+        if let Some(trace) = &mut self.trace {
+            for entry in &mut trace.entries {
+                count += swap(&mut entry.event, old, new);
+            }
+        }
+
+        self.generation += 1;
+
+        #[cfg(feature = "paranoid")]
+        self.check_invariants("rename_event");
+        Ok(count)
+    }
+}
+
+
+impl Schema {
+    /// Renames a state in the initial state, every state and transition
+    /// record, and the initial and expected states of every scenario.
+    ///
+    /// Returns how many references were rewritten.
+    pub fn rename_state(&mut self, old: &str, new: &str) -> Result<usize, RenameError<String>>
+    {
+        let used = |name: &str| {
+            self.initial == name
+                || self.states.iter().any(|s| s.name == name)
+                || self.transitions.iter().any(|t| t.from == name || t.to == name)
+                || self.scenarios.iter().any(|s| s.initial == name || s.expected == name)
+        };
+
+        if used(new) {
+            return Err(RenameError::AlreadyExists(new.to_string()));
+        }
+        if !used(old) {
+            return Err(RenameError::NotFound(old.to_string()));
+        }
+
+        let mut count = 0;
+
+        let names = std::iter::once(&mut self.initial)
+            .chain(self.states.iter_mut().map(|s| &mut s.name))
+            .chain(self.transitions.iter_mut().flat_map(|t| [&mut t.from, &mut t.to]))
+            .chain(self.scenarios.iter_mut().flat_map(|s| [&mut s.initial, &mut s.expected]));
+
+        for name in names {
+            if name == old {
+                *name = new.to_string();
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+
+    /// Renames an event in every transition record and scenario.
+    ///
+    /// Returns how many references were rewritten.
+    pub fn rename_event(&mut self, old: &str, new: &str) -> Result<usize, RenameError<String>>
+    {
+        let used = self.transitions.iter().any(|t| t.event == new)
+            || self.scenarios.iter().any(|s| s.events.iter().any(|event| event == new));
+
+        if used {
+            return Err(RenameError::AlreadyExists(new.to_string()));
+        }
+
+        let mut count = 0;
+        let events = self.transitions
+            .iter_mut()
+            .map(|t| &mut t.event)
+            .chain(self.scenarios.iter_mut().flat_map(|s| &mut s.events));
+
+        for event in events {
+            if event == old {
+                *event = new.to_string();
+                count += 1;
+            }
+        }
+        if count == 0 {
+            return Err(RenameError::NotFound(old.to_string()));
+        }
+        Ok(count)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        builder::StateMachineBuilder,
+        clock::{Clock, MockClock},
+        coalesce::CoalescePolicy,
+        fsm::FSM
+    };
+    use std::{sync::Arc, time::Duration};
+
+
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    enum State {
+        Red,
+        Yellow,
+        Amber,
+        Green
+    }
+
+
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    enum Event {
+        Next,
+        Stop,
+        Advance
+    }
+
+
+    fn light() -> StateMachine<State, Event>
+    {
+        let mut fsm = StateMachineBuilder::new(State::Red)
+            .transition(State::Red, Event::Next, State::Yellow)
+            .transition(State::Yellow, Event::Next, State::Green)
+            .transition(State::Yellow, Event::Stop, State::Red)
+            .transition(State::Green, Event::Next, State::Yellow)
+            .cooldown(Duration::from_secs(1))
+            .tag(State::Yellow, "caution")
+            .build()
+            .unwrap();
+
+        fsm.set_timeout(State::Yellow, Duration::from_secs(3), Event::Stop);
+        fsm
+    }
+
+
+    #[test]
+    fn test_rename_current_state()
+    {
+        let mut fsm = light();
+
+        fsm.enable_trace(4);
+        fsm.trigger(Event::Next).unwrap();
+        assert_eq!(fsm.state(), State::Yellow);
+
+        // current 1, sources 2, targets 2, tag 1, timeout 1, trace 1
+        assert_eq!(fsm.rename_state(&State::Yellow, State::Amber), Ok(8));
+        assert_eq!(fsm.state(), State::Amber);
+        assert!(fsm.has_tag("caution"));
+        assert!(!fsm.states().contains(&State::Yellow));
+        assert_eq!(
+            fsm.trace().map(|e| e.outcome.clone()).collect::<Vec<_>>(),
+            [TraceOutcome::Transitioned{ to: State::Amber }]
+        );
+
+        fsm.trigger(Event::Next).unwrap();
+        assert_eq!(fsm.state(), State::Green);
+        fsm.trigger(Event::Next).unwrap();
+        assert_eq!(fsm.state(), State::Amber);
+    }
+
+
+    #[test]
+    fn test_rename_state_errors_and_generation()
+    {
+        let mut fsm = light();
+        let generation = fsm.generation();
+
+        assert_eq!(
+            fsm.rename_state(&State::Red, State::Green),
+            Err(RenameError::AlreadyExists(State::Green))
+        );
+        assert_eq!(
+            fsm.rename_state(&State::Amber, State::Yellow),
+            Err(RenameError::AlreadyExists(State::Yellow))
+        );
+        assert_eq!(fsm.generation(), generation);
+
+        assert_eq!(fsm.rename_state(&State::Red, State::Amber), Ok(4));
+        assert!(fsm.generation() > generation);
+        assert_eq!(fsm.state(), State::Amber);
+    }
+
+
+    #[test]
+    fn test_rename_deadline_expiry_state()
+    {
+        let mut fsm = light();
+        let clock = Arc::new(MockClock::new());
+
+        fsm.set_clock(clock.clone());
+        fsm.set_deadline(clock.now(), DeadlineAction::Enter(State::Yellow));
+
+        // sources 2, targets 2, tag 1, timeout 1, deadline 1
+        assert_eq!(fsm.rename_state(&State::Yellow, State::Amber), Ok(7));
+        assert_eq!(fsm.tick(), Ok(true));
+        assert_eq!(fsm.state(), State::Amber);
+    }
+
+
+    #[test]
+    fn test_rename_event()
+    {
+        let mut fsm = light();
+
+        assert_eq!(
+            fsm.rename_event(&Event::Next, Event::Stop),
+            Err(RenameError::AlreadyExists(Event::Stop))
+        );
+        // transition key 1, timeout 1
+        assert_eq!(fsm.rename_event(&Event::Stop, Event::Advance), Ok(2));
+        fsm.trigger(Event::Next).unwrap();
+        fsm.trigger(Event::Advance).unwrap();
+        assert_eq!(fsm.state(), State::Red);
+    }
+
+
+    #[test]
+    fn test_rename_event_in_runtime_configuration()
+    {
+        let mut fsm = light();
+        let clock = Arc::new(MockClock::new());
+
+        fsm.set_clock(clock.clone());
+        fsm.set_alphabet([Event::Next]).unwrap();
+        fsm.enable_quarantine();
+        assert!(fsm.trigger(Event::Stop).is_err());
+        fsm.set_alphabet([Event::Next, Event::Stop]).unwrap();
+        fsm.queue_coalesce(Event::Stop, CoalescePolicy::KeepFirst);
+        fsm.set_deadline(clock.now() + Duration::from_secs(5), DeadlineAction::Post(Event::Stop));
+
+        // transition key 1, timeout 1, alphabet 2, quarantine 1, deadline 1,
+        // coalescing 1
+        assert_eq!(fsm.rename_event(&Event::Stop, Event::Advance), Ok(7));
+        assert_eq!(fsm.alphabet(), Some(vec![Event::Advance, Event::Next]));
+        assert_eq!(fsm.unknown_events().get("Advance"), Some(&1));
+
+        fsm.trigger(Event::Next).unwrap();
+        fsm.trigger_str("Advance").unwrap();
+        assert_eq!(fsm.state(), State::Red);
+
+        fsm.trigger(Event::Next).unwrap();
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(fsm.tick(), Ok(true));
+        assert_eq!(fsm.state(), State::Red);
+
+        let poster = fsm.poster();
+        poster.post(Event::Advance);
+        poster.post(Event::Advance);
+        assert_eq!(fsm.queue_depth(), 1);
+    }
+
+
+    #[test]
+    fn test_rename_in_schema()
+    {
+        let mut schema = Schema::from_json(r#"{
+            "initial": "Red",
+            "transitions": [
+                {"from": "Red", "event": "Next", "to": "Yellow"},
+                {"from": "Yellow", "event": "Next", "to": "Red"}
+            ]
+        }"#).unwrap();
+
+        assert_eq!(schema.rename_state("Red", "Stop"), Ok(3));
+        assert_eq!(schema.initial, "Stop");
+        assert_eq!(schema.rename_event("Next", "Go"), Ok(2));
+        assert_eq!(
+            schema.rename_state("Missing", "Other"),
+            Err(RenameError::NotFound("Missing".to_string()))
+        );
+    }
+
+
+    #[test]
+    fn test_rename_in_schema_scenarios()
+    {
+        let mut schema = Schema::from_json(r#"{
+            "initial": "Red",
+            "transitions": [
+                {"from": "Red", "event": "Next", "to": "Yellow"},
+                {"from": "Yellow", "event": "Next", "to": "Red"}
+            ],
+            "states": [{"name": "Red", "tags": ["stop"]}, {"name": "Lonely"}],
+            "scenarios": [
+                {"name": "cycle", "initial": "Red", "events": ["Next", "Next"], "final": "Red"},
+                {"name": "half", "initial": "Yellow", "events": ["Next"], "final": "Red"}
+            ]
+        }"#).unwrap();
+
+        // initial 1, state record 1, transitions 2, scenario initial 1,
+        // scenario expected 2
+        assert_eq!(schema.rename_state("Red", "Stop"), Ok(7));
+        assert_eq!(schema.states[0].name, "Stop");
+        assert_eq!(schema.rename_state("Lonely", "Alone"), Ok(1));
+        assert_eq!(schema.rename_event("Next", "Go"), Ok(5));
+        assert_eq!(schema.scenarios[0].events, ["Go", "Go"]);
+        assert_eq!(
+            (schema.scenarios[1].initial.as_str(), schema.scenarios[1].expected.as_str()),
+            ("Yellow", "Stop")
+        );
+    }
+}
@@ -0,0 +1,223 @@
+//! Comparing two versions of a machine against one recorded event stream.
+
+use std::{fmt::Debug, hash::Hash};
+
+use crate::{error::TriggerCode, fsm::StateMachine};
+
+
+/// A recorded sequence of events, e.g. from production.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Recording<E> {
+    pub events: Vec<E>
+}
+
+
+impl<E: Copy> Recording<E> {
+    pub fn new(events: Vec<E>) -> Self
+    {
+        Self{ events }
+    }
+
+
+    /// Records the events of a machine's trace, oldest first.
+    pub fn from_trace<S>(fsm: &StateMachine<S, E>) -> Self
+    where S: Copy + Hash + Eq + Debug, E: Hash + Eq + Debug
+    {
+        Self{ events: fsm.trace().map(|entry| entry.event).collect() }
+    }
+}
+
+
+/// What one machine made of one recorded event.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StepOutcome<S> {
+    Accepted { to: S },
+    Rejected(TriggerCode)
+}
+
+
+/// One recorded step on which the two machines disagreed. Fields may be
+/// added; build expected divergences with `new`:
+///
+/// ```compile_fail
+/// use pfsm::replay::{Divergence, StepOutcome};
+///
+/// let divergence = Divergence{
+///     index: 0,
+///     event: 'a',
+///     old: StepOutcome::Accepted{ to: 1 },
+///     new: StepOutcome::Accepted{ to: 2 }
+/// };
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Divergence<S, E> {
+    pub index: usize,
+    pub event: E,
+    pub old: StepOutcome<S>,
+    pub new: StepOutcome<S>
+}
+
+
+impl<S, E> Divergence<S, E> {
+    pub fn new(index: usize, event: E, old: StepOutcome<S>, new: StepOutcome<S>) -> Self
+    {
+        Self{ index, event, old, new }
+    }
+}
+
+
+/// First divergence of each kind, and how many steps showed each kind.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DivergenceReport<S, E> {
+    pub steps: usize,
+    /// Both accepted the event but reached different states.
+    pub first_state: Option<Divergence<S, E>>,
+    pub state_count: usize,
+    /// One accepted the event, the other rejected it.
+    pub first_acceptance: Option<Divergence<S, E>>,
+    pub acceptance_count: usize,
+    /// Both rejected the event, for different reasons.
+    pub first_rejection: Option<Divergence<S, E>>,
+    pub rejection_count: usize
+}
+
+
+impl<S, E> DivergenceReport<S, E> {
+    pub fn is_equivalent(&self) -> bool
+    {
+        self.state_count + self.acceptance_count + self.rejection_count == 0
+    }
+}
+
+
+/// Replays `recording` through both machines by pure simulation from
+/// their initial states and reports where their behaviour differs.
+///
+/// Each machine keeps following its own states after a divergence.
+pub fn differential_replay<S, E>(
+    old: &StateMachine<S, E>,
+    new: &StateMachine<S, E>,
+    recording: &Recording<E>
+) -> DivergenceReport<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    differential_replay_with(old, new, recording, |state| *state)
+}
+
+
+/// Like `differential_replay`, for machines whose states differ but
+/// correspond: `map` translates each state of `old` into `new`'s terms
+/// before comparison.
+pub fn differential_replay_with<S, T, E>(
+    old: &StateMachine<S, E>,
+    new: &StateMachine<T, E>,
+    recording: &Recording<E>,
+    map: impl Fn(&S) -> T
+) -> DivergenceReport<T, E>
+where
+    S: Copy + Hash + Eq + Debug,
+    T: Copy + Hash + Eq + Debug,
+    E: Copy + Hash + Eq + Debug
+{
+    let mut report = DivergenceReport{
+        steps: recording.events.len(),
+        first_state: None,
+        state_count: 0,
+        first_acceptance: None,
+        acceptance_count: 0,
+        first_rejection: None,
+        rejection_count: 0
+    };
+    let (mut old_state, mut new_state) = (old.initial, new.initial);
+
+    for (index, &event) in recording.events.iter().enumerate() {
+        let old_outcome = match old.simulate(old_state, &[event]) {
+            Ok(to) => {
+                old_state = to;
+                StepOutcome::Accepted{ to: map(&to) }
+            }
+            Err((_, err)) => StepOutcome::Rejected(TriggerCode::from(&err))
+        };
+        let new_outcome = match new.simulate(new_state, &[event]) {
+            Ok(to) => {
+                new_state = to;
+                StepOutcome::Accepted{ to }
+            }
+            Err((_, err)) => StepOutcome::Rejected(TriggerCode::from(&err))
+        };
+        if old_outcome == new_outcome {
+            continue;
+        }
+
+        let divergence = Divergence{ index, event, old: old_outcome, new: new_outcome };
+        let (first, count) = match (old_outcome, new_outcome) {
+            (StepOutcome::Accepted { .. }, StepOutcome::Accepted { .. }) => {
+                (&mut report.first_state, &mut report.state_count)
+            }
+            (StepOutcome::Rejected(_), StepOutcome::Rejected(_)) => {
+                (&mut report.first_rejection, &mut report.rejection_count)
+            }
+            _ => (&mut report.first_acceptance, &mut report.acceptance_count)
+        };
+        first.get_or_insert(divergence);
+        *count += 1;
+    }
+    report
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::builder::StateMachineBuilder;
+
+
+    fn light(yellow_to: char) -> StateMachine<char, u8>
+    {
+        StateMachineBuilder::new('r')
+            .transition('r', 1, 'y')
+            .transition('y', 2, yellow_to)
+            .transition('g', 3, 'r')
+            .transition('b', 3, 'r')
+            .build()
+            .unwrap()
+    }
+
+
+    #[test]
+    fn test_retargeted_edge_diverges_at_recorded_index()
+    {
+        let recording = Recording::new(vec![1, 2, 3, 1, 2, 1]);
+        let report = differential_replay(&light('g'), &light('b'), &recording);
+
+        assert_eq!(report.first_state, Some(Divergence{
+            index: 1,
+            event: 2,
+            old: StepOutcome::Accepted{ to: 'g' },
+            new: StepOutcome::Accepted{ to: 'b' }
+        }));
+        assert_eq!((report.state_count, report.acceptance_count), (2, 0));
+        assert_eq!(report.first_rejection, None);
+        assert!(!report.is_equivalent());
+
+        let mapped = differential_replay_with(
+            &light('g'),
+            &light('b'),
+            &recording,
+            |s| if *s == 'g' { 'b' } else { *s }
+        );
+        assert!(mapped.is_equivalent());
+
+        let mut disabled = light('g');
+        disabled.set_enabled('g', 3, false);
+        let report = differential_replay(&light('g'), &disabled, &recording);
+        assert_eq!(report.first_acceptance, Some(Divergence::new(
+            2,
+            3,
+            StepOutcome::Accepted{ to: 'r' },
+            StepOutcome::Rejected(TriggerCode::Disabled)
+        )));
+    }
+}
@@ -0,0 +1,161 @@
+use std::{fmt::{self, Debug, Display}, hash::Hash};
+
+use crate::{error::TransitionError, fsm::StateMachine};
+
+
+/// A documented flow: from `initial`, `events` should lead to `expected`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Scenario<S, E> {
+    pub name: String,
+    pub initial: S,
+    pub events: Vec<E>,
+    pub expected: S
+}
+
+
+/// Where and why a scenario diverged from the machine.
+///
+/// `step` is the index of the rejected event, or the number of events if
+/// they all went through but ended in the wrong state. Fields may be
+/// added; build expected failures with `new`:
+///
+/// ```compile_fail
+/// use pfsm::scenario::ScenarioFailure;
+///
+/// let failure = ScenarioFailure{ scenario: "happy".into(), step: 0, reason: String::new() };
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ScenarioFailure {
+    pub scenario: String,
+    pub step: usize,
+    pub reason: String
+}
+
+
+impl ScenarioFailure {
+    pub fn new(scenario: impl Into<String>, step: usize, reason: impl Into<String>) -> Self
+    {
+        Self{ scenario: scenario.into(), step, reason: reason.into() }
+    }
+}
+
+
+impl Display for ScenarioFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "Scenario '{}' diverged at step {}: {}", self.scenario, self.step, self.reason)
+    }
+}
+
+
+impl std::error::Error for ScenarioFailure {}
+
+
+impl ScenarioFailure {
+    /// Returns the numeric code listed in [`ERROR_CODES`](crate::error::ERROR_CODES).
+    pub fn code(&self) -> u16
+    {
+        702
+    }
+}
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    /// Follows `events` from `from` through the transition table and
+    /// returns the state reached, without touching the machine.
+    ///
+    /// The walk is structural: disabled transitions, terminal states and
+    /// the alphabet are honoured, while guards, cooldowns and actions are
+    /// not run. On rejection returns the index of the event and why.
+    pub fn simulate(&self, from: S, events: &[E]) -> Result<S, (usize, TransitionError<S, E>)>
+    {
+        let mut state = from;
+
+        for (step, &event) in events.iter().enumerate() {
+            let error = if self.alphabet.as_ref().is_some_and(|a| !a.events.contains(&event)) {
+                TransitionError::UnknownEvent{ state, name: format!("{event:?}") }
+            } else if self.terminals.contains(&state) {
+                TransitionError::MachineFinished{ state, event }
+            } else {
+                match self.transitions.get(&(state, event)) {
+                    Some(t) if t.enabled => {
+                        state = t.next_state;
+                        continue;
+                    }
+                    Some(_) => TransitionError::Disabled{ state, event },
+                    None => TransitionError::NoTransition{ state, event }
+                }
+            };
+            return Err((step, error));
+        }
+        Ok(state)
+    }
+
+
+    /// Simulates every scenario and returns the ones that diverged.
+    pub fn check_scenarios(&self, scenarios: &[Scenario<S, E>]) -> Vec<ScenarioFailure>
+    {
+        scenarios
+            .iter()
+            .filter_map(|scenario| {
+                let (step, reason) = match self.simulate(scenario.initial, &scenario.events) {
+                    Ok(state) if state == scenario.expected => return None,
+                    Ok(state) => (
+                        scenario.events.len(),
+                        format!("expected to end in '{:?}' but ended in '{state:?}'", scenario.expected)
+                    ),
+                    Err((step, error)) => (step, error.to_string())
+                };
+                Some(ScenarioFailure{ scenario: scenario.name.clone(), step, reason })
+            })
+            .collect()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{builder::StateMachineBuilder, fsm::FSM};
+
+
+    #[test]
+    fn test_check_scenarios()
+    {
+        let fsm = StateMachineBuilder::new('r')
+            .transition('r', 1, 'y')
+            .transition('y', 2, 'g')
+            .transition('g', 3, 'r')
+            .build()
+            .unwrap();
+        let scenario = |name: &str, events: Vec<u8>, expected| Scenario{
+            name: name.to_string(),
+            initial: 'r',
+            events,
+            expected
+        };
+
+        let failures = fsm.check_scenarios(&[
+            scenario("cycle", vec![1, 2, 3], 'r'),
+            scenario("skips yellow", vec![1, 3], 'r'),
+            scenario("stops early", vec![1], 'g')
+        ]);
+
+        assert_eq!(failures, [
+            ScenarioFailure{
+                scenario: "skips yellow".to_string(),
+                step: 1,
+                reason: "No transition found for event '3' from state ''y''".to_string()
+            },
+            ScenarioFailure{
+                scenario: "stops early".to_string(),
+                step: 1,
+                reason: "expected to end in ''g'' but ended in ''y''".to_string()
+            }
+        ]);
+        assert_eq!(fsm.state(), 'r');
+    }
+}
@@ -0,0 +1,776 @@
+//! Data-driven machine definitions loaded from JSON.
+//!
+//! A schema file looks like:
+//!
+//! ```json
+//! {
+//!     "initial": "Red",
+//!     "states": [
+//!         {"name": "Red", "tags": ["stop"], "on_enter": "log_stop"},
+//!         {"name": "Off", "terminal": true}
+//!     ],
+//!     "transitions": [
+//!         {"from": "Red", "event": "RedTimeout", "to": "Yellow",
+//!          "action": "notify_ops", "guard": "is_business_hours",
+//!          "cooldown_ms": 500, "enabled": false}
+//!     ],
+//!     "scenarios": [
+//!         {"name": "warm up", "initial": "Red", "events": ["RedTimeout"],
+//!          "final": "Yellow"}
+//!     ]
+//! }
+//! ```
+//!
+//! State and event names are mapped to values through a [`Resolver`];
+//! `action`, `guard`, `on_enter` and `on_exit` names are looked up in the
+//! registries. `states` and `scenarios` are optional; `build_strict` also
+//! checks every scenario against the built machine. Members this version does not know
+//! about are kept in `extra` and written back out unchanged.
+
+use std::{
+    collections::HashMap,
+    fmt::{self, Debug, Display},
+    hash::Hash,
+    time::Duration
+};
+
+use crate::{
+    fsm::{StateMachine, Transition, FSM},
+    json::{self, JsonError, Value},
+    naming::{NameCollision, Names},
+    registry::{ActionRegistry, GuardRegistry, UnknownName},
+    scenario::{Scenario, ScenarioFailure}
+};
+
+
+/// One transition record of a schema file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TransitionSpec {
+    pub from: String,
+    pub event: String,
+    pub to: String,
+    pub action: Option<String>,
+    pub guard: Option<String>,
+    pub cooldown: Option<Duration>,
+    pub disabled: bool,
+    /// Unrecognised members, in file order.
+    pub extra: Vec<(String, Value)>
+}
+
+
+/// Per-state metadata of a schema file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StateSpec {
+    pub name: String,
+    pub tags: Vec<String>,
+    pub on_enter: Option<String>,
+    pub on_exit: Option<String>,
+    pub terminal: bool,
+    /// Unrecognised members, in file order.
+    pub extra: Vec<(String, Value)>
+}
+
+
+/// An acceptance scenario of a schema file, by name.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ScenarioSpec {
+    pub name: String,
+    pub initial: String,
+    pub events: Vec<String>,
+    pub expected: String
+}
+
+
+/// Parsed, not yet resolved, schema file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Schema {
+    pub initial: String,
+    pub states: Vec<StateSpec>,
+    pub transitions: Vec<TransitionSpec>,
+    pub scenarios: Vec<ScenarioSpec>,
+    /// Unrecognised top-level members, in file order.
+    pub extra: Vec<(String, Value)>
+}
+
+
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum SchemaError {
+    Json(JsonError),
+    Invalid(String),
+    UnknownState(String),
+    UnknownEvent(String),
+    UnknownName(UnknownName),
+    DuplicateTransition { from: String, event: String },
+    /// Scenarios that `build_strict` found broken.
+    Scenarios(Vec<ScenarioFailure>)
+}
+
+
+impl Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        match self {
+            SchemaError::Json(err) => write!(f, "Invalid JSON: {err}"),
+            SchemaError::Invalid(msg) => write!(f, "Invalid schema: {msg}"),
+            SchemaError::UnknownState(name) => write!(f, "Unknown state '{name}'"),
+            SchemaError::UnknownEvent(name) => write!(f, "Unknown event '{name}'"),
+            SchemaError::UnknownName(err) => write!(f, "{err}"),
+            SchemaError::DuplicateTransition { from, event } => write!(
+                f, "Duplicate transition for event '{event}' from state '{from}'"
+            ),
+            SchemaError::Scenarios(failures) => {
+                write!(f, "{} scenario(s) failed", failures.len())?;
+                for failure in failures {
+                    write!(f, "\n  {failure}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+
+impl std::error::Error for SchemaError {}
+
+
+impl SchemaError {
+    /// Returns the numeric code listed in [`ERROR_CODES`](crate::error::ERROR_CODES).
+    pub fn code(&self) -> u16
+    {
+        match self {
+            SchemaError::Json(_) => 401,
+            SchemaError::Invalid(_) => 402,
+            SchemaError::UnknownState(_) => 403,
+            SchemaError::UnknownEvent(_) => 404,
+            SchemaError::UnknownName(_) => 405,
+            SchemaError::DuplicateTransition { .. } => 406,
+            SchemaError::Scenarios(_) => 407
+        }
+    }
+}
+
+
+impl From<JsonError> for SchemaError {
+    fn from(err: JsonError) -> Self
+    {
+        SchemaError::Json(err)
+    }
+}
+
+
+impl From<UnknownName> for SchemaError {
+    fn from(err: UnknownName) -> Self
+    {
+        SchemaError::UnknownName(err)
+    }
+}
+
+
+pub type NameLookup<T> = Box<dyn Fn(&str) -> Option<T>>;
+
+
+/// Maps the names used in schema files to state and event values.
+pub struct Resolver<S, E> {
+    state: NameLookup<S>,
+    event: NameLookup<E>
+}
+
+
+impl<S: 'static, E: 'static> Resolver<S, E> {
+    pub fn new(
+        state: impl Fn(&str) -> Option<S> + 'static,
+        event: impl Fn(&str) -> Option<E> + 'static
+    ) -> Self
+    {
+        Self{ state: Box::new(state), event: Box::new(event) }
+    }
+
+
+    /// Resolves names by matching the `Debug` rendering of the given values.
+    pub fn from_variants(states: &[S], events: &[E]) -> Result<Self, NameCollision<S, E>>
+    where S: Copy + Eq + Debug, E: Copy + Eq + Debug
+    {
+        Self::from_variants_with(states, events, &Names::default())
+    }
+
+
+    /// Resolves names by matching the names `names` gives the values.
+    pub fn from_variants_with(
+        states: &[S],
+        events: &[E],
+        names: &Names<S, E>
+    ) -> Result<Self, NameCollision<S, E>>
+    where S: Copy + Eq, E: Copy + Eq
+    {
+        let states = names.state_map(states)?;
+        let events = names.event_map(events)?;
+
+        Ok(Self::new(
+            move |name| states.get(name).copied(),
+            move |name| events.get(name).copied()
+        ))
+    }
+}
+
+
+impl<S, E> Resolver<S, E> {
+    pub fn state(&self, name: &str) -> Result<S, SchemaError>
+    {
+        (self.state)(name).ok_or_else(|| SchemaError::UnknownState(name.to_string()))
+    }
+
+
+    pub fn event(&self, name: &str) -> Result<E, SchemaError>
+    {
+        (self.event)(name).ok_or_else(|| SchemaError::UnknownEvent(name.to_string()))
+    }
+}
+
+
+impl Schema {
+    pub fn from_json(text: &str) -> Result<Self, SchemaError>
+    {
+        Self::from_value(&json::parse(text)?)
+    }
+
+
+    pub fn from_value(value: &Value) -> Result<Self, SchemaError>
+    {
+        let initial = required_str(value, "initial")?.to_string();
+        let states = match value.get("states") {
+            None => Vec::new(),
+            Some(Value::Array(items)) => items
+                .iter()
+                .map(StateSpec::from_value)
+                .collect::<Result<_, _>>()?,
+            Some(_) => return Err(SchemaError::Invalid("'states' must be an array".to_string()))
+        };
+        let transitions = value
+            .get("transitions")
+            .and_then(Value::as_array)
+            .ok_or_else(|| SchemaError::Invalid("missing 'transitions' array".to_string()))?
+            .iter()
+            .map(TransitionSpec::from_value)
+            .collect::<Result<_, _>>()?;
+        let scenarios = match value.get("scenarios") {
+            None => Vec::new(),
+            Some(Value::Array(items)) => items
+                .iter()
+                .map(ScenarioSpec::from_value)
+                .collect::<Result<_, _>>()?,
+            Some(_) => return Err(SchemaError::Invalid("'scenarios' must be an array".to_string()))
+        };
+
+        Ok(Self{
+            initial,
+            states,
+            transitions,
+            scenarios,
+            extra: extra_members(value, &["initial", "states", "transitions", "scenarios"])
+        })
+    }
+
+
+    pub fn to_value(&self) -> Value
+    {
+        let mut members = vec![("initial".to_string(), Value::from(self.initial.as_str()))];
+
+        if !self.states.is_empty() {
+            members.push((
+                "states".to_string(),
+                Value::Array(self.states.iter().map(StateSpec::to_value).collect())
+            ));
+        }
+        members.push((
+            "transitions".to_string(),
+            Value::Array(self.transitions.iter().map(TransitionSpec::to_value).collect())
+        ));
+        if !self.scenarios.is_empty() {
+            members.push((
+                "scenarios".to_string(),
+                Value::Array(self.scenarios.iter().map(ScenarioSpec::to_value).collect())
+            ));
+        }
+        members.extend(self.extra.iter().cloned());
+        Value::Object(members)
+    }
+
+
+    pub fn to_json(&self) -> String
+    {
+        self.to_value().to_string()
+    }
+
+
+    /// Resolves every name and builds the machine.
+    ///
+    /// Any unknown state, event, action or guard name is an error.
+    pub fn build<S, E>(
+        &self,
+        resolver: &Resolver<S, E>,
+        actions: &ActionRegistry,
+        guards: &GuardRegistry
+    ) -> Result<StateMachine<S, E>, SchemaError>
+    where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+    {
+        let initial = resolver.state(&self.initial)?;
+        let mut transitions = HashMap::new();
+
+        for spec in &self.transitions {
+            let key = (resolver.state(&spec.from)?, resolver.event(&spec.event)?);
+            let transition = spec.resolve(resolver, actions, guards)?;
+
+            if transitions.insert(key, transition).is_some() {
+                return Err(SchemaError::DuplicateTransition{
+                    from: spec.from.clone(),
+                    event: spec.event.clone()
+                });
+            }
+        }
+
+        let mut fsm = StateMachine::initialize(initial, transitions);
+
+        for spec in &self.states {
+            let state = resolver.state(&spec.name)?;
+
+            if !spec.tags.is_empty() {
+                fsm.tags.insert(state, spec.tags.clone());
+            }
+            if let Some(name) = &spec.on_enter {
+                fsm.entry_actions.insert(state, actions.resolve(name)?);
+            }
+            if let Some(name) = &spec.on_exit {
+                fsm.exit_actions.insert(state, actions.resolve(name)?);
+            }
+            if spec.terminal {
+                fsm.terminals.insert(state);
+            }
+        }
+        Ok(fsm)
+    }
+
+
+    /// Like `build`, then runs every scenario against the machine and
+    /// fails with all divergences if any scenario is broken.
+    pub fn build_strict<S, E>(
+        &self,
+        resolver: &Resolver<S, E>,
+        actions: &ActionRegistry,
+        guards: &GuardRegistry
+    ) -> Result<StateMachine<S, E>, SchemaError>
+    where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+    {
+        let fsm = self.build(resolver, actions, guards)?;
+        let scenarios = self.scenarios
+            .iter()
+            .map(|spec| spec.resolve(resolver))
+            .collect::<Result<Vec<_>, _>>()?;
+        let failures = fsm.check_scenarios(&scenarios);
+
+        if failures.is_empty() {
+            Ok(fsm)
+        } else {
+            Err(SchemaError::Scenarios(failures))
+        }
+    }
+}
+
+
+impl ScenarioSpec {
+    pub fn from_value(value: &Value) -> Result<Self, SchemaError>
+    {
+        let events = value
+            .get("events")
+            .and_then(Value::as_array)
+            .and_then(|items| {
+                items.iter().map(|e| e.as_str().map(str::to_string)).collect::<Option<_>>()
+            })
+            .ok_or_else(|| SchemaError::Invalid("scenario needs an 'events' string array".to_string()))?;
+
+        Ok(Self{
+            name: required_str(value, "name")?.to_string(),
+            initial: required_str(value, "initial")?.to_string(),
+            events,
+            expected: required_str(value, "final")?.to_string()
+        })
+    }
+
+
+    pub fn to_value(&self) -> Value
+    {
+        Value::Object(vec![
+            ("name".to_string(), Value::from(self.name.as_str())),
+            ("initial".to_string(), Value::from(self.initial.as_str())),
+            (
+                "events".to_string(),
+                Value::Array(self.events.iter().map(|e| Value::from(e.as_str())).collect())
+            ),
+            ("final".to_string(), Value::from(self.expected.as_str()))
+        ])
+    }
+
+
+    pub fn resolve<S, E>(&self, resolver: &Resolver<S, E>) -> Result<Scenario<S, E>, SchemaError>
+    {
+        Ok(Scenario{
+            name: self.name.clone(),
+            initial: resolver.state(&self.initial)?,
+            events: self.events.iter().map(|e| resolver.event(e)).collect::<Result<_, _>>()?,
+            expected: resolver.state(&self.expected)?
+        })
+    }
+}
+
+
+impl StateSpec {
+    pub fn from_value(value: &Value) -> Result<Self, SchemaError>
+    {
+        let tags = match value.get("tags") {
+            None => Vec::new(),
+            Some(Value::Array(items)) => items
+                .iter()
+                .map(|tag| tag.as_str().map(str::to_string))
+                .collect::<Option<_>>()
+                .ok_or_else(|| SchemaError::Invalid("tags must be strings".to_string()))?,
+            Some(_) => return Err(SchemaError::Invalid("'tags' must be an array".to_string()))
+        };
+
+        Ok(Self{
+            name: required_str(value, "name")?.to_string(),
+            tags,
+            on_enter: optional_str(value, "on_enter")?,
+            on_exit: optional_str(value, "on_exit")?,
+            terminal: match value.get("terminal") {
+                None => false,
+                Some(Value::Bool(terminal)) => *terminal,
+                Some(_) => return Err(SchemaError::Invalid(
+                    "field 'terminal' must be a boolean".to_string()
+                ))
+            },
+            extra: extra_members(value, &["name", "tags", "on_enter", "on_exit", "terminal"])
+        })
+    }
+
+
+    pub fn to_value(&self) -> Value
+    {
+        let mut members = vec![("name".to_string(), Value::from(self.name.as_str()))];
+
+        if !self.tags.is_empty() {
+            members.push((
+                "tags".to_string(),
+                Value::Array(self.tags.iter().map(|t| Value::from(t.as_str())).collect())
+            ));
+        }
+        if let Some(action) = &self.on_enter {
+            members.push(("on_enter".to_string(), Value::from(action.as_str())));
+        }
+        if let Some(action) = &self.on_exit {
+            members.push(("on_exit".to_string(), Value::from(action.as_str())));
+        }
+        if self.terminal {
+            members.push(("terminal".to_string(), Value::Bool(true)));
+        }
+        members.extend(self.extra.iter().cloned());
+        Value::Object(members)
+    }
+}
+
+
+impl TransitionSpec {
+    pub fn from_value(value: &Value) -> Result<Self, SchemaError>
+    {
+        Ok(Self{
+            from: required_str(value, "from")?.to_string(),
+            event: required_str(value, "event")?.to_string(),
+            to: required_str(value, "to")?.to_string(),
+            action: optional_str(value, "action")?,
+            guard: optional_str(value, "guard")?,
+            cooldown: match value.get("cooldown_ms") {
+                None | Some(Value::Null) => None,
+                Some(Value::Number(ms)) if *ms >= 0.0 && ms.fract() == 0.0 =>
+                    Some(Duration::from_millis(*ms as u64)),
+                Some(_) => return Err(SchemaError::Invalid(
+                    "field 'cooldown_ms' must be a non-negative integer".to_string()
+                ))
+            },
+            disabled: match value.get("enabled") {
+                None => false,
+                Some(Value::Bool(enabled)) => !enabled,
+                Some(_) => return Err(SchemaError::Invalid(
+                    "field 'enabled' must be a boolean".to_string()
+                ))
+            },
+            extra: extra_members(
+                value,
+                &["from", "event", "to", "action", "guard", "cooldown_ms", "enabled"]
+            )
+        })
+    }
+
+
+    pub fn to_value(&self) -> Value
+    {
+        let mut members = vec![
+            ("from".to_string(), Value::from(self.from.as_str())),
+            ("event".to_string(), Value::from(self.event.as_str())),
+            ("to".to_string(), Value::from(self.to.as_str()))
+        ];
+
+        if let Some(action) = &self.action {
+            members.push(("action".to_string(), Value::from(action.as_str())));
+        }
+        if let Some(guard) = &self.guard {
+            members.push(("guard".to_string(), Value::from(guard.as_str())));
+        }
+        if let Some(cooldown) = self.cooldown {
+            members.push(("cooldown_ms".to_string(), Value::Number(cooldown.as_millis() as f64)));
+        }
+        if self.disabled {
+            members.push(("enabled".to_string(), Value::Bool(false)));
+        }
+        members.extend(self.extra.iter().cloned());
+        Value::Object(members)
+    }
+
+
+    pub(crate) fn resolve<S, E>(
+        &self,
+        resolver: &Resolver<S, E>,
+        actions: &ActionRegistry,
+        guards: &GuardRegistry
+    ) -> Result<Transition<S>, SchemaError>
+    where S: Copy
+    {
+        let action = self.action.as_deref().map(|name| actions.resolve(name)).transpose()?;
+        let mut transition = Transition::create(resolver.state(&self.to)?, action);
+
+        if let Some(name) = &self.guard {
+            transition = transition.with_guard(guards.resolve(name)?);
+        }
+        if let Some(cooldown) = self.cooldown {
+            transition = transition.with_cooldown(cooldown);
+        }
+        transition.enabled = !self.disabled;
+        Ok(transition)
+    }
+}
+
+
+fn required_str<'a>(value: &'a Value, key: &str) -> Result<&'a str, SchemaError>
+{
+    value
+        .get(key)
+        .and_then(Value::as_str)
+        .ok_or_else(|| SchemaError::Invalid(format!("missing string field '{key}'")))
+}
+
+
+fn optional_str(value: &Value, key: &str) -> Result<Option<String>, SchemaError>
+{
+    match value.get(key) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(s)) => Ok(Some(s.clone())),
+        Some(_) => Err(SchemaError::Invalid(format!("field '{key}' must be a string")))
+    }
+}
+
+
+/// Members of `value` whose keys are not in `known`.
+fn extra_members(value: &Value, known: &[&str]) -> Vec<(String, Value)>
+{
+    value
+        .as_object()
+        .unwrap_or_default()
+        .iter()
+        .filter(|(key, _)| !known.contains(&key.as_str()))
+        .cloned()
+        .collect()
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{cell::Cell, rc::Rc};
+
+
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    enum State {
+        Red,
+        Yellow,
+        Green
+    }
+
+
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    enum Event {
+        Next,
+        Back
+    }
+
+
+    fn resolver() -> Resolver<State, Event>
+    {
+        Resolver::from_variants(
+            &[State::Red, State::Yellow, State::Green],
+            &[Event::Next, Event::Back]
+        ).unwrap()
+    }
+
+
+    const SCHEMA: &str = r#"{
+        "initial": "Red",
+        "transitions": [
+            {"from": "Red", "event": "Next", "to": "Yellow", "action": "notify_ops"},
+            {"from": "Yellow", "event": "Next", "to": "Green", "guard": "is_business_hours"},
+            {"from": "Green", "event": "Back", "to": "Red", "action": "page_oncall"}
+        ]
+    }"#;
+
+
+    #[test]
+    fn test_load_with_registries()
+    {
+        let notified = Rc::new(Cell::new(0));
+        let open = Rc::new(Cell::new(false));
+        let mut actions = ActionRegistry::new();
+        let mut guards = GuardRegistry::new();
+
+        let n = notified.clone();
+        actions.register("notify_ops", move || n.set(n.get() + 1));
+        actions.register("page_oncall", || {});
+        let o = open.clone();
+        guards.register("is_business_hours", move || o.get());
+
+        let schema = Schema::from_json(SCHEMA).unwrap();
+        let mut fsm = schema.build(&resolver(), &actions, &guards).unwrap();
+
+        assert!(fsm.trigger(Event::Next).is_ok());
+        assert_eq!(notified.get(), 1);
+        assert!(fsm.trigger(Event::Next).is_err());
+        open.set(true);
+        assert!(fsm.trigger(Event::Next).is_ok());
+        assert_eq!(fsm.state(), State::Green);
+    }
+
+
+    #[test]
+    fn test_unknown_name_lists_available()
+    {
+        let mut actions = ActionRegistry::new();
+        let mut guards = GuardRegistry::new();
+
+        actions.register("notify_ops", || {});
+        guards.register("is_business_hours", || true);
+
+        let schema = Schema::from_json(SCHEMA).unwrap();
+        let err = schema.build(&resolver(), &actions, &guards).err().unwrap();
+
+        assert_eq!(err, SchemaError::UnknownName(UnknownName{
+            kind: "action",
+            name: "page_oncall".to_string(),
+            available: vec!["notify_ops".to_string()]
+        }));
+        assert_eq!(
+            err.to_string(),
+            "Unknown action 'page_oncall' (available: notify_ops)"
+        );
+    }
+
+
+    #[test]
+    fn test_json_round_trip()
+    {
+        let schema = Schema::from_json(SCHEMA).unwrap();
+
+        assert_eq!(Schema::from_json(&schema.to_json()).unwrap(), schema);
+    }
+
+
+    #[test]
+    fn test_metadata_round_trip()
+    {
+        const DECORATED: &str = r##"{
+            "initial": "Red",
+            "states": [
+                {"name": "Red", "tags": ["stop", "safe"], "on_enter": "log", "colour": "#f00"},
+                {"name": "Green", "on_exit": "log", "terminal": true}
+            ],
+            "transitions": [
+                {"from": "Red", "event": "Next", "to": "Yellow", "action": "log",
+                 "cooldown_ms": 1500, "priority": 3},
+                {"from": "Yellow", "event": "Next", "to": "Green", "guard": "always"},
+                {"from": "Yellow", "event": "Back", "to": "Red", "enabled": false},
+                {"from": "Green", "event": "Back", "to": "Red"}
+            ],
+            "version": 2,
+            "docs": {"owner": "ops"}
+        }"##;
+        let mut actions = ActionRegistry::new();
+        let mut guards = GuardRegistry::new();
+
+        actions.register("log", || {});
+        guards.register("always", || true);
+
+        let schema = Schema::from_json(DECORATED).unwrap();
+        let exported = Schema::from_json(&schema.to_json()).unwrap();
+
+        assert_eq!(exported, schema);
+        assert_eq!(
+            exported.extra,
+            json::parse(r#"{"version": 2, "docs": {"owner": "ops"}}"#).unwrap().as_object().unwrap()
+        );
+        assert_eq!(exported.states[0].extra, [("colour".to_string(), Value::from("#f00"))]);
+        assert_eq!(exported.transitions[0].extra, [("priority".to_string(), Value::Number(3.0))]);
+
+        let original = schema.build(&resolver(), &actions, &guards).unwrap().describe();
+        let reimported = exported.build(&resolver(), &actions, &guards).unwrap().describe();
+
+        assert_eq!(reimported, original);
+        assert_eq!(original.tags, [(State::Red, vec!["stop".to_string(), "safe".to_string()])]);
+        let red_next = &original.transitions[1];
+        assert_eq!((red_next.from, red_next.cooldown), (State::Red, Some(Duration::from_millis(1500))));
+        assert!(red_next.has_action);
+        assert!(!original.transitions[2].enabled);
+        assert_eq!(original.terminals, [State::Green]);
+    }
+
+
+    #[test]
+    fn test_build_strict_checks_scenarios()
+    {
+        let mut schema = Schema::from_json(r#"{
+            "initial": "Red",
+            "transitions": [
+                {"from": "Red", "event": "Next", "to": "Yellow"},
+                {"from": "Yellow", "event": "Next", "to": "Green"},
+                {"from": "Green", "event": "Back", "to": "Red"}
+            ],
+            "scenarios": [
+                {"name": "full cycle", "initial": "Red",
+                 "events": ["Next", "Next", "Back"], "final": "Red"}
+            ]
+        }"#).unwrap();
+        let (actions, guards) = (ActionRegistry::new(), GuardRegistry::new());
+
+        assert_eq!(Schema::from_json(&schema.to_json()).unwrap(), schema);
+        assert!(schema.build_strict(&resolver(), &actions, &guards).is_ok());
+
+        schema.scenarios.push(ScenarioSpec{
+            name: "back from yellow".to_string(),
+            initial: "Red".to_string(),
+            events: vec!["Next".to_string(), "Back".to_string()],
+            expected: "Red".to_string()
+        });
+        let err = schema.build_strict(&resolver(), &actions, &guards).err().unwrap();
+
+        assert_eq!(
+            err.to_string(),
+            "1 scenario(s) failed\n  Scenario 'back from yellow' diverged at step 1: \
+             No transition found for event 'Back' from state 'Yellow'"
+        );
+        assert!(schema.build(&resolver(), &actions, &guards).is_ok());
+    }
+}
@@ -0,0 +1,321 @@
+//! Reproducible fault injection for resilience testing.
+
+use std::{collections::HashMap, fmt::Debug, hash::Hash};
+
+use crate::{
+    error::TransitionError,
+    fsm::{StateMachine, Transition, FSM},
+    origin::Origin
+};
+
+
+/// Small seeded pseudo-random generator (SplitMix64).
+///
+/// Identical seeds always produce identical sequences.
+#[derive(Clone, Debug)]
+pub struct SimRng {
+    state: u64
+}
+
+
+impl SimRng {
+    pub fn new(seed: u64) -> Self
+    {
+        Self{ state: seed }
+    }
+
+
+    pub fn next_u64(&mut self) -> u64
+    {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+
+    /// Returns a value uniformly distributed in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64
+    {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+
+    /// Returns `true` with probability `p`.
+    pub fn chance(&mut self, p: f64) -> bool
+    {
+        p > 0.0 && self.next_f64() < p
+    }
+
+
+    /// Returns a value uniformly distributed in `[0, n)`; `n` must be non-zero.
+    pub fn below(&mut self, n: usize) -> usize
+    {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+
+/// Fault probabilities applied by [`ChaosStateMachine`].
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct ChaosConfig<E> {
+    /// Probability of rejecting an event even though a transition exists.
+    pub reject_event: f64,
+    /// Probability of taking a transition without running its action.
+    pub drop_action: f64,
+    /// Probability of following an accepted event with a spurious one.
+    pub spurious_event: f64,
+    /// Events injected as spurious internal events.
+    pub spurious_events: Vec<E>
+}
+
+
+impl<E> ChaosConfig<E> {
+    pub fn with_reject_event(mut self, probability: f64) -> Self
+    {
+        self.reject_event = probability;
+        self
+    }
+
+
+    pub fn with_drop_action(mut self, probability: f64) -> Self
+    {
+        self.drop_action = probability;
+        self
+    }
+
+
+    /// Sets the probability of a spurious event and the events drawn from.
+    pub fn with_spurious_events(mut self, probability: f64, events: Vec<E>) -> Self
+    {
+        self.spurious_event = probability;
+        self.spurious_events = events;
+        self
+    }
+}
+
+
+impl<E> Default for ChaosConfig<E> {
+    fn default() -> Self
+    {
+        Self{
+            reject_event: 0.0,
+            drop_action: 0.0,
+            spurious_event: 0.0,
+            spurious_events: Vec::new()
+        }
+    }
+}
+
+
+/// One injected fault, in the order it happened.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Fault<S, E> {
+    RejectedEvent { state: S, event: E },
+    DroppedAction { from: S, event: E },
+    SpuriousEvent { state: S, event: E, accepted: bool }
+}
+
+
+/// Wrapper injecting reproducible faults around a [`StateMachine`].
+pub struct ChaosStateMachine<S: Copy, E: Copy> {
+    inner: StateMachine<S, E>,
+    config: ChaosConfig<E>,
+    rng: SimRng,
+    faults: Vec<Fault<S, E>>
+}
+
+
+impl<S, E> ChaosStateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    pub fn new(inner: StateMachine<S, E>, config: ChaosConfig<E>, seed: u64) -> Self
+    {
+        Self{ inner, config, rng: SimRng::new(seed), faults: Vec::new() }
+    }
+
+
+    /// Returns every fault injected so far.
+    pub fn faults(&self) -> &[Fault<S, E>]
+    {
+        &self.faults
+    }
+
+
+    pub fn inner(&self) -> &StateMachine<S, E>
+    {
+        &self.inner
+    }
+
+
+    pub fn into_inner(self) -> StateMachine<S, E>
+    {
+        self.inner
+    }
+}
+
+
+impl<S, E> FSM<S, E> for ChaosStateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    /// Wraps a fresh machine with a fault-free configuration.
+    fn initialize(
+        initial: S,
+        transitions: HashMap<(S, E), Transition<S>>
+    ) -> Self
+    {
+        Self::new(StateMachine::initialize(initial, transitions), ChaosConfig::default(), 0)
+    }
+
+
+    /// Injected rejections look exactly like a missing transition. They
+    /// are only rolled for events the inner machine would take.
+    fn trigger(&mut self, event: E) -> Result<(), TransitionError<S, E>>
+    {
+        let state = self.inner.state();
+
+        if self.inner.would_fire(event) && self.rng.chance(self.config.reject_event) {
+            self.faults.push(Fault::RejectedEvent{ state, event });
+            return Err(TransitionError::NoTransition{ state, event });
+        }
+
+        if self.rng.chance(self.config.drop_action) {
+            self.inner.fire(event, Origin::unspecified(), false)?;
+            self.faults.push(Fault::DroppedAction{ from: state, event });
+        } else {
+            self.inner.fire(event, Origin::unspecified(), true)?;
+        }
+
+        if !self.config.spurious_events.is_empty()
+            && self.rng.chance(self.config.spurious_event)
+        {
+            let index = self.rng.below(self.config.spurious_events.len());
+            let spurious = self.config.spurious_events[index];
+            let state = self.inner.state();
+            let accepted = self.inner.trigger(spurious).is_ok();
+
+            self.faults.push(Fault::SpuriousEvent{ state, event: spurious, accepted });
+        }
+        Ok(())
+    }
+
+
+    fn state(&self) -> S
+    {
+        self.inner.state()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::{self, turnstile::{self, Event, State}, Log};
+
+
+    #[test]
+    fn test_with_methods_cover_every_knob()
+    {
+        let expected = ChaosConfig{
+            reject_event: 0.1,
+            drop_action: 0.2,
+            spurious_event: 0.3,
+            spurious_events: vec!['x']
+        };
+        let built = ChaosConfig::default()
+            .with_reject_event(0.1)
+            .with_drop_action(0.2)
+            .with_spurious_events(0.3, vec!['x']);
+
+        assert_eq!(built, expected);
+    }
+
+
+    fn chaos(seed: u64, log: &Log) -> ChaosStateMachine<State, Event>
+    {
+        let config = ChaosConfig{
+            reject_event: 0.2,
+            drop_action: 0.2,
+            spurious_event: 0.2,
+            spurious_events: vec![Event::Coin, Event::Push]
+        };
+        ChaosStateMachine::new(turnstile::logged(log), config, seed)
+    }
+
+
+    #[test]
+    fn test_rng_is_reproducible()
+    {
+        let mut a = SimRng::new(42);
+        let mut b = SimRng::new(42);
+
+        for _ in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+        assert!((0..100).all(|_| a.next_f64() < 1.0));
+    }
+
+
+    #[test]
+    fn test_fixed_seed_fault_sequence()
+    {
+        let log = Log::default();
+        let mut fsm = chaos(7, &log);
+        let mut accepted = 0;
+
+        for i in 0..12 {
+            let event = if i % 2 == 0 { Event::Coin } else { Event::Push };
+            let before = fsm.state();
+
+            if fsm.trigger(event).is_ok() {
+                accepted += 1;
+            } else {
+                // A refused event never moves the inner machine.
+                assert_eq!(fsm.state(), before);
+            }
+        }
+
+        assert_eq!(fsm.faults(), EXPECTED_FAULTS);
+
+        let dropped = fsm.faults()
+            .iter()
+            .filter(|f| matches!(f, Fault::DroppedAction { .. }))
+            .count() as u32;
+        let spurious = fsm.faults()
+            .iter()
+            .filter(|f| matches!(f, Fault::SpuriousEvent { accepted: true, .. }))
+            .count() as u32;
+        assert_eq!(log.borrow().len() as u32, accepted + spurious - dropped);
+
+        // Identical seeds replay identical faults.
+        let mut replay = chaos(7, &Log::default());
+        for i in 0..12 {
+            let _ = replay.trigger(if i % 2 == 0 { Event::Coin } else { Event::Push });
+        }
+        assert_eq!(replay.faults(), fsm.faults());
+    }
+
+
+    const EXPECTED_FAULTS: &[Fault<State, Event>] = &[
+        Fault::DroppedAction{ from: State::Locked, event: Event::Coin },
+        Fault::SpuriousEvent{ state: State::Unlocked, event: Event::Push, accepted: true },
+        Fault::DroppedAction{ from: State::Unlocked, event: Event::Push },
+        Fault::RejectedEvent{ state: State::Unlocked, event: Event::Push }
+    ];
+
+
+    #[test]
+    fn test_initialize_injects_nothing()
+    {
+        let mut fsm = ChaosStateMachine::initialize(
+            turnstile::INITIAL,
+            fixtures::transition_map(&turnstile::TRANSITIONS, None)
+        );
+
+        assert!(fsm.trigger(Event::Coin).is_ok());
+        assert!(fsm.trigger(Event::Push).is_ok());
+        assert!(fsm.faults().is_empty());
+    }
+}
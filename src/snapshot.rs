@@ -0,0 +1,109 @@
+use std::{fmt::Debug, hash::Hash};
+
+use crate::{
+    deadline::DeadlineSnapshot,
+    deprecate::ResumeReport,
+    fsm::StateMachine,
+    trace::TraceEntry
+};
+
+
+/// Runtime state captured by `snapshot` and reinstated by `restore`.
+///
+/// Only the current state, the trace and the deadline are captured; the
+/// transition table, actions and policies stay with the machine.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot<S, E> {
+    pub state: S,
+    pub trace: Vec<TraceEntry<S, E>>,
+    pub deadline: Option<DeadlineSnapshot<S, E>>
+}
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    pub fn snapshot(&self) -> Snapshot<S, E>
+    {
+        Snapshot{
+            state: self.state,
+            trace: self.trace().cloned().collect(),
+            deadline: self.deadline_snapshot()
+        }
+    }
+
+
+    /// Reinstates a snapshot without running any action.
+    ///
+    /// The trace is replaced only if tracing is enabled, keeping the most
+    /// recent entries that fit. Time in state restarts and cooldowns are
+    /// forgotten. The snapshot's deadline replaces the machine's. Resuming
+    /// in a deprecated state is allowed and flagged in the report.
+    pub fn restore(&mut self, snapshot: Snapshot<S, E>) -> ResumeReport<S>
+    {
+        self.state = snapshot.state;
+        self.entered_at = self.clock.now();
+        self.last_fired.clear();
+        self.restore_deadline(snapshot.deadline);
+
+        if let Some(trace) = &mut self.trace {
+            trace.entries.clear();
+            trace.predating_swap = 0;
+            for entry in snapshot.trace {
+                trace.record(entry);
+            }
+        }
+
+        #[cfg(feature = "paranoid")]
+        self.check_invariants("restore");
+
+        self.resume_report()
+    }
+
+
+    /// Moves to `state` without looking up a transition or running any
+    /// action. Nothing checks that `state` belongs to the machine.
+    pub fn force_state(&mut self, state: S)
+    {
+        self.state = state;
+        self.entered_at = self.clock.now();
+
+        #[cfg(feature = "paranoid")]
+        self.check_invariants("force_state");
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::{builder::StateMachineBuilder, fsm::FSM};
+
+
+    #[test]
+    fn test_snapshot_restore()
+    {
+        let entered = std::rc::Rc::new(std::cell::Cell::new(0));
+        let e = entered.clone();
+        let mut fsm = StateMachineBuilder::new(0)
+            .transition(0, 'a', 1)
+            .transition(1, 'a', 2)
+            .on_enter(2, move || e.set(e.get() + 1))
+            .build()
+            .unwrap();
+
+        fsm.enable_trace(4);
+        fsm.trigger('a').unwrap();
+        let snapshot = fsm.snapshot();
+
+        fsm.trigger('a').unwrap();
+        assert_eq!(fsm.trace().count(), 2);
+
+        fsm.restore(snapshot.clone());
+        assert_eq!(fsm.state(), 1);
+        assert_eq!(fsm.snapshot(), snapshot);
+
+        fsm.force_state(2);
+        assert_eq!(fsm.state(), 2);
+        assert_eq!(entered.get(), 1);
+    }
+}
@@ -0,0 +1,182 @@
+//! Compact JSON status for health-check endpoints.
+//!
+//! Every key is always present so dashboards can rely on the shape; a
+//! value is `null` when the machine does not collect what it needs:
+//!
+//! | key                | value                                                |
+//! |--------------------|------------------------------------------------------|
+//! | `name`             | the name given by `set_name`; `null` if unnamed      |
+//! | `state`            | current state, `Debug` rendered                      |
+//! | `configuration`    | active configuration, as rendered by its `Display`   |
+//! | `time_in_state_ms` | time since the current state was entered             |
+//! | `last_transition`  | `{from, event, to, age_ms}`; `null` without a trace  |
+//! | `transitions`      | transitions taken so far; `reset` does not clear it  |
+//! | `rejections`       | rejected events in the trace; `null` without a trace |
+//! | `unknown_events`   | quarantined unknown events; `null` if not enabled    |
+//! | `queue_depth`      | posted events waiting to be processed                |
+//! | `finished`         | whether a terminal state was reached                 |
+//! | `faulted`          | whether the last traced event panicked or overflowed |
+//! |                    | its cascade; `null` without a trace                  |
+
+use std::{fmt::Debug, hash::Hash};
+
+use crate::{
+    error::TransitionError,
+    fsm::StateMachine,
+    json::Value,
+    trace::TraceOutcome
+};
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    /// Returns the machine's status as one line of JSON; see the module
+    /// documentation for the keys.
+    pub fn status_json(&self) -> String
+    {
+        self.status_value().to_string()
+    }
+
+
+    pub fn status_value(&self) -> Value
+    {
+        let now = self.clock.now();
+        let millis = |d: std::time::Duration| Value::Number(d.as_millis() as f64);
+        let trace = self.trace.as_ref().map(|trace| &trace.entries);
+
+        let last_transition = match trace {
+            None => Value::Null,
+            Some(entries) => entries
+                .iter()
+                .rev()
+                .find_map(|entry| match &entry.outcome {
+                    TraceOutcome::Transitioned{ to } => Some(Value::Object(vec![
+                        ("from".to_string(), debug(&entry.state)),
+                        ("event".to_string(), debug(&entry.event)),
+                        ("to".to_string(), debug(to)),
+                        ("age_ms".to_string(), millis(now.duration_since(entry.at)))
+                    ])),
+                    TraceOutcome::Rejected(_) => None
+                })
+                .unwrap_or(Value::Null)
+        };
+        let rejections = trace.map_or(Value::Null, |entries| {
+            let count = entries
+                .iter()
+                .filter(|entry| matches!(entry.outcome, TraceOutcome::Rejected(_)))
+                .count();
+            Value::Number(count as f64)
+        });
+        let faulted = trace.map_or(Value::Null, |entries| {
+            Value::Bool(entries.back().is_some_and(|entry| matches!(
+                entry.outcome,
+                TraceOutcome::Rejected(
+                    TransitionError::ActionPanicked { .. } | TransitionError::CascadeOverflow { .. }
+                )
+            )))
+        });
+        let unknown_events = if self.quarantine_enabled {
+            Value::Number(self.quarantine.values().sum::<u64>() as f64)
+        } else {
+            Value::Null
+        };
+
+        Value::Object(vec![
+            ("name".to_string(), self.name().map_or(Value::Null, Value::from)),
+            ("state".to_string(), debug(&self.state)),
+            ("configuration".to_string(), Value::from(self.configuration().to_string())),
+            ("time_in_state_ms".to_string(), millis(self.time_in_state())),
+            ("last_transition".to_string(), last_transition),
+            ("transitions".to_string(), Value::Number(self.sequence as f64)),
+            ("rejections".to_string(), rejections),
+            ("unknown_events".to_string(), unknown_events),
+            ("queue_depth".to_string(), Value::Number(self.queue_depth() as f64)),
+            ("finished".to_string(), Value::Bool(self.is_finished())),
+            ("faulted".to_string(), faulted)
+        ])
+    }
+}
+
+
+fn debug(value: &impl Debug) -> Value
+{
+    Value::from(format!("{value:?}"))
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{builder::StateMachineBuilder, clock::MockClock, fsm::FSM, json};
+    use std::{sync::Arc, time::Duration};
+
+
+    const KEYS: [&str; 11] = [
+        "name", "state", "configuration", "time_in_state_ms", "last_transition",
+        "transitions", "rejections", "unknown_events", "queue_depth", "finished", "faulted"
+    ];
+
+
+    fn keys(status: &Value) -> Vec<&str>
+    {
+        status.as_object().unwrap().iter().map(|(key, _)| key.as_str()).collect()
+    }
+
+
+    #[test]
+    fn test_minimal_machine()
+    {
+        let fsm = StateMachineBuilder::new(0).transition(0, 'a', 1).build().unwrap();
+        let status = json::parse(&fsm.status_json()).unwrap();
+
+        assert_eq!(keys(&status), KEYS);
+        assert_eq!(status.get("name"), Some(&Value::Null));
+        assert_eq!(status.get("state").unwrap().as_str(), Some("0"));
+        assert_eq!(status.get("last_transition"), Some(&Value::Null));
+        assert_eq!(status.get("faulted"), Some(&Value::Null));
+        assert_eq!(status.get("finished").unwrap().as_bool(), Some(false));
+    }
+
+
+    #[test]
+    fn test_traced_machine()
+    {
+        let clock = Arc::new(MockClock::new());
+        let mut fsm = StateMachineBuilder::new(0)
+            .transition(0, 'a', 1)
+            .transition(1, 'b', 2)
+            .terminal(2)
+            .build()
+            .unwrap();
+
+        fsm.set_clock(clock.clone());
+        fsm.set_name("door");
+        fsm.set_alphabet(['a', 'b']).unwrap();
+        fsm.enable_quarantine();
+        fsm.enable_trace(8);
+        fsm.trigger('a').unwrap();
+        fsm.trigger('a').unwrap_err();
+        fsm.trigger_str("x").unwrap_err();
+        fsm.trigger('b').unwrap();
+        clock.advance(Duration::from_millis(1500));
+
+        let status = json::parse(&fsm.status_json()).unwrap();
+        assert_eq!(keys(&status), KEYS);
+        assert_eq!(status.get("name").unwrap().as_str(), Some("door"));
+        assert_eq!(
+            status.get("last_transition").unwrap().to_string(),
+            r#"{"from":"1","event":"'b'","to":"2","age_ms":1500}"#
+        );
+        assert_eq!(status.get("time_in_state_ms").unwrap().as_f64(), Some(1500.0));
+        assert_eq!(status.get("transitions").unwrap().as_f64(), Some(2.0));
+        assert_eq!(status.get("rejections").unwrap().as_f64(), Some(1.0));
+        assert_eq!(status.get("unknown_events").unwrap().as_f64(), Some(1.0));
+        assert_eq!(status.get("finished").unwrap().as_bool(), Some(true));
+        assert_eq!(status.get("faulted").unwrap().as_bool(), Some(false));
+
+        fsm.reset();
+        let status = fsm.status_value();
+        assert_eq!(status.get("transitions").unwrap().as_f64(), Some(2.0));
+    }
+}
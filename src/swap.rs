@@ -0,0 +1,289 @@
+//! Replacing a running machine's structure, as after reloading a schema.
+//!
+//! `hot_swap` takes the structure of another machine — transitions,
+//! aliases, the alphabet, timeouts, tags, entry and exit actions,
+//! terminal and deprecated states, and the initial state — and keeps
+//! everything that happened at runtime: the current state, the trace,
+//! counters, state data, the deadline, policies, observers, middleware
+//! and breakpoints. The flag provider is replaced only if the new machine
+//! has one. The closures of the new structure were built against the new
+//! machine's shared handles, so those are adopted too, with the queued
+//! events moved over: posters, origin readers and cancellation tokens
+//! obtained before the swap must be fetched again.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Debug, Display},
+    hash::Hash
+};
+
+use crate::{coalesce::PostQueue, fsm::StateMachine};
+
+
+/// What `hot_swap` changed, in the order used by `describe`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SwapReport<S, E> {
+    /// The state the machine is in after the swap.
+    pub state: S,
+    /// The state left because the new structure does not know it.
+    pub replaced_state: Option<S>,
+    pub added: Vec<(S, E)>,
+    pub removed: Vec<(S, E)>,
+    /// Transitions kept under the same state and event, with a new target.
+    pub retargeted: Vec<(S, E)>,
+    /// Queued events the new structure does not know, which were dropped.
+    pub dropped_events: Vec<E>
+}
+
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SwapError<S> {
+    /// The current state is not in the new structure and no fallback was
+    /// given.
+    StateRemoved { state: S },
+    /// The fallback is not in the new structure either.
+    UnknownFallback { fallback: S }
+}
+
+
+impl<S: Debug> Display for SwapError<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        match self {
+            SwapError::StateRemoved { state } => {
+                write!(f, "current state {state:?} does not exist in the new structure")
+            }
+            SwapError::UnknownFallback { fallback } => {
+                write!(f, "fallback state {fallback:?} does not exist in the new structure")
+            }
+        }
+    }
+}
+
+
+impl<S: Debug> std::error::Error for SwapError<S> {}
+
+
+impl<S> SwapError<S> {
+    /// Returns the numeric code listed in [`ERROR_CODES`](crate::error::ERROR_CODES).
+    pub fn code(&self) -> u16
+    {
+        match self {
+            SwapError::StateRemoved { .. } => 503,
+            SwapError::UnknownFallback { .. } => 504
+        }
+    }
+}
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    /// Replaces the machine's structure by that of `new`, staying in the
+    /// current state if `new` knows it and moving to `fallback` if not.
+    /// No action runs either way. On error the machine is unchanged.
+    pub fn hot_swap(
+        &mut self,
+        new: StateMachine<S, E>,
+        fallback: Option<S>
+    ) -> Result<SwapReport<S, E>, SwapError<S>>
+    {
+        let states = new.states();
+        let replaced_state = if states.contains(&self.state) {
+            None
+        } else {
+            match fallback {
+                None => return Err(SwapError::StateRemoved{ state: self.state }),
+                Some(fallback) if !states.contains(&fallback) => {
+                    return Err(SwapError::UnknownFallback{ fallback });
+                }
+                Some(_) => Some(self.state)
+            }
+        };
+
+        let old = self.describe().transitions;
+        let before: HashMap<(S, E), S> = old.iter().map(|t| ((t.from, t.event), t.to)).collect();
+        let mut added = Vec::new();
+        let mut retargeted = Vec::new();
+
+        for t in new.describe().transitions {
+            match before.get(&(t.from, t.event)) {
+                None => added.push((t.from, t.event)),
+                Some(to) if *to != t.to => retargeted.push((t.from, t.event)),
+                Some(_) => {}
+            }
+        }
+        let removed: Vec<(S, E)> = old
+            .iter()
+            .map(|t| (t.from, t.event))
+            .filter(|key| !new.transitions.contains_key(key))
+            .collect();
+
+        let known: HashSet<E> = new.transitions
+            .keys()
+            .map(|(_, event)| *event)
+            .chain(new.aliases.keys().copied())
+            .chain(new.timeouts.values().map(|(_, event)| *event))
+            .collect();
+        let mut queue = self.posted.replace(PostQueue::default());
+        let dropped_events = queue.drop_unless(|event| known.contains(event));
+
+        if let (Some(_), Some(fallback)) = (replaced_state, fallback) {
+            self.state = fallback;
+            self.entered_at = self.clock.now();
+        }
+        self.last_fired.retain(|key, _| new.transitions.contains_key(key));
+        self.initial = new.initial;
+        self.transitions = new.transitions;
+        self.timeouts = new.timeouts;
+        self.tags = new.tags;
+        self.entry_actions = new.entry_actions;
+        self.exit_actions = new.exit_actions;
+        self.terminals = new.terminals;
+        self.deprecated = new.deprecated;
+        self.groups.extend(new.groups);
+        self.initial_choice = new.initial_choice;
+        self.initial_selection = new.initial_selection;
+        self.aliases = new.aliases;
+        self.alphabet = new.alphabet;
+        if new.flag_provider.is_some() {
+            self.flag_provider = new.flag_provider;
+        }
+        if let Some(trace) = &mut self.trace {
+            trace.predating_swap = trace.entries.len();
+        }
+        self.guard_memo = new.guard_memo;
+        self.guard_deps = new.guard_deps;
+        new.posted.borrow_mut().prepend(queue);
+        self.posted = new.posted;
+        self.origin = new.origin;
+        self.cancel = new.cancel;
+        self.generation += 1;
+
+        #[cfg(feature = "paranoid")]
+        self.check_invariants("hot_swap");
+
+        Ok(SwapReport{
+            state: self.state,
+            replaced_state,
+            added,
+            removed,
+            retargeted,
+            dropped_events
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{builder::StateMachineBuilder, error::TransitionError, fsm::FSM};
+
+
+    fn reloaded(state_kept: bool) -> StateMachine<&'static str, &'static str>
+    {
+        let builder = StateMachineBuilder::new("idle")
+            .transition("idle", "start", "running")
+            .transition("running", "stop", "idle");
+
+        if state_kept {
+            builder.transition("running", "pause", "paused").build().unwrap()
+        } else {
+            builder.transition("idle", "error", "idle").build().unwrap()
+        }
+    }
+
+
+    fn running() -> StateMachine<&'static str, &'static str>
+    {
+        let mut fsm = StateMachineBuilder::new("idle")
+            .transition("idle", "start", "running")
+            .transition("running", "stop", "stopped")
+            .transition("running", "pause", "paused")
+            .transition("paused", "resume", "running")
+            .build()
+            .unwrap();
+
+        fsm.enable_trace(8);
+        fsm.trigger("start").unwrap();
+        fsm.trigger("pause").unwrap();
+        fsm
+    }
+
+
+    #[test]
+    fn test_current_state_preserved()
+    {
+        let mut fsm = running();
+        let generation = fsm.generation();
+        let poster = fsm.poster();
+
+        poster.post("resume");
+        poster.post("stop");
+        let report = fsm.hot_swap(reloaded(true), None).unwrap();
+
+        assert_eq!(report.state, "paused");
+        assert_eq!(report.replaced_state, None);
+        assert_eq!(report.removed, [("paused", "resume")]);
+        assert_eq!(report.retargeted, [("running", "stop")]);
+        assert!(report.added.is_empty());
+        assert_eq!(report.dropped_events, ["resume"]);
+        assert_eq!(fsm.queue_depth(), 1);
+        assert_eq!(fsm.generation(), generation + 1);
+        assert_eq!(fsm.trace().count(), 2);
+        assert_eq!(fsm.state(), "paused");
+    }
+
+
+    #[test]
+    fn test_fallback_replaces_removed_state()
+    {
+        let mut fsm = running();
+
+        assert_eq!(
+            fsm.hot_swap(reloaded(false), None),
+            Err(SwapError::StateRemoved{ state: "paused" })
+        );
+        assert_eq!(
+            fsm.hot_swap(reloaded(false), Some("paused")),
+            Err(SwapError::UnknownFallback{ fallback: "paused" })
+        );
+        assert_eq!(fsm.state(), "paused");
+
+        let report = fsm.hot_swap(reloaded(false), Some("idle")).unwrap();
+        assert_eq!((report.state, report.replaced_state), ("idle", Some("paused")));
+        assert_eq!(report.added, [("idle", "error")]);
+
+        fsm.trigger("start").unwrap();
+        fsm.trigger("stop").unwrap();
+        assert_eq!(fsm.state(), "idle");
+    }
+
+
+    #[test]
+    fn test_swapped_in_actions_post_to_the_machine()
+    {
+        let mut fsm = running();
+        fsm.poster().post("resume");
+
+        let mut new = StateMachineBuilder::new("idle")
+            .transition("paused", "resume", "running")
+            .transition("running", "b", "done")
+            .build()
+            .unwrap();
+        let poster = new.poster();
+        new.set_alphabet(["resume", "b"]).unwrap();
+        new.set_entry_action("running", Box::new(move || poster.post("b")));
+
+        fsm.hot_swap(new, None).unwrap();
+        assert_eq!(fsm.run_until_quiescent(4).unwrap().internal_events, 2);
+        assert_eq!(fsm.state(), "done");
+        assert_eq!(
+            fsm.trigger("stop"),
+            Err(TransitionError::UnknownEvent{ state: "done", name: "\"stop\"".to_string() })
+        );
+    }
+}
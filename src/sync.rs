@@ -0,0 +1,268 @@
+//! Keeping a replica in step with a primary machine by shipping deltas.
+//!
+//! Both sides must be built from the same definition; a delta carries a
+//! fingerprint of that definition and is refused by a replica with a
+//! different one.
+
+use std::{
+    fmt::{self, Debug, Display},
+    hash::Hash
+};
+
+use crate::{
+    fsm::StateMachine,
+    json::{self, Value},
+    schema::{Resolver, SchemaError}
+};
+
+
+/// The primary's current state, tagged with enough to validate it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StateDelta<S> {
+    pub fingerprint: u64,
+    pub generation: u64,
+    /// Transition sequence number of the primary.
+    pub sequence: u64,
+    /// Transitions taken since the sequence number passed to `delta_since`.
+    pub skipped: u64,
+    pub state: S
+}
+
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SyncError {
+    FingerprintMismatch { local: u64, remote: u64 },
+    GenerationMismatch { local: u64, remote: u64 },
+    /// The delta is older than what the replica already applied.
+    Stale { local: u64, remote: u64 }
+}
+
+
+impl Display for SyncError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        match self {
+            SyncError::FingerprintMismatch { local, remote } => write!(
+                f, "Delta is for definition {remote:016x}, replica runs {local:016x}"
+            ),
+            SyncError::GenerationMismatch { local, remote } => write!(
+                f, "Delta is for generation {remote}, replica is at {local}"
+            ),
+            SyncError::Stale { local, remote } => write!(
+                f, "Delta sequence {remote} is behind replica sequence {local}"
+            )
+        }
+    }
+}
+
+
+impl std::error::Error for SyncError {}
+
+
+impl SyncError {
+    /// Returns the numeric code listed in [`ERROR_CODES`](crate::error::ERROR_CODES).
+    pub fn code(&self) -> u16
+    {
+        match self {
+            SyncError::FingerprintMismatch { .. } => 301,
+            SyncError::GenerationMismatch { .. } => 302,
+            SyncError::Stale { .. } => 303
+        }
+    }
+}
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    /// Returns a stable hash of the machine's structure: its description
+    /// rendered with `Debug`, hashed with FNV-1a. Which states hold data
+    /// is runtime content, so it is left out.
+    pub fn fingerprint(&self) -> u64
+    {
+        let mut description = self.describe();
+
+        description.with_data.clear();
+        format!("{description:?}")
+            .bytes()
+            .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+                (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+            })
+    }
+
+
+    /// Returns the delta bringing a replica last synced at sequence
+    /// `since` up to date.
+    pub fn delta_since(&self, since: u64) -> StateDelta<S>
+    {
+        StateDelta{
+            fingerprint: self.fingerprint(),
+            generation: self.generation,
+            sequence: self.sequence,
+            skipped: self.sequence.saturating_sub(since),
+            state: self.state
+        }
+    }
+
+
+    /// Moves to the delta's state without running any action.
+    ///
+    /// Re-applying the current delta is a no-op; older ones are refused.
+    pub fn apply_delta(&mut self, delta: &StateDelta<S>) -> Result<(), SyncError>
+    {
+        let fingerprint = self.fingerprint();
+
+        if delta.fingerprint != fingerprint {
+            return Err(SyncError::FingerprintMismatch{
+                local: fingerprint,
+                remote: delta.fingerprint
+            });
+        }
+        if delta.generation != self.generation {
+            return Err(SyncError::GenerationMismatch{
+                local: self.generation,
+                remote: delta.generation
+            });
+        }
+        if delta.sequence < self.sequence {
+            return Err(SyncError::Stale{ local: self.sequence, remote: delta.sequence });
+        }
+        if delta.state != self.state {
+            self.state = delta.state;
+            self.entered_at = self.clock.now();
+        }
+        self.sequence = delta.sequence;
+
+        #[cfg(feature = "paranoid")]
+        self.check_invariants("apply_delta");
+        Ok(())
+    }
+}
+
+
+impl<S: Debug> StateDelta<S> {
+    /// Encodes the delta as JSON, naming the state by its `Debug`
+    /// rendering. The fingerprint is a hex string to survive JSON numbers.
+    pub fn to_json(&self) -> String
+    {
+        Value::Object(vec![
+            ("fingerprint".to_string(), Value::from(format!("{:016x}", self.fingerprint))),
+            ("generation".to_string(), Value::Number(self.generation as f64)),
+            ("sequence".to_string(), Value::Number(self.sequence as f64)),
+            ("skipped".to_string(), Value::Number(self.skipped as f64)),
+            ("state".to_string(), Value::from(format!("{:?}", self.state)))
+        ]).to_string()
+    }
+
+
+    pub fn from_json<E>(text: &str, resolver: &Resolver<S, E>) -> Result<Self, SchemaError>
+    {
+        let value = json::parse(text)?;
+        let number = |key: &str| {
+            value
+                .get(key)
+                .and_then(Value::as_f64)
+                .filter(|n| *n >= 0.0 && n.fract() == 0.0)
+                .map(|n| n as u64)
+                .ok_or_else(|| SchemaError::Invalid(format!("missing integer field '{key}'")))
+        };
+        let fingerprint = value
+            .get("fingerprint")
+            .and_then(Value::as_str)
+            .and_then(|hex| u64::from_str_radix(hex, 16).ok())
+            .ok_or_else(|| SchemaError::Invalid("missing hex field 'fingerprint'".to_string()))?;
+        let state = value
+            .get("state")
+            .and_then(Value::as_str)
+            .ok_or_else(|| SchemaError::Invalid("missing string field 'state'".to_string()))?;
+
+        Ok(Self{
+            fingerprint,
+            generation: number("generation")?,
+            sequence: number("sequence")?,
+            skipped: number("skipped")?,
+            state: resolver.state(state)?
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{builder::StateMachineBuilder, fsm::FSM};
+    use std::{cell::Cell, rc::Rc};
+
+
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    enum State {
+        Idle,
+        Busy,
+        Done
+    }
+
+
+    fn machine(actions: Rc<Cell<u32>>) -> StateMachine<State, char>
+    {
+        StateMachineBuilder::new(State::Idle)
+            .transition(State::Idle, 's', State::Busy)
+            .action(move || actions.set(actions.get() + 1))
+            .transition(State::Busy, 'd', State::Done)
+            .transition(State::Done, 'r', State::Idle)
+            .build()
+            .unwrap()
+    }
+
+
+    #[test]
+    fn test_replica_converges()
+    {
+        let client_actions = Rc::new(Cell::new(0));
+        let mut server = machine(Rc::default());
+        let mut client = machine(client_actions.clone());
+        let resolver =
+            Resolver::from_variants(&[State::Idle, State::Busy, State::Done], &['s']).unwrap();
+
+        client.set_state_data(State::Busy, 7u32);
+        assert_eq!(server.fingerprint(), client.fingerprint());
+
+        for event in ['s', 'd', 'r', 's'] {
+            server.trigger(event).unwrap();
+            let wire = server.delta_since(client.sequence()).to_json();
+            let delta = StateDelta::from_json(&wire, &resolver).unwrap();
+
+            assert_eq!(delta.skipped, 1);
+            client.apply_delta(&delta).unwrap();
+            assert_eq!(client.state(), server.state());
+        }
+        assert_eq!(client.sequence(), 4);
+        assert_eq!(client_actions.get(), 0);
+    }
+
+
+    #[test]
+    fn test_stale_and_foreign_deltas_rejected()
+    {
+        let mut server = machine(Rc::default());
+        let mut client = machine(Rc::default());
+
+        server.trigger('s').unwrap();
+        let old = server.delta_since(0);
+        server.trigger('d').unwrap();
+        client.apply_delta(&server.delta_since(0)).unwrap();
+
+        assert_eq!(client.apply_delta(&old), Err(SyncError::Stale{ local: 2, remote: 1 }));
+        assert_eq!(client.state(), State::Done);
+
+        let mut other = StateMachineBuilder::new(State::Idle)
+            .transition(State::Idle, 's', State::Done)
+            .build()
+            .unwrap();
+        assert!(matches!(
+            other.apply_delta(&server.delta_since(0)),
+            Err(SyncError::FingerprintMismatch { .. })
+        ));
+        assert_eq!(other.state(), State::Idle);
+    }
+}
@@ -0,0 +1,353 @@
+//! Machines described once and instantiated per parameter set.
+//!
+//! A [`TemplateBuilder`] mirrors [`StateMachineBuilder`], except that
+//! guards, actions and durations receive a parameter struct `P`. Each
+//! call to [`MachineTemplate::instantiate`] shares one copy of the
+//! parameters among all closures of the new machine.
+
+use std::{collections::HashSet, fmt::Debug, hash::Hash, rc::Rc, time::Duration};
+
+use crate::{
+    builder::{BuildError, StateMachineBuilder},
+    fsm::StateMachine,
+    initial::{template_selector, InitialGuard}
+};
+
+
+type Step<S, E, P> =
+    Box<dyn Fn(StateMachineBuilder<S, E>, &Rc<P>) -> StateMachineBuilder<S, E>>;
+type Timeout<S, E, P> = (S, E, Box<dyn Fn(&P) -> Duration>);
+
+
+/// Builds a [`MachineTemplate`]. Transition modifiers apply to the most
+/// recently added transition, and panic if there is none, as with
+/// `StateMachineBuilder`.
+pub struct TemplateBuilder<S: Copy, E: Copy, P> {
+    initial: S,
+    keys: Vec<(S, E)>,
+    steps: Vec<Step<S, E, P>>,
+    timeouts: Vec<Timeout<S, E, P>>
+}
+
+
+/// An immutable machine description parameterized by `P`.
+pub struct MachineTemplate<S: Copy, E: Copy, P> {
+    initial: S,
+    steps: Vec<Step<S, E, P>>,
+    timeouts: Vec<Timeout<S, E, P>>
+}
+
+
+impl<S, E, P> TemplateBuilder<S, E, P>
+where S: Copy + Hash + Eq + Debug + 'static, E: Copy + Hash + Eq + Debug + 'static, P: 'static
+{
+    pub fn new(initial: S) -> Self
+    {
+        Self{ initial, keys: Vec::new(), steps: Vec::new(), timeouts: Vec::new() }
+    }
+
+
+    pub fn transition(mut self, from: S, event: E, to: S) -> Self
+    {
+        self.keys.push((from, event));
+        self.step(move |builder, _| builder.transition(from, event, to))
+    }
+
+
+    pub fn action(self, action: impl Fn(&P) + 'static) -> Self
+    {
+        let action = Rc::new(action);
+
+        self.modify(move |builder, params| {
+            let (action, params) = (action.clone(), params.clone());
+            builder.action(move || action(&params))
+        })
+    }
+
+
+    pub fn guard(self, guard: impl Fn(&P) -> bool + 'static) -> Self
+    {
+        let guard = Rc::new(guard);
+
+        self.modify(move |builder, params| {
+            let (guard, params) = (guard.clone(), params.clone());
+            builder.guard(move || guard(&params))
+        })
+    }
+
+
+    pub fn cooldown(self, cooldown: impl Fn(&P) -> Duration + 'static) -> Self
+    {
+        self.modify(move |builder, params| builder.cooldown(cooldown(params)))
+    }
+
+
+    /// Adds `to` as another candidate target of the last transition.
+    pub fn alternative(self, to: S) -> Self
+    {
+        self.modify(move |builder, _| builder.alternative(to))
+    }
+
+
+    /// Sets the weight of the last candidate of the last transition.
+    pub fn weight(self, weight: impl Fn(&P) -> f64 + 'static) -> Self
+    {
+        self.modify(move |builder, params| builder.weight(weight(params)))
+    }
+
+
+    pub fn on_enter(self, state: S, action: impl Fn(&P) + 'static) -> Self
+    {
+        let action = Rc::new(action);
+
+        self.step(move |builder, params| {
+            let (action, params) = (action.clone(), params.clone());
+            builder.on_enter(state, move || action(&params))
+        })
+    }
+
+
+    pub fn on_exit(self, state: S, action: impl Fn(&P) + 'static) -> Self
+    {
+        let action = Rc::new(action);
+
+        self.step(move |builder, params| {
+            let (action, params) = (action.clone(), params.clone());
+            builder.on_exit(state, move || action(&params))
+        })
+    }
+
+
+    /// Chooses each instance's initial state from its parameters; see
+    /// `StateMachineBuilder::initial_choice`.
+    pub fn initial_choice(self, branches: Vec<(InitialGuard<P>, S)>, fallback: S) -> Self
+    {
+        let branches = Rc::new(branches);
+
+        self.step(move |mut builder, params| {
+            builder.initial_choice = Some(template_selector(&branches, params, fallback));
+            builder
+        })
+    }
+
+
+    pub fn group(self, group: &str) -> Self
+    {
+        let group = group.to_string();
+
+        self.modify(move |builder, _| builder.group(&group))
+    }
+
+
+    pub fn feature_flag(self, flag: &str) -> Self
+    {
+        let flag = flag.to_string();
+
+        self.modify(move |builder, _| builder.feature_flag(&flag))
+    }
+
+
+    pub fn tag(self, state: S, tag: &str) -> Self
+    {
+        let tag = tag.to_string();
+
+        self.step(move |builder, _| builder.tag(state, &tag))
+    }
+
+
+    pub fn terminal(self, state: S) -> Self
+    {
+        self.step(move |builder, _| builder.terminal(state))
+    }
+
+
+    /// Triggers `event` once the machine has spent `after(params)` in
+    /// `state`; see `StateMachine::set_timeout`.
+    pub fn timeout(
+        mut self,
+        state: S,
+        after: impl Fn(&P) -> Duration + 'static,
+        event: E
+    ) -> Self
+    {
+        self.timeouts.push((state, event, Box::new(after)));
+        self
+    }
+
+
+    /// Checks the structure once, so that instantiating cannot fail.
+    pub fn build(self) -> Result<MachineTemplate<S, E, P>, BuildError<S, E>>
+    {
+        let mut seen = HashSet::with_capacity(self.keys.len());
+
+        if let Some(&(from, event)) = self.keys.iter().find(|key| !seen.insert(**key)) {
+            return Err(BuildError::DuplicateTransition{ from, event });
+        }
+
+        Ok(MachineTemplate{ initial: self.initial, steps: self.steps, timeouts: self.timeouts })
+    }
+
+
+    /// Adds a step that modifies the last transition.
+    fn modify(
+        self,
+        step: impl Fn(StateMachineBuilder<S, E>, &Rc<P>) -> StateMachineBuilder<S, E> + 'static
+    ) -> Self
+    {
+        assert!(!self.keys.is_empty(), "transition modifiers must follow a call to `transition`");
+        self.step(step)
+    }
+
+
+    fn step(
+        mut self,
+        step: impl Fn(StateMachineBuilder<S, E>, &Rc<P>) -> StateMachineBuilder<S, E> + 'static
+    ) -> Self
+    {
+        self.steps.push(Box::new(step));
+        self
+    }
+}
+
+
+impl<S, E, P> MachineTemplate<S, E, P>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    /// Creates a machine with `params` baked into its closures and
+    /// durations.
+    pub fn instantiate(&self, params: P) -> StateMachine<S, E>
+    {
+        let params = Rc::new(params);
+        let builder = self.steps
+            .iter()
+            .fold(StateMachineBuilder::new(self.initial), |builder, step| step(builder, &params));
+        let mut fsm = builder
+            .build()
+            .expect("duplicates are rejected by `TemplateBuilder::build`");
+
+        for (state, event, after) in &self.timeouts {
+            fsm.set_timeout(*state, after(&params), *event);
+        }
+        fsm
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{clock::MockClock, error::TransitionError, fsm::FSM};
+    use std::{cell::Cell, sync::Arc};
+
+
+    struct Review {
+        reviewers: u32,
+        deadline: Duration
+    }
+
+
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    enum State {
+        Open,
+        Approved,
+        Expired
+    }
+
+
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    enum Event {
+        Approve,
+        Expire
+    }
+
+
+    fn template(approvals: &Rc<Cell<u32>>) -> MachineTemplate<State, Event, Review>
+    {
+        let approvals = approvals.clone();
+
+        TemplateBuilder::new(State::Open)
+            .transition(State::Open, Event::Approve, State::Approved)
+            .guard(move |review: &Review| approvals.get() >= review.reviewers)
+            .transition(State::Open, Event::Expire, State::Expired)
+            .timeout(State::Open, |review| review.deadline, Event::Expire)
+            .terminal(State::Approved)
+            .terminal(State::Expired)
+            .build()
+            .unwrap()
+    }
+
+
+    fn secs(n: u64) -> Duration
+    {
+        Duration::from_secs(n)
+    }
+
+
+    #[test]
+    fn test_parameters_differ_between_instances()
+    {
+        let approvals = Rc::new(Cell::new(2));
+        let template = template(&approvals);
+        let clock = Arc::new(MockClock::new());
+        let mut small = template.instantiate(Review{ reviewers: 2, deadline: secs(60) });
+        let mut large = template.instantiate(Review{ reviewers: 3, deadline: secs(10) });
+        small.set_clock(clock.clone());
+        large.set_clock(clock.clone());
+
+        assert!(matches!(
+            large.trigger(Event::Approve),
+            Err(TransitionError::GuardRejected{ .. })
+        ));
+        clock.advance(secs(30));
+        assert!(!small.tick().unwrap());
+        assert!(large.tick().unwrap());
+        assert_eq!(large.state(), State::Expired);
+
+        small.trigger(Event::Approve).unwrap();
+        assert_eq!(small.state(), State::Approved);
+    }
+
+
+    #[test]
+    fn test_initial_choice_per_instance()
+    {
+        let template = TemplateBuilder::new(State::Open)
+            .initial_choice(
+                vec![(Box::new(|review: &Review| review.reviewers == 0), State::Approved)],
+                State::Open
+            )
+            .transition(State::Open, Event::Approve, State::Approved)
+            .build()
+            .unwrap();
+
+        let unreviewed = template.instantiate(Review{ reviewers: 0, deadline: secs(1) });
+        let reviewed = template.instantiate(Review{ reviewers: 1, deadline: secs(1) });
+        assert_eq!(unreviewed.state(), State::Approved);
+        assert_eq!(reviewed.state(), State::Open);
+    }
+
+
+    #[test]
+    #[should_panic(expected = "transition modifiers must follow a call to `transition`")]
+    fn test_modifier_before_transition_panics()
+    {
+        let _: TemplateBuilder<State, Event, Review> =
+            TemplateBuilder::new(State::Open).guard(|review: &Review| review.reviewers > 0);
+    }
+
+
+    #[test]
+    fn test_duplicate_rejected_at_build()
+    {
+        let result: Result<MachineTemplate<State, Event, Review>, _> =
+            TemplateBuilder::new(State::Open)
+                .transition(State::Open, Event::Expire, State::Expired)
+                .transition(State::Open, Event::Expire, State::Approved)
+                .build();
+
+        assert!(matches!(
+            result,
+            Err(BuildError::DuplicateTransition{ from: State::Open, event: Event::Expire })
+        ));
+    }
+}
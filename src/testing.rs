@@ -0,0 +1,161 @@
+//! Helpers for testing machines built with this crate.
+
+use std::{fmt::Debug, fmt::Write, hash::Hash};
+
+use crate::fsm::{StateMachine, FSM};
+
+
+/// Triggers each event in turn and asserts the state after every step.
+///
+/// ```ignore
+/// assert_fsm_path!(fsm, Red -RedTimeout-> Yellow -Yellow2GreenTimeout-> Green);
+/// assert_fsm_path!(fsm, Green !> RedTimeout);
+/// ```
+///
+/// The first state is the expected starting state. A trailing `!> Event`
+/// asserts that the next event is rejected. On failure the whole expected
+/// and actual paths are printed with the failing step in brackets,
+/// followed by `explain()` for the failing event.
+#[macro_export]
+macro_rules! assert_fsm_path {
+    ($fsm:expr, $first:ident $(- $event:ident -> $state:ident)* $(!> $rejected:ident)?) => {
+        $crate::testing::check_path(
+            &mut $fsm,
+            &[$first $(, $state)*],
+            &[$($event),*],
+            None $(.or(Some($rejected)))?
+        )
+    };
+}
+
+
+/// Implementation of [`assert_fsm_path!`].
+#[track_caller]
+pub fn check_path<S, E>(
+    fsm: &mut StateMachine<S, E>,
+    states: &[S],
+    events: &[E],
+    rejected: Option<E>
+)
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    let mut actual = vec![format!("{:?}", fsm.state())];
+
+    if fsm.state() != states[0] {
+        fail(states, events, rejected, &actual, 0, None);
+    }
+
+    for (step, (event, expected)) in events.iter().zip(&states[1..]).enumerate() {
+        let explanation = fsm.explain(*event).to_string();
+
+        match fsm.trigger(*event) {
+            Ok(()) if fsm.state() == *expected => {
+                actual.push(format!("{:?}", fsm.state()));
+            }
+            Ok(()) => {
+                actual.push(format!("{:?}", fsm.state()));
+                fail(states, events, rejected, &actual, step + 1, Some(explanation));
+            }
+            Err(err) => {
+                actual.push(format!("{:?} ({err})", fsm.state()));
+                fail(states, events, rejected, &actual, step + 1, Some(explanation));
+            }
+        }
+    }
+
+    if let Some(event) = rejected {
+        let explanation = fsm.explain(event).to_string();
+
+        if fsm.trigger(event).is_ok() {
+            actual.push(format!("{:?} (accepted)", fsm.state()));
+            fail(states, events, rejected, &actual, events.len() + 1, Some(explanation));
+        }
+    }
+}
+
+
+#[track_caller]
+fn fail<S: Debug, E: Copy + Debug>(
+    states: &[S],
+    events: &[E],
+    rejected: Option<E>,
+    actual: &[String],
+    step: usize,
+    explanation: Option<String>
+) -> !
+{
+    let expected: Vec<String> = states.iter().map(|s| format!("{s:?}")).collect();
+    let mut report = format!("FSM path mismatch at step {step}\n  expected: ");
+
+    render(&mut report, &expected, events, step);
+    if let Some(event) = rejected {
+        if step > events.len() {
+            let _ = write!(report, " [!> {event:?}]");
+        } else {
+            let _ = write!(report, " !> {event:?}");
+        }
+    }
+
+    // A wrongly accepted rejection shows up as one more actual step.
+    let all_events: Vec<E> = events.iter().copied().chain(rejected).collect();
+    report.push_str("\n  actual:   ");
+    render(&mut report, actual, &all_events, step);
+
+    if let Some(explanation) = explanation {
+        let _ = write!(report, "\n  explain:  {explanation}");
+    }
+    panic!("{report}");
+}
+
+
+fn render<E: Debug>(out: &mut String, states: &[String], events: &[E], step: usize)
+{
+    for (i, state) in states.iter().enumerate() {
+        if i > 0 {
+            let _ = write!(out, " -{:?}-> ", events[i - 1]);
+        }
+        if i == step {
+            let _ = write!(out, "[{state}]");
+        } else {
+            out.push_str(state);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::fixtures::turnstile::{self, Event::*, State::*};
+
+
+    #[test]
+    fn test_path_with_rejection()
+    {
+        let mut fsm = turnstile::machine();
+
+        assert_fsm_path!(fsm, Locked -Coin-> Unlocked -Push-> Locked !> Push);
+    }
+
+
+    #[test]
+    #[should_panic(expected = "FSM path mismatch at step 2
+  expected: Locked -Coin-> Unlocked -Coin-> [Locked]
+  actual:   Locked -Coin-> Unlocked -Coin-> [Unlocked (No transition found for event 'Coin' from state 'Unlocked')]
+  explain:  Coin in Unlocked: no transition (valid events: [Push])")]
+    fn test_failure_report()
+    {
+        let mut fsm = turnstile::machine();
+
+        assert_fsm_path!(fsm, Locked -Coin-> Unlocked -Coin-> Locked);
+    }
+
+
+    #[test]
+    #[should_panic(expected = "expected: Locked [!> Coin]")]
+    fn test_accepted_rejection_report()
+    {
+        let mut fsm = turnstile::machine();
+
+        assert_fsm_path!(fsm, Locked !> Coin);
+    }
+}
@@ -0,0 +1,316 @@
+//! Transition tours: event sequences exercising every edge of a machine.
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt::{self, Debug, Display},
+    hash::Hash
+};
+
+use crate::fsm::StateMachine;
+
+
+/// Some enabled transitions cannot be reached by any tour from the
+/// initial state; `uncovered` lists them as `(from, event)` pairs.
+///
+/// Fields may be added, so it cannot be built outside this crate:
+///
+/// ```compile_fail
+/// let error: pfsm::tour::TourError<u8, char> = pfsm::tour::TourError{ uncovered: Vec::new() };
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TourError<S, E> {
+    pub uncovered: Vec<(S, E)>
+}
+
+
+impl<S: Debug, E: Debug> Display for TourError<S, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "No tour covers transitions {:?}", self.uncovered)
+    }
+}
+
+
+impl<S: Debug, E: Debug> std::error::Error for TourError<S, E> {}
+
+
+impl<S, E> TourError<S, E> {
+    /// Returns the numeric code listed in [`ERROR_CODES`](crate::error::ERROR_CODES).
+    pub fn code(&self) -> u16
+    {
+        701
+    }
+}
+
+
+/// Which transitions a tour exercised when replayed from the initial state.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TourCoverage<S, E> {
+    pub covered: Vec<(S, E)>,
+    pub missed: Vec<(S, E)>,
+    /// Index of the first event the machine would reject, if any; the
+    /// replay stops there.
+    pub rejected_at: Option<usize>
+}
+
+
+impl<S, E> TourCoverage<S, E> {
+    /// Fraction of enabled transitions covered, in `[0, 1]`.
+    pub fn ratio(&self) -> f64
+    {
+        let total = self.covered.len() + self.missed.len();
+
+        if total == 0 { 1.0 } else { self.covered.len() as f64 / total as f64 }
+    }
+
+
+    pub fn is_complete(&self) -> bool
+    {
+        self.missed.is_empty() && self.rejected_at.is_none()
+    }
+}
+
+
+impl<S, E> StateMachine<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    /// Computes events that, triggered from the initial state, take every
+    /// enabled transition at least once.
+    ///
+    /// The tour follows the same structural rules as `simulate`: guards
+    /// and cooldowns are ignored and a choice follows its first candidate.
+    /// It heads for the nearest uncovered transition after which all the
+    /// others remain reachable, so it finds a tour whenever one exists,
+    /// though not necessarily the shortest.
+    pub fn transition_tour(&self) -> Result<Vec<E>, TourError<S, E>>
+    {
+        let edges = self.tour_edges();
+        let mut graph = TourGraph::new(self, &edges);
+        let mut uncovered: HashSet<(S, E)> = edges.into_iter().collect();
+        let mut state = self.initial;
+        let mut tour = Vec::new();
+
+        while !uncovered.is_empty() {
+            let Some(path) = graph.next_path(state, &uncovered) else {
+                let mut uncovered: Vec<(S, E)> = uncovered.into_iter().collect();
+                uncovered.sort_by_cached_key(|(s, e)| format!("{s:?}\0{e:?}"));
+                return Err(TourError{ uncovered });
+            };
+            for event in path {
+                uncovered.remove(&(state, event));
+                state = graph.target(state, event);
+                tour.push(event);
+            }
+        }
+        Ok(tour)
+    }
+
+
+    /// Replays `tour` structurally from the initial state and reports
+    /// which enabled transitions it took.
+    pub fn verify_tour(&self, tour: &[E]) -> TourCoverage<S, E>
+    {
+        let mut taken = HashSet::new();
+        let mut state = self.initial;
+        let mut rejected_at = None;
+
+        for (i, &event) in tour.iter().enumerate() {
+            match self.simulate(state, &[event]) {
+                Ok(next) => {
+                    taken.insert((state, event));
+                    state = next;
+                }
+                Err(_) => {
+                    rejected_at = Some(i);
+                    break;
+                }
+            }
+        }
+
+        let (covered, missed) = self.tour_edges()
+            .into_iter()
+            .partition(|edge| taken.contains(edge));
+        TourCoverage{ covered, missed, rejected_at }
+    }
+
+
+    /// Enabled transitions a tour must take, ordered by `Debug` rendering.
+    fn tour_edges(&self) -> Vec<(S, E)>
+    {
+        let mut edges: Vec<(S, E)> = self.transitions
+            .iter()
+            .filter(|((from, _), t)| t.enabled && !self.terminals.contains(from))
+            .map(|(key, _)| *key)
+            .collect();
+
+        edges.sort_by_cached_key(|(s, e)| format!("{s:?}\0{e:?}"));
+        edges
+    }
+}
+
+
+/// The transitions a tour takes, with the states reachable from each
+/// state, computed as needed.
+struct TourGraph<S, E> {
+    outgoing: HashMap<S, Vec<(E, S)>>,
+    reachable: HashMap<S, HashSet<S>>
+}
+
+
+impl<S, E> TourGraph<S, E>
+where S: Copy + Hash + Eq + Debug, E: Copy + Hash + Eq + Debug
+{
+    fn new(fsm: &StateMachine<S, E>, edges: &[(S, E)]) -> Self
+    {
+        let mut outgoing: HashMap<S, Vec<(E, S)>> = HashMap::new();
+
+        for &(from, event) in edges {
+            let to = fsm.transitions[&(from, event)].next_state;
+            outgoing.entry(from).or_default().push((event, to));
+        }
+        Self{ outgoing, reachable: HashMap::new() }
+    }
+
+
+    fn target(&self, from: S, event: E) -> S
+    {
+        self.outgoing[&from].iter().find(|(e, _)| *e == event).unwrap().1
+    }
+
+
+    fn reachable_from(&mut self, from: S) -> &HashSet<S>
+    {
+        if !self.reachable.contains_key(&from) {
+            let mut seen = HashSet::from([from]);
+            let mut pending = vec![from];
+
+            while let Some(state) = pending.pop() {
+                for &(_, to) in self.outgoing.get(&state).into_iter().flatten() {
+                    if seen.insert(to) {
+                        pending.push(to);
+                    }
+                }
+            }
+            self.reachable.insert(from, seen);
+        }
+        &self.reachable[&from]
+    }
+
+
+    /// Returns the shortest event path from `from` ending in the nearest
+    /// uncovered transition after which every other uncovered one can
+    /// still be reached, or in the nearest one if none qualifies.
+    fn next_path(&mut self, from: S, uncovered: &HashSet<(S, E)>) -> Option<Vec<E>>
+    {
+        let mut parents: HashMap<S, (S, E)> = HashMap::new();
+        let mut queue = VecDeque::from([from]);
+        let mut seen = HashSet::from([from]);
+        let mut candidates = Vec::new();
+
+        while let Some(state) = queue.pop_front() {
+            for &(event, next) in self.outgoing.get(&state).into_iter().flatten() {
+                if uncovered.contains(&(state, event)) {
+                    candidates.push((state, event));
+                }
+                if seen.insert(next) {
+                    parents.insert(next, (state, event));
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        let paths: Vec<Vec<E>> = candidates
+            .into_iter()
+            .map(|(state, event)| {
+                let mut path = vec![event];
+                let mut at = state;
+
+                while let Some(&(parent, event)) = parents.get(&at) {
+                    path.push(event);
+                    at = parent;
+                }
+                path.reverse();
+                path
+            })
+            .collect();
+
+        for path in &paths {
+            let mut taken = HashSet::new();
+            let mut state = from;
+
+            for &event in path {
+                taken.insert((state, event));
+                state = self.target(state, event);
+            }
+            let reachable = self.reachable_from(state);
+            let keeps_tour = uncovered
+                .iter()
+                .all(|edge| taken.contains(edge) || reachable.contains(&edge.0));
+
+            if keeps_tour {
+                return Some(path.clone());
+            }
+        }
+        paths.into_iter().next()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{builder::StateMachineBuilder, fixtures::traffic_light, fsm::FSM};
+
+
+    #[test]
+    fn test_traffic_light_tour()
+    {
+        use traffic_light::Event::*;
+
+        let fsm = traffic_light::machine();
+        let tour = fsm.transition_tour().unwrap();
+
+        assert_eq!(tour, [RedTimeout, Yellow2GreenTimeout, GreenTimeout, Yellow2RedTimeout]);
+        let coverage = fsm.verify_tour(&tour);
+        assert!(coverage.is_complete());
+        assert_eq!(coverage.ratio(), 1.0);
+
+        let partial = fsm.verify_tour(&[RedTimeout, Yellow2RedTimeout, GreenTimeout]);
+        assert_eq!(partial.covered.len(), 2);
+        assert_eq!(partial.rejected_at, Some(2));
+        assert_eq!(partial.ratio(), 0.5);
+    }
+
+
+    #[test]
+    fn test_unreachable_edge_reported()
+    {
+        let fsm = StateMachineBuilder::new(0)
+            .transition(0, 'a', 1)
+            .transition(1, 'b', 0)
+            .transition(2, 'c', 0)
+            .build()
+            .unwrap();
+
+        assert_eq!(fsm.transition_tour(), Err(TourError{ uncovered: vec![(2, 'c')] }));
+        assert_eq!(fsm.state(), 0);
+    }
+
+
+    #[test]
+    fn test_dead_end_taken_last()
+    {
+        let fsm = StateMachineBuilder::new(0)
+            .transition(0, 'a', 1)
+            .transition(0, 'b', 2)
+            .transition(2, 'c', 0)
+            .build()
+            .unwrap();
+
+        let tour = fsm.transition_tour().unwrap();
+        assert_eq!(tour, ['b', 'c', 'a']);
+        assert!(fsm.verify_tour(&tour).is_complete());
+    }
+}
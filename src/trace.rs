@@ -0,0 +1,83 @@
+use std::{
+    collections::VecDeque,
+    fmt::{self, Debug, Display},
+    time::Instant
+};
+
+use crate::{configuration::Configuration, error::TransitionError, origin::Origin};
+
+
+/// What happened to one triggered event.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TraceOutcome<S, E> {
+    Transitioned { to: S },
+    Rejected(TransitionError<S, E>)
+}
+
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TraceEntry<S, E> {
+    pub state: S,
+    pub event: E,
+    pub outcome: TraceOutcome<S, E>,
+    pub origin: Origin,
+    /// Posts the event stood for after coalescing; 1 unless coalesced.
+    pub coalesced: usize,
+    pub at: Instant
+}
+
+
+impl<S: Copy, E> TraceEntry<S, E> {
+    /// The configuration the event was received in.
+    pub fn configuration(&self) -> Configuration<S>
+    {
+        Configuration::flat(self.state)
+    }
+}
+
+
+/// Renders as `Red -RedTimeout-> Yellow`, or `-> rejected`, naming
+/// configurations the way `Configuration` displays them.
+impl<S: Debug, E: Debug> Display for TraceEntry<S, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "{} -{:?}-> ", Configuration::flat(&self.state), self.event)?;
+        match &self.outcome {
+            TraceOutcome::Transitioned { to } => write!(f, "{}", Configuration::flat(to)),
+            TraceOutcome::Rejected(_) => f.write_str("rejected")
+        }
+    }
+}
+
+
+/// Bounded log of the most recent triggers; oldest entries are dropped.
+#[derive(Clone, Debug)]
+pub(crate) struct Trace<S, E> {
+    pub(crate) entries: VecDeque<TraceEntry<S, E>>,
+    capacity: usize,
+    /// Oldest entries recorded before the structure was last hot-swapped,
+    /// which may reference states and events it no longer has.
+    pub(crate) predating_swap: usize
+}
+
+
+impl<S, E> Trace<S, E> {
+    pub(crate) fn new(capacity: usize) -> Self
+    {
+        Self{ entries: VecDeque::with_capacity(capacity), capacity, predating_swap: 0 }
+    }
+
+
+    pub(crate) fn record(&mut self, entry: TraceEntry<S, E>)
+    {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+            self.predating_swap = self.predating_swap.saturating_sub(1);
+        }
+        self.entries.push_back(entry);
+    }
+}
@@ -0,0 +1,141 @@
+//! Regression guard: the trigger fast path must not touch the heap.
+//!
+//! Skipped under `paranoid`, whose per-trigger checks allocate by design.
+#![cfg(not(feature = "paranoid"))]
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell
+};
+
+use pfsm::{
+    error::TriggerCode,
+    fixtures::traffic_light::{self, Event, State},
+    fsm::{StateMachine, FSM},
+    policy::Policies
+};
+
+
+struct CountingAllocator;
+
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8
+    {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        unsafe { System.alloc(layout) }
+    }
+
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout)
+    {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8
+    {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+
+fn allocations_during(f: impl FnOnce()) -> usize
+{
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+
+fn light() -> StateMachine<State, Event>
+{
+    let ticks = std::rc::Rc::new(Cell::new(0));
+
+    traffic_light::builder()
+        .action(move || ticks.set(ticks.get() + 1))
+        .guard(|| true)
+        .on_enter(State::Green, || {})
+        .on_exit(State::Red, || {})
+        .build()
+        .unwrap()
+}
+
+
+/// Events driving `light` round its cycle, `n` times in all.
+fn cycle(n: usize) -> impl Iterator<Item = Event>
+{
+    traffic_light::TRANSITIONS.into_iter().map(|(_, event, _)| event).cycle().take(n)
+}
+
+
+#[test]
+fn test_successful_trigger_does_not_allocate()
+{
+    let mut fsm = light();
+
+    let count = allocations_during(|| {
+        for event in cycle(1000) {
+            fsm.trigger(event).unwrap();
+        }
+    });
+    assert_eq!(count, 0);
+}
+
+
+#[test]
+fn test_rejected_trigger_does_not_allocate()
+{
+    let mut fsm = light();
+
+    let count = allocations_during(|| {
+        assert!(fsm.trigger(Event::GreenTimeout).is_err());
+        assert_eq!(fsm.try_trigger_quiet(Event::GreenTimeout), Err(TriggerCode::NoTransition));
+    });
+    assert_eq!(count, 0);
+}
+
+
+#[test]
+fn test_enabled_trace_does_not_allocate_per_trigger()
+{
+    let mut fsm = light();
+
+    fsm.enable_trace(16);
+    let count = allocations_during(|| {
+        for event in cycle(100) {
+            fsm.trigger(event).unwrap();
+        }
+    });
+    assert_eq!(count, 0);
+    assert_eq!(fsm.trace().count(), 16);
+}
+
+
+#[test]
+fn test_rejection_context_allocates_only_when_enabled()
+{
+    let mut fsm = light();
+
+    fsm.enable_trace(16);
+    let count = allocations_during(|| {
+        assert!(fsm.trigger(Event::GreenTimeout).unwrap_err().context().is_none());
+    });
+    assert_eq!(count, 0);
+
+    fsm.set_policies(Policies::default().with_rejection_context(true));
+    let count = allocations_during(|| {
+        assert!(fsm.trigger(Event::GreenTimeout).unwrap_err().context().is_some());
+    });
+    assert!(count > 0);
+}
@@ -0,0 +1,204 @@
+use std::{cell::RefCell, rc::Rc};
+
+use pfsm::{
+    builder::StateMachineBuilder,
+    error::TransitionError,
+    explain::Verdict,
+    fsm::{BatchError, StateMachine, FSM}
+};
+
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+enum State {
+    Idle,
+    HasCredit,
+    Vending,
+    OutOfOrder
+}
+
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+enum Event {
+    Coin,
+    Select,
+    Done,
+    Refund,
+    Fault
+}
+
+
+const COIN: u32 = 25;
+const PRICE: u32 = 100;
+
+
+#[derive(Debug, Default)]
+struct Vending {
+    credit: u32,
+    stock: u32,
+    dispensed: u32,
+    refunded: u32,
+    log: Vec<&'static str>
+}
+
+
+type Context = Rc<RefCell<Vending>>;
+
+
+fn vending_machine(ctx: &Context) -> StateMachine<State, Event>
+{
+    let insert = |ctx: &Context| {
+        let ctx = ctx.clone();
+        move || ctx.borrow_mut().credit += COIN
+    };
+    let (can_buy, buy, done) = (ctx.clone(), ctx.clone(), ctx.clone());
+    let (refund, closed, broken) = (ctx.clone(), ctx.clone(), ctx.clone());
+
+    StateMachineBuilder::new(State::Idle)
+        .transition(State::Idle, Event::Coin, State::HasCredit)
+        .action(insert(ctx))
+        .transition(State::HasCredit, Event::Coin, State::HasCredit)
+        .action(insert(ctx))
+        .transition(State::HasCredit, Event::Select, State::Vending)
+        .guard(move || {
+            let ctx = can_buy.borrow();
+            ctx.credit >= PRICE && ctx.stock > 0
+        })
+        .action(move || {
+            let mut ctx = buy.borrow_mut();
+            ctx.credit -= PRICE;
+            ctx.stock -= 1;
+        })
+        .transition(State::Vending, Event::Done, State::Idle)
+        .action(move || done.borrow_mut().dispensed += 1)
+        .transition(State::HasCredit, Event::Refund, State::Idle)
+        .transition(State::Idle, Event::Fault, State::OutOfOrder)
+        .transition(State::HasCredit, Event::Fault, State::OutOfOrder)
+        .transition(State::Vending, Event::Fault, State::OutOfOrder)
+        .on_enter(State::Idle, move || {
+            let mut ctx = refund.borrow_mut();
+            ctx.refunded += std::mem::take(&mut ctx.credit);
+            ctx.log.push("enter Idle");
+        })
+        .on_exit(State::Vending, move || closed.borrow_mut().log.push("exit Vending"))
+        .on_enter(State::OutOfOrder, move || broken.borrow_mut().log.push("enter OutOfOrder"))
+        .tag(State::HasCredit, "refundable")
+        .tag(State::Vending, "busy")
+        .build()
+        .unwrap()
+}
+
+
+fn stocked(stock: u32) -> Context
+{
+    Rc::new(RefCell::new(Vending{ stock, ..Vending::default() }))
+}
+
+
+#[test]
+fn test_purchase()
+{
+    let ctx = stocked(2);
+    let mut fsm = vending_machine(&ctx);
+
+    assert_eq!(fsm.trigger_batch([Event::Coin; 4]), Ok(4));
+    assert_eq!(fsm.state(), State::HasCredit);
+    assert!(fsm.has_tag("refundable"));
+    assert_eq!(ctx.borrow().credit, 100);
+
+    fsm.trigger(Event::Select).unwrap();
+    assert_eq!(fsm.state(), State::Vending);
+    assert_eq!((ctx.borrow().credit, ctx.borrow().stock), (0, 1));
+
+    fsm.trigger(Event::Done).unwrap();
+    assert_eq!(fsm.state(), State::Idle);
+    assert_eq!(ctx.borrow().dispensed, 1);
+    assert_eq!(ctx.borrow().refunded, 0);
+    assert_eq!(ctx.borrow().log, ["exit Vending", "enter Idle"]);
+}
+
+
+#[test]
+fn test_guard_on_credit_and_stock()
+{
+    let ctx = stocked(1);
+    let mut fsm = vending_machine(&ctx);
+
+    fsm.trigger_batch([Event::Coin; 3]).unwrap();
+    assert_eq!(fsm.explain(Event::Select).verdict, Verdict::GuardRejected{ to: State::Vending });
+    assert!(fsm.trigger(Event::Select).is_err());
+    assert_eq!(fsm.state(), State::HasCredit);
+
+    fsm.trigger_batch([Event::Coin, Event::Coin, Event::Select, Event::Done]).unwrap();
+    assert_eq!(ctx.borrow().stock, 0);
+    assert_eq!(ctx.borrow().refunded, 25);
+
+    // Sold out: credit is accepted but nothing can be bought.
+    fsm.trigger_batch([Event::Coin; 4]).unwrap();
+    assert!(fsm.trigger(Event::Select).is_err());
+    fsm.trigger(Event::Refund).unwrap();
+    assert_eq!(ctx.borrow().refunded, 125);
+}
+
+
+#[test]
+fn test_batch_stops_at_first_rejection()
+{
+    let ctx = stocked(1);
+    let mut fsm = vending_machine(&ctx);
+    let result = fsm.trigger_batch([Event::Coin, Event::Select, Event::Coin]);
+
+    assert_eq!(result.as_ref().map_err(|e| e.index), Err(1));
+    let BatchError { error, .. } = result.unwrap_err();
+    assert_eq!(error, TransitionError::GuardRejected{ state: State::HasCredit, event: Event::Select });
+    assert_eq!(error.to_string(), "Guard rejected event 'Select' in state 'HasCredit'");
+    assert_eq!(ctx.borrow().credit, 25);
+}
+
+
+#[test]
+fn test_error_state_and_reset()
+{
+    let ctx = stocked(1);
+    let mut fsm = vending_machine(&ctx);
+
+    fsm.trigger_batch([Event::Coin, Event::Fault]).unwrap();
+    assert_eq!(fsm.state(), State::OutOfOrder);
+    assert_eq!(ctx.borrow().log, ["enter OutOfOrder"]);
+    assert!(fsm.trigger(Event::Coin).is_err());
+    assert_eq!(fsm.sink_states(), [State::OutOfOrder]);
+
+    fsm.reset();
+    assert_eq!(fsm.state(), State::Idle);
+    // Reset runs no actions, so the stranded credit stays put.
+    assert_eq!(ctx.borrow().credit, 25);
+    assert_eq!(ctx.borrow().log, ["enter OutOfOrder"]);
+    fsm.trigger(Event::Coin).unwrap();
+    assert_eq!(ctx.borrow().credit, 50);
+}
+
+
+#[test]
+fn test_dot_snapshot()
+{
+    let fsm = vending_machine(&stocked(0));
+
+    assert_eq!(fsm.to_dot().unwrap(), r#"digraph {
+    __start [shape=point];
+    __start -> "Idle";
+    __current [shape=plaintext, label="current: Idle"];
+    __current -> "Idle" [style=dotted];
+    "HasCredit" [tooltip="refundable"];
+    "Idle";
+    "OutOfOrder";
+    "Vending" [tooltip="busy"];
+    "HasCredit" -> "HasCredit" [label="Coin"];
+    "HasCredit" -> "OutOfOrder" [label="Fault"];
+    "HasCredit" -> "Idle" [label="Refund"];
+    "HasCredit" -> "Vending" [label="Select [guarded]"];
+    "Idle" -> "HasCredit" [label="Coin"];
+    "Idle" -> "OutOfOrder" [label="Fault"];
+    "Vending" -> "Idle" [label="Done"];
+    "Vending" -> "OutOfOrder" [label="Fault"];
+}
+"#);
+}